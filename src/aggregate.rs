@@ -0,0 +1,215 @@
+use crate::decimal::{Decimal, MAX_PRECISION};
+use crate::errors::{ExecutionError, ParsingError};
+use crate::expression::{parse_expression, Expr};
+use crate::lexer::{Token, Tokens};
+use crate::logical_plan::LogicalPlan;
+use crate::schema::{Field, Schema};
+use crate::select::{expand_select_list, LogicalSelectListNode};
+use crate::value::DataType;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+    AnyValue,
+    ArrayAgg,
+    StringAgg,
+}
+
+impl AggregateFunction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "COUNT" => Some(AggregateFunction::Count),
+            "SUM" => Some(AggregateFunction::Sum),
+            "AVG" => Some(AggregateFunction::Avg),
+            "MIN" => Some(AggregateFunction::Min),
+            "MAX" => Some(AggregateFunction::Max),
+            "ANY_VALUE" => Some(AggregateFunction::AnyValue),
+            "ARRAY_AGG" => Some(AggregateFunction::ArrayAgg),
+            "STRING_AGG" => Some(AggregateFunction::StringAgg),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AggregateFunction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            AggregateFunction::Count => "COUNT",
+            AggregateFunction::Sum => "SUM",
+            AggregateFunction::Avg => "AVG",
+            AggregateFunction::Min => "MIN",
+            AggregateFunction::Max => "MAX",
+            AggregateFunction::AnyValue => "ANY_VALUE",
+            AggregateFunction::ArrayAgg => "ARRAY_AGG",
+            AggregateFunction::StringAgg => "STRING_AGG",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A call of an aggregate function. `argument` is `None` for `COUNT(*)`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateCall {
+    pub function: AggregateFunction,
+    pub argument: Option<Expr>,
+    pub distinct: bool,
+    /// Separator of STRING_AGG
+    pub delimiter: Option<String>,
+}
+
+impl AggregateCall {
+    /// Parses `COUNT(*)` or `FUNCTION([DISTINCT] expr)`, with an optional
+    /// string literal delimiter for STRING_AGG.
+    pub fn parse(tokens: &mut Tokens) -> Result<Self, ParsingError> {
+        let name = tokens.expect_identifier()?;
+        let function = AggregateFunction::from_name(&name)
+            .ok_or_else(|| ParsingError::new(&format!("Expected an aggregate function, found {}", name)))?;
+        tokens.expect_symbol("(")?;
+        if function == AggregateFunction::Count && tokens.accept_symbol("*") {
+            tokens.expect_symbol(")")?;
+            return Ok(Self {
+                function,
+                argument: None,
+                distinct: false,
+                delimiter: None,
+            });
+        }
+        let distinct = tokens.accept_keyword("DISTINCT");
+        let argument = parse_expression(tokens)?;
+        let delimiter = if function == AggregateFunction::StringAgg && tokens.accept_symbol(",") {
+            match tokens.next() {
+                Some(Token::StringLiteral(delimiter)) => Some(delimiter),
+                _ => return Err(ParsingError::new("The delimiter of STRING_AGG must be a string literal")),
+            }
+        } else {
+            None
+        };
+        tokens.expect_symbol(")")?;
+        Ok(Self {
+            function,
+            argument: Some(argument),
+            distinct,
+            delimiter,
+        })
+    }
+
+    /// Type of the result for rows of `schema`.
+    pub fn data_type(&self, schema: &Schema) -> Result<DataType, ExecutionError> {
+        let Some(argument) = &self.argument else {
+            return Ok(DataType::Int64);
+        };
+        let argument_type = argument.data_type(schema)?;
+        let invalid = || ExecutionError::new(&format!("{} cannot aggregate {} values", self.function, argument_type));
+        match self.function {
+            AggregateFunction::Count => Ok(DataType::Int64),
+            AggregateFunction::Sum => match argument_type {
+                DataType::Int64 | DataType::Float64 => Ok(argument_type),
+                DataType::Decimal(_, scale) => Ok(DataType::Decimal(MAX_PRECISION, scale)),
+                _ => Err(invalid()),
+            },
+            AggregateFunction::Avg => match argument_type {
+                DataType::Int64 | DataType::Float64 => Ok(DataType::Float64),
+                DataType::Decimal(_, scale) => Ok(DataType::Decimal(MAX_PRECISION, Decimal::quotient_scale(scale, 0))),
+                _ => Err(invalid()),
+            },
+            AggregateFunction::Min | AggregateFunction::Max => match argument_type {
+                DataType::Array(_) => Err(invalid()),
+                _ => Ok(argument_type),
+            },
+            AggregateFunction::AnyValue => Ok(argument_type),
+            AggregateFunction::ArrayAgg => match argument_type {
+                DataType::Array(_) => Err(invalid()),
+                _ => Ok(DataType::Array(Box::new(argument_type))),
+            },
+            AggregateFunction::StringAgg => match argument_type {
+                DataType::String => Ok(DataType::String),
+                _ => Err(invalid()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for AggregateCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Some(argument) = &self.argument else {
+            return write!(f, "{}(*)", self.function);
+        };
+        write!(f, "{}(", self.function)?;
+        if self.distinct {
+            write!(f, "DISTINCT ")?;
+        }
+        write!(f, "{}", argument)?;
+        if let Some(delimiter) = &self.delimiter {
+            write!(f, ", '{}'", delimiter)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalAggregateListNode {
+    pub call: AggregateCall,
+    pub alias: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalAggregateNode {
+    pub input: Box<LogicalPlan>,
+    pub group_by: Vec<LogicalSelectListNode>,
+    pub aggregates: Vec<LogicalAggregateListNode>,
+}
+
+impl LogicalAggregateNode {
+    pub fn new(
+        input: LogicalPlan,
+        group_by: Vec<LogicalSelectListNode>,
+        aggregates: Vec<LogicalAggregateListNode>,
+    ) -> Self {
+        Self {
+            input: Box::new(input),
+            group_by,
+            aggregates,
+        }
+    }
+
+    /// The grouping list as (expression, alias) pairs.
+    pub fn group_by_list(&self) -> Vec<(Expr, Option<String>)> {
+        self.group_by
+            .iter()
+            .map(|group| (group.field.clone(), group.alias.clone()))
+            .collect()
+    }
+
+    /// The aggregate list as (call, alias) pairs.
+    pub fn aggregate_list(&self) -> Vec<(AggregateCall, Option<String>)> {
+        self.aggregates
+            .iter()
+            .map(|aggregate| (aggregate.call.clone(), aggregate.alias.clone()))
+            .collect()
+    }
+}
+
+/// The output of an aggregation over rows of `input`: the grouping columns,
+/// named like select list entries, followed by one column per aggregate,
+/// named by its alias or its text.
+pub fn aggregate_fields(
+    group_by: &[(Expr, Option<String>)],
+    aggregates: &[(AggregateCall, Option<String>)],
+    input: &Schema,
+) -> Result<Vec<Field>, ExecutionError> {
+    let group_by = group_by.iter().map(|(expr, alias)| (expr, alias.as_deref()));
+    let mut fields: Vec<Field> = expand_select_list(group_by, input)?
+        .into_iter()
+        .map(|(_, field)| field)
+        .collect();
+    for (call, alias) in aggregates {
+        let name = alias.clone().unwrap_or_else(|| call.to_string());
+        fields.push(Field::new(None, &name, call.data_type(input)?));
+    }
+    Ok(fields)
+}
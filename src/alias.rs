@@ -0,0 +1,17 @@
+use crate::logical_plan::LogicalPlan;
+
+/// Names the result of a subquery: its columns are qualified by the alias.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalAliasNode {
+    pub input: Box<LogicalPlan>,
+    pub alias: String,
+}
+
+impl LogicalAliasNode {
+    pub fn new(input: LogicalPlan, alias: &str) -> Self {
+        Self {
+            input: Box::new(input),
+            alias: alias.to_string(),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use crate::catalog::{Catalog, TableSchema};
+use crate::errors::ExecutionError;
+use crate::hyperloglog::HyperLogLog;
+use crate::random::Random;
+use crate::statistics::{ColumnStatistics, Histogram, TableStatistics};
+use crate::value::{Row, Value};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+pub const HISTOGRAM_BUCKETS: usize = 16;
+const SAMPLE_SEED: u64 = 0x5107_4DB5;
+
+/// Scans the table (keeping roughly `sample_percent` of the rows when given)
+/// and builds fresh statistics for every column.
+pub fn analyze_table(catalog: &Catalog, table: &str, sample_percent: Option<f64>) -> Result<TableStatistics, ExecutionError> {
+    let schema = catalog.get_table(table)?;
+    let fraction = sample_percent.map_or(1.0, |percent| (percent / 100.0).clamp(0.0, 1.0));
+    let mut random = Random::new(SAMPLE_SEED);
+
+    let mut records = catalog.open_table(table)?;
+    let page_count = records.page_count() as u64;
+    let mut row_count = 0u64;
+    let mut sample: Vec<Row> = Vec::new();
+    for row in records.scan_records() {
+        row_count += 1;
+        if fraction >= 1.0 || random.next_f64() < fraction {
+            sample.push(row);
+        }
+    }
+
+    let mut columns = BTreeMap::new();
+    for (index, column) in schema.columns.iter().enumerate() {
+        let values: Vec<Value> = sample.iter().map(|row| row[index].clone()).collect();
+        columns.insert(column.name.to_lowercase(), column_statistics(values, row_count));
+    }
+
+    Ok(TableStatistics {
+        row_count,
+        page_count,
+        columns,
+        modified_rows: 0,
+    })
+}
+
+fn column_statistics(values: Vec<Value>, row_count: u64) -> ColumnStatistics {
+    let sampled = values.len() as f64;
+    let mut sketch = HyperLogLog::default();
+    let mut non_null: Vec<Value> = Vec::new();
+    for value in values {
+        if !value.is_null() {
+            sketch.add(&value);
+            non_null.push(value);
+        }
+    }
+
+    let null_fraction = if sampled > 0.0 {
+        1.0 - non_null.len() as f64 / sampled
+    } else {
+        0.0
+    };
+    let mut distinct_count = sketch.estimate().min(non_null.len() as f64);
+    // A sample that is mostly unique probably comes from a unique column, so
+    // scale it to the table; otherwise the sample has likely seen most values.
+    if !non_null.is_empty() && distinct_count > 0.9 * non_null.len() as f64 {
+        distinct_count *= row_count as f64 * (1.0 - null_fraction) / non_null.len() as f64;
+    }
+
+    ColumnStatistics {
+        null_fraction,
+        distinct_count,
+        histogram: build_histogram(non_null),
+        sketch,
+    }
+}
+
+/// Equi-depth histogram over the non-null values.
+fn build_histogram(mut values: Vec<Value>) -> Option<Histogram> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(|a, b| a.compare(b).unwrap_or(Ordering::Equal));
+
+    let buckets = HISTOGRAM_BUCKETS.min(values.len());
+    let bounds = (0..=buckets)
+        .map(|i| values[(i * (values.len() - 1)) / buckets].clone())
+        .collect();
+    Some(Histogram { bounds })
+}
+
+/// Folds the rows written by a statement into existing statistics, given the
+/// table's page count afterwards. Histograms are left alone until the next
+/// ANALYZE; `modified_rows` tracks how stale they are. Distinct counts only
+/// grow, as the sketches cannot forget removed values.
+pub fn apply_changes(
+    statistics: &mut TableStatistics,
+    schema: &TableSchema,
+    inserted: &[Row],
+    deleted: &[Row],
+    page_count: u64,
+) {
+    let old_rows = statistics.row_count as f64;
+    statistics.row_count = (statistics.row_count + inserted.len() as u64).saturating_sub(deleted.len() as u64);
+    statistics.modified_rows += (inserted.len() + deleted.len()) as u64;
+    statistics.page_count = page_count;
+
+    for (index, column) in schema.columns.iter().enumerate() {
+        let Some(column_statistics) = statistics.columns.get_mut(&column.name.to_lowercase()) else {
+            continue;
+        };
+        let nulls = |rows: &[Row]| rows.iter().filter(|row| row[index].is_null()).count() as f64;
+        let remaining = (column_statistics.null_fraction * old_rows - nulls(deleted)).max(0.0) + nulls(inserted);
+        column_statistics.null_fraction = if statistics.row_count > 0 {
+            (remaining / statistics.row_count as f64).min(1.0)
+        } else {
+            0.0
+        };
+
+        let mut added = HyperLogLog::default();
+        for row in inserted {
+            if !row[index].is_null() {
+                added.add(&row[index]);
+            }
+        }
+        let before = column_statistics.sketch.estimate();
+        column_statistics.sketch.merge(&added);
+        column_statistics.distinct_count += column_statistics.sketch.estimate() - before;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+    use crate::parser::parse_statement;
+    use tempfile::tempdir;
+
+    #[test]
+    fn collects_and_refreshes_column_statistics() {
+        let dir = tempdir().unwrap();
+        let mut database = Database::open(dir.path()).unwrap();
+        let mut run = |sql: &str| database.execute(parse_statement(sql).unwrap()).unwrap();
+        run("CREATE TABLE events (id INT64 PRIMARY KEY, kind STRING, score INT64)");
+        for chunk in 0..10 {
+            let rows: Vec<String> = (chunk * 100..(chunk + 1) * 100)
+                .map(|i| {
+                    let kind = if i % 4 == 0 { "NULL".to_string() } else { format!("'kind{}'", i % 5) };
+                    format!("({}, {}, {})", i, kind, i % 100)
+                })
+                .collect();
+            run(&format!("INSERT INTO events VALUES {}", rows.join(", ")));
+        }
+        run("ANALYZE events");
+
+        let statistics = database.catalog().table_statistics("events");
+        assert_eq!(statistics.row_count, 1000);
+        let id = statistics.column("id").unwrap();
+        assert!((id.distinct_count - 1000.0).abs() < 50.0);
+        let kind = statistics.column("kind").unwrap();
+        assert!((kind.null_fraction - 0.25).abs() < 1e-9);
+        assert!((kind.distinct_count - 5.0).abs() < 0.5);
+        let score = statistics.column("score").unwrap();
+        let below = score.histogram.as_ref().unwrap().fraction_below(&Value::Int(25), false);
+        assert!((below - 0.25).abs() < 0.05, "fraction {}", below);
+
+        let mut run = |sql: &str| database.execute(parse_statement(sql).unwrap()).unwrap();
+        run("INSERT INTO events VALUES (5000, 'new', 1), (5001, 'newer', 2)");
+        let statistics = database.catalog().table_statistics("events");
+        assert_eq!(statistics.row_count, 1002);
+        assert!((statistics.column("kind").unwrap().distinct_count - 7.0).abs() < 0.5);
+        assert!(!statistics.is_stale());
+
+        // updates and deletes take the NULLs of the rows they replace out again
+        let mut run = |sql: &str| database.execute(parse_statement(sql).unwrap()).unwrap();
+        run("UPDATE events SET kind = 'filled' WHERE kind IS NULL AND id < 500");
+        let null_fraction = database.catalog().table_statistics("events").column("kind").unwrap().null_fraction;
+        assert!((null_fraction - 125.0 / 1002.0).abs() < 1e-9, "fraction {}", null_fraction);
+        let mut run = |sql: &str| database.execute(parse_statement(sql).unwrap()).unwrap();
+        run("DELETE FROM events WHERE kind IS NULL");
+        let rows: Vec<String> = (2000..2500).map(|i| format!("({}, 'more', 0)", i)).collect();
+        run(&format!("INSERT INTO events VALUES {}", rows.join(", ")));
+        let statistics = database.catalog().table_statistics("events");
+        assert_eq!(statistics.row_count, 1377);
+        assert_eq!(statistics.column("kind").unwrap().null_fraction, 0.0);
+        let records = database.catalog().open_table("events").unwrap();
+        assert_eq!(statistics.page_count, records.page_count() as u64);
+    }
+}
@@ -0,0 +1,405 @@
+use crate::catalog::Catalog;
+use crate::errors::ExecutionError;
+use crate::expression::{BinaryOperator, Expr, UnaryOperator};
+use crate::function::{function_names, lookup};
+use crate::logical_plan::LogicalPlan;
+use crate::schema::{Field, Schema};
+use crate::value::{DataType, Value};
+
+/// Checks a query against the catalog before it is optimized: every table,
+/// column and function must exist, operands must have types their operator
+/// takes and conditions must be BOOL. Where an operator or function computes
+/// in a wider type than an operand has, the operand is converted: literals
+/// at once, other values by an inserted CAST. Unknown names are reported with
+/// the closest known name, ambiguous columns with the columns they could be.
+///
+/// Subqueries that became APPLY operators are checked when the type of their
+/// column is found.
+pub fn bind(plan: LogicalPlan, catalog: &Catalog) -> Result<LogicalPlan, ExecutionError> {
+    let mut error = None;
+    let plan = plan.map_inputs(|input| {
+        bind(input, catalog).unwrap_or_else(|e| {
+            error.get_or_insert(e);
+            LogicalPlan::OneRow
+        })
+    });
+    if let Some(error) = error {
+        return Err(error);
+    }
+    if let LogicalPlan::Scan(scan) = &plan {
+        if catalog.get_table(&scan.table_name).is_err() {
+            let names = catalog.tables().map(|table| table.name.clone());
+            return Err(ExecutionError::new(&format!(
+                "Table {} does not exist{}",
+                scan.table_name,
+                suggestion(&scan.table_name, names)
+            )));
+        }
+    }
+
+    let plan = match plan {
+        // the array is read from the input, the condition from the joined rows
+        LogicalPlan::Unnest(mut unnest) => {
+            let input = unnest.input.schema(catalog)?;
+            unnest.array = bind_expr(unnest.array, &input)?;
+            let output = Schema::new(unnest.fields(&input)?);
+            unnest.condition = bind_condition(unnest.condition, &output, "JOIN UNNEST ... ON")?;
+            LogicalPlan::Unnest(unnest)
+        }
+        mut plan => {
+            let scope = plan.expr_scope(catalog)?;
+            let names: Vec<String> = plan.output_names().into_iter().map(|(name, _)| name).collect();
+            let mut error = None;
+            let mut plan = plan.map_exprs(&mut |expr| {
+                bind_expr(expr.clone(), &scope).unwrap_or_else(|e| {
+                    error.get_or_insert(e);
+                    expr
+                })
+            });
+            if let Some(error) = error {
+                return Err(error);
+            }
+            // converted operands do not rename the columns they compute
+            plan.keep_output_names(names);
+            match plan {
+                LogicalPlan::Filter(mut filter) => {
+                    filter.condition = bind_condition(filter.condition, &scope, "WHERE")?;
+                    LogicalPlan::Filter(filter)
+                }
+                LogicalPlan::Join(mut join) => {
+                    join.condition = bind_condition(join.condition, &scope, "JOIN ... ON")?;
+                    LogicalPlan::Join(join)
+                }
+                plan => plan,
+            }
+        }
+    };
+    plan.schema(catalog)?;
+    Ok(plan)
+}
+
+fn bind_condition(condition: Expr, scope: &Schema, clause: &str) -> Result<Expr, ExecutionError> {
+    match type_of(&condition, scope)? {
+        None | Some(DataType::Bool) => Ok(condition),
+        Some(data_type) => Err(ExecutionError::new(&format!(
+            "{} needs a BOOL condition, got {} of type {}",
+            clause, condition, data_type
+        ))),
+    }
+}
+
+/// The type of a bound expression, `None` for an untyped NULL.
+fn type_of(expr: &Expr, scope: &Schema) -> Result<Option<DataType>, ExecutionError> {
+    match expr {
+        Expr::Literal(Value::Null) => Ok(None),
+        expr => expr.data_type(scope).map(Some),
+    }
+}
+
+fn bind_expr(expr: Expr, scope: &Schema) -> Result<Expr, ExecutionError> {
+    let bind = |expr: Box<Expr>| bind_expr(*expr, scope).map(Box::new);
+    let expr = match expr {
+        // the projection expands it into the columns of its input
+        Expr::Wildcard => return Ok(expr),
+        Expr::Column { qualifier, name } => {
+            resolve(scope, qualifier.as_deref(), &name)?;
+            Expr::Column { qualifier, name }
+        }
+        Expr::Unary { op, expr } => {
+            let expr = bind(expr)?;
+            let valid = match (op, type_of(&expr, scope)?) {
+                (_, None) => true,
+                (UnaryOperator::Not, Some(data_type)) => data_type == DataType::Bool,
+                (UnaryOperator::Minus, Some(data_type)) => is_number(&data_type),
+            };
+            if !valid {
+                let data_type = expr.data_type(scope)?;
+                let op = if op == UnaryOperator::Not { "NOT" } else { "-" };
+                return Err(ExecutionError::new(&format!("Cannot apply {} to {}", op, data_type)));
+            }
+            Expr::Unary { op, expr }
+        }
+        Expr::Binary { left, op, right } => bind_binary(bind_expr(*left, scope)?, op, bind_expr(*right, scope)?, scope)?,
+        Expr::IsNull { expr, negated } => Expr::IsNull {
+            expr: bind(expr)?,
+            negated,
+        },
+        Expr::Function { name, args } => {
+            let function = lookup(&name).map_err(|e| {
+                let names = function_names().map(|name| name.to_string());
+                ExecutionError::new(&format!("{}{}", e.message, suggestion(&name, names)))
+            })?;
+            let mut args = args.into_iter().map(|arg| bind_expr(arg, scope)).collect::<Result<Vec<_>, _>>()?;
+            let types = args.iter().map(|arg| type_of(arg, scope)).collect::<Result<Vec<_>, _>>()?;
+            function.return_type(&types)?;
+            if let Some(start) = function.common_from() {
+                if let Some(common) = function.common_type(&types[start..])? {
+                    for (arg, data_type) in args[start..].iter_mut().zip(&types[start..]) {
+                        if data_type.as_ref().is_some_and(|data_type| *data_type != common) {
+                            *arg = convert(std::mem::replace(arg, Expr::Wildcard), &common)?;
+                        }
+                    }
+                }
+            }
+            Expr::Function { name, args }
+        }
+        Expr::Cast { expr, data_type, safe } => Expr::Cast {
+            expr: bind(expr)?,
+            data_type,
+            safe,
+        },
+        Expr::Struct { fields } => Expr::Struct {
+            fields: fields
+                .into_iter()
+                .map(|(name, expr)| bind_expr(expr, scope).map(|expr| (name, expr)))
+                .collect::<Result<_, _>>()?,
+        },
+        Expr::Field { expr, name } => Expr::Field { expr: bind(expr)?, name },
+        expr => expr,
+    };
+    // what is left to check, such as casts and fields, the type shows
+    expr.data_type(scope)?;
+    Ok(expr)
+}
+
+fn bind_binary(mut left: Expr, op: BinaryOperator, mut right: Expr, scope: &Schema) -> Result<Expr, ExecutionError> {
+    let (Some(left_type), Some(right_type)) = (type_of(&left, scope)?, type_of(&right, scope)?) else {
+        // an untyped NULL goes with any operand of a type the operator takes
+        let data_type = match (type_of(&left, scope)?, type_of(&right, scope)?) {
+            (Some(data_type), None) | (None, Some(data_type)) => data_type,
+            _ => return Ok(Expr::binary(left, op, right)),
+        };
+        let valid = match op {
+            BinaryOperator::And | BinaryOperator::Or => data_type == DataType::Bool,
+            BinaryOperator::Plus => is_number(&data_type) || data_type == DataType::String,
+            BinaryOperator::Minus | BinaryOperator::Multiply | BinaryOperator::Divide => is_number(&data_type),
+            _ => true,
+        };
+        if !valid {
+            return Err(ExecutionError::new(&format!("Cannot apply {} to {} and NULL", op, data_type)));
+        }
+        return Ok(Expr::binary(left, op, right));
+    };
+    let invalid = || ExecutionError::new(&format!("Cannot apply {} to {} and {}", op, left_type, right_type));
+    match op {
+        BinaryOperator::And | BinaryOperator::Or => {
+            if left_type != DataType::Bool || right_type != DataType::Bool {
+                return Err(invalid());
+            }
+        }
+        BinaryOperator::Plus | BinaryOperator::Minus | BinaryOperator::Multiply | BinaryOperator::Divide => {
+            match (&left_type, &right_type) {
+                (DataType::String, DataType::String) if op == BinaryOperator::Plus => {}
+                // integers and decimals meet floats as floats
+                (DataType::Float64, other) if is_number(other) && *other != DataType::Float64 => {
+                    right = convert(right, &DataType::Float64)?
+                }
+                (other, DataType::Float64) if is_number(other) && *other != DataType::Float64 => {
+                    left = convert(left, &DataType::Float64)?
+                }
+                (left, right) if is_number(left) && is_number(right) => {}
+                _ => return Err(invalid()),
+            }
+        }
+        // comparisons
+        _ => match (&left_type, &right_type) {
+            // a string literal compared with a date or time is read as one
+            (data_type, DataType::String) if data_type.is_temporal() && is_literal(&right) => right = convert(right, data_type)?,
+            (DataType::String, data_type) if data_type.is_temporal() && is_literal(&left) => left = convert(left, data_type)?,
+            (DataType::Float64, DataType::Int64 | DataType::Decimal(..)) if is_literal(&right) => {
+                right = convert(right, &DataType::Float64)?
+            }
+            (DataType::Int64 | DataType::Decimal(..), DataType::Float64) if is_literal(&left) => {
+                left = convert(left, &DataType::Float64)?
+            }
+            _ => match left_type.common_supertype(&right_type) {
+                Some(DataType::Array(_) | DataType::Json) | None => {
+                    return Err(ExecutionError::new(&format!("Cannot compare {} with {}", left_type, right_type)));
+                }
+                Some(_) => {}
+            },
+        },
+    }
+    Ok(Expr::binary(left, op, right))
+}
+
+fn is_number(data_type: &DataType) -> bool {
+    matches!(data_type, DataType::Int64 | DataType::Float64 | DataType::Decimal(..))
+}
+
+fn is_literal(expr: &Expr) -> bool {
+    matches!(expr, Expr::Literal(_))
+}
+
+/// The expression converted to `data_type`: a literal becomes a literal of
+/// the type, any other expression a CAST.
+fn convert(expr: Expr, data_type: &DataType) -> Result<Expr, ExecutionError> {
+    match expr {
+        Expr::Literal(value) => value
+            .cast(data_type)
+            .map(Expr::Literal)
+            .map_err(|e| ExecutionError::new(&e)),
+        expr => Ok(Expr::Cast {
+            expr: Box::new(expr),
+            data_type: data_type.clone(),
+            safe: false,
+        }),
+    }
+}
+
+/// Checks that a column reference names exactly one column of the scope.
+fn resolve(scope: &Schema, qualifier: Option<&str>, name: &str) -> Result<(), ExecutionError> {
+    let display = |field: &Field| match &field.qualifier {
+        Some(qualifier) => format!("{}.{}", qualifier, field.name),
+        None => field.name.clone(),
+    };
+    let reference = match qualifier {
+        Some(qualifier) => format!("{}.{}", qualifier, name),
+        None => name.to_string(),
+    };
+    let matches: Vec<&Field> = scope.fields.iter().filter(|field| field.matches(qualifier, name)).collect();
+    match matches.as_slice() {
+        [_] => Ok(()),
+        [] => {
+            // a column of the name under another qualifier is the likely one
+            let named: Vec<&Field> = scope.fields.iter().filter(|field| field.matches(None, name)).collect();
+            let hint = match named.as_slice() {
+                [field] if qualifier.is_some() => format!(", did you mean {}?", display(field)),
+                _ if qualifier.is_some() => suggestion(&reference, scope.fields.iter().map(display)),
+                _ => suggestion(name, scope.fields.iter().map(|field| field.name.clone())),
+            };
+            Err(ExecutionError::new(&format!("Unknown column {}{}", reference, hint)))
+        }
+        fields => {
+            let mut candidates: Vec<String> = fields.iter().map(|field| display(field)).collect();
+            let last = candidates.pop().expect("several columns");
+            Err(ExecutionError::new(&format!(
+                "Column {} is ambiguous, it could be {} or {}",
+                reference,
+                candidates.join(", "),
+                last
+            )))
+        }
+    }
+}
+
+/// `", did you mean x?"` for the candidate closest to `name`, or nothing if
+/// none is close enough to be what was meant: about a third of the letters
+/// may be wrong.
+fn suggestion(name: &str, candidates: impl Iterator<Item = String>) -> String {
+    let name = name.to_lowercase();
+    let allowed = (name.chars().count() / 3).max(1);
+    candidates
+        .map(|candidate| (edit_distance(&name, &candidate.to_lowercase()), candidate))
+        .filter(|(distance, _)| *distance <= allowed)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| format!(", did you mean {}?", candidate))
+        .unwrap_or_default()
+}
+
+/// The number of characters to insert, delete, replace or swap with their
+/// neighbor to turn one string into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // distances[i][j] is the distance between the first i and j characters
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    distances[0] = (0..=b.len()).collect();
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1)
+                .min(distances[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expression::parse_expression;
+    use crate::lexer::Tokens;
+
+    fn expr(text: &str) -> Expr {
+        parse_expression(&mut Tokens::new(text).unwrap()).unwrap()
+    }
+
+    fn scope() -> Schema {
+        Schema::new(vec![
+            Field::new(Some("users"), "id", DataType::Int64),
+            Field::new(Some("users"), "name", DataType::String),
+            Field::new(Some("orders"), "id", DataType::Int64),
+            Field::new(Some("orders"), "amount", DataType::Float64),
+            Field::new(Some("orders"), "created", DataType::Date),
+        ])
+    }
+
+    #[test]
+    fn ranks_suggestions_by_edit_distance() {
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("form", "from"), 1);
+        assert_eq!(edit_distance("ab", "ba"), 1);
+
+        let suggest = |name: &str, candidates: &[&str]| suggestion(name, candidates.iter().map(|c| c.to_string()));
+        // the closest candidate wins, the first of equally close ones
+        assert_eq!(suggest("nmae", &["names", "name", "age"]), ", did you mean name?");
+        assert_eq!(suggest("agee", &["ages", "age"]), ", did you mean ages?");
+        assert_eq!(suggest("NAME", &["Id", "Name"]), ", did you mean Name?");
+        // about a third of the letters may differ, and at least one
+        assert_eq!(suggest("amnt", &["amount"]), "");
+        assert_eq!(suggest("amout", &["amount"]), ", did you mean amount?");
+        assert_eq!(suggest("x", &["y"]), ", did you mean y?");
+        assert_eq!(suggest("x", &["id"]), "");
+        assert_eq!(suggest("createdat", &["created_at"]), ", did you mean created_at?");
+    }
+
+    #[test]
+    fn resolves_names_and_converts_operands() {
+        let scope = scope();
+        let bind = |text: &str| bind_expr(expr(text), &scope);
+        let cast = |text: &str| Expr::Cast {
+            expr: Box::new(expr(text)),
+            data_type: DataType::Float64,
+            safe: false,
+        };
+        assert_eq!(bind("amount > 1").unwrap(), expr("amount > 1.0"));
+        assert_eq!(bind("created < '2024-01-31'").unwrap(), expr("created < DATE '2024-01-31'"));
+        assert_eq!(bind("amount + users.id").unwrap(), Expr::binary(expr("amount"), BinaryOperator::Plus, cast("users.id")));
+        assert_eq!(
+            bind("COALESCE(orders.id, amount)").unwrap(),
+            Expr::Function {
+                name: "COALESCE".to_string(),
+                args: vec![cast("orders.id"), expr("amount")],
+            }
+        );
+        // a column compared with a number of another type is left to the
+        // comparison, and an untyped NULL fits any operand
+        assert_eq!(bind("users.id = amount").unwrap(), expr("users.id = amount"));
+        assert_eq!(bind("NULL + 1 > amount OR NOT NULL").unwrap(), expr("NULL + 1 > amount OR NOT NULL"));
+
+        for (text, error) in [
+            ("id", "Column id is ambiguous, it could be users.id or orders.id"),
+            ("users.nmae", "Unknown column users.nmae, did you mean users.name?"),
+            ("orders.name", "Unknown column orders.name, did you mean users.name?"),
+            ("amont", "Unknown column amont, did you mean amount?"),
+            ("total", "Unknown column total"),
+            ("CONCT(name)", "Unknown function CONCT, did you mean CONCAT?"),
+            ("name > 1", "Cannot compare STRING with INT64"),
+            ("created = amount", "Cannot compare DATE with FLOAT64"),
+            ("NOT users.id", "Cannot apply NOT to INT64"),
+            ("name - NULL", "Cannot apply - to STRING and NULL"),
+            ("name AND TRUE", "Cannot apply AND to STRING and BOOL"),
+        ] {
+            assert_eq!(bind(text).unwrap_err().message, error, "{}", text);
+        }
+    }
+}
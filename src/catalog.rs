@@ -0,0 +1,158 @@
+use crate::errors::ExecutionError;
+use crate::schema::{Field, Schema};
+use crate::statistics::{TableStatistics, DEFAULT_PAGE_COUNT};
+use crate::value::{DataType, Row};
+use serde::{Deserialize, Serialize};
+use sloth_db::storage::record_manager::RecordManager;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CATALOG_FILE: &str = "catalog.bin";
+const STATISTICS_FILE: &str = "statistics.bin";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Column {
+    pub name: String,
+    pub data_type: DataType,
+    pub nullable: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ReferentialAction {
+    Restrict,
+    Cascade,
+    SetNull,
+    /// Like RESTRICT, but checked once the statement is done
+    NoAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForeignKey {
+    pub columns: Vec<String>,
+    pub referenced_table: String,
+    pub referenced_columns: Vec<String>,
+    pub on_delete: ReferentialAction,
+    pub on_update: ReferentialAction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TableSchema {
+    pub name: String,
+    pub columns: Vec<Column>,
+    pub primary_key: Vec<String>,
+    pub foreign_keys: Vec<ForeignKey>,
+}
+
+impl TableSchema {
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns
+            .iter()
+            .position(|c| c.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Positions of the given columns, failing on the first unknown one.
+    pub fn column_indices(&self, names: &[String]) -> Result<Vec<usize>, ExecutionError> {
+        names
+            .iter()
+            .map(|name| {
+                self.column_index(name).ok_or_else(|| {
+                    ExecutionError::new(&format!("Table {} has no column {}", self.name, name))
+                })
+            })
+            .collect()
+    }
+
+    /// Row layout of the table with every column qualified by the table name.
+    pub fn to_schema(&self) -> Schema {
+        Schema::new(
+            self.columns
+                .iter()
+                .map(|c| Field::new(Some(&self.name), &c.name, c.data_type.clone()))
+                .collect(),
+        )
+    }
+}
+
+/// Table definitions of a database directory, persisted next to the table files.
+#[derive(Clone)]
+pub struct Catalog {
+    directory: PathBuf,
+    tables: BTreeMap<String, TableSchema>,
+    statistics: BTreeMap<String, TableStatistics>,
+}
+
+impl Catalog {
+    pub fn open(directory: &Path) -> Result<Self, ExecutionError> {
+        fs::create_dir_all(directory).map_err(|e| ExecutionError::new(&e.to_string()))?;
+        Ok(Self {
+            directory: directory.to_path_buf(),
+            tables: load(&directory.join(CATALOG_FILE))?,
+            statistics: load(&directory.join(STATISTICS_FILE))?,
+        })
+    }
+
+    fn save(&self) -> Result<(), ExecutionError> {
+        let bytes = bincode::serialize(&self.tables).map_err(|e| ExecutionError::new(&e.to_string()))?;
+        fs::write(self.directory.join(CATALOG_FILE), bytes).map_err(|e| ExecutionError::new(&e.to_string()))
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    pub fn get_table(&self, name: &str) -> Result<&TableSchema, ExecutionError> {
+        self.tables
+            .get(&name.to_lowercase())
+            .ok_or_else(|| ExecutionError::new(&format!("Table {} does not exist", name)))
+    }
+
+    pub fn tables(&self) -> impl Iterator<Item = &TableSchema> {
+        self.tables.values()
+    }
+
+    pub fn add_table(&mut self, table: TableSchema) -> Result<(), ExecutionError> {
+        let key = table.name.to_lowercase();
+        if self.tables.contains_key(&key) {
+            return Err(ExecutionError::new(&format!("Table {} already exists", table.name)));
+        }
+        self.tables.insert(key, table);
+        self.save()
+    }
+
+    /// Statistics gathered by ANALYZE, if the table was ever analyzed.
+    pub fn stored_statistics(&self, name: &str) -> Option<&TableStatistics> {
+        self.statistics.get(&name.to_lowercase())
+    }
+
+    pub fn set_statistics(&mut self, name: &str, statistics: TableStatistics) -> Result<(), ExecutionError> {
+        self.statistics.insert(name.to_lowercase(), statistics);
+        let bytes = bincode::serialize(&self.statistics).map_err(|e| ExecutionError::new(&e.to_string()))?;
+        fs::write(self.directory.join(STATISTICS_FILE), bytes).map_err(|e| ExecutionError::new(&e.to_string()))
+    }
+
+    /// Collected statistics of the table, or an estimate from its size on disk.
+    pub fn table_statistics(&self, name: &str) -> TableStatistics {
+        if let Some(statistics) = self.stored_statistics(name) {
+            return statistics.clone();
+        }
+        match self.open_table(name) {
+            Ok(records) => TableStatistics::from_page_count(records.page_count() as u64),
+            Err(_) => TableStatistics::from_page_count(DEFAULT_PAGE_COUNT),
+        }
+    }
+
+    pub fn open_table(&self, name: &str) -> Result<RecordManager<Row>, ExecutionError> {
+        let table = self.get_table(name)?;
+        let path = self.directory.join(format!("{}.tbl", table.name.to_lowercase()));
+        Ok(RecordManager::new(&path))
+    }
+}
+
+fn load<T: serde::de::DeserializeOwned + Default>(path: &Path) -> Result<T, ExecutionError> {
+    if !path.exists() {
+        return Ok(T::default());
+    }
+    let bytes = fs::read(path).map_err(|e| ExecutionError::new(&e.to_string()))?;
+    bincode::deserialize(&bytes).map_err(|e| ExecutionError::new(&e.to_string()))
+}
@@ -0,0 +1,254 @@
+use crate::catalog::Catalog;
+use crate::errors::ExecutionError;
+use crate::expression::Expr;
+use crate::logical_plan::LogicalPlan;
+use crate::schema::Field;
+use crate::set_operation::{set_operation_fields, SetOperator};
+
+/// `name AS (query)` of a WITH clause.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommonTableExpr {
+    /// Number the planner gives the table, unique within the query; zero
+    /// until then
+    pub id: usize,
+    pub name: String,
+    pub plan: LogicalPlan,
+}
+
+impl CommonTableExpr {
+    pub fn new(name: &str, plan: LogicalPlan) -> Self {
+        Self {
+            id: 0,
+            name: name.to_string(),
+            plan,
+        }
+    }
+}
+
+/// Computes each named table once, before the query that reads them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalWithNode {
+    pub tables: Vec<CommonTableExpr>,
+    /// `WITH RECURSIVE`: a table may read itself
+    pub recursive: bool,
+    pub input: Box<LogicalPlan>,
+}
+
+impl LogicalWithNode {
+    pub fn new(tables: Vec<CommonTableExpr>, recursive: bool, input: LogicalPlan) -> Self {
+        Self {
+            tables,
+            recursive,
+            input: Box::new(input),
+        }
+    }
+}
+
+/// Reads the rows of a table of a WITH clause, or the working table of a
+/// recursive one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalCommonTableScanNode {
+    pub id: usize,
+    pub name: String,
+    pub alias: Option<String>,
+    pub fields: Vec<Field>,
+}
+
+impl LogicalCommonTableScanNode {
+    fn new(id: usize, name: &str, alias: Option<&str>, fields: &[Field]) -> Self {
+        let qualifier = alias.unwrap_or(name);
+        Self {
+            id,
+            name: name.to_string(),
+            alias: alias.map(str::to_string),
+            fields: fields
+                .iter()
+                .map(|field| Field::new(Some(qualifier), &field.name, field.data_type.clone()))
+                .collect(),
+        }
+    }
+}
+
+/// A recursive table: the rows of `anchor`, then those `recursive` returns
+/// when it reads the rows added last as the working table `id`, until it
+/// returns no new rows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalRecursiveUnionNode {
+    pub id: usize,
+    pub name: String,
+    pub anchor: Box<LogicalPlan>,
+    pub recursive: Box<LogicalPlan>,
+    /// UNION ALL rather than UNION DISTINCT
+    pub all: bool,
+}
+
+/// Replaces the tables of FROM items that name a table of an enclosing WITH
+/// clause by scans of that table, and checks that recursive tables are a
+/// UNION of a query that does not read them and one that does. Tables no
+/// query reads are left out.
+pub fn resolve_common_tables(plan: LogicalPlan, catalog: &Catalog) -> Result<LogicalPlan, ExecutionError> {
+    Resolver {
+        catalog,
+        scope: Vec::new(),
+        count: 0,
+    }
+    .resolve(plan)
+}
+
+/// A table of a WITH clause the query can read.
+struct Binding {
+    name: String,
+    id: usize,
+    fields: Vec<Field>,
+    read: bool,
+}
+
+struct Resolver<'a> {
+    catalog: &'a Catalog,
+    /// Tables of the enclosing WITH clauses, innermost last
+    scope: Vec<Binding>,
+    count: usize,
+}
+
+impl Resolver<'_> {
+    fn resolve(&mut self, plan: LogicalPlan) -> Result<LogicalPlan, ExecutionError> {
+        match plan {
+            LogicalPlan::With(with) => {
+                let depth = self.scope.len();
+                let mut tables = Vec::new();
+                for mut table in with.tables {
+                    table.plan = if with.recursive && reads_table(&table.plan, &table.name) {
+                        self.resolve_recursive(&table.name, table.plan)?
+                    } else {
+                        self.resolve(table.plan)?
+                    };
+                    self.count += 1;
+                    table.id = self.count;
+                    self.scope.push(Binding {
+                        name: table.name.clone(),
+                        id: table.id,
+                        fields: table.plan.schema(self.catalog)?.fields,
+                        read: false,
+                    });
+                    tables.push(table);
+                }
+                let input = self.resolve(*with.input)?;
+                let bindings = self.scope.split_off(depth);
+                tables.retain(|table| bindings.iter().any(|binding| binding.id == table.id && binding.read));
+                if tables.is_empty() {
+                    return Ok(input);
+                }
+                Ok(LogicalPlan::With(LogicalWithNode::new(tables, with.recursive, input)))
+            }
+            LogicalPlan::Scan(scan) => {
+                let binding = self
+                    .scope
+                    .iter_mut()
+                    .rev()
+                    .find(|binding| binding.name.eq_ignore_ascii_case(&scan.table_name));
+                let Some(binding) = binding else {
+                    return Ok(LogicalPlan::Scan(scan));
+                };
+                if scan.sample.is_some() {
+                    return Err(ExecutionError::new(&format!("TABLESAMPLE needs a stored table, {} is a WITH table", binding.name)));
+                }
+                binding.read = true;
+                let scan = LogicalCommonTableScanNode::new(binding.id, &binding.name, scan.alias.as_deref(), &binding.fields);
+                Ok(LogicalPlan::CommonTableScan(scan))
+            }
+            plan => {
+                let mut error = None;
+                let plan = plan.map_inputs(|input| {
+                    self.resolve(input).unwrap_or_else(|e| {
+                        error.get_or_insert(e);
+                        LogicalPlan::OneRow
+                    })
+                });
+                let plan = plan.map_exprs(&mut |expr| {
+                    expr.transform(&mut |expr| {
+                        let mut resolve = |subquery: Box<LogicalPlan>| {
+                            Box::new(self.resolve(*subquery).unwrap_or_else(|e| {
+                                error.get_or_insert(e);
+                                LogicalPlan::OneRow
+                            }))
+                        };
+                        match expr {
+                            Expr::Subquery(subquery) => Expr::Subquery(resolve(subquery)),
+                            Expr::InSubquery { expr, subquery, negated } => Expr::InSubquery {
+                                expr,
+                                subquery: resolve(subquery),
+                                negated,
+                            },
+                            Expr::Exists { subquery, negated } => Expr::Exists {
+                                subquery: resolve(subquery),
+                                negated,
+                            },
+                            expr => expr,
+                        }
+                    })
+                });
+                match error {
+                    Some(error) => Err(error),
+                    None => Ok(plan),
+                }
+            }
+        }
+    }
+
+    fn resolve_recursive(&mut self, name: &str, plan: LogicalPlan) -> Result<LogicalPlan, ExecutionError> {
+        let set = match plan {
+            LogicalPlan::SetOperation(set) if set.operator == SetOperator::Union && !reads_table(&set.left, name) => set,
+            _ => {
+                return Err(ExecutionError::new(&format!(
+                    "The recursive table {} must be a query that does not read it followed by UNION and a query that does",
+                    name
+                )))
+            }
+        };
+        let anchor = self.resolve(*set.left)?;
+        let fields = anchor.schema(self.catalog)?.fields;
+        self.count += 1;
+        let id = self.count;
+        self.scope.push(Binding {
+            name: name.to_string(),
+            id,
+            fields: fields.clone(),
+            read: true,
+        });
+        let recursive = self.resolve(*set.right);
+        self.scope.pop();
+        let recursive = recursive?;
+        // the rows of every step are read as rows of the first part
+        let output = set_operation_fields(SetOperator::Union, &anchor.schema(self.catalog)?, &recursive.schema(self.catalog)?)?;
+        if output.iter().zip(&fields).any(|(output, field)| output.data_type != field.data_type) {
+            return Err(ExecutionError::new(&format!(
+                "The recursive part of {} must return the column types of the part before it",
+                name
+            )));
+        }
+        Ok(LogicalPlan::RecursiveUnion(LogicalRecursiveUnionNode {
+            id,
+            name: name.to_string(),
+            anchor: Box::new(anchor),
+            recursive: Box::new(recursive),
+            all: set.all,
+        }))
+    }
+}
+
+/// Whether the plan or one of its subqueries has a FROM item named `name`.
+fn reads_table(plan: &LogicalPlan, name: &str) -> bool {
+    if let LogicalPlan::Scan(scan) = plan {
+        return scan.table_name.eq_ignore_ascii_case(name);
+    }
+    let mut found = plan.inputs().into_iter().any(|input| reads_table(input, name));
+    plan.clone().map_exprs(&mut |expr| {
+        expr.transform(&mut |expr| {
+            if let Expr::Subquery(subquery) | Expr::InSubquery { subquery, .. } | Expr::Exists { subquery, .. } = &expr {
+                found |= reads_table(subquery, name);
+            }
+            expr
+        })
+    });
+    found
+}
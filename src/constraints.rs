@@ -0,0 +1,346 @@
+use crate::catalog::{Catalog, ForeignKey, ReferentialAction};
+use crate::errors::ExecutionError;
+use crate::expression::Expr;
+use crate::value::{Row, Value};
+use sloth_db::storage::record_manager::RecordId;
+use std::collections::{HashMap, HashSet};
+
+/// A row of a table as seen by the statement being executed.
+struct Entry {
+    // `None` for rows inserted by this statement
+    id: Option<RecordId>,
+    row: Row,
+    // the stored row, kept once the statement updates it
+    original: Option<Row>,
+    deleted: bool,
+    changed: bool,
+}
+
+/// Rows a committed statement wrote to one table; an update counts as a
+/// deletion of the old row plus an insertion of the new one.
+pub struct TableChanges {
+    pub table: String,
+    pub inserted: Vec<Row>,
+    pub deleted: Vec<Row>,
+}
+
+/// Pending modifications of one statement. Referential actions are applied as
+/// rows are changed; NOT NULL, primary and foreign keys and NO ACTION are
+/// validated against the final state in `commit`, so nothing is written unless
+/// every constraint holds.
+pub struct ChangeSet<'a> {
+    catalog: &'a Catalog,
+    tables: HashMap<String, Vec<Entry>>,
+}
+
+impl<'a> ChangeSet<'a> {
+    pub fn new(catalog: &'a Catalog) -> Self {
+        Self {
+            catalog,
+            tables: HashMap::new(),
+        }
+    }
+
+    fn entries(&mut self, table: &str) -> Result<&mut Vec<Entry>, ExecutionError> {
+        let key = table.to_lowercase();
+        if !self.tables.contains_key(&key) {
+            let mut records = self.catalog.open_table(table)?;
+            let entries = records
+                .scan_entries()
+                .map(|(id, row)| Entry {
+                    id: Some(id),
+                    row,
+                    original: None,
+                    deleted: false,
+                    changed: false,
+                })
+                .collect();
+            self.tables.insert(key.clone(), entries);
+        }
+        Ok(self.tables.get_mut(&key).unwrap())
+    }
+
+    pub fn row(&mut self, table: &str, index: usize) -> Result<Row, ExecutionError> {
+        Ok(self.entries(table)?[index].row.clone())
+    }
+
+    /// Indices of the live rows matching the filter.
+    pub fn find(&mut self, table: &str, filter: Option<&Expr>) -> Result<Vec<usize>, ExecutionError> {
+        let schema = self.catalog.get_table(table)?.to_schema();
+        let mut indices = Vec::new();
+        for (index, entry) in self.entries(table)?.iter().enumerate() {
+            if entry.deleted {
+                continue;
+            }
+            let matches = match filter {
+                Some(filter) => filter.is_satisfied(&schema, &entry.row)?,
+                None => true,
+            };
+            if matches {
+                indices.push(index);
+            }
+        }
+        Ok(indices)
+    }
+
+    /// Live rows of `table` whose `columns` hold exactly `key`.
+    fn find_key(&mut self, table: &str, columns: &[usize], key: &[Value]) -> Result<Vec<usize>, ExecutionError> {
+        Ok(self
+            .entries(table)?
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| !entry.deleted && project(&entry.row, columns) == key)
+            .map(|(index, _)| index)
+            .collect())
+    }
+
+    pub fn insert(&mut self, table: &str, row: Row) -> Result<(), ExecutionError> {
+        self.entries(table)?.push(Entry {
+            id: None,
+            row,
+            original: None,
+            deleted: false,
+            changed: true,
+        });
+        Ok(())
+    }
+
+    pub fn delete(&mut self, table: &str, index: usize) -> Result<(), ExecutionError> {
+        let entry = &mut self.entries(table)?[index];
+        if entry.deleted {
+            return Ok(());
+        }
+        entry.deleted = true;
+        let row = entry.row.clone();
+
+        for (child, foreign_key) in self.referencing(table) {
+            let key = self.referenced_key(&foreign_key, &row)?;
+            let child_columns = self.catalog.get_table(&child)?.column_indices(&foreign_key.columns)?;
+            for child_index in self.find_key(&child, &child_columns, &key)? {
+                match foreign_key.on_delete {
+                    ReferentialAction::Restrict => {
+                        return Err(still_referenced(table, &child, "delete"));
+                    }
+                    ReferentialAction::NoAction => {}
+                    ReferentialAction::Cascade => self.delete(&child, child_index)?,
+                    ReferentialAction::SetNull => {
+                        let mut child_row = self.row(&child, child_index)?;
+                        for &column in &child_columns {
+                            child_row[column] = Value::Null;
+                        }
+                        self.update(&child, child_index, child_row)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn update(&mut self, table: &str, index: usize, new_row: Row) -> Result<(), ExecutionError> {
+        let entry = &mut self.entries(table)?[index];
+        if entry.deleted {
+            return Ok(());
+        }
+        let old_row = std::mem::replace(&mut entry.row, new_row.clone());
+        if entry.id.is_some() && entry.original.is_none() {
+            entry.original = Some(old_row.clone());
+        }
+        entry.changed = true;
+
+        for (child, foreign_key) in self.referencing(table) {
+            let old_key = self.referenced_key(&foreign_key, &old_row)?;
+            let new_key = self.referenced_key(&foreign_key, &new_row)?;
+            if old_key == new_key {
+                continue;
+            }
+            let child_columns = self.catalog.get_table(&child)?.column_indices(&foreign_key.columns)?;
+            for child_index in self.find_key(&child, &child_columns, &old_key)? {
+                let mut child_row = self.row(&child, child_index)?;
+                match foreign_key.on_update {
+                    ReferentialAction::Restrict => {
+                        return Err(still_referenced(table, &child, "update"));
+                    }
+                    ReferentialAction::NoAction => continue,
+                    ReferentialAction::Cascade => {
+                        for (&column, value) in child_columns.iter().zip(&new_key) {
+                            child_row[column] = value.clone();
+                        }
+                    }
+                    ReferentialAction::SetNull => {
+                        for &column in &child_columns {
+                            child_row[column] = Value::Null;
+                        }
+                    }
+                }
+                self.update(&child, child_index, child_row)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Foreign keys of every table (including `table` itself) that point at `table`.
+    fn referencing(&self, table: &str) -> Vec<(String, ForeignKey)> {
+        self.catalog
+            .tables()
+            .flat_map(|child| {
+                child
+                    .foreign_keys
+                    .iter()
+                    .filter(|fk| fk.referenced_table.eq_ignore_ascii_case(table))
+                    .map(move |fk| (child.name.clone(), fk.clone()))
+            })
+            .collect()
+    }
+
+    fn referenced_key(&self, foreign_key: &ForeignKey, parent_row: &[Value]) -> Result<Vec<Value>, ExecutionError> {
+        let parent = self.catalog.get_table(&foreign_key.referenced_table)?;
+        let columns = parent.column_indices(&foreign_key.referenced_columns)?;
+        Ok(project(parent_row, &columns))
+    }
+
+    /// Validates the final state of every modified table and writes the changes.
+    pub fn commit(mut self) -> Result<Vec<TableChanges>, ExecutionError> {
+        let names: Vec<String> = self.tables.keys().cloned().collect();
+        for name in &names {
+            self.validate(name)?;
+        }
+
+        let mut changes = Vec::new();
+        for name in &names {
+            let mut records = self.catalog.open_table(name)?;
+            let mut table_changes = TableChanges {
+                table: name.clone(),
+                inserted: Vec::new(),
+                deleted: Vec::new(),
+            };
+            for entry in &self.tables[name] {
+                let stored = || entry.original.clone().unwrap_or_else(|| entry.row.clone());
+                match (entry.id, entry.deleted, entry.changed) {
+                    (Some(id), true, _) => {
+                        records.delete_record(id);
+                        table_changes.deleted.push(stored());
+                    }
+                    (Some(id), false, true) => {
+                        records.update_record(id, &entry.row)?;
+                        table_changes.deleted.push(stored());
+                        table_changes.inserted.push(entry.row.clone());
+                    }
+                    (None, false, _) => {
+                        records.insert_record(&entry.row)?;
+                        table_changes.inserted.push(entry.row.clone());
+                    }
+                    _ => {}
+                }
+            }
+            changes.push(table_changes);
+        }
+        Ok(changes)
+    }
+
+    fn validate(&mut self, table: &str) -> Result<(), ExecutionError> {
+        let schema = self.catalog.get_table(table)?.clone();
+        // NO ACTION only rejects a removed key that a row still references
+        // once the statement is done
+        for (child, foreign_key) in self.referencing(table) {
+            let parent_columns = schema.column_indices(&foreign_key.referenced_columns)?;
+            let child_columns = self.catalog.get_table(&child)?.column_indices(&foreign_key.columns)?;
+            let removed: Vec<(Vec<Value>, &str)> = self
+                .entries(table)?
+                .iter()
+                .filter(|entry| entry.id.is_some())
+                .filter_map(|entry| {
+                    let key = project(entry.original.as_ref().unwrap_or(&entry.row), &parent_columns);
+                    if entry.deleted {
+                        (foreign_key.on_delete == ReferentialAction::NoAction).then_some((key, "delete"))
+                    } else {
+                        let changed = project(&entry.row, &parent_columns) != key;
+                        (changed && foreign_key.on_update == ReferentialAction::NoAction).then_some((key, "update"))
+                    }
+                })
+                .collect();
+            for (key, operation) in removed {
+                if self.find_key(table, &parent_columns, &key)?.is_empty()
+                    && !self.find_key(&child, &child_columns, &key)?.is_empty()
+                {
+                    return Err(still_referenced(table, &child, operation));
+                }
+            }
+        }
+
+        let changed_rows: Vec<Row> = self
+            .entries(table)?
+            .iter()
+            .filter(|entry| entry.changed && !entry.deleted)
+            .map(|entry| entry.row.clone())
+            .collect();
+        if changed_rows.is_empty() {
+            return Ok(());
+        }
+
+        for row in &changed_rows {
+            for (column, value) in schema.columns.iter().zip(row) {
+                if !column.nullable && value.is_null() {
+                    return Err(ExecutionError::new(&format!(
+                        "Column {}.{} cannot be NULL",
+                        schema.name, column.name
+                    )));
+                }
+            }
+
+            for foreign_key in &schema.foreign_keys {
+                let key = project(row, &schema.column_indices(&foreign_key.columns)?);
+                // MATCH SIMPLE: a key with a NULL part references nothing
+                if key.iter().any(Value::is_null) {
+                    continue;
+                }
+                let parent_columns = self
+                    .catalog
+                    .get_table(&foreign_key.referenced_table)?
+                    .column_indices(&foreign_key.referenced_columns)?;
+                if self
+                    .find_key(&foreign_key.referenced_table, &parent_columns, &key)?
+                    .is_empty()
+                {
+                    return Err(ExecutionError::new(&format!(
+                        "Foreign key violation: {} {} has no matching row in {}",
+                        schema.name,
+                        format_key(&key),
+                        foreign_key.referenced_table
+                    )));
+                }
+            }
+        }
+
+        if !schema.primary_key.is_empty() {
+            let columns = schema.column_indices(&schema.primary_key)?;
+            let mut seen = HashSet::new();
+            for entry in self.entries(table)?.iter().filter(|entry| !entry.deleted) {
+                let key = project(&entry.row, &columns);
+                if !seen.insert(key.clone()) {
+                    return Err(ExecutionError::new(&format!(
+                        "Duplicate primary key {} in {}",
+                        format_key(&key),
+                        schema.name
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn project(row: &[Value], columns: &[usize]) -> Vec<Value> {
+    columns.iter().map(|&column| row[column].clone()).collect()
+}
+
+fn format_key(key: &[Value]) -> String {
+    let values: Vec<String> = key.iter().map(|value| value.to_string()).collect();
+    format!("({})", values.join(", "))
+}
+
+fn still_referenced(table: &str, child: &str, operation: &str) -> ExecutionError {
+    ExecutionError::new(&format!(
+        "Cannot {} row of {}: it is still referenced by {}",
+        operation, table, child
+    ))
+}
@@ -0,0 +1,244 @@
+use crate::catalog::{Catalog, TableSchema};
+use crate::expression::{BinaryOperator, Expr, UnaryOperator};
+use crate::physical_plan::JoinAlgorithm;
+use crate::statistics::{ColumnStatistics, TableStatistics};
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+/// Selectivity of predicates the estimator knows nothing about.
+const DEFAULT_SELECTIVITY: f64 = 0.1;
+/// Selectivity of a range predicate without a histogram.
+const DEFAULT_RANGE_SELECTIVITY: f64 = 1.0 / 3.0;
+/// Hashing a row is more expensive than reading it.
+const HASH_BUILD_FACTOR: f64 = 2.0;
+/// Elements of an array whose value is only known when the query runs.
+const DEFAULT_ARRAY_LENGTH: f64 = 4.0;
+
+/// Cardinality estimates for predicates over a fixed set of tables, each
+/// known by the qualifier its columns have in the query.
+pub struct Estimator {
+    tables: Vec<(String, TableStatistics, Option<TableSchema>)>,
+    /// Estimated rows of the tables of WITH clauses by their number, known
+    /// once their plans are converted
+    common_tables: RefCell<HashMap<usize, f64>>,
+}
+
+impl Estimator {
+    /// Takes (table, qualifier) pairs, so a table scanned under two aliases
+    /// appears twice.
+    pub fn new(catalog: &Catalog, tables: &[(String, String)]) -> Self {
+        Self {
+            tables: tables
+                .iter()
+                .map(|(name, qualifier)| {
+                    (
+                        qualifier.clone(),
+                        catalog.table_statistics(name),
+                        catalog.get_table(name).ok().cloned(),
+                    )
+                })
+                .collect(),
+            common_tables: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_common_table_rows(&self, id: usize, rows: f64) {
+        self.common_tables.borrow_mut().insert(id, rows);
+    }
+
+    /// Rows of the table of a WITH clause, at least one.
+    pub fn common_table_rows(&self, id: usize) -> f64 {
+        self.common_tables.borrow().get(&id).copied().unwrap_or(1.0).max(1.0)
+    }
+
+    /// Rows of the table with the given qualifier, at least one.
+    pub fn table_rows(&self, name: &str) -> f64 {
+        self.tables
+            .iter()
+            .position(|(table, _, _)| table.eq_ignore_ascii_case(name))
+            .map_or(1.0, |table| self.row_count(table))
+    }
+
+    fn row_count(&self, table: usize) -> f64 {
+        (self.tables[table].1.row_count as f64).max(1.0)
+    }
+
+    /// The table a column belongs to, if it can be determined.
+    fn column_table(&self, qualifier: Option<&str>, name: &str) -> Option<usize> {
+        if let Some(qualifier) = qualifier {
+            return self.tables.iter().position(|(table, _, _)| table.eq_ignore_ascii_case(qualifier));
+        }
+        if self.tables.len() == 1 {
+            return Some(0);
+        }
+        self.tables.iter().position(|(_, statistics, schema)| {
+            statistics.column(name).is_some()
+                || schema.as_ref().is_some_and(|schema| schema.column_index(name).is_some())
+        })
+    }
+
+    fn column_statistics(&self, expr: &Expr) -> Option<(usize, Option<&ColumnStatistics>)> {
+        match expr {
+            Expr::Column { qualifier, name } => {
+                let table = self.column_table(qualifier.as_deref(), name)?;
+                Some((table, self.tables[table].1.column(name)))
+            }
+            _ => None,
+        }
+    }
+
+    /// Number of distinct values of a column; unknown columns are assumed unique.
+    fn distinct_count(&self, expr: &Expr) -> Option<f64> {
+        let (table, statistics) = self.column_statistics(expr)?;
+        Some(match statistics {
+            Some(statistics) => statistics.distinct_count.max(1.0),
+            None => self.row_count(table),
+        })
+    }
+
+    /// Estimated number of groups of rows with distinct values of the
+    /// expressions, at most the number of input rows.
+    pub fn group_count(&self, exprs: &[Expr], input_rows: f64) -> f64 {
+        if exprs.is_empty() {
+            return 1.0;
+        }
+        exprs
+            .iter()
+            .map(|expr| self.distinct_count(expr).unwrap_or(input_rows * DEFAULT_SELECTIVITY))
+            .product::<f64>()
+            .clamp(1.0, input_rows.max(1.0))
+    }
+
+    /// Estimated fraction of rows satisfying the predicate.
+    pub fn selectivity(&self, predicate: &Expr) -> f64 {
+        match predicate {
+            Expr::Literal(Value::Bool(b)) => {
+                if *b {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Expr::Binary {
+                left,
+                op: BinaryOperator::And,
+                right,
+            } => self.selectivity(left) * self.selectivity(right),
+            Expr::Binary {
+                left,
+                op: BinaryOperator::Or,
+                right,
+            } => {
+                let (left, right) = (self.selectivity(left), self.selectivity(right));
+                left + right - left * right
+            }
+            Expr::Unary {
+                op: UnaryOperator::Not,
+                expr,
+            } => 1.0 - self.selectivity(expr),
+            Expr::IsNull { expr, negated } => {
+                let null_fraction = match self.column_statistics(expr) {
+                    Some((_, Some(statistics))) => statistics.null_fraction,
+                    _ => DEFAULT_SELECTIVITY,
+                };
+                if *negated {
+                    1.0 - null_fraction
+                } else {
+                    null_fraction
+                }
+            }
+            Expr::Binary { left, op, right } => self.comparison_selectivity(left, *op, right),
+            _ => DEFAULT_SELECTIVITY,
+        }
+    }
+
+    fn comparison_selectivity(&self, left: &Expr, op: BinaryOperator, right: &Expr) -> f64 {
+        // normalize `literal op column` to `column op' literal`
+        let (column, op, literal) = match (left, right) {
+            (Expr::Column { .. }, Expr::Column { .. }) => {
+                let equality = match (self.distinct_count(left), self.distinct_count(right)) {
+                    (Some(left), Some(right)) => 1.0 / left.max(right),
+                    _ => DEFAULT_SELECTIVITY,
+                };
+                return match op {
+                    BinaryOperator::Eq | BinaryOperator::IsNotDistinctFrom => equality,
+                    BinaryOperator::NotEq | BinaryOperator::IsDistinctFrom => 1.0 - equality,
+                    _ => DEFAULT_RANGE_SELECTIVITY,
+                };
+            }
+            (Expr::Column { .. }, Expr::Literal(value)) => (left, op, value),
+            (Expr::Literal(value), Expr::Column { .. }) => (right, mirror(op), value),
+            _ => return DEFAULT_SELECTIVITY,
+        };
+
+        let Some((table, statistics)) = self.column_statistics(column) else {
+            return DEFAULT_SELECTIVITY;
+        };
+        let non_null = 1.0 - statistics.map_or(0.0, |s| s.null_fraction);
+        let equality = match statistics {
+            Some(statistics) => non_null / statistics.distinct_count.max(1.0),
+            None => 1.0 / self.row_count(table).max(1.0 / DEFAULT_SELECTIVITY),
+        };
+        let below = |inclusive| {
+            statistics
+                .and_then(|s| s.histogram.as_ref())
+                .map(|histogram| non_null * histogram.fraction_below(literal, inclusive))
+        };
+
+        match op {
+            BinaryOperator::Eq | BinaryOperator::IsNotDistinctFrom => equality,
+            BinaryOperator::NotEq => non_null - equality,
+            BinaryOperator::IsDistinctFrom => 1.0 - equality,
+            BinaryOperator::Lt => below(false).unwrap_or(DEFAULT_RANGE_SELECTIVITY),
+            BinaryOperator::LtEq => below(true).unwrap_or(DEFAULT_RANGE_SELECTIVITY),
+            BinaryOperator::Gt => below(true).map_or(DEFAULT_RANGE_SELECTIVITY, |b| non_null - b),
+            BinaryOperator::GtEq => below(false).map_or(DEFAULT_RANGE_SELECTIVITY, |b| non_null - b),
+            _ => DEFAULT_SELECTIVITY,
+        }
+    }
+}
+
+/// The operator with its operands swapped: `a < b` is `b > a`.
+fn mirror(op: BinaryOperator) -> BinaryOperator {
+    match op {
+        BinaryOperator::Lt => BinaryOperator::Gt,
+        BinaryOperator::LtEq => BinaryOperator::GtEq,
+        BinaryOperator::Gt => BinaryOperator::Lt,
+        BinaryOperator::GtEq => BinaryOperator::LtEq,
+        op => op,
+    }
+}
+
+/// Cost of joining inputs of the given sizes; the right input is the build
+/// side of a hash join.
+pub fn join_cost(algorithm: JoinAlgorithm, left_rows: f64, right_rows: f64) -> f64 {
+    match algorithm {
+        JoinAlgorithm::NestedLoop => left_rows * right_rows,
+        JoinAlgorithm::Hash => HASH_BUILD_FACTOR * right_rows + left_rows,
+        JoinAlgorithm::SortMerge => sort_cost(left_rows) + sort_cost(right_rows) + left_rows + right_rows,
+    }
+}
+
+/// Cost of hashing every input row into its group.
+pub fn aggregate_cost(input_rows: f64) -> f64 {
+    HASH_BUILD_FACTOR * input_rows
+}
+
+/// Elements UNNEST produces for every row; constructors list theirs.
+pub fn array_length(array: &Expr) -> f64 {
+    match array {
+        Expr::Literal(Value::Array(items)) => items.len() as f64,
+        Expr::Function { name, args } if name == "ARRAY" => args.len() as f64,
+        _ => DEFAULT_ARRAY_LENGTH,
+    }
+}
+
+pub fn sort_cost(rows: f64) -> f64 {
+    rows * rows.max(2.0).log2()
+}
+
+/// Cost of keeping the first `kept` of `rows` rows in a bounded heap.
+pub fn top_n_cost(rows: f64, kept: f64) -> f64 {
+    rows * kept.min(rows).max(2.0).log2()
+}
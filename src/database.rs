@@ -0,0 +1,404 @@
+use crate::analyze::{analyze_table, apply_changes};
+use crate::binder::bind;
+use crate::catalog::{Catalog, ReferentialAction, TableSchema};
+use crate::common_table::resolve_common_tables;
+use crate::constraints::{ChangeSet, TableChanges};
+use crate::errors::ExecutionError;
+use crate::executor::{self, CommonTables, ExecutionContext, QueryResult, DEFAULT_MEMORY_BUDGET};
+use crate::expression::Expr;
+use crate::logical_plan::LogicalPlan;
+use crate::nested::resolve_fields;
+use crate::optimizer::optimize;
+use crate::physical_plan::convert_to_physical_plan;
+use crate::schema::Schema;
+use crate::statement::Statement;
+use crate::subquery::unnest_subqueries;
+use crate::value::{Row, Value};
+use std::path::Path;
+use std::time::Instant;
+
+pub struct Database {
+    catalog: Catalog,
+    memory_budget: usize,
+}
+
+impl Database {
+    pub fn open(directory: &Path) -> Result<Self, ExecutionError> {
+        Ok(Self {
+            catalog: Catalog::open(directory)?,
+            memory_budget: DEFAULT_MEMORY_BUDGET,
+        })
+    }
+
+    /// Bytes each operator may hold in memory before spilling to disk.
+    pub fn set_memory_budget(&mut self, bytes: usize) {
+        self.memory_budget = bytes;
+    }
+
+    fn context(&self) -> ExecutionContext<'_> {
+        ExecutionContext {
+            catalog: &self.catalog,
+            memory_budget: self.memory_budget,
+            common_tables: CommonTables::default(),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn catalog(&self) -> &Catalog {
+        &self.catalog
+    }
+
+    /// Runs a statement and describes the outcome; queries return their rows as a table.
+    pub fn execute(&mut self, statement: Statement) -> Result<String, ExecutionError> {
+        match statement {
+            Statement::CreateTable(table) => {
+                let name = table.name.clone();
+                self.create_table(table)?;
+                Ok(format!("Created table {}", name))
+            }
+            Statement::Insert { table, columns, rows } => {
+                let count = self.insert(&table, columns, rows)?;
+                Ok(format!("Inserted {} row(s) into {}", count, table))
+            }
+            Statement::Update {
+                table,
+                assignments,
+                filter,
+            } => {
+                let count = self.update(&table, &assignments, filter.as_ref())?;
+                Ok(format!("Updated {} row(s) in {}", count, table))
+            }
+            Statement::Delete { table, filter } => {
+                let count = self.delete(&table, filter.as_ref())?;
+                Ok(format!("Deleted {} row(s) from {}", count, table))
+            }
+            Statement::Analyze { table, sample_percent } => self.analyze(table, sample_percent),
+            Statement::Set { name, value } => self.set(&name, &value),
+            Statement::Query(plan) => Ok(self.query(plan)?.to_string()),
+            Statement::Explain { analyze, query, plan } => self.explain(&query, plan, analyze),
+        }
+    }
+
+    fn set(&mut self, name: &str, value: &Expr) -> Result<String, ExecutionError> {
+        let value = value.evaluate(&Schema::default(), &[])?;
+        match (name.to_lowercase().as_str(), value) {
+            ("memory_budget", Value::Int(bytes)) if bytes > 0 => {
+                self.set_memory_budget(bytes as usize);
+                Ok(format!("Set memory_budget to {} bytes", bytes))
+            }
+            ("memory_budget", value) => Err(ExecutionError::new(&format!(
+                "memory_budget must be a positive number of bytes, got {}",
+                value
+            ))),
+            (name, _) => Err(ExecutionError::new(&format!("Unknown setting {}", name))),
+        }
+    }
+
+    pub fn query(&self, plan: LogicalPlan) -> Result<QueryResult, ExecutionError> {
+        let plan = resolve_common_tables(plan, &self.catalog)?;
+        let plan = unnest_subqueries(plan, &self.catalog)?;
+        let plan = resolve_fields(plan, &self.catalog);
+        let plan = bind(plan, &self.catalog)?;
+        let plan = optimize(plan, &self.catalog);
+        let physical_plan = convert_to_physical_plan(&plan, &self.catalog);
+        let mut root = executor::build(&physical_plan.root, &self.context())?;
+        executor::execute(&mut root)
+    }
+
+    /// The physical plan with its estimates; with ANALYZE the query is run and
+    /// every operator also shows what it actually did.
+    fn explain(&self, query: &str, plan: LogicalPlan, analyze: bool) -> Result<String, ExecutionError> {
+        let plan = resolve_common_tables(plan, &self.catalog)?;
+        let plan = unnest_subqueries(plan, &self.catalog)?;
+        let plan = resolve_fields(plan, &self.catalog);
+        let plan = bind(plan, &self.catalog)?;
+        let plan = optimize(plan, &self.catalog);
+        let physical_plan = convert_to_physical_plan(&plan, &self.catalog);
+        if !analyze {
+            return Ok(format!("Plan for `{}`:\n{}", query, physical_plan));
+        }
+
+        let mut root = executor::build(&physical_plan.root, &self.context())?;
+        let start = Instant::now();
+        let result = executor::execute(&mut root)?;
+        Ok(format!(
+            "Plan for `{}`:\n{}{} row(s) in {:.3} ms",
+            query,
+            root,
+            result.rows.len(),
+            start.elapsed().as_secs_f64() * 1000.0
+        ))
+    }
+
+    fn create_table(&mut self, mut table: TableSchema) -> Result<(), ExecutionError> {
+        for (i, column) in table.columns.iter().enumerate() {
+            if table.column_index(&column.name) != Some(i) {
+                return Err(ExecutionError::new(&format!("Duplicate column {}", column.name)));
+            }
+        }
+        for index in table.column_indices(&table.primary_key)? {
+            table.columns[index].nullable = false;
+        }
+
+        for i in 0..table.foreign_keys.len() {
+            let self_reference = table.foreign_keys[i]
+                .referenced_table
+                .eq_ignore_ascii_case(&table.name);
+            let parent = if self_reference {
+                table.clone()
+            } else {
+                self.catalog.get_table(&table.foreign_keys[i].referenced_table)?.clone()
+            };
+
+            let foreign_key = &mut table.foreign_keys[i];
+            if parent.primary_key.is_empty() {
+                return Err(ExecutionError::new(&format!(
+                    "Referenced table {} has no primary key",
+                    parent.name
+                )));
+            }
+            // REFERENCES parent without a column list points at its primary key
+            if foreign_key.referenced_columns.is_empty() {
+                foreign_key.referenced_columns = parent.primary_key.clone();
+            }
+            if foreign_key.columns.len() != foreign_key.referenced_columns.len() {
+                return Err(ExecutionError::new(&format!(
+                    "Foreign key has {} columns but the key it references in {} has {}",
+                    foreign_key.columns.len(),
+                    parent.name,
+                    foreign_key.referenced_columns.len()
+                )));
+            }
+            let mut referenced: Vec<String> = foreign_key.referenced_columns.iter().map(|c| c.to_lowercase()).collect();
+            let mut primary_key: Vec<String> = parent.primary_key.iter().map(|c| c.to_lowercase()).collect();
+            referenced.sort();
+            primary_key.sort();
+            if referenced != primary_key {
+                return Err(ExecutionError::new(&format!(
+                    "Foreign key must reference the primary key of {}",
+                    parent.name
+                )));
+            }
+            foreign_key.referenced_table = parent.name.clone();
+
+            let foreign_key = &table.foreign_keys[i];
+            let child_columns = table.column_indices(&foreign_key.columns)?;
+            let parent_columns = parent.column_indices(&foreign_key.referenced_columns)?;
+            for (&child_column, &parent_column) in child_columns.iter().zip(&parent_columns) {
+                let (child, parent) = (&table.columns[child_column], &parent.columns[parent_column]);
+                if child.data_type != parent.data_type {
+                    return Err(ExecutionError::new(&format!(
+                        "Foreign key column {} is {} but references {} of type {}",
+                        child.name, child.data_type, parent.name, parent.data_type
+                    )));
+                }
+                let sets_null = foreign_key.on_delete == ReferentialAction::SetNull
+                    || foreign_key.on_update == ReferentialAction::SetNull;
+                if sets_null && !child.nullable {
+                    return Err(ExecutionError::new(&format!(
+                        "SET NULL action on NOT NULL column {}",
+                        child.name
+                    )));
+                }
+            }
+        }
+
+        self.catalog.add_table(table)
+    }
+
+    fn insert(&mut self, table: &str, columns: Option<Vec<String>>, rows: Vec<Vec<Expr>>) -> Result<usize, ExecutionError> {
+        let schema = self.catalog.get_table(table)?.clone();
+        let targets = match columns {
+            Some(columns) => schema.column_indices(&columns)?,
+            None => (0..schema.columns.len()).collect(),
+        };
+
+        let mut changes = ChangeSet::new(&self.catalog);
+        let count = rows.len();
+        for values in rows {
+            if values.len() != targets.len() {
+                return Err(ExecutionError::new(&format!(
+                    "Expected {} values but got {}",
+                    targets.len(),
+                    values.len()
+                )));
+            }
+            let mut row: Row = vec![Value::Null; schema.columns.len()];
+            for (&target, expr) in targets.iter().zip(&values) {
+                let value = expr.evaluate(&Schema::default(), &[])?;
+                row[target] = value.coerce_to(schema.columns[target].data_type.clone())?;
+            }
+            changes.insert(table, row)?;
+        }
+        let changes = changes.commit()?;
+        self.record_changes(changes)?;
+        Ok(count)
+    }
+
+    fn update(&mut self, table: &str, assignments: &[(String, Expr)], filter: Option<&Expr>) -> Result<usize, ExecutionError> {
+        let table_schema = self.catalog.get_table(table)?.clone();
+        let schema = table_schema.to_schema();
+        let targets: Vec<String> = assignments.iter().map(|(column, _)| column.clone()).collect();
+        let targets = table_schema.column_indices(&targets)?;
+
+        let mut changes = ChangeSet::new(&self.catalog);
+        let indices = changes.find(table, filter)?;
+        for &index in &indices {
+            let old_row = changes.row(table, index)?;
+            let mut new_row = old_row.clone();
+            for (&target, (_, expr)) in targets.iter().zip(assignments) {
+                let value = expr.evaluate(&schema, &old_row)?;
+                new_row[target] = value.coerce_to(table_schema.columns[target].data_type.clone())?;
+            }
+            changes.update(table, index, new_row)?;
+        }
+        let changes = changes.commit()?;
+        self.record_changes(changes)?;
+        Ok(indices.len())
+    }
+
+    /// Analyzes one table, or every table whose statistics are missing or stale.
+    fn analyze(&mut self, table: Option<String>, sample_percent: Option<f64>) -> Result<String, ExecutionError> {
+        let tables: Vec<String> = match table {
+            Some(table) => vec![self.catalog.get_table(&table)?.name.clone()],
+            None => self
+                .catalog
+                .tables()
+                .filter(|t| self.catalog.stored_statistics(&t.name).is_none_or(|s| s.is_stale()))
+                .map(|t| t.name.clone())
+                .collect(),
+        };
+
+        for table in &tables {
+            let statistics = analyze_table(&self.catalog, table, sample_percent)?;
+            self.catalog.set_statistics(table, statistics)?;
+        }
+        Ok(format!("Analyzed {} table(s)", tables.len()))
+    }
+
+    /// Keeps collected statistics current after a statement wrote rows.
+    fn record_changes(&mut self, changes: Vec<TableChanges>) -> Result<(), ExecutionError> {
+        for change in changes {
+            let Some(mut statistics) = self.catalog.stored_statistics(&change.table).cloned() else {
+                continue;
+            };
+            let schema = self.catalog.get_table(&change.table)?;
+            let page_count = self.catalog.open_table(&change.table)?.page_count() as u64;
+            apply_changes(&mut statistics, schema, &change.inserted, &change.deleted, page_count);
+            self.catalog.set_statistics(&change.table, statistics)?;
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, table: &str, filter: Option<&Expr>) -> Result<usize, ExecutionError> {
+        let mut changes = ChangeSet::new(&self.catalog);
+        let indices = changes.find(table, filter)?;
+        for &index in &indices {
+            changes.delete(table, index)?;
+        }
+        let changes = changes.commit()?;
+        self.record_changes(changes)?;
+        Ok(indices.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Database;
+    use crate::parser::parse_statement;
+    use crate::value::{Row, Value};
+    use tempfile::tempdir;
+
+    fn run(database: &mut Database, sql: &str) -> Result<String, String> {
+        let statement = parse_statement(sql).map_err(|e| e.to_string())?;
+        database.execute(statement).map_err(|e| e.to_string())
+    }
+
+    fn rows(database: &Database, table: &str) -> Vec<Row> {
+        database.catalog.open_table(table).unwrap().scan_records().collect()
+    }
+
+    fn setup(on_delete: &str) -> (tempfile::TempDir, Database) {
+        let dir = tempdir().unwrap();
+        let mut database = Database::open(dir.path()).unwrap();
+        run(&mut database, "CREATE TABLE customers (id INT64 PRIMARY KEY, name STRING)").unwrap();
+        run(
+            &mut database,
+            &format!(
+                "CREATE TABLE orders (id INT64 PRIMARY KEY, customer_id INT64, \
+                 FOREIGN KEY (customer_id) REFERENCES customers ON DELETE {} ON UPDATE CASCADE)",
+                on_delete
+            ),
+        )
+        .unwrap();
+        run(&mut database, "INSERT INTO customers VALUES (1, 'Alice'), (2, 'Bob')").unwrap();
+        run(&mut database, "INSERT INTO orders VALUES (10, 1), (11, 1), (12, 2)").unwrap();
+        (dir, database)
+    }
+
+    #[test]
+    fn rejects_orphan_child_rows() {
+        let (_dir, mut database) = setup("RESTRICT");
+
+        let result = run(&mut database, "INSERT INTO orders VALUES (13, 3)");
+        assert!(result.unwrap_err().contains("Foreign key violation"));
+        let result = run(&mut database, "UPDATE orders SET customer_id = 7 WHERE id = 10");
+        assert!(result.is_err());
+        // a NULL key references nothing and is allowed
+        run(&mut database, "INSERT INTO orders (id) VALUES (14)").unwrap();
+        assert_eq!(rows(&database, "orders").len(), 4);
+
+        let result = run(
+            &mut database,
+            "CREATE TABLE returns (order_id INT64, customer_id INT64, FOREIGN KEY (order_id, customer_id) REFERENCES orders)",
+        );
+        assert!(result.unwrap_err().contains("Foreign key has 2 columns but the key it references in orders has 1"));
+    }
+
+    #[test]
+    fn restrict_keeps_referenced_parent() {
+        let (_dir, mut database) = setup("RESTRICT");
+
+        assert!(run(&mut database, "DELETE FROM customers WHERE id = 1").is_err());
+        assert_eq!(rows(&database, "customers").len(), 2);
+        run(&mut database, "DELETE FROM orders WHERE customer_id = 1").unwrap();
+        run(&mut database, "DELETE FROM customers WHERE id = 1").unwrap();
+        assert_eq!(rows(&database, "customers").len(), 1);
+    }
+
+    #[test]
+    fn no_action_checks_references_once_the_statement_is_done() {
+        let dir = tempdir().unwrap();
+        let mut database = Database::open(dir.path()).unwrap();
+        run(
+            &mut database,
+            "CREATE TABLE employees (id INT64 PRIMARY KEY, manager INT64, \
+             FOREIGN KEY (manager) REFERENCES employees ON DELETE NO ACTION ON UPDATE NO ACTION)",
+        )
+        .unwrap();
+        run(&mut database, "INSERT INTO employees VALUES (1, NULL), (2, 1), (3, 2)").unwrap();
+
+        let result = run(&mut database, "DELETE FROM employees WHERE id = 2");
+        assert!(result.unwrap_err().contains("Cannot delete row of employees: it is still referenced by employees"));
+        assert!(run(&mut database, "UPDATE employees SET id = 4 WHERE id = 1").is_err());
+        // the key a row gives up is taken by another row in the same statement
+        run(&mut database, "UPDATE employees SET id = 5 - id WHERE id > 1").unwrap();
+        assert_eq!(rows(&database, "employees")[1..], [vec![Value::Int(3), Value::Int(1)], vec![Value::Int(2), Value::Int(2)]]);
+        run(&mut database, "DELETE FROM employees").unwrap();
+        assert!(rows(&database, "employees").is_empty());
+    }
+
+    #[test]
+    fn cascade_and_set_null_propagate_to_children() {
+        let (_dir, mut database) = setup("CASCADE");
+        run(&mut database, "UPDATE customers SET id = 5 WHERE id = 2").unwrap();
+        assert!(rows(&database, "orders").contains(&vec![Value::Int(12), Value::Int(5)]));
+        run(&mut database, "DELETE FROM customers WHERE name = 'Alice'").unwrap();
+        assert_eq!(rows(&database, "orders"), vec![vec![Value::Int(12), Value::Int(5)]]);
+
+        let (_dir, mut database) = setup("SET NULL");
+        run(&mut database, "DELETE FROM customers WHERE id = 1").unwrap();
+        let orders = rows(&database, "orders");
+        assert_eq!(orders.iter().filter(|row| row[1].is_null()).count(), 2);
+    }
+}
@@ -0,0 +1,450 @@
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::SerializeTuple;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+/// Most digits a DECIMAL holds, and the precision of NUMERIC.
+pub const MAX_PRECISION: u8 = 38;
+/// Scale of NUMERIC without a precision and scale.
+pub const DEFAULT_SCALE: u8 = 9;
+/// Fractional digits a quotient gets beyond those of its operands.
+const DIVISION_DIGITS: u8 = 6;
+
+/// How a value with more fractional digits than a scale allows is rounded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RoundingMode {
+    HalfAwayFromZero,
+    HalfEven,
+    TowardZero,
+    Floor,
+    Ceiling,
+}
+
+impl RoundingMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "ROUND_HALF_AWAY_FROM_ZERO" => Some(RoundingMode::HalfAwayFromZero),
+            "ROUND_HALF_EVEN" => Some(RoundingMode::HalfEven),
+            "ROUND_TOWARD_ZERO" => Some(RoundingMode::TowardZero),
+            "ROUND_FLOOR" => Some(RoundingMode::Floor),
+            "ROUND_CEILING" => Some(RoundingMode::Ceiling),
+            _ => None,
+        }
+    }
+}
+
+/// An exact decimal number, `mantissa / 10^scale`, of at most 38 digits.
+/// Values of a DECIMAL(p, s) column all have scale `s`.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    mantissa: i128,
+    scale: u8,
+}
+
+const fn pow10(exponent: u8) -> i128 {
+    10i128.pow(exponent as u32)
+}
+
+/// Whether a quotient truncated toward zero, with a non-zero remainder,
+/// moves one away from zero when rounded by the mode.
+fn rounds_away(quotient: i128, remainder: i128, divisor: i128, negative: bool, mode: RoundingMode) -> bool {
+    // compared without doubling the remainder, which could overflow
+    let (remainder, divisor) = (remainder.abs(), divisor.abs());
+    let half = remainder.cmp(&(divisor - remainder));
+    match mode {
+        RoundingMode::HalfAwayFromZero => half != Ordering::Less,
+        RoundingMode::HalfEven => half == Ordering::Greater || (half == Ordering::Equal && quotient % 2 != 0),
+        RoundingMode::TowardZero => false,
+        RoundingMode::Floor => negative,
+        RoundingMode::Ceiling => !negative,
+    }
+}
+
+/// `a * b` in four 64-bit limbs, the least significant first.
+fn widening_mul(a: u128, b: u128) -> [u64; 4] {
+    let (a, b) = ([a as u64, (a >> 64) as u64], [b as u64, (b >> 64) as u64]);
+    let mut limbs = [0u64; 4];
+    for i in 0..2 {
+        let mut carry = 0u128;
+        for j in 0..2 {
+            let t = a[i] as u128 * b[j] as u128 + limbs[i + j] as u128 + carry;
+            limbs[i + j] = t as u64;
+            carry = t >> 64;
+        }
+        limbs[i + 2] = carry as u64;
+    }
+    limbs
+}
+
+/// Divides the limbs by ten and returns the digit dropped.
+fn divide_by_ten(limbs: &mut [u64; 4]) -> u64 {
+    let mut remainder = 0u128;
+    for limb in limbs.iter_mut().rev() {
+        let current = remainder << 64 | *limb as u128;
+        *limb = (current / 10) as u64;
+        remainder = current % 10;
+    }
+    remainder as u64
+}
+
+/// `n / d` for a positive `d`, rounded by the mode.
+fn divide_rounded(n: i128, d: i128, mode: RoundingMode) -> i128 {
+    let (quotient, remainder) = (n / d, n % d);
+    if remainder != 0 && rounds_away(quotient, remainder, d, n < 0, mode) {
+        quotient + n.signum()
+    } else {
+        quotient
+    }
+}
+
+impl Decimal {
+    /// `None` when the value has more than 38 digits.
+    fn new(mantissa: i128, scale: u8) -> Option<Self> {
+        (mantissa.unsigned_abs() < pow10(MAX_PRECISION) as u128 && scale <= MAX_PRECISION).then_some(Self { mantissa, scale })
+    }
+
+    pub fn from_int(i: i64) -> Self {
+        Self {
+            mantissa: i as i128,
+            scale: 0,
+        }
+    }
+
+    /// Reads `[+|-]digits[.digits]`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let valid = !(whole.is_empty() && fraction.is_empty())
+            && whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit())
+            && fraction.len() <= MAX_PRECISION as usize;
+        if !valid {
+            return None;
+        }
+        let whole = whole.trim_start_matches('0');
+        if whole.len() + fraction.len() > MAX_PRECISION as usize {
+            return None;
+        }
+        let mantissa: i128 = format!("0{}{}", whole, fraction).parse().ok()?;
+        Self::new(if negative { -mantissa } else { mantissa }, fraction.len() as u8)
+    }
+
+    /// The exact value of a float as it is written, e.g. 0.1 for 0.1.
+    pub fn from_f64(x: f64) -> Option<Self> {
+        if !x.is_finite() {
+            return None;
+        }
+        Self::parse(&x.to_string())
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.mantissa as f64 / pow10(self.scale) as f64
+    }
+
+    /// The integer the value rounds to; `None` outside the INT64 range.
+    pub fn to_i64(self, mode: RoundingMode) -> Option<i64> {
+        divide_rounded(self.mantissa, pow10(self.scale), mode).try_into().ok()
+    }
+
+    pub fn scale(self) -> u8 {
+        self.scale
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.mantissa == 0
+    }
+
+    /// Digits before the decimal point.
+    pub fn integer_digits(self) -> u8 {
+        let mut whole = (self.mantissa / pow10(self.scale)).unsigned_abs();
+        let mut digits = 0;
+        while whole > 0 {
+            whole /= 10;
+            digits += 1;
+        }
+        digits
+    }
+
+    /// The value with `scale` fractional digits, rounded by the mode;
+    /// `None` when that takes more than 38 digits.
+    pub fn rescale(self, scale: u8, mode: RoundingMode) -> Option<Self> {
+        match scale.cmp(&self.scale) {
+            Ordering::Equal => Some(self),
+            Ordering::Greater => Self::new(self.mantissa.checked_mul(pow10(scale - self.scale))?, scale),
+            Ordering::Less => Self::new(divide_rounded(self.mantissa, pow10(self.scale - scale), mode), scale),
+        }
+    }
+
+    /// Rounds to `digits` fractional digits, or to tens, hundreds, ... for
+    /// negative `digits`, keeping the scale.
+    pub fn round(self, digits: i64, mode: RoundingMode) -> Option<Self> {
+        if digits >= self.scale as i64 {
+            return Some(self);
+        }
+        let dropped = (self.scale as i64 - digits).min(MAX_PRECISION as i64) as u8;
+        let rounded = divide_rounded(self.mantissa, pow10(dropped), mode);
+        Self::new(rounded.checked_mul(pow10(dropped))?, self.scale)
+    }
+
+    pub fn neg(self) -> Self {
+        Self {
+            mantissa: -self.mantissa,
+            scale: self.scale,
+        }
+    }
+
+    pub fn abs(self) -> Self {
+        Self {
+            mantissa: self.mantissa.abs(),
+            scale: self.scale,
+        }
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        let scale = self.scale.max(other.scale);
+        let (a, b) = (
+            self.rescale(scale, RoundingMode::TowardZero)?,
+            other.rescale(scale, RoundingMode::TowardZero)?,
+        );
+        Self::new(a.mantissa.checked_add(b.mantissa)?, scale)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.checked_add(other.neg())
+    }
+
+    /// The product with `product_scale` fractional digits, rounded half away
+    /// from zero. The mantissas are multiplied in 256 bits, so a product
+    /// only fails when its rounded value has more than 38 digits.
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        let scale = Self::product_scale(self.scale, other.scale);
+        let mut limbs = widening_mul(self.mantissa.unsigned_abs(), other.mantissa.unsigned_abs());
+        let mut dropped = 0;
+        for _ in scale..self.scale + other.scale {
+            dropped = divide_by_ten(&mut limbs);
+        }
+        if limbs[2] != 0 || limbs[3] != 0 {
+            return None;
+        }
+        let magnitude = (limbs[1] as u128) << 64 | limbs[0] as u128;
+        let magnitude = i128::try_from(magnitude + (dropped >= 5) as u128).ok()?;
+        let negative = (self.mantissa < 0) != (other.mantissa < 0);
+        Self::new(if negative { -magnitude } else { magnitude }, scale)
+    }
+
+    /// The quotient with `scale` fractional digits, rounded by the mode;
+    /// `scale` is at least that of `self`. `None` for a zero divisor or a
+    /// quotient of more than 38 digits.
+    pub fn checked_div(self, other: Self, scale: u8, mode: RoundingMode) -> Option<Self> {
+        if other.mantissa == 0 || scale < self.scale {
+            return None;
+        }
+        let negative = (self.mantissa < 0) != (other.mantissa < 0);
+        let (n, d) = (self.mantissa.abs(), other.mantissa.abs());
+        // long division, one digit of the quotient at a time, so that
+        // the dividend is never scaled up
+        let (mut quotient, mut remainder) = (n / d, n % d);
+        for _ in 0..scale - self.scale + other.scale {
+            let shifted = remainder.checked_mul(10)?;
+            quotient = quotient.checked_mul(10)?.checked_add(shifted / d)?;
+            remainder = shifted % d;
+        }
+        if remainder != 0 && rounds_away(quotient, remainder, d, negative, mode) {
+            quotient = quotient.checked_add(1)?;
+        }
+        Self::new(if negative { -quotient } else { quotient }, scale)
+    }
+
+    /// Scale of a product of operands with these scales: that of the exact
+    /// product, up to the NUMERIC scale or the larger scale of the operands.
+    pub fn product_scale(left: u8, right: u8) -> u8 {
+        (left + right).min(left.max(right).max(DEFAULT_SCALE))
+    }
+
+    /// Scale of a quotient of operands with these scales: six digits more
+    /// than the larger scale, within the same bound as a product.
+    pub fn quotient_scale(left: u8, right: u8) -> u8 {
+        (left.max(right) + DIVISION_DIGITS).min(left.max(right).max(DEFAULT_SCALE))
+    }
+
+    /// The mantissa without trailing zeros, the same for equal values.
+    fn normalized(self) -> (i128, u8) {
+        let (mut mantissa, mut scale) = (self.mantissa, self.scale);
+        while scale > 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+        (mantissa, scale)
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let scale = self.scale.max(other.scale);
+        match (
+            self.rescale(scale, RoundingMode::TowardZero),
+            other.rescale(scale, RoundingMode::TowardZero),
+        ) {
+            (Some(a), Some(b)) => a.mantissa.cmp(&b.mantissa),
+            // only the larger value overflows when scaled up
+            (None, _) => self.mantissa.signum().cmp(&0),
+            (_, None) => 0.cmp(&other.mantissa.signum()),
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Equal values are equal whatever their scales, so 1.50 equals 1.5.
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized() == other.normalized()
+    }
+}
+
+impl Eq for Decimal {}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized().hash(state);
+    }
+}
+
+/// Writes all fractional digits of the scale, e.g. 10.50 at scale 2.
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        let sign = if self.mantissa < 0 { "-" } else { "" };
+        if fraction.is_empty() {
+            write!(f, "{}{}", sign, whole)
+        } else {
+            write!(f, "{}{}.{}", sign, whole, fraction)
+        }
+    }
+}
+
+/// Stored as the scale, the number of mantissa bytes, and the mantissa in
+/// as few little-endian bytes as hold it: 2 to 18 bytes, 4 for most amounts
+/// of money.
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let bytes = self.mantissa.to_le_bytes();
+        // the bits of the value and its sign; the other bytes only extend the sign
+        let bits = match self.mantissa {
+            0 => 0,
+            m if m > 0 => 129 - m.leading_zeros(),
+            m => 129 - m.leading_ones(),
+        };
+        let length = bits.div_ceil(8) as usize;
+        let mut tuple = serializer.serialize_tuple(2 + length)?;
+        tuple.serialize_element(&self.scale)?;
+        tuple.serialize_element(&(length as u8))?;
+        for byte in &bytes[..length] {
+            tuple.serialize_element(byte)?;
+        }
+        tuple.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct DecimalVisitor;
+
+        impl<'de> Visitor<'de> for DecimalVisitor {
+            type Value = Decimal;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a scale, a length and mantissa bytes")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Decimal, A::Error> {
+                let mut next = |what: &str| -> Result<u8, A::Error> {
+                    seq.next_element()?.ok_or_else(|| de::Error::custom(format!("missing {} of a DECIMAL", what)))
+                };
+                let scale = next("scale")?;
+                let length = next("length")? as usize;
+                if length > 16 {
+                    return Err(de::Error::custom("DECIMAL mantissa longer than 16 bytes"));
+                }
+                let mut bytes = [0u8; 16];
+                for byte in bytes.iter_mut().take(length) {
+                    *byte = next("mantissa")?;
+                }
+                if length > 0 && bytes[length - 1] & 0x80 != 0 {
+                    bytes[length..].fill(0xff);
+                }
+                Ok(Decimal {
+                    mantissa: i128::from_le_bytes(bytes),
+                    scale,
+                })
+            }
+        }
+
+        // the tuple is read up to the length its second element gives
+        deserializer.deserialize_tuple(2 + 16, DecimalVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Decimal, RoundingMode};
+
+    #[test]
+    fn encodes_decimals_in_few_bytes() {
+        for (text, size) in [("0", 2), ("-0.01", 3), ("1234.56", 5), ("-99999999.99", 7), ("99999999999999999999999999999999999999", 18)] {
+            let decimal = Decimal::parse(text).unwrap();
+            let bytes = bincode::serialize(&decimal).unwrap();
+            assert_eq!(bytes.len(), size, "{}", text);
+            let decoded: Decimal = bincode::deserialize(&bytes).unwrap();
+            assert_eq!(decoded.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn divides_and_rounds_by_mode() {
+        let d = |s: &str| Decimal::parse(s).unwrap();
+        let divide = |a: &str, b: &str, mode| d(a).checked_div(d(b), 2, mode).unwrap().to_string();
+        assert_eq!(divide("1", "8", RoundingMode::HalfAwayFromZero), "0.13");
+        assert_eq!(divide("1", "8", RoundingMode::HalfEven), "0.12");
+        assert_eq!(divide("-1", "8", RoundingMode::HalfAwayFromZero), "-0.13");
+        assert_eq!(divide("-1", "3", RoundingMode::Floor), "-0.34");
+        assert_eq!(divide("-1", "3", RoundingMode::Ceiling), "-0.33");
+        assert_eq!(divide("2", "3", RoundingMode::TowardZero), "0.66");
+        assert_eq!(d("2.345").round(2, RoundingMode::HalfEven).unwrap().to_string(), "2.340");
+        assert_eq!(d("-2.5").to_i64(RoundingMode::HalfAwayFromZero), Some(-3));
+        assert!(d("1").checked_div(d("0"), 2, RoundingMode::HalfEven).is_none());
+        assert!(d("99999999999999999999999999999999999999").checked_add(d("1")).is_none());
+    }
+
+    #[test]
+    fn multiplies_to_the_product_scale() {
+        let d = |s: &str| Decimal::parse(s).unwrap();
+        let multiply = |a: &str, b: &str| d(a).checked_mul(d(b)).map(|product| product.to_string());
+        assert_eq!(multiply("1.25", "-0.5").as_deref(), Some("-0.625"));
+        // NUMERIC operands keep the NUMERIC scale even where the scaled
+        // mantissas multiply to more than 38 digits
+        let trillion = "1000000000000.000000000";
+        assert_eq!(multiply(trillion, trillion).as_deref(), Some("1000000000000000000000000.000000000"));
+        assert_eq!(multiply("0.000000005", "0.5").as_deref(), Some("0.000000003"));
+        assert_eq!(multiply("-0.000000005", "0.4").as_deref(), Some("-0.000000002"));
+        assert_eq!(multiply("0.0000000000001", "0.0000000001").as_deref(), Some("0.0000000000000"));
+        assert_eq!(multiply("99999999999999999999999999999.000000000", "10.000000000"), None);
+        assert_eq!(Decimal::product_scale(2, 2), 4);
+        assert_eq!(Decimal::product_scale(9, 9), 9);
+        assert_eq!(Decimal::product_scale(12, 3), 12);
+        assert_eq!(Decimal::quotient_scale(2, 0), 8);
+        assert_eq!(Decimal::quotient_scale(9, 9), 9);
+    }
+}
@@ -24,3 +24,34 @@ impl From<String> for ParsingError {
         ParsingError::new(&error)
     }
 }
+
+#[derive(Debug)]
+pub struct ExecutionError {
+    pub message: String,
+}
+
+impl ExecutionError {
+    pub fn new(message: &str) -> Self {
+        Self {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ExecutionError: {}", self.message)
+    }
+}
+
+impl From<String> for ExecutionError {
+    fn from(error: String) -> Self {
+        ExecutionError::new(&error)
+    }
+}
+
+impl From<sloth_db::storage::Error> for ExecutionError {
+    fn from(error: sloth_db::storage::Error) -> Self {
+        ExecutionError::new(&error.to_string())
+    }
+}
@@ -0,0 +1,1790 @@
+mod aggregate;
+mod alias;
+mod apply;
+mod common_table;
+mod filter;
+mod hash_join;
+mod join;
+mod limit;
+mod project;
+mod scan;
+mod set_operation;
+mod sort;
+mod spill;
+mod unnest;
+mod window;
+
+use crate::catalog::Catalog;
+use crate::errors::ExecutionError;
+use crate::physical_plan::{Operator, PhysicalNode};
+use crate::schema::Schema;
+use crate::temporal::format_value;
+use crate::value::{Row, Value};
+use sloth_db::storage::BufferStats;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Memory an operator may use for its working set before it spills to disk.
+pub const DEFAULT_MEMORY_BUDGET: usize = 16 * 1024 * 1024;
+
+/// What executors need besides their plan node.
+pub struct ExecutionContext<'a> {
+    pub catalog: &'a Catalog,
+    pub memory_budget: usize,
+    pub common_tables: CommonTables,
+}
+
+/// The rows of the tables of WITH clauses, and of the working tables of
+/// recursive ones, by the number the planner gave the table. Subqueries run
+/// per row share them with the query around them.
+#[derive(Clone, Default)]
+pub struct CommonTables(Rc<RefCell<HashMap<usize, SharedRows>>>);
+
+/// Rows one executor writes and others read.
+pub type SharedRows = Rc<RefCell<Vec<Row>>>;
+
+impl CommonTables {
+    /// The rows of table `id`, empty until they are computed.
+    pub fn get(&self, id: usize) -> SharedRows {
+        self.0.borrow_mut().entry(id).or_default().clone()
+    }
+}
+
+impl ExecutionContext<'_> {
+    /// Where operators put their spill files.
+    pub fn spill_directory(&self) -> PathBuf {
+        self.catalog.directory().join("tmp")
+    }
+}
+
+/// A pull-based (Volcano) operator: `open` starts or restarts the stream and
+/// `next` returns rows until it yields `None`.
+pub trait Executor {
+    fn schema(&self) -> &Schema;
+    fn open(&mut self) -> Result<(), ExecutionError>;
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError>;
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        Vec::new()
+    }
+
+    /// Page requests this operator made itself, excluding its children.
+    fn buffer_stats(&self) -> BufferStats {
+        BufferStats::default()
+    }
+
+    /// Extra facts for EXPLAIN ANALYZE, such as whether the operator spilled.
+    fn details(&self) -> Option<String> {
+        None
+    }
+}
+
+/// What an operator actually did, as reported by EXPLAIN ANALYZE. Time
+/// includes the time spent in the children.
+#[derive(Debug, Clone, Default)]
+pub struct Metrics {
+    pub rows: u64,
+    pub loops: u64,
+    pub elapsed: Duration,
+}
+
+/// An executor together with the plan node it came from and its metrics.
+pub struct ExecutionNode {
+    label: String,
+    estimated_rows: f64,
+    estimated_cost: f64,
+    metrics: Metrics,
+    executor: Box<dyn Executor>,
+}
+
+impl ExecutionNode {
+    pub fn schema(&self) -> &Schema {
+        self.executor.schema()
+    }
+
+    pub fn open(&mut self) -> Result<(), ExecutionError> {
+        let start = Instant::now();
+        self.metrics.loops += 1;
+        let result = self.executor.open();
+        self.metrics.elapsed += start.elapsed();
+        result
+    }
+
+    pub fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        let start = Instant::now();
+        let row = self.executor.next();
+        self.metrics.elapsed += start.elapsed();
+        if let Ok(Some(_)) = row {
+            self.metrics.rows += 1;
+        }
+        row
+    }
+
+    /// Reads every remaining row.
+    pub fn drain(&mut self) -> Result<Vec<Row>, ExecutionError> {
+        let mut rows = Vec::new();
+        while let Some(row) = self.next()? {
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    /// Page requests of this subtree.
+    fn buffer_stats(&self) -> BufferStats {
+        self.executor
+            .children()
+            .iter()
+            .map(|child| child.buffer_stats())
+            .fold(self.executor.buffer_stats(), |total, stats| BufferStats {
+                hits: total.hits + stats.hits,
+                misses: total.misses + stats.misses,
+            })
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        let buffers = self.buffer_stats();
+        write!(
+            f,
+            "{}{} (rows={:.0}, cost={:.0}) (actual rows={}, loops={}, time={:.3} ms, buffers hit={} miss={}",
+            "  ".repeat(depth),
+            self.label,
+            self.estimated_rows,
+            self.estimated_cost,
+            self.metrics.rows,
+            self.metrics.loops,
+            self.metrics.elapsed.as_secs_f64() * 1000.0,
+            buffers.hits,
+            buffers.misses
+        )?;
+        match self.executor.details() {
+            Some(details) => writeln!(f, ", {})", details)?,
+            None => writeln!(f, ")")?,
+        }
+        for child in self.executor.children() {
+            child.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Prints the tree with estimates next to the actual metrics.
+impl fmt::Display for ExecutionNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
+
+/// Instantiates the executors of a physical plan.
+pub fn build(node: &PhysicalNode, context: &ExecutionContext) -> Result<ExecutionNode, ExecutionError> {
+    let catalog = context.catalog;
+    let mut children = node
+        .children
+        .iter()
+        .map(|child| build(child, context))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let executor: Box<dyn Executor> = match &node.operator {
+        Operator::OneRow => Box::new(OneRowExecutor::new()),
+        Operator::Scan {
+            table,
+            alias,
+            columns,
+            filter,
+            sample,
+        } => Box::new(scan::ScanExecutor::new(
+            catalog,
+            table,
+            alias.as_deref(),
+            columns.as_deref(),
+            filter.clone(),
+            sample.as_ref(),
+        )?),
+        Operator::Join {
+            algorithm,
+            join_type,
+            condition,
+        } => {
+            let right = children.pop().unwrap();
+            let left = children.pop().unwrap();
+            join::new_join(*algorithm, *join_type, left, right, condition.clone(), context)?
+        }
+        Operator::Filter { condition } => Box::new(filter::FilterExecutor::new(children.remove(0), condition.clone())?),
+        Operator::Project { fields } => Box::new(project::ProjectExecutor::new(children.remove(0), fields)?),
+        Operator::Sort { keys } => Box::new(sort::SortExecutor::new(
+            children.remove(0),
+            keys.clone(),
+            context.memory_budget,
+            context.spill_directory(),
+        )?),
+        Operator::Aggregate { group_by, aggregates } => Box::new(aggregate::HashAggregateExecutor::new(
+            children.remove(0),
+            group_by,
+            aggregates,
+            context.memory_budget,
+            context.spill_directory(),
+        )?),
+        Operator::Alias { name } => Box::new(alias::AliasExecutor::new(children.remove(0), name)),
+        Operator::SetOperation { operator, all } => {
+            let right = children.pop().unwrap();
+            let left = children.pop().unwrap();
+            Box::new(set_operation::SetOperationExecutor::new(left, right, *operator, *all)?)
+        }
+        Operator::Window { windows } => Box::new(window::WindowExecutor::new(children.remove(0), windows)?),
+        Operator::Apply { subquery, kind, name, .. } => Box::new(apply::ApplyExecutor::new(
+            children.remove(0),
+            subquery,
+            kind,
+            name,
+            context,
+        )?),
+        Operator::With { tables } => {
+            let input = children.pop().unwrap();
+            let tables = tables.iter().map(|(id, _)| *id).zip(children).collect();
+            Box::new(common_table::WithExecutor::new(tables, input, context))
+        }
+        Operator::CommonTableScan { id, fields, .. } => Box::new(common_table::CommonTableScanExecutor::new(
+            context.common_tables.get(*id),
+            fields,
+        )),
+        Operator::RecursiveUnion { id, all, .. } => {
+            let recursive = children.pop().unwrap();
+            let anchor = children.pop().unwrap();
+            Box::new(common_table::RecursiveUnionExecutor::new(
+                anchor,
+                recursive,
+                context.common_tables.get(*id),
+                *all,
+            )?)
+        }
+        Operator::Limit { limit, offset } => Box::new(limit::LimitExecutor::new(children.remove(0), *limit, *offset)),
+        Operator::TopN { keys, limit, offset } => Box::new(limit::TopNExecutor::new(
+            children.remove(0),
+            keys.clone(),
+            *limit,
+            *offset,
+        )?),
+        Operator::Unnest {
+            array,
+            alias,
+            offset,
+            outer,
+            condition,
+        } => Box::new(unnest::UnnestExecutor::new(
+            children.remove(0),
+            array.clone(),
+            alias,
+            offset.as_deref(),
+            *outer,
+            condition.clone(),
+        )?),
+    };
+
+    Ok(ExecutionNode {
+        label: node.operator.to_string(),
+        estimated_rows: node.estimated_rows,
+        estimated_cost: node.estimated_cost,
+        metrics: Metrics::default(),
+        executor,
+    })
+}
+
+/// Runs the plan to completion.
+pub fn execute(root: &mut ExecutionNode) -> Result<QueryResult, ExecutionError> {
+    root.open()?;
+    let rows = root.drain()?;
+    Ok(QueryResult {
+        schema: root.schema().clone(),
+        rows,
+    })
+}
+
+/// Orders key tuples for sorting: NULLs first, incomparable values as equal.
+pub fn compare_keys(a: &[Value], b: &[Value]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| match (a.is_null(), b.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => a.compare(b).unwrap_or(Ordering::Equal),
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Produces a single row without columns.
+struct OneRowExecutor {
+    schema: Schema,
+    done: bool,
+}
+
+impl OneRowExecutor {
+    fn new() -> Self {
+        Self {
+            schema: Schema::default(),
+            done: false,
+        }
+    }
+}
+
+impl Executor for OneRowExecutor {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.done = false;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        if self.done {
+            return Ok(None);
+        }
+        self.done = true;
+        Ok(Some(Vec::new()))
+    }
+}
+
+pub struct QueryResult {
+    pub schema: Schema,
+    pub rows: Vec<Row>,
+}
+
+/// Renders the rows as a text table.
+impl fmt::Display for QueryResult {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cell = |value: &Value| match value {
+            Value::String(s) => s.clone(),
+            Value::Decimal(d) => d.to_string(),
+            Value::Json(json) => json.to_string(),
+            value => format_value(value).unwrap_or_else(|| value.to_string()),
+        };
+        let headers: Vec<String> = self.schema.fields.iter().map(|field| field.name.clone()).collect();
+        let cells: Vec<Vec<String>> = self.rows.iter().map(|row| row.iter().map(cell).collect()).collect();
+        let mut widths: Vec<usize> = headers.iter().map(|h| h.chars().count()).collect();
+        for row in &cells {
+            for (width, value) in widths.iter_mut().zip(row) {
+                *width = (*width).max(value.chars().count());
+            }
+        }
+
+        let separator: String = widths.iter().map(|w| format!("+{}", "-".repeat(w + 2))).collect::<String>() + "+";
+        let line = |values: &[String]| {
+            values
+                .iter()
+                .zip(&widths)
+                .map(|(value, width)| format!("| {:<width$} ", value, width = width))
+                .collect::<String>()
+                + "|"
+        };
+        writeln!(f, "{}", separator)?;
+        writeln!(f, "{}", line(&headers))?;
+        writeln!(f, "{}", separator)?;
+        for row in &cells {
+            writeln!(f, "{}", line(row))?;
+        }
+        writeln!(f, "{}", separator)?;
+        write!(f, "{} row(s)", self.rows.len())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::{ExecutionNode, Executor, Metrics};
+    use crate::database::Database;
+    use crate::errors::ExecutionError;
+    use crate::json::Json;
+    use crate::schema::{Field, Schema};
+    use crate::value::{DataType, Row};
+    use crate::parser::parse_statement;
+    use crate::statement::Statement;
+    use crate::value::Value;
+    use tempfile::tempdir;
+
+    struct RowsExecutor {
+        schema: Schema,
+        rows: Vec<Row>,
+        position: usize,
+    }
+
+    impl Executor for RowsExecutor {
+        fn schema(&self) -> &Schema {
+            &self.schema
+        }
+
+        fn open(&mut self) -> Result<(), ExecutionError> {
+            self.position = 0;
+            Ok(())
+        }
+
+        fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+            self.position += 1;
+            Ok(self.rows.get(self.position - 1).cloned())
+        }
+    }
+
+    /// An input node producing the rows, with INT64 columns `k` and `v`
+    /// qualified by the name.
+    pub(crate) fn input(name: &str, rows: Vec<Row>) -> ExecutionNode {
+        let fields = ["k", "v"].iter().map(|c| Field::new(Some(name), c, DataType::Int64)).collect();
+        ExecutionNode {
+            label: name.to_string(),
+            estimated_rows: 0.0,
+            estimated_cost: 0.0,
+            metrics: Metrics::default(),
+            executor: Box::new(RowsExecutor {
+                schema: Schema::new(fields),
+                rows,
+                position: 0,
+            }),
+        }
+    }
+
+    pub(crate) fn rows(keys: impl Iterator<Item = Option<i64>>) -> Vec<Row> {
+        keys.enumerate()
+            .map(|(i, key)| vec![key.map_or(Value::Null, Value::Int), Value::Int(i as i64)])
+            .collect()
+    }
+
+    fn setup() -> (tempfile::TempDir, Database) {
+        let dir = tempdir().unwrap();
+        let mut database = Database::open(dir.path()).unwrap();
+        for sql in [
+            "CREATE TABLE users (id INT64 PRIMARY KEY, name STRING, age INT64)",
+            "CREATE TABLE orders (id INT64 PRIMARY KEY, user_id INT64, amount FLOAT64)",
+            "INSERT INTO users VALUES (1, 'Alice', 30), (2, 'Bob', 17), (3, 'Carol', 45)",
+            "INSERT INTO orders VALUES (10, 1, 5.0), (11, 1, 7.5), (12, 3, 20.0), (13, 2, 1.0)",
+        ] {
+            database.execute(parse_statement(sql).unwrap()).unwrap();
+        }
+        (dir, database)
+    }
+
+    fn query(database: &Database, sql: &str) -> Vec<Vec<Value>> {
+        match parse_statement(sql).unwrap() {
+            Statement::Query(plan) => database.query(plan).unwrap().rows,
+            _ => panic!("not a query"),
+        }
+    }
+
+    #[test]
+    fn executes_joins_filters_projections_and_sorts() {
+        let (_dir, database) = setup();
+        let rows = query(
+            &database,
+            "FROM users |> JOIN orders ON users.id = orders.user_id |> WHERE age >= 18 \
+             |> SELECT name, amount * 2 AS doubled |> ORDER BY doubled",
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::String("Alice".into()), Value::Float(10.0)],
+                vec![Value::String("Alice".into()), Value::Float(15.0)],
+                vec![Value::String("Carol".into()), Value::Float(40.0)],
+            ]
+        );
+        assert_eq!(query(&database, "SELECT 1 + 2 AS three"), vec![vec![Value::Int(3)]]);
+        let rows = query(&database, "FROM orders |> ORDER BY user_id DESC, amount ASC |> SELECT id");
+        assert_eq!(rows, [12, 13, 10, 11].map(|id| vec![Value::Int(id)]));
+        // stages apply in pipe order: sort on a column the SELECT drops, filter on its alias
+        let rows = query(
+            &database,
+            "from users |> order by age |> select name, age * 2 as double_age |> where double_age > 40 |> select name",
+        );
+        assert_eq!(rows, vec![vec![Value::String("Alice".into())], vec![Value::String("Carol".into())]]);
+    }
+
+    #[test]
+    fn executes_outer_cross_and_using_joins_over_aliases_and_subqueries() {
+        let (_dir, mut database) = setup();
+        for sql in [
+            "INSERT INTO users VALUES (4, 'Dave', 60)",
+            "INSERT INTO orders VALUES (14, 9, 0.5)",
+            "CREATE TABLE contracts (id INT64 PRIMARY KEY, user_id INT64)",
+            "INSERT INTO contracts VALUES (1, 3)",
+        ] {
+            database.execute(parse_statement(sql).unwrap()).unwrap();
+        }
+        let string = |s: &str| Value::String(s.into());
+
+        // a condition on the right side filters before the join, not the result
+        let rows = query(
+            &database,
+            "FROM users |> LEFT JOIN orders ON users.id = orders.user_id AND orders.amount > 6 \
+             |> SELECT name, orders.id |> ORDER BY name",
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![string("Alice"), Value::Int(11)],
+                vec![string("Bob"), Value::Null],
+                vec![string("Carol"), Value::Int(12)],
+                vec![string("Dave"), Value::Null],
+            ]
+        );
+        // a filter on the padded side must stay above the join
+        let rows = query(
+            &database,
+            "FROM users |> LEFT OUTER JOIN orders ON users.id = orders.user_id |> WHERE orders.id IS NULL |> SELECT name",
+        );
+        assert_eq!(rows, vec![vec![string("Dave")]]);
+
+        let rows = query(
+            &database,
+            "FROM users AS u |> RIGHT JOIN orders AS o ON u.id = o.user_id |> WHERE o.amount < 2 \
+             |> SELECT o.id, u.name |> ORDER BY o.id",
+        );
+        assert_eq!(rows, vec![vec![Value::Int(13), string("Bob")], vec![Value::Int(14), Value::Null]]);
+
+        let rows = query(
+            &database,
+            "FROM users |> FULL OUTER JOIN (FROM orders |> SELECT user_id AS id, amount) AS o USING (id) \
+             |> SELECT id, name, amount |> ORDER BY id, amount",
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![Value::Int(1), string("Alice"), Value::Float(5.0)],
+                vec![Value::Int(1), string("Alice"), Value::Float(7.5)],
+                vec![Value::Int(2), string("Bob"), Value::Float(1.0)],
+                vec![Value::Int(3), string("Carol"), Value::Float(20.0)],
+                vec![Value::Int(4), string("Dave"), Value::Null],
+                vec![Value::Int(9), Value::Null, Value::Float(0.5)],
+            ]
+        );
+
+        assert_eq!(query(&database, "FROM users |> CROSS JOIN orders |> SELECT users.id").len(), 20);
+        // without an equality the join is a nested loop; only Bob's age is below an amount
+        let rows = query(
+            &database,
+            "FROM users |> FULL JOIN orders ON users.age < orders.amount |> SELECT name, orders.id |> ORDER BY name, orders.id",
+        );
+        assert_eq!(rows.len(), 8);
+        assert_eq!(rows[3], vec![Value::Null, Value::Int(14)]);
+        assert_eq!(rows[5], vec![string("Bob"), Value::Int(12)]);
+        let rows = query(
+            &database,
+            "FROM users |> JOIN users older ON older.age > users.age |> WHERE users.name = 'Bob' \
+             |> SELECT older.name |> ORDER BY older.name",
+        );
+        assert_eq!(rows, vec![vec![string("Alice")], vec![string("Carol")], vec![string("Dave")]]);
+        let rows = query(&database, "FROM users |> JOIN contracts ON users.id = contracts.user_id |> SELECT name");
+        assert_eq!(rows, vec![vec![string("Carol")]]);
+
+        let explain = database
+            .execute(parse_statement("EXPLAIN FROM users |> LEFT JOIN orders AS o USING (id)").unwrap())
+            .unwrap();
+        assert!(explain.contains("HASH LEFT JOIN ON users.id = o.id"), "{}", explain);
+        assert!(explain.contains("SCAN TABLE orders AS o"), "{}", explain);
+    }
+
+    #[test]
+    fn joins_more_inputs_than_the_join_order_search_takes() {
+        let (_dir, database) = setup();
+        // the last two joins keep their written order above a search over the first 64 inputs
+        let joins: String = (1..66).map(|i| format!(" |> JOIN users AS t{} ON t{}.id = t{}.id", i, i, i - 1)).collect();
+        let sql = format!("FROM users AS t0{} |> WHERE t65.age > 18 |> SELECT t0.id |> ORDER BY id", joins);
+        assert_eq!(query(&database, &sql), vec![vec![Value::Int(1)], vec![Value::Int(3)]]);
+    }
+
+    #[test]
+    fn keeps_the_written_column_order_of_reordered_joins() {
+        let (_dir, mut database) = setup();
+        let values: Vec<String> = (100..600).map(|i| format!("({}, {}, 1.0)", i, i % 3 + 1)).collect();
+        for sql in [format!("INSERT INTO orders VALUES {}", values.join(", ")), "ANALYZE".to_string()] {
+            database.execute(parse_statement(&sql).unwrap()).unwrap();
+        }
+        // the hash join builds on the few users, read second
+        let sql = "FROM users |> JOIN orders ON users.id = orders.user_id";
+        let explain = database.execute(parse_statement(&format!("EXPLAIN {}", sql)).unwrap()).unwrap();
+        assert!(explain.contains("PROJECT users.id, users.name, users.age, orders.id, orders.user_id, orders.amount"), "{}", explain);
+        assert!(explain.find("SCAN TABLE orders") < explain.find("SCAN TABLE users"), "{}", explain);
+        let rows = query(&database, &format!("{} |> WHERE orders.id = 10 |> SELECT *", sql));
+        assert_eq!(
+            rows,
+            vec![vec![Value::Int(1), Value::String("Alice".into()), Value::Int(30), Value::Int(10), Value::Int(1), Value::Float(5.0)]]
+        );
+    }
+
+    #[test]
+    fn aggregates_groups_and_whole_inputs() {
+        let (_dir, mut database) = setup();
+        database
+            .execute(parse_statement("INSERT INTO orders VALUES (14, 3, NULL), (15, 1, 5.0)").unwrap())
+            .unwrap();
+        let string = |s: &str| Value::String(s.into());
+
+        let rows = query(
+            &database,
+            "FROM orders |> JOIN users ON users.id = orders.user_id \
+             |> AGGREGATE COUNT(*) AS orders, COUNT(amount), SUM(amount) AS total, AVG(amount), MIN(amount), \
+                MAX(amount), COUNT(DISTINCT amount) AS different, ANY_VALUE(age) \
+             GROUP AND ORDER BY name DESC",
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![
+                    string("Carol"),
+                    Value::Int(2),
+                    Value::Int(1),
+                    Value::Float(20.0),
+                    Value::Float(20.0),
+                    Value::Float(20.0),
+                    Value::Float(20.0),
+                    Value::Int(1),
+                    Value::Int(45),
+                ],
+                vec![
+                    string("Bob"),
+                    Value::Int(1),
+                    Value::Int(1),
+                    Value::Float(1.0),
+                    Value::Float(1.0),
+                    Value::Float(1.0),
+                    Value::Float(1.0),
+                    Value::Int(1),
+                    Value::Int(17),
+                ],
+                vec![
+                    string("Alice"),
+                    Value::Int(3),
+                    Value::Int(3),
+                    Value::Float(17.5),
+                    Value::Float(17.5 / 3.0),
+                    Value::Float(5.0),
+                    Value::Float(7.5),
+                    Value::Int(2),
+                    Value::Int(30),
+                ],
+            ]
+        );
+
+        let rows = query(
+            &database,
+            "FROM users |> JOIN orders ON users.id = orders.user_id |> ORDER BY orders.id \
+             |> AGGREGATE ARRAY_AGG(orders.id) AS ids, STRING_AGG(DISTINCT name, ', ') AS names \
+             GROUP BY age >= 18 AS adult |> WHERE adult",
+        );
+        let ids = Value::Array([10, 11, 12, 14, 15].map(Value::Int).to_vec());
+        assert_eq!(rows, vec![vec![Value::Bool(true), ids, string("Alice, Carol")]]);
+
+        // a filter on an aggregate stays above it
+        let rows = query(&database, "FROM orders |> AGGREGATE COUNT(*) AS n GROUP BY user_id |> WHERE n > 1 |> SELECT user_id");
+        assert_eq!(rows, vec![vec![Value::Int(1)], vec![Value::Int(3)]]);
+        let rows = query(&database, "FROM users |> WHERE age > 100 |> AGGREGATE COUNT(*), SUM(age)");
+        assert_eq!(rows, vec![vec![Value::Int(0), Value::Null]]);
+        assert!(query(&database, "FROM users |> WHERE age > 100 |> AGGREGATE COUNT(*) GROUP BY name").is_empty());
+    }
+
+    #[test]
+    fn extends_sets_drops_and_renames_columns() {
+        let (_dir, database) = setup();
+        let string = |s: &str| Value::String(s.into());
+
+        let rows = query(
+            &database,
+            "FROM users |> EXTEND age + 1 AS next, id * 10 |> SET name = 'x', age = age * 2 \
+             |> DROP id |> RENAME next AS later |> AS u |> WHERE u.later > 20",
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![string("x"), Value::Int(60), Value::Int(31), Value::Int(10)],
+                vec![string("x"), Value::Int(90), Value::Int(46), Value::Int(30)],
+            ]
+        );
+
+        // AS names joined rows so that their columns can be told apart
+        let rows = query(
+            &database,
+            "FROM users |> AS a |> JOIN users AS b ON a.id + 1 = b.id |> SELECT a.name, b.name |> ORDER BY a.name",
+        );
+        assert_eq!(rows, vec![vec![string("Alice"), string("Bob")], vec![string("Bob"), string("Carol")]]);
+
+        for (sql, error) in [
+            ("FROM users |> DROP missing", "missing"),
+            ("FROM users |> SET missing = 1", "missing"),
+            ("FROM users |> RENAME missing AS other", "missing"),
+            ("FROM users |> DROP id, name, age", "every column"),
+        ] {
+            let Statement::Query(plan) = parse_statement(sql).unwrap() else {
+                panic!("not a query");
+            };
+            let Err(message) = database.query(plan) else {
+                panic!("{} succeeded", sql);
+            };
+            let message = message.to_string();
+            assert!(message.contains(error), "{}: {}", sql, message);
+        }
+    }
+
+    #[test]
+    fn limits_rows_and_stops_reading_early() {
+        let (_dir, mut database) = setup();
+        let int = |values: &[i64]| values.iter().map(|&v| vec![Value::Int(v)]).collect::<Vec<_>>();
+
+        assert_eq!(query(&database, "FROM users |> ORDER BY age DESC |> LIMIT 2 |> SELECT id"), int(&[3, 1]));
+        assert_eq!(query(&database, "FROM users |> ORDER BY age |> LIMIT 5 OFFSET 1 |> SELECT id"), int(&[1, 3]));
+        assert_eq!(query(&database, "FROM users |> ORDER BY id |> LIMIT 2 OFFSET 3"), Vec::<Vec<Value>>::new());
+        assert_eq!(query(&database, "FROM users |> LIMIT 0"), Vec::<Vec<Value>>::new());
+        // a filter after the limit applies to the limited rows only
+        assert_eq!(query(&database, "FROM users |> ORDER BY id |> LIMIT 2 |> WHERE age > 20 |> SELECT id"), int(&[1]));
+
+        let explain = database
+            .execute(parse_statement("EXPLAIN ANALYZE FROM users |> ORDER BY name |> LIMIT 1").unwrap())
+            .unwrap();
+        assert!(explain.contains("TOP-N ORDER BY name LIMIT 1"), "{}", explain);
+        assert!(!explain.lines().any(|line| line.trim_start().starts_with("ORDER BY")), "{}", explain);
+
+        let explain = database
+            .execute(parse_statement("EXPLAIN ANALYZE FROM users |> LIMIT 1 OFFSET 1").unwrap())
+            .unwrap();
+        let scan = explain.lines().find(|line| line.contains("SCAN TABLE users")).unwrap();
+        assert!(scan.contains("actual rows=2,"), "{}", explain);
+
+        assert!(parse_statement("FROM users |> LIMIT -1").is_err());
+        assert!(parse_statement("FROM users |> LIMIT 1.5").is_err());
+    }
+
+    #[test]
+    fn combines_queries_with_set_operations() {
+        let (_dir, mut database) = setup();
+        let int = |values: &[i64]| values.iter().map(|&v| vec![Value::Int(v)]).collect::<Vec<_>>();
+        let ids = "FROM users |> SELECT id";
+
+        let union = |operation: &str| format!("{} |> {} (FROM orders |> SELECT user_id) |> ORDER BY id", ids, operation);
+        assert_eq!(query(&database, &union("UNION ALL")), int(&[1, 1, 1, 2, 2, 3, 3]));
+        assert_eq!(query(&database, &union("UNION DISTINCT")), int(&[1, 2, 3]));
+        assert_eq!(
+            query(&database, &format!("{} |> UNION DISTINCT (SELECT 4), (SELECT 1 AS one) |> ORDER BY id", ids)),
+            int(&[1, 2, 3, 4])
+        );
+        assert_eq!(
+            query(&database, "FROM orders |> SELECT user_id |> INTERSECT DISTINCT (FROM users |> WHERE age > 20 |> SELECT id)"),
+            int(&[1, 3])
+        );
+        assert_eq!(
+            query(&database, "FROM orders |> SELECT user_id |> EXCEPT DISTINCT (FROM users |> WHERE age < 20 |> SELECT id)"),
+            int(&[1, 3])
+        );
+        // columns of both inputs widen to a common type
+        let rows = query(&database, "SELECT 2 AS x |> UNION DISTINCT (FROM orders |> SELECT amount) |> ORDER BY x |> LIMIT 3");
+        assert_eq!(rows, [1.0, 2.0, 5.0].map(|x| vec![Value::Float(x)]));
+
+        // filters above apply to both inputs
+        let explain = database
+            .execute(parse_statement(&format!("EXPLAIN {} |> UNION ALL (FROM orders |> SELECT user_id) |> WHERE id > 1", ids)).unwrap())
+            .unwrap();
+        assert!(explain.contains("SCAN TABLE users COLUMNS (id) FILTER id > 1"), "{}", explain);
+        assert!(explain.contains("FILTER user_id > 1"), "{}", explain);
+
+        for (sql, error) in [
+            ("FROM users |> UNION ALL (FROM orders |> SELECT id, user_id)", "same number of columns"),
+            ("FROM users |> SELECT name |> EXCEPT DISTINCT (FROM orders |> SELECT id)", "incompatible types"),
+        ] {
+            let Statement::Query(plan) = parse_statement(sql).unwrap() else {
+                panic!("not a query");
+            };
+            let Err(message) = database.query(plan) else {
+                panic!("{} succeeded", sql);
+            };
+            assert!(message.to_string().contains(error), "{}: {}", sql, message);
+        }
+        assert!(parse_statement("FROM users |> INTERSECT ALL (FROM users)").is_err());
+        assert!(parse_statement("FROM users |> UNION (FROM users)").is_err());
+    }
+
+    #[test]
+    fn runs_subqueries_and_decorrelates_exists_and_in() {
+        let (_dir, mut database) = setup();
+        let int = |values: &[i64]| values.iter().map(|&v| vec![Value::Int(v)]).collect::<Vec<_>>();
+        let ids = |database: &Database, condition: &str| {
+            query(database, &format!("FROM users |> WHERE {} |> SELECT id |> ORDER BY id", condition))
+        };
+
+        assert_eq!(ids(&database, "EXISTS (FROM orders |> WHERE orders.user_id = users.id |> WHERE amount > 6)"), int(&[1, 3]));
+        assert_eq!(ids(&database, "NOT EXISTS (FROM orders |> WHERE user_id = users.id |> WHERE amount > 6)"), int(&[2]));
+        assert_eq!(ids(&database, "id IN (FROM orders |> WHERE amount < 6 |> SELECT user_id)"), int(&[1, 2]));
+        assert_eq!(ids(&database, "id NOT IN (FROM orders |> WHERE amount < 6 |> SELECT user_id)"), int(&[3]));
+        // a subquery reading the outer row outside its WHERE runs per row
+        assert_eq!(ids(&database, "EXISTS (FROM orders |> SELECT user_id + users.age AS x |> WHERE x = 31)"), int(&[1]));
+
+        let rows = query(
+            &database,
+            "FROM users |> SELECT name, (FROM orders |> WHERE user_id = users.id |> AGGREGATE SUM(amount)) AS total \
+             |> ORDER BY name",
+        );
+        let expected = [("Alice", 12.5), ("Bob", 1.0), ("Carol", 20.0)];
+        assert_eq!(rows, expected.map(|(name, total)| vec![Value::String(name.to_string()), Value::Float(total)]));
+        let rows = query(&database, "FROM orders |> WHERE amount > (FROM orders |> AGGREGATE AVG(amount)) |> SELECT id |> ORDER BY id");
+        assert_eq!(rows, int(&[12]));
+        let rows = query(
+            &database,
+            "FROM users |> EXTEND id IN (FROM orders |> WHERE amount > 6 |> SELECT user_id) AS big |> ORDER BY id |> SELECT big",
+        );
+        assert_eq!(rows, [true, false, true].map(|b| vec![Value::Bool(b)]));
+
+        // NOT IN is never true once the subquery returns a NULL
+        database
+            .execute(parse_statement("INSERT INTO orders VALUES (14, NULL, 3.0)").unwrap())
+            .unwrap();
+        assert_eq!(ids(&database, "id NOT IN (FROM orders |> WHERE amount > 6 |> SELECT user_id)"), int(&[2]));
+        assert_eq!(ids(&database, "id NOT IN (FROM orders |> SELECT user_id)"), int(&[]));
+        let rows = query(&database, "SELECT 4 NOT IN (FROM orders |> SELECT user_id) AS x");
+        assert_eq!(rows, vec![vec![Value::Null]]);
+
+        let explain = database
+            .execute(parse_statement("EXPLAIN FROM users |> WHERE EXISTS (FROM orders |> WHERE user_id = users.id)").unwrap())
+            .unwrap();
+        assert!(explain.contains("SEMI JOIN ON orders.user_id = users.id"), "{}", explain);
+        let explain = database
+            .execute(parse_statement("EXPLAIN FROM users |> WHERE id NOT IN (FROM orders |> SELECT user_id)").unwrap())
+            .unwrap();
+        assert!(explain.contains("ANTI JOIN"), "{}", explain);
+        let analyzed = database
+            .execute(
+                parse_statement("EXPLAIN ANALYZE FROM orders |> SELECT (FROM users |> WHERE id = orders.user_id |> SELECT name) AS name")
+                    .unwrap(),
+            )
+            .unwrap();
+        // the orders of user 1 share one run
+        assert!(analyzed.contains("APPLY SCALAR SUBQUERY AS $subquery_1"), "{}", analyzed);
+        assert!(analyzed.contains("ran the subquery 4 times"), "{}", analyzed);
+        // EXPLAIN shows the subquery under the node, with the outer column it reads
+        let explain = database
+            .execute(
+                parse_statement("EXPLAIN FROM orders |> SELECT (FROM users |> WHERE id = orders.user_id |> SELECT name) AS name")
+                    .unwrap(),
+            )
+            .unwrap();
+        let lines: Vec<&str> = explain.lines().map(|line| line.split(" (rows=").next().unwrap()).collect();
+        assert_eq!(
+            lines[2..],
+            [
+                "  APPLY SCALAR SUBQUERY AS $subquery_1",
+                "    SCAN TABLE orders",
+                "    PROJECT name",
+                "      SCAN TABLE users COLUMNS (id, name) FILTER id = orders.user_id",
+            ],
+            "{}",
+            explain
+        );
+
+        let Statement::Query(plan) = parse_statement("SELECT (FROM users |> SELECT id) AS id").unwrap() else {
+            panic!("not a query");
+        };
+        let Err(message) = database.query(plan) else {
+            panic!("a scalar subquery returned several rows");
+        };
+        assert!(message.to_string().contains("more than one row"), "{}", message);
+    }
+
+    #[test]
+    fn computes_common_tables_once_and_recursive_ones_until_they_end() {
+        let (_dir, mut database) = setup();
+        for sql in [
+            "CREATE TABLE employees (id INT64 PRIMARY KEY, name STRING, manager INT64)",
+            "INSERT INTO employees VALUES (1, 'Ann', NULL), (2, 'Ben', 1), (3, 'Cid', 1), (4, 'Dee', 2), (5, 'Eve', 4)",
+        ] {
+            database.execute(parse_statement(sql).unwrap()).unwrap();
+        }
+        let int = Value::Int;
+        let string = |s: &str| Value::String(s.to_string());
+
+        let rows = query(
+            &database,
+            "WITH big AS (FROM orders |> WHERE amount > 6), \
+                totals AS (FROM big |> AGGREGATE SUM(amount) AS total GROUP BY user_id) \
+             FROM totals |> ORDER BY user_id |> SELECT user_id, total",
+        );
+        assert_eq!(rows, vec![vec![int(1), Value::Float(7.5)], vec![int(3), Value::Float(20.0)]]);
+        let rows = query(
+            &database,
+            "WITH big AS (FROM orders |> WHERE amount > 6) FROM users |> WHERE id IN (FROM big |> SELECT user_id) \
+             |> SELECT name, (FROM big |> WHERE user_id = users.id |> AGGREGATE COUNT(*)) AS n |> ORDER BY name",
+        );
+        assert_eq!(rows, vec![vec![string("Alice"), int(1)], vec![string("Carol"), int(1)]]);
+
+        // both readers share one computation of the table
+        let sql = "WITH adults AS (FROM users |> WHERE age > 20) \
+                   FROM adults |> JOIN adults AS other ON adults.id < other.id |> SELECT adults.id, other.id";
+        assert_eq!(query(&database, sql), vec![vec![int(1), int(3)]]);
+        let analyzed = database.execute(parse_statement(&format!("EXPLAIN ANALYZE {}", sql)).unwrap()).unwrap();
+        assert_eq!(analyzed.matches("SCAN TABLE users").count(), 1, "{}", analyzed);
+        assert_eq!(analyzed.matches("SCAN COMMON TABLE adults").count(), 2, "{}", analyzed);
+
+        let rows = query(
+            &database,
+            "WITH RECURSIVE reports AS ( \
+                FROM employees |> WHERE id = 2 |> SELECT id, name \
+                |> UNION ALL (FROM employees |> JOIN reports ON employees.manager = reports.id |> SELECT employees.id, employees.name)) \
+             FROM reports |> ORDER BY id |> SELECT name",
+        );
+        assert_eq!(rows, ["Ben", "Dee", "Eve"].map(|name| vec![string(name)]));
+
+        // UNION DISTINCT ends on cycles, UNION ALL does not
+        let reachable = |union: &str| {
+            format!(
+                "WITH RECURSIVE edges AS (SELECT 1 AS a, 2 AS b |> UNION ALL (SELECT 2, 3), (SELECT 3, 1)), \
+                    reachable AS (SELECT 1 AS node |> {} (FROM reachable |> JOIN edges ON node = a |> SELECT b)) \
+                 FROM reachable |> ORDER BY node",
+                union
+            )
+        };
+        assert_eq!(query(&database, &reachable("UNION DISTINCT")), [1, 2, 3].map(|i| vec![int(i)]));
+        for (sql, error) in [
+            (reachable("UNION ALL"), "did not end"),
+            ("WITH RECURSIVE r AS (FROM r) FROM r".to_string(), "must be a query that does not read it"),
+            (
+                "WITH RECURSIVE r AS (SELECT 1 AS x |> UNION ALL (FROM r |> SELECT x + 0.5)) FROM r".to_string(),
+                "column types",
+            ),
+        ] {
+            let Statement::Query(plan) = parse_statement(&sql).unwrap() else {
+                panic!("not a query");
+            };
+            let Err(message) = database.query(plan) else {
+                panic!("{} succeeded", sql);
+            };
+            assert!(message.to_string().contains(error), "{}: {}", sql, message);
+        }
+        assert!(parse_statement("WITH t AS (SELECT 1), t AS (SELECT 2) FROM t").is_err());
+    }
+
+    #[test]
+    fn evaluates_scalar_functions_and_casts() {
+        let (_dir, database) = setup();
+        let int = Value::Int;
+        let float = Value::Float;
+        let string = |s: &str| Value::String(s.to_string());
+
+        let rows = query(
+            &database,
+            "FROM users |> WHERE id = 1 |> SELECT CONCAT(UPPER(name), '-', LOWER(name)), SUBSTR(name, 2, 3), SUBSTR(name, -2), \
+             LENGTH(TRIM('  x  ')), TRIM('xxaxx', 'x'), REPLACE(name, 'l', 'L'), REGEXP_CONTAINS(name, '^A.i'), SPLIT('a,b,,c')",
+        );
+        assert_eq!(
+            rows,
+            vec![vec![
+                string("ALICE-alice"),
+                string("lic"),
+                string("ce"),
+                int(1),
+                string("a"),
+                string("ALice"),
+                Value::Bool(true),
+                Value::Array(["a", "b", "", "c"].map(string).to_vec()),
+            ]]
+        );
+        let rows = query(
+            &database,
+            "SELECT ABS(-3), ABS(-2.5), ROUND(2.5), ROUND(2.71828, 2), FLOOR(-1.5), CEIL(1.2), MOD(-7, 3), POW(2, 10), SQRT(16), LN(1)",
+        );
+        assert_eq!(
+            rows,
+            vec![vec![int(3), float(2.5), float(3.0), float(2.72), float(-2.0), float(2.0), int(-1), float(1024.0), float(4.0), float(0.0)]]
+        );
+        let rows = query(
+            &database,
+            "FROM users |> ORDER BY id |> SELECT IF(age > 20, name, NULL), IFNULL(NULLIF(age, 17), 0), COALESCE(NULL, NULL, id)",
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![string("Alice"), int(30), int(1)],
+                vec![Value::Null, int(0), int(2)],
+                vec![string("Carol"), int(45), int(3)],
+            ]
+        );
+        // strict functions give NULL for a NULL argument
+        assert_eq!(query(&database, "SELECT UPPER(NULL), CONCAT('a', NULL)"), vec![vec![Value::Null, Value::Null]]);
+
+        let rows = query(
+            &database,
+            "SELECT CAST('42' AS INT64), CAST(2.5 AS INT64), CAST(-2.5 AS INT64), CAST(7 AS STRING), CAST('true' AS BOOL), \
+             CAST(1 AS BOOL), CAST(TRUE AS INT64), CAST(' 1.5 ' AS FLOAT64), SAFE_CAST('x' AS INT64), CAST(NULL AS STRING)",
+        );
+        assert_eq!(
+            rows,
+            vec![vec![
+                int(42),
+                int(3),
+                int(-3),
+                string("7"),
+                Value::Bool(true),
+                Value::Bool(true),
+                int(1),
+                float(1.5),
+                Value::Null,
+                Value::Null,
+            ]]
+        );
+        // a typed NULL keeps its type through constant folding
+        let rows = query(&database, "SELECT CAST(NULL AS STRING) AS s |> UNION ALL (SELECT 'a')");
+        assert_eq!(rows, vec![vec![Value::Null], vec![string("a")]]);
+
+        // arguments are checked while planning, values while running
+        for (sql, error) in [
+            ("SELECT LOWER(1)", "Argument 1 of LOWER must be STRING, got INT64"),
+            ("SELECT SUBSTR('a')", "SUBSTR takes 2 to 3 arguments, got 1"),
+            ("SELECT CONCAT()", "CONCAT takes at least 1 arguments, got 0"),
+            ("SELECT IF(TRUE, 'a', 1)", "incompatible types STRING and INT64"),
+            ("SELECT NO_SUCH(1)", "Unknown function NO_SUCH"),
+            ("SELECT CAST(1.5 AS BOOL)", "Cannot cast FLOAT64 to BOOL"),
+            ("FROM users |> SELECT CAST(name AS INT64)", "Cannot cast 'Alice' to INT64"),
+            ("SELECT SQRT(-4)", "SQRT of the negative number -4"),
+            ("SELECT MOD(1, 0)", "Division by zero"),
+            ("SELECT REGEXP_CONTAINS('a', '(')", "Invalid regular expression"),
+        ] {
+            let Statement::Query(plan) = parse_statement(sql).unwrap() else {
+                panic!("not a query");
+            };
+            let Err(message) = database.query(plan) else {
+                panic!("{} succeeded", sql);
+            };
+            assert!(message.to_string().contains(error), "{}: {}", sql, message);
+        }
+    }
+
+    #[test]
+    fn computes_with_dates_times_and_time_zones() {
+        let (_dir, mut database) = setup();
+        for sql in [
+            "CREATE TABLE events (id INT64 PRIMARY KEY, at TIMESTAMP, day DATE)",
+            "INSERT INTO events VALUES (1, '2024-03-10 06:59:59', '2024-03-10'), \
+             (2, TIMESTAMP '2024-03-10 07:00:00', DATE '2024-01-31'), (3, '2024-11-03T05:30:00Z', '2024-02-29')",
+        ] {
+            database.execute(parse_statement(sql).unwrap()).unwrap();
+        }
+        let int = Value::Int;
+        let string = |s: &str| Value::String(s.to_string());
+        let strings = |query: &str| -> Vec<Vec<Value>> { query.split('|').map(|row| row.split(',').map(string).collect()).collect() };
+
+        // New York leaves standard time at 7:00 UTC on 2024-03-10 and returns
+        // to it at 6:00 UTC on 2024-11-03; Paris is on standard time throughout
+        let rows = query(
+            &database,
+            "FROM events |> ORDER BY id |> SELECT FORMAT_TIMESTAMP('%F %T %z', at, 'America/New_York'), \
+             CAST(EXTRACT(HOUR FROM at AT TIME ZONE 'Europe/Paris') AS STRING), CAST(DATE_ADD(day, INTERVAL 1 MONTH) AS STRING), \
+             CAST(DATE_DIFF(day, DATE '2024-01-01', DAY) AS STRING)",
+        );
+        assert_eq!(
+            rows,
+            strings(
+                "2024-03-10 01:59:59 -0500,7,2024-04-10,69|2024-03-10 03:00:00 -0400,8,2024-02-29,30|\
+                 2024-11-03 01:30:00 -0400,6,2024-03-29,59"
+            )
+        );
+        let rows = query(&database, "FROM events |> WHERE at >= TIMESTAMP '2024-03-10 02:00:00-05' |> ORDER BY day |> SELECT id");
+        assert_eq!(rows, vec![vec![int(2)], vec![int(3)]]);
+
+        let rows = query(
+            &database,
+            "SELECT CAST(DATE_TRUNC(TIMESTAMP '2024-05-16 13:14:15.5', MONTH) AS STRING), \
+             CAST(DATE_TRUNC(DATETIME '2024-05-16 13:14:15', WEEK) AS STRING), CAST(DATE_TRUNC(TIME '13:14:15', HOUR) AS STRING), \
+             CAST(DATE_SUB(DATE '2024-03-31', INTERVAL 1 QUARTER) AS STRING), CAST(DATE_ADD(TIME '23:30:00', INTERVAL 45 MINUTE) AS STRING), \
+             CAST(DATE_ADD(DATE '2024-02-29', INTERVAL 1 YEAR) AS STRING)",
+        );
+        assert_eq!(rows, strings("2024-05-01 00:00:00+00,2024-05-12 00:00:00,13:00:00,2023-12-31,00:15:00,2025-02-28"));
+        let rows = query(
+            &database,
+            "SELECT DATE_DIFF(DATE '2024-12-31', DATE '2024-01-01', MONTH), DATE_DIFF(TIMESTAMP '2024-01-01 10:00:00', \
+             TIMESTAMP '2024-01-01 08:30:00', HOUR), EXTRACT(DAYOFWEEK FROM DATE '2024-05-16'), EXTRACT(WEEK FROM DATE '2024-01-07'), \
+             EXTRACT(QUARTER FROM DATETIME '2024-05-16 00:00:00'), EXTRACT(MILLISECOND FROM TIME '00:00:01.234567')",
+        );
+        assert_eq!(rows, vec![vec![int(11), int(1), int(5), int(1), int(2), int(234)]]);
+
+        // parsing and formatting in zones, and converting between instants and wall clocks
+        let rows = query(
+            &database,
+            "SELECT CAST(PARSE_TIMESTAMP('%d/%m/%Y %H:%M', '05/07/2024 14:30', 'Asia/Kolkata') AS STRING), \
+             CAST(PARSE_TIMESTAMP('%b %e %Y %I:%M %p %Z', 'Mar 3 2024 9:05 PM Europe/Berlin') AS STRING), \
+             CAST(TIMESTAMP('2024-01-15 12:00', 'Australia/Sydney') AS STRING), \
+             CAST(DATETIME(TIMESTAMP '2024-07-01 02:00:00', 'Australia/Sydney') AS STRING), \
+             CAST(DATE(TIMESTAMP '2024-07-01 23:00:00', '+05:30') AS STRING), \
+             FORMAT_TIMESTAMP('%A %d %B %Y %H:%M:%E3S %Z', TIMESTAMP '2024-06-01 10:00:00.25', 'Europe/London')",
+        );
+        assert_eq!(
+            rows,
+            vec![vec![
+                string("2024-07-05 09:00:00+00"),
+                string("2024-03-03 20:05:00+00"),
+                string("2024-01-15 01:00:00+00"),
+                string("2024-07-01 12:00:00"),
+                string("2024-07-02"),
+                string("Saturday 01 June 2024 11:00:00.250 Europe/London"),
+            ]]
+        );
+        // a time skipped when clocks go forward lands after the change
+        let rows = query(&database, "SELECT CAST(TIMESTAMP('2024-03-31 02:30:00', 'Europe/Berlin') AS STRING)");
+        assert_eq!(rows, vec![vec![string("2024-03-31 01:30:00+00")]]);
+
+        // date parts stay keywords where a column has their name, and calls
+        // print the way they are written
+        let Statement::Query(plan) = parse_statement("FROM events |> SELECT DATE_DIFF(day, DATE '2024-01-01', DAY), EXTRACT(DAY FROM day)").unwrap() else {
+            panic!("not a query");
+        };
+        let result = database.query(plan).unwrap();
+        let names: Vec<&str> = result.schema.fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!(names, ["DATE_DIFF(day, DATE '2024-01-01', DAY)", "EXTRACT(DAY FROM day)"]);
+
+        assert!(parse_statement("SELECT DATE '2024-02-30'").is_err());
+        assert!(parse_statement("SELECT DATE_ADD(DATE '2024-01-01', INTERVAL 1 FORTNIGHT)").is_err());
+        for (sql, error) in [
+            ("SELECT DATE_TRUNC(DATE '2024-01-01', HOUR)", "DATE_TRUNC cannot use HOUR on a DATE"),
+            ("SELECT EXTRACT(DAY FROM 1)", "Argument 2 of EXTRACT must be DATE, TIME, DATETIME or TIMESTAMP, got INT64"),
+            ("SELECT DATE_DIFF(DATE '2024-01-01', DATETIME '2024-01-01 00:00:00', DAY)", "same type"),
+            ("SELECT FORMAT_TIMESTAMP('%F', CURRENT_TIMESTAMP, 'Mars/Olympus')", "Unknown time zone Mars/Olympus"),
+            ("SELECT PARSE_TIMESTAMP('%Y-%m-%d', '2024-13-01')", "Cannot parse '2024-13-01'"),
+            ("SELECT DATE_ADD(DATE '9999-12-31', INTERVAL 1 DAY)", "outside the range"),
+            ("SELECT CAST(DATE '2024-01-01' AS TIME)", "Cannot cast DATE to TIME"),
+            ("SELECT DATE '2024-01-01' < TIMESTAMP '2024-01-01 00:00:00'", "Cannot compare DATE with TIMESTAMP"),
+        ] {
+            let Statement::Query(plan) = parse_statement(sql).unwrap() else {
+                panic!("not a query");
+            };
+            let Err(message) = database.query(plan) else {
+                panic!("{} succeeded", sql);
+            };
+            assert!(message.to_string().contains(error), "{}: {}", sql, message);
+        }
+    }
+
+    #[test]
+    fn stores_and_computes_exact_decimals() {
+        let (_dir, mut database) = setup();
+        for sql in [
+            "CREATE TABLE payments (id INT64 PRIMARY KEY, user_id INT64, amount NUMERIC(10, 2), rate DECIMAL(5, 4))",
+            // amounts are rounded half away from zero to the scale of the column
+            "INSERT INTO payments VALUES (1, 1, 10.005, 0.0825), (2, 1, NUMERIC '0.10', 0.07), (3, 3, 19.99, NULL), (4, 2, 1, 1)",
+        ] {
+            database.execute(parse_statement(sql).unwrap()).unwrap();
+        }
+        let text = |rows: Vec<Vec<Value>>| -> Vec<Vec<String>> {
+            rows.iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|value| match value {
+                            Value::Decimal(d) => d.to_string(),
+                            value => value.to_string(),
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+        let expected = |rows: &[&[&str]]| -> Vec<Vec<String>> {
+            rows.iter().map(|row| row.iter().map(|cell| cell.to_string()).collect()).collect()
+        };
+
+        let rows = query(
+            &database,
+            "FROM payments |> ORDER BY id |> SELECT amount, amount * rate, amount / 3, amount + 1, -amount",
+        );
+        assert_eq!(
+            text(rows),
+            expected(&[
+                &["10.01", "0.825825", "3.33666667", "11.01", "-10.01"],
+                &["0.10", "0.007000", "0.03333333", "1.10", "-0.10"],
+                &["19.99", "NULL", "6.66333333", "20.99", "-19.99"],
+                &["1.00", "1.000000", "0.33333333", "2.00", "-1.00"],
+            ])
+        );
+        let rows = query(&database, "FROM payments |> AGGREGATE SUM(amount), AVG(amount), MAX(rate), COUNT(DISTINCT amount)");
+        assert_eq!(text(rows), expected(&[&["31.10", "7.77500000", "1.0000", "4"]]));
+
+        // unlike floats, sums of decimals are exact
+        let rows = query(
+            &database,
+            "SELECT NUMERIC '0.1' + NUMERIC '0.2' = NUMERIC '0.3', 0.1 + 0.2 = 0.3, NUMERIC '1.50' = NUMERIC '1.5', NUMERIC '2' > 1",
+        );
+        assert_eq!(rows, vec![vec![Value::Bool(true), Value::Bool(false), Value::Bool(true), Value::Bool(true)]]);
+        let rows = query(
+            &database,
+            "SELECT ROUND(NUMERIC '2.25', 1), ROUND(NUMERIC '2.25', 1, 'ROUND_HALF_EVEN'), ROUND(NUMERIC '-2.25', 1, 'ROUND_FLOOR'), \
+             ROUND(2.5, 0, 'ROUND_HALF_EVEN'), FLOOR(NUMERIC '-1.5'), ABS(NUMERIC '-3.10'), CAST('123.456' AS NUMERIC(5, 2)), \
+             CAST(NUMERIC '2.5' AS INT64), CAST(NUMERIC '0.125' AS STRING)",
+        );
+        assert_eq!(
+            text(rows),
+            expected(&[&["2.30", "2.20", "-2.30", "2", "-2.0", "3.10", "123.46", "3", "'0.125'"]])
+        );
+
+        // products and quotients of NUMERIC keep its scale
+        let rows = query(
+            &database,
+            "SELECT CAST('1000000000000' AS NUMERIC) * CAST('1000000000000' AS NUMERIC), CAST(1 AS NUMERIC) / CAST(3 AS NUMERIC)",
+        );
+        assert_eq!(text(rows), expected(&[&["1000000000000000000000000.000000000", "0.333333333"]]));
+
+        // decimal keys join integer keys, and unions take the wider decimal
+        let rows = query(
+            &database,
+            "FROM payments |> WHERE rate IS NULL |> JOIN users ON users.id = amount - 16.99 + NUMERIC '0' |> SELECT name",
+        );
+        assert_eq!(rows, vec![vec![Value::String("Carol".to_string())]]);
+        let rows = query(&database, "FROM users |> JOIN payments ON payments.amount = users.id |> SELECT name");
+        assert_eq!(rows, vec![vec![Value::String("Alice".to_string())]]);
+        let rows = query(&database, "SELECT CAST(1.5 AS NUMERIC(3, 1)) AS x |> UNION ALL (SELECT NUMERIC '12.25') |> ORDER BY x");
+        assert_eq!(text(rows), expected(&[&["1.50"], &["12.25"]]));
+
+        for (sql, error) in [
+            ("INSERT INTO payments VALUES (5, 1, 123456789.5, 0)", "Cannot store 123456789.5 in a NUMERIC(10, 2) column"),
+            ("INSERT INTO payments VALUES (5, 1, 'a lot', 0)", "Cannot store 'a lot' in a NUMERIC(10, 2) column"),
+        ] {
+            let Err(message) = database.execute(parse_statement(sql).unwrap()) else {
+                panic!("{} succeeded", sql);
+            };
+            assert!(message.to_string().contains(error), "{}: {}", sql, message);
+        }
+        assert!(parse_statement("SELECT CAST(1 AS NUMERIC(40, 2))").is_err());
+        assert!(parse_statement("SELECT NUMERIC '1.2.3'").is_err());
+        for (sql, error) in [
+            ("SELECT NUMERIC '1' / NUMERIC '0'", "Division by zero"),
+            ("SELECT NUMERIC '99999999999999999999999999999999999999' + 1", "NUMERIC overflow"),
+            ("SELECT CAST(TRUE AS NUMERIC)", "Cannot cast BOOL to NUMERIC"),
+            ("SELECT ROUND(NUMERIC '1', 0, 'ROUND_UP')", "Unknown rounding mode ROUND_UP"),
+        ] {
+            let Statement::Query(plan) = parse_statement(sql).unwrap() else {
+                panic!("not a query");
+            };
+            let Err(message) = database.query(plan) else {
+                panic!("{} succeeded", sql);
+            };
+            assert!(message.to_string().contains(error), "{}: {}", sql, message);
+        }
+    }
+
+    #[test]
+    fn nests_arrays_and_structs_and_unnests_them() {
+        let (_dir, mut database) = setup();
+        for sql in [
+            "CREATE TABLE posts (id INT64 PRIMARY KEY, scores ARRAY<INT64>, author STRUCT<name STRING, tags ARRAY<STRING>>)",
+            "INSERT INTO posts VALUES (1, [3, 1, 2], STRUCT('Alice' AS name, ['x', 'y'] AS tags)), \
+             (2, [], STRUCT('Bob', ARRAY<STRING>[])), (3, NULL, NULL)",
+        ] {
+            database.execute(parse_statement(sql).unwrap()).unwrap();
+        }
+        let int = |v: i64| Value::Int(v);
+        let string = |s: &str| Value::String(s.into());
+
+        let rows = query(
+            &database,
+            "FROM posts |> WHERE id = 1 |> SELECT author.name, posts.author.name, scores[OFFSET(0)], scores[ORDINAL(3)], \
+             scores[SAFE_OFFSET(5)], author.tags[1], ARRAY_LENGTH(scores), ARRAY_CONCAT(scores, [4])",
+        );
+        assert_eq!(
+            rows,
+            vec![vec![
+                string("Alice"),
+                string("Alice"),
+                int(3),
+                int(2),
+                Value::Null,
+                string("y"),
+                int(3),
+                Value::Array(vec![int(3), int(1), int(2), int(4)]),
+            ]]
+        );
+        let rows = query(&database, "SELECT STRUCT(1 AS a, 'b' AS b).b, [1, 2.5], ARRAY_LENGTH(NULL)");
+        assert_eq!(rows, vec![vec![string("b"), Value::Array(vec![Value::Float(1.0), Value::Float(2.5)]), Value::Null]]);
+
+        // the joined rows repeat once per element; LEFT keeps rows without any
+        let rows = query(
+            &database,
+            "FROM posts |> JOIN UNNEST(scores) AS score WITH OFFSET AS i |> SELECT id, score, i |> ORDER BY id, i",
+        );
+        assert_eq!(rows, vec![vec![int(1), int(3), int(0)], vec![int(1), int(1), int(1)], vec![int(1), int(2), int(2)]]);
+        let rows = query(
+            &database,
+            "FROM posts |> LEFT JOIN UNNEST(scores) AS score ON score > 1 |> SELECT id, score |> ORDER BY id, score",
+        );
+        assert_eq!(
+            rows,
+            vec![vec![int(1), int(2)], vec![int(1), int(3)], vec![int(2), Value::Null], vec![int(3), Value::Null]]
+        );
+        let rows = query(
+            &database,
+            "FROM posts |> CROSS JOIN UNNEST(author.tags) |> WHERE tags <> 'x' AND id < 3 |> SELECT id, tags",
+        );
+        assert_eq!(rows, vec![vec![int(1), string("y")]]);
+        let rows = query(&database, "FROM UNNEST([10, 20]) AS x WITH OFFSET |> AGGREGATE SUM(x * offset)");
+        assert_eq!(rows, vec![vec![int(20)]]);
+
+        for (sql, error) in [
+            ("FROM posts |> SELECT scores[OFFSET(3)]", "OFFSET(3) is out of range for an array of 3 elements"),
+            ("FROM posts |> JOIN UNNEST(id) AS x", "UNNEST needs an ARRAY, got INT64"),
+            ("FROM posts |> SELECT author.age", "has no field age"),
+            ("SELECT [[1]]", "An ARRAY cannot hold ARRAYs"),
+        ] {
+            let Statement::Query(plan) = parse_statement(sql).unwrap() else {
+                panic!("not a query");
+            };
+            let Err(message) = database.query(plan) else {
+                panic!("{} succeeded", sql);
+            };
+            assert!(message.to_string().contains(error), "{}: {}", sql, message);
+        }
+        assert!(parse_statement("FROM posts |> RIGHT JOIN UNNEST(scores) AS x").is_err());
+        assert!(parse_statement("FROM UNNEST([1, 2])").is_err());
+    }
+
+    #[test]
+    fn stores_and_queries_json_documents() {
+        let (dir, mut database) = setup();
+        // the third document does not fit into a page
+        let items: Vec<String> = (0..500).map(|i| format!("{{\"sku\": \"item-{}\"}}", i)).collect();
+        for sql in [
+            "CREATE TABLE events (id INT64 PRIMARY KEY, payload JSON)".to_string(),
+            "INSERT INTO events VALUES (1, JSON '{\"kind\": \"click\", \"user\": {\"name\": \"Ann\", \"tags\": [\"a\", \"b\"]}, \"n\": 3}'), \
+             (2, '{\"kind\": \"view\", \"n\": 1.5}'), (4, NULL)"
+                .to_string(),
+            format!("INSERT INTO events VALUES (3, '{{\"kind\": \"order\", \"items\": [{}]}}')", items.join(", ")),
+        ] {
+            database.execute(parse_statement(&sql).unwrap()).unwrap();
+        }
+        drop(database);
+        let database = Database::open(dir.path()).unwrap();
+        let int = |v: i64| Value::Int(v);
+        let string = |s: &str| Value::String(s.into());
+        let json = |text: &str| Value::Json(Json::parse(text).unwrap());
+
+        let rows = query(
+            &database,
+            "FROM events |> WHERE JSON_VALUE(payload, '$.kind') = 'click' OR payload.items IS NOT NULL \
+             |> EXTEND payload.user.name AS who, JSON_VALUE(payload, '$.items[499].sku') AS last \
+             |> SELECT id, who, last |> ORDER BY id",
+        );
+        assert_eq!(rows, vec![vec![int(1), json("\"Ann\""), Value::Null], vec![int(3), Value::Null, string("item-499")]]);
+        let rows = query(
+            &database,
+            "FROM events |> WHERE id < 3 |> SELECT JSON_QUERY(payload, '$.user.tags'), JSON_EXTRACT_ARRAY(payload, '$.user.tags'), \
+             CAST(payload.n AS STRING), payload.missing |> ORDER BY 3 DESC",
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![json("[\"a\", \"b\"]"), Value::Array(vec![json("\"a\""), json("\"b\"")]), string("3"), Value::Null],
+                vec![Value::Null, Value::Null, string("1.5"), Value::Null],
+            ]
+        );
+        // text is parsed on the fly, and what is found stays text
+        let rows = query(
+            &database,
+            "SELECT JSON_VALUE('{\"a\": [1, {\"b c\": \"x\"}]}', '$.a[1][\"b c\"]'), JSON_QUERY('{\"a\": {\"b\": null}}', '$.a'), \
+             JSON_EXTRACT_ARRAY('[1, \"2\"]'), CAST(JSON '{\"b\": 1, \"a\": [true]}' AS STRING), JSON_VALUE(JSON '{\"a\": {}}', '$.a')",
+        );
+        assert_eq!(
+            rows,
+            vec![vec![
+                string("x"),
+                string("{\"b\":null}"),
+                Value::Array(vec![string("1"), string("\"2\"")]),
+                string("{\"a\":[true],\"b\":1}"),
+                Value::Null,
+            ]]
+        );
+
+        for (sql, error) in [
+            ("SELECT CAST('{\"a\": 1' AS JSON)", "Cannot cast '{\"a\": 1' to JSON"),
+            ("SELECT JSON_VALUE(JSON '{}', 'a')", "Invalid JSONPath a"),
+            ("SELECT JSON_VALUE(1, '$')", "Argument 1 of JSON_VALUE must be JSON or STRING, got INT64"),
+            ("SELECT CAST(JSON '1' AS INT64)", "Cannot cast JSON to INT64"),
+        ] {
+            let Statement::Query(plan) = parse_statement(sql).unwrap() else {
+                panic!("not a query");
+            };
+            let Err(message) = database.query(plan) else {
+                panic!("{} succeeded", sql);
+            };
+            assert!(message.to_string().contains(error), "{}: {}", sql, message);
+        }
+        assert!(parse_statement("SELECT JSON '{\"a\": }'").is_err());
+    }
+
+    #[test]
+    fn follows_three_valued_logic_with_nulls() {
+        let (_dir, mut database) = setup();
+        for sql in [
+            "CREATE TABLE truths (id INT64 PRIMARY KEY, p BOOL)",
+            "INSERT INTO truths VALUES (1, TRUE), (2, FALSE), (3, NULL)",
+            "INSERT INTO users VALUES (4, NULL, NULL), (5, 'Eve', NULL)",
+            "INSERT INTO orders VALUES (14, NULL, 3.0), (15, 4, NULL)",
+        ] {
+            database.execute(parse_statement(sql).unwrap()).unwrap();
+        }
+        // T, F and N spell TRUE, FALSE and NULL
+        let truths = |row: &str| -> Vec<Value> {
+            row.chars()
+                .map(|c| match c {
+                    'T' => Value::Bool(true),
+                    'F' => Value::Bool(false),
+                    _ => Value::Null,
+                })
+                .collect()
+        };
+        let ints = |rows: &[&[Option<i64>]]| -> Vec<Vec<Value>> {
+            rows.iter()
+                .map(|row| row.iter().map(|v| v.map_or(Value::Null, Value::Int)).collect())
+                .collect()
+        };
+
+        // AND, OR, NOT, =, IS DISTINCT FROM and IS NOT DISTINCT FROM for every
+        // pair of TRUE, FALSE and NULL, evaluated per row and folded as constants
+        let rows = query(
+            &database,
+            "FROM truths AS l |> CROSS JOIN truths AS r |> ORDER BY l.id, r.id \
+             |> SELECT l.p AND r.p, l.p OR r.p, NOT l.p, l.p = r.p, l.p IS DISTINCT FROM r.p, l.p IS NOT DISTINCT FROM r.p",
+        );
+        let expected = ["TTFTFT", "FTFFTF", "NTFNTF", "FTTFTF", "FFTTFT", "FNTNTF", "NTNNTF", "FNNNTF", "NNNNFT"];
+        assert_eq!(rows, expected.iter().map(|row| truths(row)).collect::<Vec<_>>());
+        let rows = query(
+            &database,
+            "SELECT NULL AND FALSE, NULL OR TRUE, NULL AND TRUE, NOT NULL, NULL = NULL, NULL IS NOT DISTINCT FROM NULL, \
+             1 IS DISTINCT FROM NULL, 1 IS NOT DISTINCT FROM 1.0",
+        );
+        assert_eq!(rows, vec![truths("FTNNNTTT")]);
+        let rows = query(&database, "SELECT 1 + NULL, IF(NULL, 1, 2), COALESCE(NULL, NULL, 3), NULLIF(1, 1), CONCAT('a', NULL)");
+        assert_eq!(rows, ints(&[&[None, Some(2), Some(3), None, None]]));
+
+        // a filter keeps only rows where it is TRUE
+        for (condition, ids) in [
+            ("age > 20 OR name = 'Eve'", vec![1, 3, 5]),
+            ("NOT (age > 20)", vec![2]),
+            ("NOT (age > 20 AND name = 'Alice')", vec![2, 3, 5]),
+            ("age IS NOT DISTINCT FROM NULL", vec![4, 5]),
+            ("name IS DISTINCT FROM 'Eve'", vec![1, 2, 3, 4]),
+        ] {
+            let rows = query(&database, &format!("FROM users |> WHERE {} |> SELECT id |> ORDER BY id", condition));
+            assert_eq!(rows, ids.into_iter().map(|id| vec![Value::Int(id)]).collect::<Vec<_>>(), "{}", condition);
+        }
+
+        // NULL keys never match, except under IS NOT DISTINCT FROM
+        let rows = query(&database, "FROM users AS a |> JOIN users AS b ON a.age = b.age |> ORDER BY a.id, b.id |> SELECT a.id, b.id");
+        assert_eq!(rows, ints(&[&[Some(1), Some(1)], &[Some(2), Some(2)], &[Some(3), Some(3)]]));
+        let rows = query(
+            &database,
+            "FROM users AS a |> JOIN users AS b ON a.age IS NOT DISTINCT FROM b.age |> WHERE a.id > 3 |> ORDER BY a.id, b.id |> SELECT a.id, b.id",
+        );
+        assert_eq!(rows, ints(&[&[Some(4), Some(4)], &[Some(4), Some(5)], &[Some(5), Some(4)], &[Some(5), Some(5)]]));
+        let rows = query(
+            &database,
+            "FROM orders |> LEFT JOIN users ON orders.user_id = users.id |> WHERE orders.id > 13 |> ORDER BY orders.id |> SELECT orders.id, users.id",
+        );
+        assert_eq!(rows, ints(&[&[Some(14), None], &[Some(15), Some(4)]]));
+
+        // IN is NULL rather than FALSE when a NULL might have been equal
+        let rows = query(&database, "FROM users |> WHERE id NOT IN (FROM orders |> SELECT user_id) |> SELECT id");
+        assert!(rows.is_empty());
+        let rows = query(&database, "FROM users |> WHERE id IN (FROM orders |> SELECT user_id) |> SELECT id |> ORDER BY id");
+        assert_eq!(rows, ints(&[&[Some(1)], &[Some(2)], &[Some(3)], &[Some(4)]]));
+        let rows = query(
+            &database,
+            "SELECT 1 IN (FROM orders |> SELECT user_id), 9 IN (FROM orders |> SELECT user_id), NULL IN (SELECT 1 |> WHERE FALSE)",
+        );
+        assert_eq!(rows, vec![truths("TNF")]);
+
+        // NULLs sort first ascending and last descending unless told otherwise
+        for (order, ids) in [
+            ("age, id", [4, 5, 2, 1, 3]),
+            ("age DESC, id", [3, 1, 2, 4, 5]),
+            ("age NULLS LAST, id", [2, 1, 3, 4, 5]),
+            ("age DESC NULLS FIRST, id", [4, 5, 3, 1, 2]),
+        ] {
+            let rows = query(&database, &format!("FROM users |> ORDER BY {} |> SELECT id", order));
+            assert_eq!(rows, ids.iter().map(|&id| vec![Value::Int(id)]).collect::<Vec<_>>(), "{}", order);
+        }
+
+        // NULLs form one group and one distinct value, and aggregates skip them
+        let rows = query(&database, "FROM users |> AGGREGATE COUNT(*), COUNT(name) GROUP BY age |> ORDER BY age");
+        assert_eq!(
+            rows,
+            ints(&[&[None, Some(2), Some(1)], &[Some(17), Some(1), Some(1)], &[Some(30), Some(1), Some(1)], &[Some(45), Some(1), Some(1)]])
+        );
+        let rows = query(&database, "FROM users |> SELECT age |> UNION DISTINCT (FROM users |> SELECT age) |> ORDER BY age");
+        assert_eq!(rows, ints(&[&[None], &[Some(17)], &[Some(30)], &[Some(45)]]));
+        let rows = query(
+            &database,
+            "FROM users |> AGGREGATE COUNT(*), COUNT(age), COUNT(DISTINCT age), SUM(age), MIN(age), MAX(age), AVG(age)",
+        );
+        assert_eq!(
+            rows,
+            vec![[5, 3, 3, 92, 17, 45].iter().map(|&v| Value::Int(v)).chain([Value::Float(92.0 / 3.0)]).collect::<Vec<_>>()]
+        );
+        let rows = query(&database, "FROM users |> WHERE age IS NULL |> AGGREGATE COUNT(*), COUNT(age), SUM(age), MAX(age), AVG(age)");
+        assert_eq!(rows, ints(&[&[Some(2), Some(0), None, None, None]]));
+    }
+
+    #[test]
+    fn binds_names_and_types_before_planning() {
+        let (_dir, mut database) = setup();
+        for (sql, error) in [
+            ("FROM userz", "Table userz does not exist, did you mean users?"),
+            ("FROM users |> SELECT nmae", "Unknown column nmae, did you mean name?"),
+            ("FROM users |> ORDER BY agee", "Unknown column agee, did you mean age?"),
+            ("FROM users |> AGGREGATE COUNT(*) GROUP BY nam", "Unknown column nam, did you mean name?"),
+            ("FROM users AS u |> SELECT users.id", "Unknown column users.id, did you mean u.id?"),
+            ("FROM users |> JOIN orders ON users.id = orders.user_id |> SELECT usres.name", "did you mean users.name?"),
+            ("FROM users |> JOIN orders ON users.id = user_id |> SELECT id", "Column id is ambiguous, it could be users.id or orders.id"),
+            ("FROM users |> SELECT LENGHT(name)", "Unknown function LENGHT, did you mean LENGTH?"),
+            ("FROM users |> WHERE age", "WHERE needs a BOOL condition, got age of type INT64"),
+            ("FROM users |> JOIN orders ON amount", "JOIN ... ON needs a BOOL condition, got amount of type FLOAT64"),
+            ("FROM users |> WHERE name > 3", "Cannot compare STRING with INT64"),
+            ("FROM users |> WHERE age > 1 AND name", "Cannot apply AND to BOOL and STRING"),
+            ("FROM users |> WHERE NOT age", "Cannot apply NOT to INT64"),
+            ("FROM users |> SELECT id + name", "Cannot apply + to INT64 and STRING"),
+            ("FROM users |> SELECT -name", "Cannot apply - to STRING"),
+            ("SELECT DATE '2024-01-01' > '2024-13-01'", "Cannot cast '2024-13-01' to DATE"),
+        ] {
+            let Statement::Query(plan) = parse_statement(sql).unwrap() else {
+                panic!("not a query");
+            };
+            let Err(message) = database.query(plan) else {
+                panic!("{} succeeded", sql);
+            };
+            assert!(message.to_string().contains(error), "{}: {}", sql, message);
+        }
+        let Statement::Query(plan) = parse_statement("FROM users |> SELECT bogus").unwrap() else {
+            panic!("not a query");
+        };
+        let Err(message) = database.query(plan) else {
+            panic!("bogus resolved");
+        };
+        assert!(!message.to_string().contains("did you mean"), "{}", message);
+
+        // a star in a select list is expanded, not bound as an expression
+        let rows = query(&database, "FROM users |> WHERE id = 2 |> SELECT *, age + 1 |> SELECT *");
+        assert_eq!(rows, vec![vec![Value::Int(2), Value::String("Bob".into()), Value::Int(17), Value::Int(18)]]);
+
+        // operands are converted to the type their operator or function computes in
+        let rows = query(
+            &database,
+            "SELECT COALESCE(1, 2.5), IF(FALSE, 2.5, 1), IFNULL(NULL, 1), DATE '2024-01-02' > '2024-01-01', [1, 2.5]",
+        );
+        assert_eq!(
+            rows,
+            vec![vec![
+                Value::Float(1.0),
+                Value::Float(1.0),
+                Value::Int(1),
+                Value::Bool(true),
+                Value::Array(vec![Value::Float(1.0), Value::Float(2.5)]),
+            ]]
+        );
+        let Statement::Query(plan) =
+            parse_statement("FROM users |> WHERE age * 1.5 > 40 |> SELECT name, age * 1.5 |> ORDER BY name").unwrap()
+        else {
+            panic!("not a query");
+        };
+        let result = database.query(plan).unwrap();
+        let names: Vec<&str> = result.schema.fields.iter().map(|field| field.name.as_str()).collect();
+        assert_eq!(names, ["name", "age * 1.5"]);
+        assert_eq!(
+            result.rows,
+            vec![
+                vec![Value::String("Alice".into()), Value::Float(45.0)],
+                vec![Value::String("Carol".into()), Value::Float(67.5)],
+            ]
+        );
+        let plan = database
+            .execute(parse_statement("EXPLAIN FROM users |> WHERE age * 1.5 > 40 |> SELECT age * 1.5").unwrap())
+            .unwrap();
+        assert!(plan.contains("PROJECT CAST(age AS FLOAT64) * 1.5 AS age * 1.5"), "{}", plan);
+        assert!(plan.contains("FILTER CAST(age AS FLOAT64) * 1.5 > 40"), "{}", plan);
+    }
+
+    #[test]
+    fn samples_pages_or_rows_of_a_table() {
+        let (_dir, mut database) = setup();
+        let values: Vec<String> = (0..2000).map(|i| format!("({}, 'event number {}')", i, i)).collect();
+        for sql in [
+            "CREATE TABLE events (id INT64 PRIMARY KEY, note STRING)".to_string(),
+            format!("INSERT INTO events VALUES {}", values.join(", ")),
+        ] {
+            database.execute(parse_statement(&sql).unwrap()).unwrap();
+        }
+        let count = |sql: &str| match query(&database, &format!("{} |> AGGREGATE COUNT(*)", sql))[0][0] {
+            Value::Int(count) => count,
+            ref value => panic!("{}", value),
+        };
+
+        for method in ["SYSTEM", "BERNOULLI"] {
+            let sample = format!("FROM events |> TABLESAMPLE {} (25 PERCENT) REPEATABLE (7)", method);
+            let rows = query(&database, &format!("{} |> SELECT id", sample));
+            assert!((250..750).contains(&rows.len()), "{} kept {} rows", method, rows.len());
+            assert_eq!(query(&database, &format!("{} |> SELECT id", sample)), rows);
+            // the sample is drawn before the filter
+            let even = rows.iter().filter(|row| matches!(row[0], Value::Int(id) if id % 2 == 0)).count();
+            assert_eq!(count(&format!("{} |> WHERE id / 2 * 2 = id", sample)), even as i64);
+            assert_eq!(count(&format!("FROM events AS e TABLESAMPLE {} (0 PERCENT)", method)), 0);
+            assert_eq!(count(&format!("FROM events TABLESAMPLE {} (100 PERCENT)", method)), 2000);
+        }
+
+        // SYSTEM reads only the pages it keeps
+        let mut read_pages = |sql: &str| {
+            let analyzed = database.execute(parse_statement(&format!("EXPLAIN ANALYZE {}", sql)).unwrap()).unwrap();
+            let line = analyzed.lines().find(|line| line.contains("SCAN TABLE events")).unwrap().to_string();
+            let hits: u64 = line.split("hit=").nth(1).unwrap().split(' ').next().unwrap().parse().unwrap();
+            let misses: u64 = line.split("miss=").nth(1).unwrap().split([',', ')']).next().unwrap().parse().unwrap();
+            hits + misses
+        };
+        let all = read_pages("FROM events");
+        assert!(read_pages("FROM events TABLESAMPLE SYSTEM (10 PERCENT) REPEATABLE (1)") < all / 2);
+        assert_eq!(read_pages("FROM events TABLESAMPLE BERNOULLI (10 PERCENT) REPEATABLE (1)"), all);
+
+        for sql in [
+            "FROM events |> WHERE id > 3 |> TABLESAMPLE SYSTEM (10 PERCENT)",
+            "FROM events TABLESAMPLE BERNOULLI (150 PERCENT)",
+            "FROM events TABLESAMPLE RANDOM (10 PERCENT)",
+        ] {
+            assert!(parse_statement(sql).is_err(), "{}", sql);
+        }
+    }
+
+    #[test]
+    fn pivots_values_into_columns_and_back() {
+        let (_dir, mut database) = setup();
+        for sql in [
+            "CREATE TABLE sales (id INT64 PRIMARY KEY, region STRING, quarter STRING, amount INT64)",
+            "INSERT INTO sales VALUES (1, 'east', 'Q1', 10), (2, 'east', 'Q2', 20), (3, 'east', 'Q1', 5), \
+             (4, 'west', 'Q2', 7), (5, 'west', 'Q3', 1)",
+        ] {
+            database.execute(parse_statement(sql).unwrap()).unwrap();
+        }
+        let int = Value::Int;
+        let string = |s: &str| Value::String(s.to_string());
+
+        let rows = query(
+            &database,
+            "FROM sales |> SELECT region, quarter, amount |> PIVOT (SUM(amount) FOR quarter IN ('Q1', 'Q2' AS second)) \
+             |> ORDER BY region",
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![string("east"), int(15), int(20)],
+                vec![string("west"), Value::Null, int(7)],
+            ]
+        );
+        let plan = match parse_statement("FROM sales |> SELECT region, quarter |> PIVOT (COUNT(*) AS n FOR quarter IN ('Q1'))").unwrap() {
+            Statement::Query(plan) => plan,
+            _ => panic!("not a query"),
+        };
+        let result = database.query(plan).unwrap();
+        assert_eq!(result.schema.fields.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(), ["region", "n_Q1"]);
+        assert!(parse_statement("FROM sales |> PIVOT (SUM(amount), MAX(amount) FOR quarter IN ('Q1'))").is_err());
+
+        let wide = "SELECT 'a' AS k, 1 AS x, NULL AS y |> UNION ALL (SELECT 'b', 2, 3)";
+        let rows = query(
+            &database,
+            &format!("{} |> UNPIVOT (v FOR column_name IN (x, y AS 'why')) |> ORDER BY k, column_name", wide),
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![string("a"), string("x"), int(1)],
+                vec![string("b"), string("why"), int(3)],
+                vec![string("b"), string("x"), int(2)],
+            ]
+        );
+        let rows = query(&database, &format!("{} |> UNPIVOT INCLUDE NULLS (v FOR c IN (x, y)) |> WHERE k = 'a' |> SELECT v", wide));
+        assert_eq!(rows, vec![vec![int(1)], vec![Value::Null]]);
+    }
+
+    #[test]
+    fn computes_window_functions() {
+        let (_dir, mut database) = setup();
+        let int = Value::Int;
+        let float = Value::Float;
+
+        let rows = query(
+            &database,
+            "FROM orders |> WINDOW ROW_NUMBER() OVER (PARTITION BY user_id ORDER BY id) AS n, \
+                SUM(amount) OVER (PARTITION BY user_id ORDER BY id) AS running, \
+                RANK() OVER (ORDER BY user_id) AS rank, DENSE_RANK() OVER (ORDER BY user_id) AS dense \
+             |> ORDER BY id |> SELECT id, n, running, rank, dense",
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![int(10), int(1), float(5.0), int(1), int(1)],
+                vec![int(11), int(2), float(12.5), int(1), int(1)],
+                vec![int(12), int(1), float(20.0), int(4), int(3)],
+                vec![int(13), int(1), float(1.0), int(3), int(2)],
+            ]
+        );
+
+        let rows = query(
+            &database,
+            "FROM orders |> EXTEND id + 1 AS next, LAG(amount) OVER (ORDER BY id) AS previous, \
+                LEAD(amount, 2, 0) OVER (ORDER BY id) AS after_next, \
+                FIRST_VALUE(id) OVER (ORDER BY amount ROWS BETWEEN 1 PRECEDING AND 1 FOLLOWING) AS low, \
+                AVG(amount) OVER (ORDER BY amount RANGE BETWEEN 5 PRECEDING AND CURRENT ROW) AS near, \
+                COUNT(*) OVER () AS total, user_id * 2 AS doubled \
+             |> ORDER BY id |> DROP user_id, amount",
+        );
+        assert_eq!(
+            rows,
+            vec![
+                vec![int(10), int(11), Value::Null, float(20.0), int(13), float(3.0), int(4), int(2)],
+                vec![int(11), int(12), float(5.0), float(1.0), int(10), float(6.25), int(4), int(2)],
+                vec![int(12), int(13), float(7.5), float(0.0), int(11), float(20.0), int(4), int(6)],
+                vec![int(13), int(14), float(20.0), float(0.0), int(13), float(1.0), int(4), int(4)],
+            ]
+        );
+
+        // filters on the partition keys go below the window
+        let explain = database
+            .execute(
+                parse_statement(
+                    "EXPLAIN FROM orders |> WINDOW SUM(amount) OVER (PARTITION BY user_id) AS total \
+                     |> WHERE user_id = 1 AND total > 10",
+                )
+                .unwrap(),
+            )
+            .unwrap();
+        assert!(explain.contains("FILTER user_id = 1"), "{}", explain);
+        assert!(explain.contains("FILTER WHERE total > 10"), "{}", explain);
+
+        assert!(parse_statement("FROM orders |> WINDOW RANK() OVER (ORDER BY id ROWS 1 PRECEDING)").is_err());
+        assert!(parse_statement("FROM orders |> WINDOW SUM(id) OVER (ROWS BETWEEN CURRENT ROW AND 1 PRECEDING)").is_err());
+        assert!(parse_statement("FROM orders |> EXTEND SUM(id) AS total").is_err());
+        let Statement::Query(plan) =
+            parse_statement("FROM orders |> WINDOW SUM(id) OVER (ORDER BY id, user_id RANGE 1 PRECEDING)").unwrap()
+        else {
+            panic!("not a query");
+        };
+        assert!(database.query(plan).is_err());
+    }
+
+    #[test]
+    fn explain_analyze_reports_actual_rows() {
+        let (_dir, mut database) = setup();
+        let explain = database
+            .execute(parse_statement("EXPLAIN FROM users |> WHERE age > 20").unwrap())
+            .unwrap();
+        assert!(explain.contains("SCAN TABLE users FILTER age > 20"));
+        assert!(!explain.contains("actual"));
+
+        let analyzed = database
+            .execute(parse_statement("explain analyze FROM users |> WHERE age > 20").unwrap())
+            .unwrap();
+        let scan = analyzed.lines().find(|line| line.contains("SCAN TABLE users")).unwrap();
+        assert!(scan.contains("actual rows=2, loops=1"), "{}", scan);
+        // the three users share a page, which the scan requests once
+        assert!(scan.contains("buffers hit=1 miss=0") || scan.contains("buffers hit=0 miss=1"), "{}", scan);
+
+        database.execute(parse_statement("SET memory_budget = 1").unwrap()).unwrap();
+        let analyzed = database
+            .execute(parse_statement("EXPLAIN ANALYZE FROM users |> ORDER BY name DESC NULLS LAST").unwrap())
+            .unwrap();
+        assert!(analyzed.contains("ORDER BY name DESC NULLS LAST"), "{}", analyzed);
+        assert!(analyzed.contains("spilled 3 runs"), "{}", analyzed);
+    }
+}
@@ -0,0 +1,395 @@
+use super::hash_join::SPILL_PARTITIONS;
+use super::spill::{estimated_size, SpillFile};
+use super::{ExecutionNode, Executor};
+use crate::aggregate::{aggregate_fields, AggregateCall, AggregateFunction};
+use crate::decimal::{Decimal, RoundingMode};
+use crate::errors::ExecutionError;
+use crate::expression::Expr;
+use crate::schema::Schema;
+use crate::value::{Row, Value};
+use sloth_db::storage::record_manager::ScanCursor;
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+/// Partitions are split again at most this many times; deeper partitions are
+/// aggregated in memory whatever their size.
+const MAX_SPILL_DEPTH: usize = 4;
+
+/// Hash aggregation. Each input row is reduced to its grouping key followed
+/// by one argument value per aggregate. Once the groups exceed the memory
+/// budget, groups already in memory keep aggregating but rows of new groups
+/// are partitioned on their key into spill files, which are aggregated one at
+/// a time after the groups in memory have been returned. Groups come out in
+/// the order of their first row.
+pub struct HashAggregateExecutor {
+    input: ExecutionNode,
+    group_by: Vec<Expr>,
+    calls: Vec<AggregateCall>,
+    schema: Schema,
+    memory_budget: usize,
+    spill_directory: PathBuf,
+    output: std::vec::IntoIter<Row>,
+    /// Spilled partitions still to be aggregated, with how often their rows
+    /// have been partitioned
+    pending: VecDeque<(SpillFile, usize)>,
+    spilled_partitions: usize,
+}
+
+impl HashAggregateExecutor {
+    pub fn new(
+        input: ExecutionNode,
+        group_by: &[(Expr, Option<String>)],
+        aggregates: &[(AggregateCall, Option<String>)],
+        memory_budget: usize,
+        spill_directory: PathBuf,
+    ) -> Result<Self, ExecutionError> {
+        let schema = Schema::new(aggregate_fields(group_by, aggregates, input.schema())?);
+        Ok(Self {
+            input,
+            group_by: group_by.iter().map(|(expr, _)| expr.clone()).collect(),
+            calls: aggregates.iter().map(|(call, _)| call.clone()).collect(),
+            schema,
+            memory_budget,
+            spill_directory,
+            output: Vec::new().into_iter(),
+            pending: VecDeque::new(),
+            spilled_partitions: 0,
+        })
+    }
+
+    /// The grouping key of an input row followed by the aggregate arguments.
+    fn evaluate(&self, row: &[Value]) -> Result<Row, ExecutionError> {
+        let schema = self.input.schema();
+        let mut values = Vec::with_capacity(self.group_by.len() + self.calls.len());
+        for expr in &self.group_by {
+            values.push(expr.evaluate(schema, row)?);
+        }
+        for call in &self.calls {
+            values.push(match &call.argument {
+                Some(argument) => argument.evaluate(schema, row)?,
+                None => Value::Null,
+            });
+        }
+        Ok(values)
+    }
+
+    fn new_groups(&self, depth: usize) -> Groups {
+        Groups {
+            depth,
+            key_length: self.group_by.len(),
+            index: HashMap::new(),
+            entries: Vec::new(),
+            memory: 0,
+            partitions: None,
+        }
+    }
+
+    /// Adds an evaluated row to its group, or to a partition once the budget
+    /// is exhausted and the group is not in memory.
+    fn add(&self, groups: &mut Groups, row: Row) -> Result<(), ExecutionError> {
+        let key = &row[..groups.key_length];
+        let entry = match groups.index.get(key) {
+            Some(&entry) => entry,
+            None => {
+                if let Some(partitions) = &mut groups.partitions {
+                    return partitions[partition_of(key, groups.depth)].write(&row);
+                }
+                groups.memory += estimated_size(key) + self.calls.len() * std::mem::size_of::<Accumulator>();
+                groups.index.insert(key.to_vec(), groups.entries.len());
+                groups.entries.push((key.to_vec(), self.calls.iter().map(Accumulator::new).collect()));
+                groups.entries.len() - 1
+            }
+        };
+        let accumulators = &mut groups.entries[entry].1;
+        for ((accumulator, call), value) in accumulators.iter_mut().zip(&self.calls).zip(&row[groups.key_length..]) {
+            groups.memory += accumulator.update(call, value)?;
+        }
+        if groups.memory > self.memory_budget && groups.partitions.is_none() && groups.depth < MAX_SPILL_DEPTH {
+            groups.partitions = Some(
+                (0..SPILL_PARTITIONS)
+                    .map(|_| SpillFile::create(&self.spill_directory))
+                    .collect::<Result<_, _>>()?,
+            );
+        }
+        Ok(())
+    }
+
+    /// Makes the groups in memory the next output and queues their partitions.
+    fn finish(&mut self, groups: Groups) {
+        let rows: Vec<Row> = groups
+            .entries
+            .into_iter()
+            .map(|(mut key, accumulators)| {
+                key.extend(accumulators.into_iter().map(Accumulator::finish));
+                key
+            })
+            .collect();
+        self.output = rows.into_iter();
+        if let Some(partitions) = groups.partitions {
+            self.spilled_partitions += partitions.len();
+            for partition in partitions.into_iter().rev().filter(|partition| partition.rows() > 0) {
+                self.pending.push_front((partition, groups.depth + 1));
+            }
+        }
+    }
+}
+
+fn partition_of(key: &[Value], depth: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    // every level partitions differently, so a partition's rows spread out
+    (depth, key).hash(&mut hasher);
+    (hasher.finish() % SPILL_PARTITIONS as u64) as usize
+}
+
+/// Groups aggregated in memory, in the order of their first row.
+struct Groups {
+    depth: usize,
+    key_length: usize,
+    index: HashMap<Row, usize>,
+    entries: Vec<(Row, Vec<Accumulator>)>,
+    /// Estimated bytes of the keys and aggregate states
+    memory: usize,
+    partitions: Option<Vec<SpillFile>>,
+}
+
+impl Executor for HashAggregateExecutor {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.pending.clear();
+        self.spilled_partitions = 0;
+        let mut groups = self.new_groups(0);
+        if self.group_by.is_empty() {
+            // aggregating without groups yields a row even without input
+            groups.index.insert(Vec::new(), 0);
+            groups.entries.push((Vec::new(), self.calls.iter().map(Accumulator::new).collect()));
+        }
+
+        self.input.open()?;
+        while let Some(row) = self.input.next()? {
+            let row = self.evaluate(&row)?;
+            self.add(&mut groups, row)?;
+        }
+        self.finish(groups);
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        loop {
+            if let Some(row) = self.output.next() {
+                return Ok(Some(row));
+            }
+            let Some((mut partition, depth)) = self.pending.pop_front() else {
+                return Ok(None);
+            };
+            let mut groups = self.new_groups(depth);
+            let mut cursor = ScanCursor::default();
+            while let Some(row) = partition.read(&mut cursor) {
+                self.add(&mut groups, row)?;
+            }
+            self.finish(groups);
+        }
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.input]
+    }
+
+    fn details(&self) -> Option<String> {
+        (self.spilled_partitions > 0).then(|| format!("spilled into {} partitions", self.spilled_partitions))
+    }
+}
+
+/// The running state of one aggregate for one group.
+#[derive(Clone)]
+pub(super) struct Accumulator {
+    /// Values seen so far, for DISTINCT aggregates
+    seen: Option<HashSet<Value>>,
+    state: State,
+}
+
+#[derive(Clone)]
+enum State {
+    Count(i64),
+    /// NULL until the first value
+    Sum(Value),
+    /// The sum is a FLOAT64 for integers and floats, exact for NUMERIC
+    Avg { sum: Value, count: i64 },
+    /// MIN, MAX and ANY_VALUE: NULL until the first value
+    Value(Value),
+    Array(Vec<Value>),
+    String(Option<String>),
+}
+
+impl Accumulator {
+    pub(super) fn new(call: &AggregateCall) -> Self {
+        let state = match call.function {
+            AggregateFunction::Count => State::Count(0),
+            AggregateFunction::Sum => State::Sum(Value::Null),
+            AggregateFunction::Avg => State::Avg {
+                sum: Value::Null,
+                count: 0,
+            },
+            AggregateFunction::Min | AggregateFunction::Max | AggregateFunction::AnyValue => State::Value(Value::Null),
+            AggregateFunction::ArrayAgg => State::Array(Vec::new()),
+            AggregateFunction::StringAgg => State::String(None),
+        };
+        Self {
+            seen: call.distinct.then(HashSet::new),
+            state,
+        }
+    }
+
+    /// Adds the argument value of a row and returns by how many bytes the
+    /// state grew. NULLs are ignored, except that `COUNT(*)` counts every row.
+    pub(super) fn update(&mut self, call: &AggregateCall, value: &Value) -> Result<usize, ExecutionError> {
+        if call.argument.is_none() {
+            if let State::Count(count) = &mut self.state {
+                *count += 1;
+            }
+            return Ok(0);
+        }
+        if value.is_null() {
+            return Ok(0);
+        }
+        let size = estimated_size(std::slice::from_ref(value));
+        let mut grown = 0;
+        if let Some(seen) = &mut self.seen {
+            if !seen.insert(value.clone()) {
+                return Ok(0);
+            }
+            grown += size;
+        }
+
+        match &mut self.state {
+            State::Count(count) => *count += 1,
+            State::Sum(sum) => *sum = add(call, sum, value)?,
+            State::Avg { sum, count } => {
+                let value = match value {
+                    Value::Int(i) => &Value::Float(*i as f64),
+                    Value::Float(_) | Value::Decimal(_) => value,
+                    value => return Err(ExecutionError::new(&format!("Cannot average {} in {}", value, call))),
+                };
+                *sum = add(call, sum, value)?;
+                *count += 1;
+            }
+            State::Value(current) => {
+                let replace = match call.function {
+                    AggregateFunction::Min => current.is_null() || value.compare(current) == Some(Ordering::Less),
+                    AggregateFunction::Max => current.is_null() || value.compare(current) == Some(Ordering::Greater),
+                    _ => current.is_null(),
+                };
+                if replace {
+                    *current = value.clone();
+                }
+            }
+            State::Array(items) => {
+                items.push(value.clone());
+                grown += size;
+            }
+            State::String(string) => {
+                let Value::String(value) = value else {
+                    return Err(ExecutionError::new(&format!("Cannot concatenate {} in {}", value, call)));
+                };
+                match string {
+                    Some(string) => {
+                        string.push_str(call.delimiter.as_deref().unwrap_or(","));
+                        string.push_str(value);
+                    }
+                    None => *string = Some(value.clone()),
+                }
+                grown += size;
+            }
+        }
+        Ok(grown)
+    }
+
+    /// The result; aggregates other than COUNT are NULL without any value.
+    pub(super) fn finish(self) -> Value {
+        match self.state {
+            State::Count(count) => Value::Int(count),
+            State::Sum(sum) => sum,
+            State::Avg { count: 0, .. } => Value::Null,
+            State::Avg {
+                sum: Value::Decimal(sum),
+                count,
+            } => {
+                // at the sum's own scale when more digits do not fit
+                let count = Decimal::from_int(count);
+                let scale = Decimal::quotient_scale(sum.scale(), 0);
+                let average = sum
+                    .checked_div(count, scale, RoundingMode::HalfAwayFromZero)
+                    .or_else(|| sum.checked_div(count, sum.scale(), RoundingMode::HalfAwayFromZero));
+                average.map_or(Value::Null, Value::Decimal)
+            }
+            State::Avg { sum, count } => match sum {
+                Value::Float(sum) => Value::Float(sum / count as f64),
+                _ => Value::Null,
+            },
+            State::Value(value) => value,
+            State::Array(items) if items.is_empty() => Value::Null,
+            State::Array(items) => Value::Array(items),
+            State::String(string) => string.map_or(Value::Null, Value::String),
+        }
+    }
+}
+
+/// Adds a value to a sum, which is NULL before the first value; integers
+/// and decimals are added exactly.
+fn add(call: &AggregateCall, sum: &Value, value: &Value) -> Result<Value, ExecutionError> {
+    let overflow = |type_name: &str| ExecutionError::new(&format!("{} overflow in {}", type_name, call));
+    Ok(match (sum, value) {
+        (Value::Null, value) => value.clone(),
+        (Value::Int(a), Value::Int(b)) => Value::Int(a.checked_add(*b).ok_or_else(|| overflow("INT64"))?),
+        (Value::Float(a), Value::Float(b)) => Value::Float(a + b),
+        (Value::Decimal(a), Value::Decimal(b)) => Value::Decimal(a.checked_add(*b).ok_or_else(|| overflow("NUMERIC"))?),
+        (_, value) => return Err(ExecutionError::new(&format!("Cannot add {} in {}", value, call))),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::tests::{input, rows};
+    use crate::expression::parse_expression;
+    use crate::lexer::Tokens;
+    use tempfile::tempdir;
+
+    fn call(text: &str) -> (AggregateCall, Option<String>) {
+        (AggregateCall::parse(&mut Tokens::new(text).unwrap()).unwrap(), None)
+    }
+
+    #[test]
+    fn spilled_groups_aggregate_like_groups_in_memory() {
+        let dir = tempdir().unwrap();
+        let data = rows((0..2000).map(|i| (i % 13 != 0).then_some(i * 7 % 600)));
+        let group_by = [(parse_expression(&mut Tokens::new("k").unwrap()).unwrap(), None)];
+        let aggregates = [call("COUNT(*)"), call("SUM(v)"), call("COUNT(DISTINCT k)"), call("MAX(v)")];
+
+        let mut results = Vec::new();
+        for memory_budget in [usize::MAX, 2048] {
+            let mut aggregate =
+                HashAggregateExecutor::new(input("t", data.clone()), &group_by, &aggregates, memory_budget, dir.path().to_path_buf())
+                    .unwrap();
+            aggregate.open().unwrap();
+            let mut output = Vec::new();
+            while let Some(row) = aggregate.next().unwrap() {
+                output.push(row);
+            }
+            output.sort_by(|a, b| super::super::compare_keys(a, b));
+            assert_eq!(aggregate.details().is_some(), memory_budget == 2048);
+            results.push(output);
+        }
+        // 600 groups of keys and one of NULL keys
+        assert_eq!(results[0].len(), 601);
+        assert_eq!(results[0], results[1]);
+        let total: i64 = results[0].iter().map(|row| if let Value::Int(count) = row[1] { count } else { 0 }).sum();
+        assert_eq!(total, 2000);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,43 @@
+use super::{ExecutionNode, Executor};
+use crate::errors::ExecutionError;
+use crate::schema::{Field, Schema};
+use crate::value::Row;
+
+/// Passes rows through under the name of a subquery.
+pub struct AliasExecutor {
+    input: ExecutionNode,
+    schema: Schema,
+}
+
+impl AliasExecutor {
+    pub fn new(input: ExecutionNode, name: &str) -> Self {
+        let fields = input
+            .schema()
+            .fields
+            .iter()
+            .map(|field| Field::new(Some(name), &field.name, field.data_type.clone()))
+            .collect();
+        Self {
+            input,
+            schema: Schema::new(fields),
+        }
+    }
+}
+
+impl Executor for AliasExecutor {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.input.open()
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        self.input.next()
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.input]
+    }
+}
@@ -0,0 +1,159 @@
+use super::{build, CommonTables, ExecutionContext, ExecutionNode, Executor};
+use crate::catalog::Catalog;
+use crate::errors::ExecutionError;
+use crate::expression::{evaluate_binary, BinaryOperator, Expr};
+use crate::logical_plan::LogicalPlan;
+use crate::optimizer::optimize;
+use crate::physical_plan::convert_to_physical_plan;
+use crate::schema::{Field, Schema};
+use crate::subquery::{apply_type, bind_outer_columns, ApplyKind};
+use crate::value::{Row, Value};
+use std::collections::HashMap;
+
+/// Appends the outcome of a subquery to every input row. The columns of the
+/// row the subquery refers to are replaced by their values, and the bound
+/// subquery is optimized and run like a query of its own. Outcomes are
+/// remembered per binding, so an uncorrelated subquery runs once.
+pub struct ApplyExecutor {
+    input: ExecutionNode,
+    subquery: LogicalPlan,
+    kind: ApplyKind,
+    /// The subquery is planned while rows are read, when the context the
+    /// executor was built with is gone
+    catalog: Catalog,
+    memory_budget: usize,
+    common_tables: CommonTables,
+    /// Columns of the input the subquery refers to, with their positions
+    outer: Vec<(Expr, usize)>,
+    schema: Schema,
+    outcomes: HashMap<Row, Value>,
+    runs: usize,
+}
+
+impl ApplyExecutor {
+    pub fn new(
+        input: ExecutionNode,
+        subquery: &LogicalPlan,
+        kind: &ApplyKind,
+        name: &str,
+        context: &ExecutionContext,
+    ) -> Result<Self, ExecutionError> {
+        let data_type = apply_type(subquery, kind, context.catalog)?;
+        let mut outer: Vec<(Expr, usize)> = Vec::new();
+        let mut error = None;
+        bind_outer_columns(subquery.clone(), context.catalog, &mut |expr| {
+            if let Expr::Column { qualifier, name } = &expr {
+                match input.schema().resolve(qualifier.as_deref(), name) {
+                    Ok(index) if !outer.iter().any(|(column, _)| *column == expr) => outer.push((expr.clone(), index)),
+                    Ok(_) => {}
+                    Err(e) => {
+                        error.get_or_insert(e);
+                    }
+                }
+            }
+            Expr::Literal(Value::Null)
+        })?;
+        if let Some(error) = error {
+            return Err(error);
+        }
+        let mut fields = input.schema().fields.clone();
+        fields.push(Field::new(None, name, data_type));
+        Ok(Self {
+            input,
+            subquery: subquery.clone(),
+            kind: kind.clone(),
+            catalog: context.catalog.clone(),
+            memory_budget: context.memory_budget,
+            common_tables: context.common_tables.clone(),
+            outer,
+            schema: Schema::new(fields),
+            outcomes: HashMap::new(),
+            runs: 0,
+        })
+    }
+
+    /// Runs the subquery for one input row; `value` is the left side of IN.
+    fn run(&mut self, row: &Row, value: Option<Value>) -> Result<Value, ExecutionError> {
+        let outer = &self.outer;
+        let bound = bind_outer_columns(self.subquery.clone(), &self.catalog, &mut |expr| {
+            match outer.iter().find(|(column, _)| *column == expr) {
+                Some((_, index)) => Expr::Literal(row[*index].clone()),
+                None => expr,
+            }
+        })?;
+        let plan = optimize(bound, &self.catalog);
+        let physical_plan = convert_to_physical_plan(&plan, &self.catalog);
+        let context = ExecutionContext {
+            catalog: &self.catalog,
+            memory_budget: self.memory_budget,
+            common_tables: self.common_tables.clone(),
+        };
+        let mut root = build(&physical_plan.root, &context)?;
+        root.open()?;
+        self.runs += 1;
+        match value {
+            None if self.kind == ApplyKind::Exists => Ok(Value::Bool(root.next()?.is_some())),
+            None => {
+                let first = root.next()?;
+                if root.next()?.is_some() {
+                    return Err(ExecutionError::new("A scalar subquery returned more than one row"));
+                }
+                let data_type = self.schema.fields.last().unwrap().data_type.clone();
+                let value = first.map_or(Value::Null, |mut row| row.swap_remove(0));
+                value.coerce_to(data_type).map_err(|e| ExecutionError::new(&e))
+            }
+            // NULL rather than false when the value or some row is NULL
+            Some(value) => {
+                let mut outcome = Value::Bool(false);
+                while let Some(mut row) = root.next()? {
+                    match evaluate_binary(BinaryOperator::Eq, value.clone(), row.swap_remove(0))? {
+                        Value::Bool(true) => return Ok(Value::Bool(true)),
+                        Value::Null => outcome = Value::Null,
+                        _ => {}
+                    }
+                }
+                Ok(outcome)
+            }
+        }
+    }
+}
+
+impl Executor for ApplyExecutor {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.input.open()
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        let Some(mut row) = self.input.next()? else {
+            return Ok(None);
+        };
+        let mut binding: Row = self.outer.iter().map(|(_, index)| row[*index].clone()).collect();
+        let value = match &self.kind {
+            ApplyKind::In(expr) => Some(expr.evaluate(self.input.schema(), &row)?),
+            _ => None,
+        };
+        binding.extend(value.clone());
+        let outcome = match self.outcomes.get(&binding) {
+            Some(outcome) => outcome.clone(),
+            None => {
+                let outcome = self.run(&row, value)?;
+                self.outcomes.insert(binding, outcome.clone());
+                outcome
+            }
+        };
+        row.push(outcome);
+        Ok(Some(row))
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.input]
+    }
+
+    fn details(&self) -> Option<String> {
+        Some(format!("ran the subquery {} times", self.runs))
+    }
+}
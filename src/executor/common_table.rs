@@ -0,0 +1,185 @@
+use super::{CommonTables, ExecutionContext, ExecutionNode, Executor, SharedRows};
+use crate::errors::ExecutionError;
+use crate::schema::{Field, Schema};
+use crate::set_operation::{set_operation_fields, SetOperator};
+use crate::value::Row;
+use std::collections::HashSet;
+
+/// Steps after which a recursive query is assumed not to end.
+const MAX_RECURSION_STEPS: usize = 10_000;
+
+/// Computes the tables of a WITH clause in order, each of which may read the
+/// ones before it, and keeps their rows for the scans of them in the query.
+/// They are computed when the executor is first opened.
+pub struct WithExecutor {
+    tables: Vec<(usize, ExecutionNode)>,
+    input: ExecutionNode,
+    common_tables: CommonTables,
+    computed: bool,
+}
+
+impl WithExecutor {
+    pub fn new(tables: Vec<(usize, ExecutionNode)>, input: ExecutionNode, context: &ExecutionContext) -> Self {
+        Self {
+            tables,
+            input,
+            common_tables: context.common_tables.clone(),
+            computed: false,
+        }
+    }
+}
+
+impl Executor for WithExecutor {
+    fn schema(&self) -> &Schema {
+        self.input.schema()
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        if !self.computed {
+            for (id, table) in &mut self.tables {
+                table.open()?;
+                *self.common_tables.get(*id).borrow_mut() = table.drain()?;
+            }
+            self.computed = true;
+        }
+        self.input.open()
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        self.input.next()
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        let mut children: Vec<&ExecutionNode> = self.tables.iter().map(|(_, table)| table).collect();
+        children.push(&self.input);
+        children
+    }
+}
+
+/// Returns the rows kept for a table of a WITH clause or a working table, as
+/// they are when the executor is opened.
+pub struct CommonTableScanExecutor {
+    table: SharedRows,
+    schema: Schema,
+    rows: std::vec::IntoIter<Row>,
+}
+
+impl CommonTableScanExecutor {
+    pub fn new(table: SharedRows, fields: &[Field]) -> Self {
+        Self {
+            table,
+            schema: Schema::new(fields.to_vec()),
+            rows: Vec::new().into_iter(),
+        }
+    }
+}
+
+impl Executor for CommonTableScanExecutor {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.rows = self.table.borrow().clone().into_iter();
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        Ok(self.rows.next())
+    }
+}
+
+/// `WITH RECURSIVE`: returns the rows of the anchor, then runs the recursive
+/// input again and again with the rows added last as its working table until
+/// it adds none. UNION DISTINCT only adds rows not seen before, which ends
+/// the recursion over cyclic data.
+pub struct RecursiveUnionExecutor {
+    anchor: ExecutionNode,
+    recursive: ExecutionNode,
+    working_table: SharedRows,
+    all: bool,
+    schema: Schema,
+    rows: std::vec::IntoIter<Row>,
+    steps: usize,
+}
+
+impl RecursiveUnionExecutor {
+    pub fn new(
+        anchor: ExecutionNode,
+        recursive: ExecutionNode,
+        working_table: SharedRows,
+        all: bool,
+    ) -> Result<Self, ExecutionError> {
+        let fields = set_operation_fields(SetOperator::Union, anchor.schema(), recursive.schema())?;
+        Ok(Self {
+            anchor,
+            recursive,
+            working_table,
+            all,
+            schema: Schema::new(fields),
+            rows: Vec::new().into_iter(),
+            steps: 0,
+        })
+    }
+
+    /// The rows of one step converted to the output types, without those
+    /// seen before unless duplicates are kept.
+    fn new_rows(&self, rows: Vec<Row>, seen: &mut HashSet<Row>) -> Result<Vec<Row>, ExecutionError> {
+        let mut added = Vec::new();
+        for row in rows {
+            let row = row
+                .into_iter()
+                .zip(&self.schema.fields)
+                .map(|(value, field)| value.coerce_to(field.data_type.clone()).map_err(|e| ExecutionError::new(&e)))
+                .collect::<Result<Row, _>>()?;
+            if self.all || seen.insert(row.clone()) {
+                added.push(row);
+            }
+        }
+        Ok(added)
+    }
+}
+
+impl Executor for RecursiveUnionExecutor {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        let mut seen = HashSet::new();
+        self.anchor.open()?;
+        let rows = self.anchor.drain()?;
+        let mut added = self.new_rows(rows, &mut seen)?;
+        let mut result = added.clone();
+        self.steps = 0;
+        while !added.is_empty() {
+            self.steps += 1;
+            if self.steps > MAX_RECURSION_STEPS {
+                return Err(ExecutionError::new(&format!(
+                    "Recursive query did not end after {} steps",
+                    MAX_RECURSION_STEPS
+                )));
+            }
+            *self.working_table.borrow_mut() = added;
+            self.recursive.open()?;
+            let rows = self.recursive.drain()?;
+            added = self.new_rows(rows, &mut seen)?;
+            result.extend(added.iter().cloned());
+        }
+        self.working_table.borrow_mut().clear();
+        self.rows = result.into_iter();
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        Ok(self.rows.next())
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.anchor, &self.recursive]
+    }
+
+    fn details(&self) -> Option<String> {
+        Some(format!("recursed {} times", self.steps))
+    }
+}
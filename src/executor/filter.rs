@@ -0,0 +1,40 @@
+use super::{ExecutionNode, Executor};
+use crate::errors::ExecutionError;
+use crate::expression::Expr;
+use crate::schema::Schema;
+use crate::value::Row;
+
+pub struct FilterExecutor {
+    input: ExecutionNode,
+    condition: Expr,
+}
+
+impl FilterExecutor {
+    pub fn new(input: ExecutionNode, condition: Expr) -> Result<Self, ExecutionError> {
+        condition.data_type(input.schema())?;
+        Ok(Self { input, condition })
+    }
+}
+
+impl Executor for FilterExecutor {
+    fn schema(&self) -> &Schema {
+        self.input.schema()
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.input.open()
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        while let Some(row) = self.input.next()? {
+            if self.condition.is_satisfied(self.input.schema(), &row)? {
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.input]
+    }
+}
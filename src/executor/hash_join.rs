@@ -0,0 +1,376 @@
+use super::join::{concat, JoinKeys};
+use super::spill::{estimated_size, SpillFile};
+use super::{ExecutionNode, Executor};
+use crate::errors::ExecutionError;
+use crate::join::JoinType;
+use crate::schema::Schema;
+use crate::value::{Row, Value};
+use sloth_db::storage::record_manager::ScanCursor;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+
+/// Number of partitions both inputs are split into once the build side
+/// exceeds the memory budget.
+pub const SPILL_PARTITIONS: usize = 16;
+
+/// Equi-join that hashes the right (build) input and probes it with the left
+/// one. When the build side does not fit in the memory budget, both inputs are
+/// partitioned on the join key into spill files (Grace hash join) and the
+/// partitions are joined one at a time.
+pub struct HashJoinExecutor {
+    left: ExecutionNode,
+    right: ExecutionNode,
+    join_type: JoinType,
+    keys: JoinKeys,
+    /// Layout of a left row followed by a right row, which residual conditions read
+    joined_schema: Schema,
+    schema: Schema,
+    memory_budget: usize,
+    spill_directory: PathBuf,
+    /// Spilled (build, probe) partitions that are still to be joined
+    pending: VecDeque<(SpillFile, SpillFile)>,
+    partition: Option<Partition>,
+    spilled_partitions: usize,
+}
+
+enum ProbeSource {
+    Input,
+    Spilled(SpillFile, ScanCursor),
+}
+
+/// The build rows of one partition and the state of probing them.
+struct Partition {
+    rows: Vec<Row>,
+    matched: Vec<bool>,
+    table: HashMap<Row, Vec<usize>>,
+    probe: ProbeSource,
+    probe_done: bool,
+    current: Option<Probe>,
+    /// Next build row to check for being unmatched, once probing is done
+    unmatched_position: usize,
+}
+
+/// A probe row with the build rows sharing its key.
+struct Probe {
+    row: Row,
+    candidates: Vec<usize>,
+    position: usize,
+    matched: bool,
+}
+
+impl HashJoinExecutor {
+    pub fn new(
+        left: ExecutionNode,
+        right: ExecutionNode,
+        join_type: JoinType,
+        keys: JoinKeys,
+        joined_schema: Schema,
+        memory_budget: usize,
+        spill_directory: PathBuf,
+    ) -> Self {
+        let schema = if join_type.keeps_right_columns() {
+            joined_schema.clone()
+        } else {
+            left.schema().clone()
+        };
+        Self {
+            left,
+            right,
+            join_type,
+            keys,
+            joined_schema,
+            schema,
+            memory_budget,
+            spill_directory,
+            pending: VecDeque::new(),
+            partition: None,
+            spilled_partitions: 0,
+        }
+    }
+
+    fn partition_of(key: Option<&Row>) -> usize {
+        // rows with a NULL key match nothing, any partition will do
+        let Some(key) = key else {
+            return 0;
+        };
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        (hasher.finish() % SPILL_PARTITIONS as u64) as usize
+    }
+
+    fn create_partitions(&self) -> Result<Vec<SpillFile>, ExecutionError> {
+        (0..SPILL_PARTITIONS)
+            .map(|_| SpillFile::create(&self.spill_directory))
+            .collect()
+    }
+
+    fn build_partition(&self, rows: Vec<Row>, probe: ProbeSource) -> Result<Partition, ExecutionError> {
+        let mut table: HashMap<Row, Vec<usize>> = HashMap::new();
+        for (index, row) in rows.iter().enumerate() {
+            if let Some(key) = self.keys.evaluate(&self.keys.right, self.right.schema(), row)? {
+                table.entry(key).or_default().push(index);
+            }
+        }
+        Ok(Partition {
+            matched: vec![false; rows.len()],
+            rows,
+            table,
+            probe,
+            probe_done: false,
+            current: None,
+            unmatched_position: 0,
+        })
+    }
+
+    fn next_partition(&mut self) -> Result<Option<Partition>, ExecutionError> {
+        let Some((mut build, probe)) = self.pending.pop_front() else {
+            return Ok(None);
+        };
+        let rows = build.read_all();
+        self.build_partition(rows, ProbeSource::Spilled(probe, ScanCursor::default()))
+            .map(Some)
+    }
+
+    fn next_probe_row(&mut self) -> Result<Option<Row>, ExecutionError> {
+        match &mut self.partition.as_mut().unwrap().probe {
+            ProbeSource::Input => self.left.next(),
+            ProbeSource::Spilled(file, cursor) => Ok(file.read(cursor)),
+        }
+    }
+
+    fn pad_left(&self, row: &[Value]) -> Row {
+        let mut padded = vec![Value::Null; self.left.schema().fields.len()];
+        padded.extend_from_slice(row);
+        padded
+    }
+
+    fn pad_right(&self, row: &[Value]) -> Row {
+        if !self.join_type.keeps_right_columns() {
+            return row.to_vec();
+        }
+        let mut padded = row.to_vec();
+        padded.resize(padded.len() + self.right.schema().fields.len(), Value::Null);
+        padded
+    }
+}
+
+impl Executor for HashJoinExecutor {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.pending.clear();
+        self.partition = None;
+        self.spilled_partitions = 0;
+
+        self.right.open()?;
+        let mut rows = Vec::new();
+        let mut memory = 0;
+        let mut build_files: Option<Vec<SpillFile>> = None;
+        while let Some(row) = self.right.next()? {
+            if let Some(files) = &mut build_files {
+                let key = self.keys.evaluate(&self.keys.right, self.right.schema(), &row)?;
+                files[Self::partition_of(key.as_ref())].write(&row)?;
+                continue;
+            }
+            memory += estimated_size(&row);
+            rows.push(row);
+            if memory > self.memory_budget {
+                let mut files = self.create_partitions()?;
+                for row in rows.drain(..) {
+                    let key = self.keys.evaluate(&self.keys.right, self.right.schema(), &row)?;
+                    files[Self::partition_of(key.as_ref())].write(&row)?;
+                }
+                build_files = Some(files);
+            }
+        }
+
+        self.left.open()?;
+        let Some(build_files) = build_files else {
+            self.partition = Some(self.build_partition(rows, ProbeSource::Input)?);
+            return Ok(());
+        };
+
+        let mut probe_files = self.create_partitions()?;
+        while let Some(row) = self.left.next()? {
+            let key = self.keys.evaluate(&self.keys.left, self.left.schema(), &row)?;
+            probe_files[Self::partition_of(key.as_ref())].write(&row)?;
+        }
+        self.spilled_partitions = SPILL_PARTITIONS;
+        self.pending = build_files
+            .into_iter()
+            .zip(probe_files)
+            // without probe rows a partition only matters for unmatched build rows
+            .filter(|(build, probe)| {
+                build.rows() > 0 && (probe.rows() > 0 || self.join_type.preserves_right())
+                    || probe.rows() > 0 && self.join_type.preserves_left()
+            })
+            .collect();
+        self.partition = self.next_partition()?;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        let keep_unmatched_left = self.join_type.preserves_left();
+        let keep_unmatched_right = self.join_type.preserves_right();
+
+        loop {
+            let Some(partition) = &mut self.partition else {
+                return Ok(None);
+            };
+
+            if let Some(probe) = &mut partition.current {
+                if let Some(&index) = probe.candidates.get(probe.position) {
+                    probe.position += 1;
+                    let row = concat(&probe.row, &partition.rows[index]);
+                    if !self.keys.matches(&self.joined_schema, &row)? {
+                        continue;
+                    }
+                    probe.matched = true;
+                    match self.join_type {
+                        JoinType::Semi => return Ok(partition.current.take().map(|probe| probe.row)),
+                        JoinType::Anti => partition.current = None,
+                        _ => {
+                            partition.matched[index] = true;
+                            return Ok(Some(row));
+                        }
+                    }
+                    continue;
+                }
+                let probe = partition.current.take().unwrap();
+                if !probe.matched && keep_unmatched_left {
+                    return Ok(Some(self.pad_right(&probe.row)));
+                }
+                continue;
+            }
+
+            if !partition.probe_done {
+                match self.next_probe_row()? {
+                    Some(row) => {
+                        let partition = self.partition.as_mut().unwrap();
+                        let key = self.keys.evaluate(&self.keys.left, self.left.schema(), &row)?;
+                        let candidates = key
+                            .and_then(|key| partition.table.get(&key))
+                            .cloned()
+                            .unwrap_or_default();
+                        partition.current = Some(Probe {
+                            row,
+                            candidates,
+                            position: 0,
+                            matched: false,
+                        });
+                    }
+                    None => self.partition.as_mut().unwrap().probe_done = true,
+                }
+                continue;
+            }
+
+            if keep_unmatched_right {
+                while partition.unmatched_position < partition.rows.len() {
+                    let index = partition.unmatched_position;
+                    partition.unmatched_position += 1;
+                    if !partition.matched[index] {
+                        let row = partition.rows[index].clone();
+                        return Ok(Some(self.pad_left(&row)));
+                    }
+                }
+            }
+            self.partition = self.next_partition()?;
+        }
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.left, &self.right]
+    }
+
+    fn details(&self) -> Option<String> {
+        (self.spilled_partitions > 0).then(|| format!("spilled into {} partitions", self.spilled_partitions))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::compare_keys;
+    use crate::executor::tests::{input, rows};
+    use crate::expression::parse_expression;
+    use crate::lexer::Tokens;
+    use tempfile::tempdir;
+
+    /// The expected result, computed by comparing every pair of rows.
+    fn nested_loops(join_type: JoinType, left: &[Row], right: &[Row], schema: &Schema, condition: &crate::expression::Expr) -> Vec<Row> {
+        let matches = |l: &Row, r: &Row| condition.is_satisfied(schema, &concat(l, r)).unwrap();
+        let nulls = vec![Value::Null; 2];
+        let mut output = Vec::new();
+        for l in left {
+            let matched: Vec<&Row> = right.iter().filter(|r| matches(l, r)).collect();
+            match join_type {
+                JoinType::Semi if !matched.is_empty() => output.push(l.clone()),
+                JoinType::Anti if matched.is_empty() => output.push(l.clone()),
+                JoinType::Semi | JoinType::Anti => {}
+                _ => {
+                    output.extend(matched.iter().map(|r| concat(l, r)));
+                    if matched.is_empty() && matches!(join_type, JoinType::Left | JoinType::Full) {
+                        output.push(concat(l, &nulls));
+                    }
+                }
+            }
+        }
+        if matches!(join_type, JoinType::Right | JoinType::Full) {
+            for r in right.iter().filter(|r| !left.iter().any(|l| matches(l, r))) {
+                output.push(concat(&nulls, r));
+            }
+        }
+        output.sort_by(|a, b| compare_keys(a, b));
+        output
+    }
+
+    #[test]
+    fn spilled_joins_match_nested_loops_for_every_join_type() {
+        let dir = tempdir().unwrap();
+        let left = rows((0..300).map(|i| (i % 50 != 0).then_some(i % 120)));
+        let right = rows((0..200).map(|i| (i % 70 != 0).then_some(i % 90 + 60)));
+        let condition = parse_expression(&mut Tokens::new("l.k = r.k AND r.v < 150").unwrap()).unwrap();
+
+        for join_type in [
+            JoinType::Inner,
+            JoinType::Left,
+            JoinType::Right,
+            JoinType::Full,
+            JoinType::Semi,
+            JoinType::Anti,
+        ] {
+            for memory_budget in [usize::MAX, 1024] {
+                let (left_input, right_input) = (input("l", left.clone()), input("r", right.clone()));
+                let mut fields = left_input.schema().fields.clone();
+                fields.extend(right_input.schema().fields.clone());
+                let joined_schema = Schema::new(fields);
+                let keys = JoinKeys::new(Some(condition.clone()), left_input.schema(), right_input.schema()).unwrap();
+                let mut join = HashJoinExecutor::new(
+                    left_input,
+                    right_input,
+                    join_type,
+                    keys,
+                    joined_schema.clone(),
+                    memory_budget,
+                    dir.path().to_path_buf(),
+                );
+
+                join.open().unwrap();
+                let mut output = Vec::new();
+                while let Some(row) = join.next().unwrap() {
+                    output.push(row);
+                }
+                output.sort_by(|a, b| compare_keys(a, b));
+                let expected = nested_loops(join_type, &left, &right, &joined_schema, &condition);
+                assert_eq!(output, expected, "{} join with a budget of {}", join_type, memory_budget);
+                assert_eq!(join.details().is_some(), memory_budget == 1024);
+            }
+        }
+        // every spill file is removed again
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,342 @@
+use super::hash_join::HashJoinExecutor;
+use super::{compare_keys, ExecutionContext, ExecutionNode, Executor};
+use crate::errors::ExecutionError;
+use crate::expression::{BinaryOperator, Expr};
+use crate::join::JoinType;
+use crate::physical_plan::JoinAlgorithm;
+use crate::schema::Schema;
+use crate::value::{DataType, Row, Value};
+use std::cmp::Ordering;
+
+pub fn new_join(
+    algorithm: JoinAlgorithm,
+    join_type: JoinType,
+    left: ExecutionNode,
+    right: ExecutionNode,
+    condition: Option<Expr>,
+    context: &ExecutionContext,
+) -> Result<Box<dyn Executor>, ExecutionError> {
+    let mut fields = left.schema().fields.clone();
+    fields.extend(right.schema().fields.iter().cloned());
+    let schema = Schema::new(fields);
+    if let Some(condition) = &condition {
+        condition.data_type(&schema)?;
+    }
+
+    if join_type != JoinType::Inner && algorithm == JoinAlgorithm::SortMerge {
+        return Err(ExecutionError::new(&format!("{} does not support {} joins", algorithm, join_type)));
+    }
+
+    Ok(match algorithm {
+        JoinAlgorithm::NestedLoop => Box::new(NestedLoopJoinExecutor::new(left, right, join_type, condition, schema)),
+        JoinAlgorithm::Hash => {
+            let keys = JoinKeys::new(condition, left.schema(), right.schema())?;
+            Box::new(HashJoinExecutor::new(
+                left,
+                right,
+                join_type,
+                keys,
+                schema,
+                context.memory_budget,
+                context.spill_directory(),
+            ))
+        }
+        JoinAlgorithm::SortMerge => {
+            let keys = JoinKeys::new(condition, left.schema(), right.schema())?;
+            Box::new(SortMergeJoinExecutor {
+                left,
+                right,
+                keys,
+                schema,
+                output: Vec::new().into_iter(),
+            })
+        }
+    })
+}
+
+/// The equalities `left_key = right_key` of a join condition and the
+/// conjuncts that have to be checked on the joined row.
+pub struct JoinKeys {
+    pub left: Vec<Expr>,
+    pub right: Vec<Expr>,
+    /// Numeric keys of different types are compared in the type both
+    /// convert to; decimals of any scale compare as they are
+    convert: Vec<Option<DataType>>,
+    residual: Option<Expr>,
+}
+
+impl JoinKeys {
+    pub fn new(condition: Option<Expr>, left_schema: &Schema, right_schema: &Schema) -> Result<Self, ExecutionError> {
+        let mut keys = Self {
+            left: Vec::new(),
+            right: Vec::new(),
+            convert: Vec::new(),
+            residual: None,
+        };
+        let mut residual = Vec::new();
+        for conjunct in condition.map(Expr::into_conjuncts).unwrap_or_default() {
+            let Expr::Binary {
+                left,
+                op: BinaryOperator::Eq,
+                right,
+            } = &conjunct
+            else {
+                residual.push(conjunct);
+                continue;
+            };
+            let reads = |expr: &Expr, schema: &Schema| !expr.columns().is_empty() && expr.data_type(schema).is_ok();
+            let (left_key, right_key) = if reads(left, left_schema) && reads(right, right_schema) {
+                (left, right)
+            } else if reads(right, left_schema) && reads(left, right_schema) {
+                (right, left)
+            } else {
+                residual.push(conjunct);
+                continue;
+            };
+            let types = (left_key.data_type(left_schema)?, right_key.data_type(right_schema)?);
+            let numeric = |t: &DataType| matches!(t, DataType::Int64 | DataType::Float64 | DataType::Decimal(..));
+            let common = types.0.common_supertype(&types.1);
+            if types.0 != types.1 && !(numeric(&types.0) && numeric(&types.1) && common.is_some()) {
+                return Err(ExecutionError::new(&format!("Cannot compare {} with {} in {}", types.0, types.1, conjunct)));
+            }
+            let decimals = matches!(types, (DataType::Decimal(..), DataType::Decimal(..)));
+            keys.convert.push(common.filter(|_| types.0 != types.1 && !decimals));
+            keys.left.push((**left_key).clone());
+            keys.right.push((**right_key).clone());
+        }
+        keys.residual = Expr::conjunction(residual);
+        Ok(keys)
+    }
+
+    /// The key of a row, `None` if a part is NULL since NULL never equals anything.
+    pub fn evaluate(&self, keys: &[Expr], schema: &Schema, row: &[Value]) -> Result<Option<Row>, ExecutionError> {
+        let mut values = Vec::with_capacity(keys.len());
+        for (key, convert) in keys.iter().zip(&self.convert) {
+            let value = key.evaluate(schema, row)?;
+            if value.is_null() {
+                return Ok(None);
+            }
+            values.push(match convert {
+                Some(data_type) => value.coerce_to(data_type.clone())?,
+                None => value,
+            });
+        }
+        Ok(Some(values))
+    }
+
+    pub fn matches(&self, schema: &Schema, row: &[Value]) -> Result<bool, ExecutionError> {
+        match &self.residual {
+            Some(residual) => residual.is_satisfied(schema, row),
+            None => Ok(true),
+        }
+    }
+}
+
+pub fn concat(left: &[Value], right: &[Value]) -> Row {
+    let mut row = left.to_vec();
+    row.extend_from_slice(right);
+    row
+}
+
+/// A left row and how far the scan of the right input for it got.
+struct NestedLoopProbe {
+    row: Row,
+    position: usize,
+    matched: bool,
+}
+
+/// Rescans the right input for every row of the left input. Joins that keep
+/// unmatched right rows remember matches by the position of the right row in
+/// its scan and rescan the right input once more at the end to emit the rest.
+struct NestedLoopJoinExecutor {
+    left: ExecutionNode,
+    right: ExecutionNode,
+    join_type: JoinType,
+    condition: Option<Expr>,
+    /// Layout of a left row followed by a right row, which the condition reads
+    joined_schema: Schema,
+    schema: Schema,
+    current: Option<NestedLoopProbe>,
+    right_matched: Vec<bool>,
+    /// Position in the final scan for unmatched right rows, once it started
+    unmatched_position: Option<usize>,
+}
+
+impl NestedLoopJoinExecutor {
+    fn new(left: ExecutionNode, right: ExecutionNode, join_type: JoinType, condition: Option<Expr>, joined_schema: Schema) -> Self {
+        let schema = if join_type.keeps_right_columns() {
+            joined_schema.clone()
+        } else {
+            left.schema().clone()
+        };
+        Self {
+            left,
+            right,
+            join_type,
+            condition,
+            joined_schema,
+            schema,
+            current: None,
+            right_matched: Vec::new(),
+            unmatched_position: None,
+        }
+    }
+
+    fn next_unmatched_right(&mut self) -> Result<Option<Row>, ExecutionError> {
+        while let Some(right) = self.right.next()? {
+            let position = self.unmatched_position.as_mut().unwrap();
+            *position += 1;
+            if !self.right_matched.get(*position - 1).copied().unwrap_or(false) {
+                let mut row = vec![Value::Null; self.left.schema().fields.len()];
+                row.extend(right);
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Executor for NestedLoopJoinExecutor {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.current = None;
+        self.right_matched.clear();
+        self.unmatched_position = None;
+        self.left.open()
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        loop {
+            if self.unmatched_position.is_some() {
+                return self.next_unmatched_right();
+            }
+            let Some(probe) = &mut self.current else {
+                match self.left.next()? {
+                    Some(row) => {
+                        self.current = Some(NestedLoopProbe {
+                            row,
+                            position: 0,
+                            matched: false,
+                        });
+                        self.right.open()?;
+                    }
+                    None if self.join_type.preserves_right() => {
+                        self.unmatched_position = Some(0);
+                        self.right.open()?;
+                    }
+                    None => return Ok(None),
+                }
+                continue;
+            };
+            let Some(right) = self.right.next()? else {
+                let probe = self.current.take().unwrap();
+                if !probe.matched && self.join_type.preserves_left() {
+                    let mut row = probe.row;
+                    if self.join_type.keeps_right_columns() {
+                        row.resize(self.joined_schema.fields.len(), Value::Null);
+                    }
+                    return Ok(Some(row));
+                }
+                continue;
+            };
+            let position = probe.position;
+            probe.position += 1;
+            let row = concat(&probe.row, &right);
+            let satisfied = match &self.condition {
+                Some(condition) => condition.is_satisfied(&self.joined_schema, &row)?,
+                None => true,
+            };
+            if !satisfied {
+                continue;
+            }
+            probe.matched = true;
+            if self.join_type.preserves_right() {
+                if self.right_matched.len() <= position {
+                    self.right_matched.resize(position + 1, false);
+                }
+                self.right_matched[position] = true;
+            }
+            match self.join_type {
+                JoinType::Semi => return Ok(self.current.take().map(|probe| probe.row)),
+                // the left row is out as soon as it has a match
+                JoinType::Anti => self.current = None,
+                _ => return Ok(Some(row)),
+            }
+        }
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.left, &self.right]
+    }
+}
+
+/// Sorts both inputs on the join key and merges groups of equal keys.
+struct SortMergeJoinExecutor {
+    left: ExecutionNode,
+    right: ExecutionNode,
+    keys: JoinKeys,
+    schema: Schema,
+    output: std::vec::IntoIter<Row>,
+}
+
+impl SortMergeJoinExecutor {
+    fn sorted(input: &mut ExecutionNode, keys: &JoinKeys, key_exprs: &[Expr]) -> Result<Vec<(Row, Row)>, ExecutionError> {
+        input.open()?;
+        let mut rows = Vec::new();
+        while let Some(row) = input.next()? {
+            if let Some(key) = keys.evaluate(key_exprs, input.schema(), &row)? {
+                rows.push((key, row));
+            }
+        }
+        rows.sort_by(|a, b| compare_keys(&a.0, &b.0));
+        Ok(rows)
+    }
+}
+
+impl Executor for SortMergeJoinExecutor {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        let left = Self::sorted(&mut self.left, &self.keys, &self.keys.left)?;
+        let right = Self::sorted(&mut self.right, &self.keys, &self.keys.right)?;
+
+        let mut output = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < left.len() && j < right.len() {
+            match compare_keys(&left[i].0, &right[j].0) {
+                Ordering::Less => i += 1,
+                Ordering::Greater => j += 1,
+                Ordering::Equal => {
+                    let group_end = (j..right.len())
+                        .find(|&k| compare_keys(&left[i].0, &right[k].0).is_ne())
+                        .unwrap_or(right.len());
+                    while i < left.len() && compare_keys(&left[i].0, &right[j].0).is_eq() {
+                        for (_, right_row) in &right[j..group_end] {
+                            let row = concat(&left[i].1, right_row);
+                            if self.keys.matches(&self.schema, &row)? {
+                                output.push(row);
+                            }
+                        }
+                        i += 1;
+                    }
+                    j = group_end;
+                }
+            }
+        }
+        self.output = output.into_iter();
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        Ok(self.output.next())
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.left, &self.right]
+    }
+}
@@ -0,0 +1,202 @@
+use super::{ExecutionNode, Executor};
+use crate::errors::ExecutionError;
+use crate::order_by::{compare_sort_keys, SortKey};
+use crate::schema::Schema;
+use crate::value::Row;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::rc::Rc;
+
+/// Skips `offset` rows and returns the next `limit` ones. Rows are pulled one
+/// at a time, so the input (down to a table scan) stops being read as soon as
+/// the limit is reached.
+pub struct LimitExecutor {
+    input: ExecutionNode,
+    limit: usize,
+    offset: usize,
+    returned: usize,
+}
+
+impl LimitExecutor {
+    pub fn new(input: ExecutionNode, limit: usize, offset: usize) -> Self {
+        Self {
+            input,
+            limit,
+            offset,
+            returned: 0,
+        }
+    }
+}
+
+impl Executor for LimitExecutor {
+    fn schema(&self) -> &Schema {
+        self.input.schema()
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.returned = 0;
+        self.input.open()
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        if self.returned == self.limit {
+            return Ok(None);
+        }
+        if self.returned == 0 {
+            for _ in 0..self.offset {
+                if self.input.next()?.is_none() {
+                    self.returned = self.limit;
+                    return Ok(None);
+                }
+            }
+        }
+        let row = self.input.next()?;
+        self.returned = if row.is_some() { self.returned + 1 } else { self.limit };
+        Ok(row)
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.input]
+    }
+}
+
+/// A row in the top-N heap. The max heap keeps the row that sorts last on
+/// top, the later input row on ties, so it is the one evicted.
+struct Candidate {
+    key: Row,
+    position: usize,
+    row: Row,
+    keys: Rc<[SortKey]>,
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_sort_keys(&self.keys, &self.key, &other.key).then(self.position.cmp(&other.position))
+    }
+}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Candidate {}
+
+/// ORDER BY with LIMIT: reads the whole input when opened but only keeps
+/// the first `offset + limit` rows in a bounded heap, so memory does not
+/// grow with the input. Equal keys keep their input order like in a sort.
+pub struct TopNExecutor {
+    input: ExecutionNode,
+    keys: Rc<[SortKey]>,
+    limit: usize,
+    offset: usize,
+    output: std::vec::IntoIter<Row>,
+}
+
+impl TopNExecutor {
+    pub fn new(input: ExecutionNode, keys: Vec<SortKey>, limit: usize, offset: usize) -> Result<Self, ExecutionError> {
+        for key in &keys {
+            key.expr.data_type(input.schema())?;
+        }
+        Ok(Self {
+            input,
+            keys: keys.into(),
+            limit,
+            offset,
+            output: Vec::new().into_iter(),
+        })
+    }
+}
+
+impl Executor for TopNExecutor {
+    fn schema(&self) -> &Schema {
+        self.input.schema()
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.output = Vec::new().into_iter();
+        self.input.open()?;
+        let kept = self.offset.saturating_add(self.limit);
+        if self.limit == 0 {
+            return Ok(());
+        }
+
+        let mut heap = BinaryHeap::new();
+        let mut position = 0;
+        while let Some(row) = self.input.next()? {
+            let schema = self.input.schema();
+            let key = self
+                .keys
+                .iter()
+                .map(|key| key.expr.evaluate(schema, &row))
+                .collect::<Result<Row, _>>()?;
+            let candidate = Candidate {
+                key,
+                position,
+                row,
+                keys: self.keys.clone(),
+            };
+            position += 1;
+            if heap.len() < kept {
+                heap.push(candidate);
+            } else if heap.peek().is_some_and(|last| candidate < *last) {
+                heap.pop();
+                heap.push(candidate);
+            }
+        }
+
+        let rows: Vec<Row> = heap
+            .into_sorted_vec()
+            .into_iter()
+            .skip(self.offset)
+            .map(|candidate| candidate.row)
+            .collect();
+        self.output = rows.into_iter();
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        Ok(self.output.next())
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.input]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::tests::{input, rows};
+    use crate::expression::Expr;
+
+    #[test]
+    fn top_n_returns_the_same_rows_as_sort_then_limit() {
+        let data = rows((0..300).map(|i| (i % 5 != 0).then_some(i * 37 % 41)));
+        let key = Expr::Column {
+            qualifier: None,
+            name: "k".to_string(),
+        };
+        let keys = vec![SortKey::new(key, true, None)];
+
+        let mut sorted = data.clone();
+        sorted.sort_by(|a, b| compare_sort_keys(&keys, &a[..1], &b[..1]));
+        for (limit, offset) in [(0, 0), (1, 0), (10, 5), (50, 280), (1000, 0), (usize::MAX, 3)] {
+            let mut top_n = TopNExecutor::new(input("t", data.clone()), keys.clone(), limit, offset).unwrap();
+            top_n.open().unwrap();
+            let mut output = Vec::new();
+            while let Some(row) = top_n.next().unwrap() {
+                output.push(row);
+            }
+            let expected: Vec<Row> = sorted.iter().skip(offset).take(limit).cloned().collect();
+            assert_eq!(output, expected, "LIMIT {} OFFSET {}", limit, offset);
+        }
+    }
+}
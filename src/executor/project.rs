@@ -0,0 +1,52 @@
+use super::{ExecutionNode, Executor};
+use crate::errors::ExecutionError;
+use crate::expression::Expr;
+use crate::schema::Schema;
+use crate::select::expand_select_list;
+use crate::value::Row;
+
+/// Evaluates the select list; `*` expands to every input column.
+pub struct ProjectExecutor {
+    input: ExecutionNode,
+    expressions: Vec<Expr>,
+    schema: Schema,
+}
+
+impl ProjectExecutor {
+    pub fn new(input: ExecutionNode, fields: &[(Expr, Option<String>)]) -> Result<Self, ExecutionError> {
+        let select_list = fields.iter().map(|(expr, alias)| (expr, alias.as_deref()));
+        let (expressions, fields) = expand_select_list(select_list, input.schema())?.into_iter().unzip();
+        Ok(Self {
+            input,
+            expressions,
+            schema: Schema::new(fields),
+        })
+    }
+}
+
+impl Executor for ProjectExecutor {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.input.open()
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        let Some(row) = self.input.next()? else {
+            return Ok(None);
+        };
+        let schema = self.input.schema();
+        let values = self
+            .expressions
+            .iter()
+            .map(|expr| expr.evaluate(schema, &row))
+            .collect::<Result<Row, _>>()?;
+        Ok(Some(values))
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.input]
+    }
+}
@@ -0,0 +1,103 @@
+use super::Executor;
+use crate::catalog::Catalog;
+use crate::errors::ExecutionError;
+use crate::expression::Expr;
+use crate::sample::{SampleMethod, Sampler, TableSample};
+use crate::schema::{Field, Schema};
+use crate::value::Row;
+use sloth_db::storage::record_manager::{RecordManager, ScanCursor};
+use sloth_db::storage::BufferStats;
+
+/// Sequential scan of a table, filtering before the projection so the filter
+/// can read columns that are not returned. Columns are qualified by the alias
+/// of the table if it has one. A SYSTEM sample skips whole pages, a BERNOULLI
+/// one rows, before they are filtered.
+pub struct ScanExecutor {
+    records: RecordManager<Row>,
+    cursor: ScanCursor,
+    table_schema: Schema,
+    schema: Schema,
+    columns: Vec<usize>,
+    filter: Option<Expr>,
+    sample: Option<(SampleMethod, Sampler)>,
+}
+
+impl ScanExecutor {
+    pub fn new(
+        catalog: &Catalog,
+        table: &str,
+        alias: Option<&str>,
+        columns: Option<&[String]>,
+        filter: Option<Expr>,
+        sample: Option<&TableSample>,
+    ) -> Result<Self, ExecutionError> {
+        let table_schema = catalog.get_table(table)?;
+        let columns = match columns {
+            Some(columns) => table_schema.column_indices(columns)?,
+            None => (0..table_schema.columns.len()).collect(),
+        };
+        let mut table_schema = table_schema.to_schema();
+        if let Some(alias) = alias {
+            for field in &mut table_schema.fields {
+                field.qualifier = Some(alias.to_string());
+            }
+        }
+        if let Some(filter) = &filter {
+            filter.data_type(&table_schema)?;
+        }
+        let fields: Vec<Field> = columns.iter().map(|&i| table_schema.fields[i].clone()).collect();
+
+        Ok(Self {
+            records: catalog.open_table(table)?,
+            cursor: ScanCursor::default(),
+            table_schema,
+            schema: Schema::new(fields),
+            columns,
+            filter,
+            sample: sample.map(|sample| (sample.method, sample.sampler())),
+        })
+    }
+}
+
+impl Executor for ScanExecutor {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.cursor = ScanCursor::default();
+        if let Some((_, sampler)) = &mut self.sample {
+            sampler.restart();
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        loop {
+            let entry = match &mut self.sample {
+                Some((SampleMethod::System, sampler)) => {
+                    self.records.next_sampled_entry(&mut self.cursor, &mut |_| sampler.keep())
+                }
+                _ => self.records.next_entry(&mut self.cursor),
+            };
+            let Some((_, row)) = entry else {
+                return Ok(None);
+            };
+            if let Some((SampleMethod::Bernoulli, sampler)) = &mut self.sample {
+                if !sampler.keep() {
+                    continue;
+                }
+            }
+            if let Some(filter) = &self.filter {
+                if !filter.is_satisfied(&self.table_schema, &row)? {
+                    continue;
+                }
+            }
+            return Ok(Some(self.columns.iter().map(|&i| row[i].clone()).collect()));
+        }
+    }
+
+    fn buffer_stats(&self) -> BufferStats {
+        self.records.buffer_stats()
+    }
+}
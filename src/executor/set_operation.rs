@@ -0,0 +1,99 @@
+use super::{ExecutionNode, Executor};
+use crate::errors::ExecutionError;
+use crate::schema::Schema;
+use crate::set_operation::{set_operation_fields, SetOperator};
+use crate::value::Row;
+use std::collections::HashSet;
+
+/// UNION, INTERSECT and EXCEPT. Rows of both inputs are converted to the
+/// output types first, so that e.g. 1 and 1.0 are the same row. UNION ALL
+/// streams both inputs; the distinct variants remember rows in a hash set:
+/// UNION the rows returned so far, INTERSECT and EXCEPT the rows of the right
+/// input, read when the operator is opened, which they then update so that
+/// every row is returned once.
+pub struct SetOperationExecutor {
+    left: ExecutionNode,
+    right: ExecutionNode,
+    operator: SetOperator,
+    all: bool,
+    schema: Schema,
+    rows: HashSet<Row>,
+    reading_right: bool,
+}
+
+impl SetOperationExecutor {
+    pub fn new(left: ExecutionNode, right: ExecutionNode, operator: SetOperator, all: bool) -> Result<Self, ExecutionError> {
+        let fields = set_operation_fields(operator, left.schema(), right.schema())?;
+        Ok(Self {
+            left,
+            right,
+            operator,
+            all,
+            schema: Schema::new(fields),
+            rows: HashSet::new(),
+            reading_right: false,
+        })
+    }
+
+    fn convert(&self, row: Row) -> Result<Row, ExecutionError> {
+        row.into_iter()
+            .zip(&self.schema.fields)
+            .map(|(value, field)| value.coerce_to(field.data_type.clone()).map_err(|e| ExecutionError::new(&e)))
+            .collect()
+    }
+
+    /// The next row of the left input, then of the right one for UNION.
+    fn next_input_row(&mut self) -> Result<Option<Row>, ExecutionError> {
+        if !self.reading_right {
+            match self.left.next()? {
+                Some(row) => return self.convert(row).map(Some),
+                None if self.operator == SetOperator::Union => self.reading_right = true,
+                None => return Ok(None),
+            }
+        }
+        match self.right.next()? {
+            Some(row) => self.convert(row).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Executor for SetOperationExecutor {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.rows.clear();
+        self.reading_right = false;
+        self.left.open()?;
+        self.right.open()?;
+        if self.operator != SetOperator::Union {
+            while let Some(row) = self.right.next()? {
+                let row = self.convert(row)?;
+                self.rows.insert(row);
+            }
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        while let Some(row) = self.next_input_row()? {
+            let keep = match self.operator {
+                SetOperator::Union => self.all || self.rows.insert(row.clone()),
+                // removing the row makes its duplicates miss
+                SetOperator::Intersect => self.rows.remove(&row),
+                // adding the row makes its duplicates hit
+                SetOperator::Except => self.rows.insert(row.clone()),
+            };
+            if keep {
+                return Ok(Some(row));
+            }
+        }
+        Ok(None)
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.left, &self.right]
+    }
+}
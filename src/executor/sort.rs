@@ -0,0 +1,266 @@
+use super::spill::{estimated_size, SpillFile};
+use super::{ExecutionNode, Executor};
+use crate::errors::ExecutionError;
+use crate::order_by::{compare_sort_keys, SortKey};
+use crate::schema::Schema;
+use crate::value::Row;
+use sloth_db::storage::record_manager::ScanCursor;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+/// Most runs merged at once; more runs are first merged into longer ones so
+/// only this many spill files are read at the same time.
+pub const MERGE_FAN_IN: usize = 64;
+
+/// External merge sort. The input is read when the sort is opened: rows are
+/// collected until the memory budget is exhausted, then sorted and written to
+/// a spill file as a run. The sorted runs are merged k ways at a time. Rows
+/// are kept together with their key values, so spilled rows carry the keys in
+/// front of the row. Equal keys keep their input order.
+pub struct SortExecutor {
+    input: ExecutionNode,
+    keys: Rc<[SortKey]>,
+    memory_budget: usize,
+    spill_directory: PathBuf,
+    output: SortOutput,
+    spilled_runs: usize,
+}
+
+enum SortOutput {
+    Memory(std::vec::IntoIter<(Row, Row)>),
+    Merge(Merge),
+}
+
+/// A sorted spill file of rows prefixed by their keys.
+struct Run {
+    file: SpillFile,
+    cursor: ScanCursor,
+}
+
+/// The head of a run in the merge heap. Ordered in reverse so that the max
+/// heap pops the smallest key, and the earlier run on ties.
+struct Head {
+    row: Row,
+    run: usize,
+    keys: Rc<[SortKey]>,
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let n = self.keys.len();
+        compare_sort_keys(&self.keys, &other.row[..n], &self.row[..n]).then(other.run.cmp(&self.run))
+    }
+}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Head {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Head {}
+
+/// A k-way merge of sorted runs, yielding rows still prefixed by their keys.
+struct Merge {
+    runs: Vec<Run>,
+    heap: BinaryHeap<Head>,
+}
+
+impl Merge {
+    fn new(runs: Vec<SpillFile>, keys: &Rc<[SortKey]>) -> Self {
+        let mut merge = Self {
+            runs: runs
+                .into_iter()
+                .map(|file| Run {
+                    file,
+                    cursor: ScanCursor::default(),
+                })
+                .collect(),
+            heap: BinaryHeap::new(),
+        };
+        for run in 0..merge.runs.len() {
+            merge.advance(run, keys);
+        }
+        merge
+    }
+
+    fn advance(&mut self, run: usize, keys: &Rc<[SortKey]>) {
+        let Run { file, cursor } = &mut self.runs[run];
+        if let Some(row) = file.read(cursor) {
+            self.heap.push(Head {
+                row,
+                run,
+                keys: keys.clone(),
+            });
+        }
+    }
+
+    fn next(&mut self) -> Option<Row> {
+        let head = self.heap.pop()?;
+        let keys = head.keys.clone();
+        self.advance(head.run, &keys);
+        Some(head.row)
+    }
+}
+
+impl SortExecutor {
+    pub fn new(
+        input: ExecutionNode,
+        keys: Vec<SortKey>,
+        memory_budget: usize,
+        spill_directory: PathBuf,
+    ) -> Result<Self, ExecutionError> {
+        for key in &keys {
+            key.expr.data_type(input.schema())?;
+        }
+        Ok(Self {
+            input,
+            keys: keys.into(),
+            memory_budget,
+            spill_directory,
+            output: SortOutput::Memory(Vec::new().into_iter()),
+            spilled_runs: 0,
+        })
+    }
+
+    fn sort(&self, rows: &mut [(Row, Row)]) {
+        rows.sort_by(|a, b| compare_sort_keys(&self.keys, &a.0, &b.0));
+    }
+
+    fn spill(&mut self, rows: &mut Vec<(Row, Row)>) -> Result<SpillFile, ExecutionError> {
+        self.sort(rows);
+        let mut file = SpillFile::create(&self.spill_directory)?;
+        for (mut key, row) in rows.drain(..) {
+            key.extend(row);
+            file.write(&key)?;
+        }
+        self.spilled_runs += 1;
+        Ok(file)
+    }
+
+    /// Merges runs until at most `MERGE_FAN_IN` remain, oldest runs first so
+    /// that ties keep their order.
+    fn reduce_runs(&self, mut runs: Vec<SpillFile>) -> Result<Vec<SpillFile>, ExecutionError> {
+        while runs.len() > MERGE_FAN_IN {
+            let mut merge = Merge::new(runs.drain(..MERGE_FAN_IN).collect(), &self.keys);
+            let mut merged = SpillFile::create(&self.spill_directory)?;
+            while let Some(row) = merge.next() {
+                merged.write(&row)?;
+            }
+            runs.push(merged);
+            runs.rotate_right(1);
+        }
+        Ok(runs)
+    }
+}
+
+impl Executor for SortExecutor {
+    fn schema(&self) -> &Schema {
+        self.input.schema()
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.output = SortOutput::Memory(Vec::new().into_iter());
+        self.spilled_runs = 0;
+        self.input.open()?;
+
+        let mut rows = Vec::new();
+        let mut memory = 0;
+        let mut runs = Vec::new();
+        while let Some(row) = self.input.next()? {
+            let schema = self.input.schema();
+            let key = self
+                .keys
+                .iter()
+                .map(|key| key.expr.evaluate(schema, &row))
+                .collect::<Result<Row, _>>()?;
+            memory += estimated_size(&key) + estimated_size(&row);
+            rows.push((key, row));
+            if memory > self.memory_budget {
+                runs.push(self.spill(&mut rows)?);
+                memory = 0;
+            }
+        }
+
+        if runs.is_empty() {
+            self.sort(&mut rows);
+            self.output = SortOutput::Memory(rows.into_iter());
+            return Ok(());
+        }
+        if !rows.is_empty() {
+            runs.push(self.spill(&mut rows)?);
+        }
+        let runs = self.reduce_runs(runs)?;
+        self.output = SortOutput::Merge(Merge::new(runs, &self.keys));
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        Ok(match &mut self.output {
+            SortOutput::Memory(rows) => rows.next().map(|(_, row)| row),
+            SortOutput::Merge(merge) => merge.next().map(|mut row| row.split_off(self.keys.len())),
+        })
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.input]
+    }
+
+    fn details(&self) -> Option<String> {
+        (self.spilled_runs > 0).then(|| format!("spilled {} runs", self.spilled_runs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::tests::{input, rows};
+    use crate::expression::Expr;
+    use crate::value::Value;
+    use tempfile::tempdir;
+
+    fn column(name: &str) -> Expr {
+        Expr::Column {
+            qualifier: None,
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn spilled_runs_merge_into_the_in_memory_order() {
+        let dir = tempdir().unwrap();
+        let data = rows((0..500).map(|i| (i % 7 != 0).then_some(i * 37 % 23)));
+        let keys = vec![SortKey::new(column("k"), true, Some(false)), SortKey::new(column("v"), false, None)];
+
+        let mut expected = data.clone();
+        expected.sort_by(|a, b| {
+            let descending = |v: &Value| match v {
+                Value::Int(i) => (0, -i),
+                _ => (1, 0),
+            };
+            descending(&a[0]).cmp(&descending(&b[0])).then(a[1].compare(&b[1]).unwrap())
+        });
+
+        // a budget of one byte makes every row a run, more than one merge can take
+        for memory_budget in [usize::MAX, 4096, 1] {
+            let mut sort = SortExecutor::new(input("t", data.clone()), keys.clone(), memory_budget, dir.path().to_path_buf()).unwrap();
+            sort.open().unwrap();
+            let mut output = Vec::new();
+            while let Some(row) = sort.next().unwrap() {
+                output.push(row);
+            }
+            assert_eq!(output, expected, "budget of {}", memory_budget);
+            assert_eq!(sort.details().is_some(), memory_budget != usize::MAX);
+            assert!(memory_budget != 1 || sort.spilled_runs == 500);
+        }
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::errors::ExecutionError;
+use crate::json::Json;
+use crate::value::{Row, Value};
+use sloth_db::storage::record_manager::{RecordManager, ScanCursor};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_SPILL_FILE: AtomicU64 = AtomicU64::new(0);
+
+/// A temporary file of rows written by an operator that ran out of memory.
+/// The file is removed when the value is dropped.
+pub struct SpillFile {
+    path: PathBuf,
+    records: Option<RecordManager<Row>>,
+    rows: u64,
+}
+
+impl SpillFile {
+    pub fn create(directory: &Path) -> Result<Self, ExecutionError> {
+        fs::create_dir_all(directory).map_err(|e| ExecutionError::new(&e.to_string()))?;
+        let number = NEXT_SPILL_FILE.fetch_add(1, Ordering::Relaxed);
+        let path = directory.join(format!("spill-{}-{}.tmp", std::process::id(), number));
+        // a leftover of a crashed process must not be read back
+        let _ = fs::remove_file(&path);
+        Ok(Self {
+            records: Some(RecordManager::new(&path)),
+            path,
+            rows: 0,
+        })
+    }
+
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+
+    pub fn write(&mut self, row: &Row) -> Result<(), ExecutionError> {
+        self.records.as_mut().unwrap().insert_record(row)?;
+        self.rows += 1;
+        Ok(())
+    }
+
+    /// Reads the row at the cursor and advances it.
+    pub fn read(&mut self, cursor: &mut ScanCursor) -> Option<Row> {
+        self.records.as_mut().unwrap().next_entry(cursor).map(|(_, row)| row)
+    }
+
+    pub fn read_all(&mut self) -> Vec<Row> {
+        self.records.as_mut().unwrap().scan_records().collect()
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        // the pager flushes its pages on drop, so it has to go before the file
+        drop(self.records.take());
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Rough number of bytes a row occupies in memory, used against memory budgets.
+pub fn estimated_size(row: &[Value]) -> usize {
+    std::mem::size_of::<Row>() + row.iter().map(value_size).sum::<usize>()
+}
+
+/// Bytes of a value, including the elements of arrays and structs and the
+/// contents of JSON documents.
+fn value_size(value: &Value) -> usize {
+    std::mem::size_of::<Value>()
+        + match value {
+            Value::String(s) => s.len(),
+            Value::Array(items) | Value::Struct(items) => items.iter().map(value_size).sum(),
+            Value::Json(json) => json_contents_size(json),
+            _ => 0,
+        }
+}
+
+/// Bytes a JSON value holds outside of itself.
+fn json_contents_size(json: &Json) -> usize {
+    match json {
+        Json::String(s) => s.len(),
+        Json::Array(items) => items.iter().map(|item| std::mem::size_of::<Json>() + json_contents_size(item)).sum(),
+        Json::Object(members) => members
+            .iter()
+            .map(|(name, value)| std::mem::size_of::<(String, Json)>() + name.len() + json_contents_size(value))
+            .sum(),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::estimated_size;
+    use crate::json::Json;
+    use crate::value::{Row, Value};
+    use std::mem::size_of;
+
+    #[test]
+    fn sizes_values_by_their_contents() {
+        let empty = size_of::<Row>();
+        assert_eq!(estimated_size(&[Value::Int(1)]), empty + size_of::<Value>());
+        assert_eq!(estimated_size(&[Value::String("abc".into())]), empty + size_of::<Value>() + 3);
+        let text = Value::String("x".repeat(1000));
+        let nested = Value::Struct(vec![Value::Array(vec![text.clone(), text]), Value::Null]);
+        assert_eq!(estimated_size(&[nested]), empty + 5 * size_of::<Value>() + 2000);
+        let document = Json::Object(vec![("tags".into(), Json::Array(vec![Json::String("y".repeat(500)), Json::Null]))]);
+        assert_eq!(
+            estimated_size(&[Value::Json(document)]),
+            empty + size_of::<Value>() + size_of::<(String, Json)>() + 4 + 2 * size_of::<Json>() + 500
+        );
+    }
+}
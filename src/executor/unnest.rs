@@ -0,0 +1,122 @@
+use super::{ExecutionNode, Executor};
+use crate::errors::ExecutionError;
+use crate::expression::Expr;
+use crate::schema::{Field, Schema};
+use crate::value::{DataType, Row, Value};
+
+/// Joins every input row with the elements of the array it gives, in order.
+/// A NULL array has no elements.
+pub struct UnnestExecutor {
+    input: ExecutionNode,
+    array: Expr,
+    with_offset: bool,
+    outer: bool,
+    condition: Option<Expr>,
+    schema: Schema,
+    /// The input row being expanded, its elements and the next position
+    current: Option<(Row, Vec<Value>)>,
+    position: usize,
+    matched: bool,
+}
+
+impl UnnestExecutor {
+    pub fn new(
+        input: ExecutionNode,
+        array: Expr,
+        alias: &str,
+        offset: Option<&str>,
+        outer: bool,
+        condition: Option<Expr>,
+    ) -> Result<Self, ExecutionError> {
+        let element = match array.data_type(input.schema())? {
+            DataType::Array(element) => *element,
+            data_type => return Err(ExecutionError::new(&format!("UNNEST needs an ARRAY, got {}", data_type))),
+        };
+        let mut fields = input.schema().fields.clone();
+        fields.push(Field::new(None, alias, element));
+        if let Some(offset) = offset {
+            fields.push(Field::new(None, offset, DataType::Int64));
+        }
+        let schema = Schema::new(fields);
+        if let Some(condition) = &condition {
+            condition.data_type(&schema)?;
+        }
+        Ok(Self {
+            input,
+            array,
+            with_offset: offset.is_some(),
+            outer,
+            condition,
+            schema,
+            current: None,
+            position: 0,
+            matched: false,
+        })
+    }
+
+    /// The row with an element, or with NULLs for a padding row.
+    fn joined(&self, row: &Row, element: Option<(Value, usize)>) -> Row {
+        let mut joined = row.clone();
+        let (element, position) = match element {
+            Some((element, position)) => (element, Value::Int(position as i64)),
+            None => (Value::Null, Value::Null),
+        };
+        joined.push(element);
+        if self.with_offset {
+            joined.push(position);
+        }
+        joined
+    }
+}
+
+impl Executor for UnnestExecutor {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.current = None;
+        self.input.open()
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        loop {
+            let Some((row, elements)) = &self.current else {
+                let Some(row) = self.input.next()? else {
+                    return Ok(None);
+                };
+                let elements = match self.array.evaluate(self.input.schema(), &row)? {
+                    Value::Array(elements) => elements,
+                    _ => Vec::new(),
+                };
+                self.current = Some((row, elements));
+                self.position = 0;
+                self.matched = false;
+                continue;
+            };
+            if self.position == elements.len() {
+                let padding = (self.outer && !self.matched).then(|| self.joined(row, None));
+                self.current = None;
+                match padding {
+                    Some(padding) => return Ok(Some(padding)),
+                    None => continue,
+                }
+            }
+            let position = self.position;
+            self.position += 1;
+            let joined = self.joined(row, Some((elements[position].clone(), position)));
+            let satisfied = match &self.condition {
+                Some(condition) => condition.is_satisfied(&self.schema, &joined)?,
+                None => true,
+            };
+            if satisfied {
+                self.matched = true;
+                return Ok(Some(joined));
+            }
+        }
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.input]
+    }
+}
@@ -0,0 +1,309 @@
+use super::aggregate::Accumulator;
+use super::{ExecutionNode, Executor};
+use crate::errors::ExecutionError;
+use crate::expression::Expr;
+use crate::order_by::{compare_sort_keys, SortKey};
+use crate::schema::Schema;
+use crate::value::{Row, Value};
+use crate::window::{window_fields, FrameBound, FrameUnits, WindowCall, WindowFunction};
+use std::collections::VecDeque;
+
+/// Window functions over input sorted by the partition keys and then the
+/// order keys of the calls, which they all share. One partition is read into
+/// memory at a time; its rows are returned with one value per call appended.
+pub struct WindowExecutor {
+    input: ExecutionNode,
+    windows: Vec<WindowCall>,
+    partition_by: Vec<Expr>,
+    order_by: Vec<SortKey>,
+    schema: Schema,
+    /// The first row of the next partition with its partition key
+    pending: Option<(Row, Row)>,
+    output: VecDeque<Row>,
+}
+
+/// Where the rows of a partition stand relative to each other.
+struct Partition {
+    rows: Vec<Row>,
+    /// First row sorting equal to each row
+    peer_start: Vec<usize>,
+    /// One past the last row sorting equal to each row
+    peer_end: Vec<usize>,
+    /// The single ORDER BY key as a number, negated in descending order so
+    /// that it ascends, for RANGE frames with offsets
+    numeric_keys: Vec<Option<f64>>,
+}
+
+impl WindowExecutor {
+    pub fn new(input: ExecutionNode, windows: &[(WindowCall, Option<String>)]) -> Result<Self, ExecutionError> {
+        let schema = Schema::new(window_fields(windows, input.schema())?);
+        let spec = &windows[0].0.spec;
+        Ok(Self {
+            partition_by: spec.partition_by.clone(),
+            order_by: spec.order_by.clone(),
+            windows: windows.iter().map(|(call, _)| call.clone()).collect(),
+            input,
+            schema,
+            pending: None,
+            output: VecDeque::new(),
+        })
+    }
+
+    /// The next input row with its partition key.
+    fn read(&mut self) -> Result<Option<(Row, Row)>, ExecutionError> {
+        let Some(row) = self.input.next()? else {
+            return Ok(None);
+        };
+        let schema = self.input.schema();
+        let key = self
+            .partition_by
+            .iter()
+            .map(|expr| expr.evaluate(schema, &row))
+            .collect::<Result<Row, _>>()?;
+        Ok(Some((key, row)))
+    }
+
+    fn read_partition(&mut self) -> Result<Option<Vec<Row>>, ExecutionError> {
+        let Some((key, row)) = self.pending.take() else {
+            return Ok(None);
+        };
+        let mut rows = vec![row];
+        while let Some((next_key, row)) = self.read()? {
+            if next_key != key {
+                self.pending = Some((next_key, row));
+                break;
+            }
+            rows.push(row);
+        }
+        Ok(Some(rows))
+    }
+
+    fn partition(&self, rows: Vec<Row>) -> Result<Partition, ExecutionError> {
+        let schema = self.input.schema();
+        let keys = rows
+            .iter()
+            .map(|row| self.order_by.iter().map(|key| key.expr.evaluate(schema, row)).collect())
+            .collect::<Result<Vec<Row>, _>>()?;
+        let n = rows.len();
+        let (mut peer_start, mut peer_end) = (vec![0; n], vec![n; n]);
+        let mut start = 0;
+        for i in 1..=n {
+            if i == n || compare_sort_keys(&self.order_by, &keys[start], &keys[i]).is_ne() {
+                peer_start[start..i].fill(start);
+                peer_end[start..i].fill(i);
+                start = i;
+            }
+        }
+        let numeric_keys = keys
+            .iter()
+            .map(|key| {
+                let value = match key.first() {
+                    Some(Value::Int(i)) => *i as f64,
+                    Some(Value::Float(x)) => *x,
+                    Some(Value::Decimal(d)) => d.to_f64(),
+                    _ => return None,
+                };
+                Some(if self.order_by[0].descending { -value } else { value })
+            })
+            .collect();
+        Ok(Partition {
+            rows,
+            peer_start,
+            peer_end,
+            numeric_keys,
+        })
+    }
+
+    /// The values of one call for every row of the partition.
+    fn evaluate(&self, call: &WindowCall, partition: &Partition) -> Result<Vec<Value>, ExecutionError> {
+        let schema = self.input.schema();
+        let rows = &partition.rows;
+        let n = rows.len();
+        let mut values = Vec::with_capacity(n);
+        match &call.function {
+            WindowFunction::RowNumber => values.extend((1..=n).map(|i| Value::Int(i as i64))),
+            WindowFunction::Rank => values.extend(partition.peer_start.iter().map(|&start| Value::Int(start as i64 + 1))),
+            WindowFunction::DenseRank => {
+                let mut rank = 0;
+                for i in 0..n {
+                    if partition.peer_start[i] == i {
+                        rank += 1;
+                    }
+                    values.push(Value::Int(rank));
+                }
+            }
+            WindowFunction::Lag | WindowFunction::Lead => {
+                let offset = call.offset()?;
+                for i in 0..n {
+                    let other = match call.function {
+                        WindowFunction::Lag => i.checked_sub(offset),
+                        _ => i.checked_add(offset).filter(|&other| other < n),
+                    };
+                    values.push(match (other, call.args.get(2)) {
+                        (Some(other), _) => call.args[0].evaluate(schema, &rows[other])?,
+                        (None, Some(default)) => default.evaluate(schema, &rows[i])?,
+                        (None, None) => Value::Null,
+                    });
+                }
+            }
+            WindowFunction::FirstValue | WindowFunction::LastValue => {
+                for i in 0..n {
+                    let (start, end) = self.frame(call, partition, i);
+                    let row = match call.function {
+                        WindowFunction::FirstValue => start,
+                        _ => end.wrapping_sub(1),
+                    };
+                    values.push(if start < end {
+                        call.args[0].evaluate(schema, &rows[row])?
+                    } else {
+                        Value::Null
+                    });
+                }
+            }
+            WindowFunction::Aggregate(aggregate) => {
+                let arguments = rows
+                    .iter()
+                    .map(|row| match &aggregate.argument {
+                        Some(argument) => argument.evaluate(schema, row),
+                        None => Ok(Value::Null),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                // frames starting at the partition start only grow, so one
+                // accumulator takes every row once
+                let growing = call
+                    .spec
+                    .frame
+                    .as_ref()
+                    .is_none_or(|frame| frame.start == FrameBound::UnboundedPreceding);
+                let mut running = Accumulator::new(aggregate);
+                let mut added = 0;
+                let mut previous: Option<((usize, usize), Value)> = None;
+                for i in 0..n {
+                    let frame = self.frame(call, partition, i);
+                    if let Some((_, value)) = previous.as_ref().filter(|(last, _)| *last == frame) {
+                        values.push(value.clone());
+                        continue;
+                    }
+                    let value = if growing {
+                        while added < frame.1 {
+                            running.update(aggregate, &arguments[added])?;
+                            added += 1;
+                        }
+                        running.clone().finish()
+                    } else {
+                        let mut accumulator = Accumulator::new(aggregate);
+                        for argument in &arguments[frame.0..frame.1] {
+                            accumulator.update(aggregate, argument)?;
+                        }
+                        accumulator.finish()
+                    };
+                    values.push(value.clone());
+                    previous = Some((frame, value));
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    /// The rows `start..end` of the partition in the frame of row `i`.
+    fn frame(&self, call: &WindowCall, partition: &Partition, i: usize) -> (usize, usize) {
+        let n = partition.rows.len();
+        let Some(frame) = &call.spec.frame else {
+            return if self.order_by.is_empty() { (0, n) } else { (0, partition.peer_end[i]) };
+        };
+        let (start, end) = match frame.units {
+            FrameUnits::Rows => {
+                let start = match frame.start {
+                    FrameBound::UnboundedPreceding => 0,
+                    FrameBound::Preceding(k) => i.saturating_sub(k as usize),
+                    FrameBound::CurrentRow => i,
+                    FrameBound::Following(k) => i.saturating_add(k as usize).min(n),
+                    FrameBound::UnboundedFollowing => n,
+                };
+                let end = match frame.end {
+                    FrameBound::UnboundedPreceding => 0,
+                    FrameBound::Preceding(k) => (i + 1).saturating_sub(k as usize),
+                    FrameBound::CurrentRow => i + 1,
+                    FrameBound::Following(k) => i.saturating_add(k as usize).saturating_add(1).min(n),
+                    FrameBound::UnboundedFollowing => n,
+                };
+                (start, end)
+            }
+            FrameUnits::Range => (
+                range_bound(partition, i, frame.start, true),
+                range_bound(partition, i, frame.end, false),
+            ),
+        };
+        (start, end.max(start))
+    }
+}
+
+/// A bound of a RANGE frame: the first row of the frame for the start, one
+/// past its last row for the end. NULL keys only have their peers in range.
+fn range_bound(partition: &Partition, i: usize, bound: FrameBound, start: bool) -> usize {
+    let n = partition.rows.len();
+    let (delta, inclusive) = match bound {
+        FrameBound::UnboundedPreceding => return 0,
+        FrameBound::UnboundedFollowing => return n,
+        FrameBound::CurrentRow => return if start { partition.peer_start[i] } else { partition.peer_end[i] },
+        FrameBound::Preceding(k) => (-(k as f64), !start),
+        FrameBound::Following(k) => (k as f64, !start),
+    };
+    let Some(value) = partition.numeric_keys[i] else {
+        return if start { partition.peer_start[i] } else { partition.peer_end[i] };
+    };
+    // NULL keys sort together at one end, the numbers in between ascend
+    let keys = &partition.numeric_keys;
+    let first = keys.iter().position(Option::is_some).unwrap();
+    let last = keys.iter().rposition(Option::is_some).unwrap() + 1;
+    let target = value + delta;
+    first
+        + keys[first..last].partition_point(|key| {
+            let key = key.unwrap();
+            if inclusive {
+                key <= target
+            } else {
+                key < target
+            }
+        })
+}
+
+impl Executor for WindowExecutor {
+    fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    fn open(&mut self) -> Result<(), ExecutionError> {
+        self.output.clear();
+        self.input.open()?;
+        self.pending = self.read()?;
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<Option<Row>, ExecutionError> {
+        while self.output.is_empty() {
+            let Some(rows) = self.read_partition()? else {
+                return Ok(None);
+            };
+            let partition = self.partition(rows)?;
+            let columns = self
+                .windows
+                .iter()
+                .map(|call| self.evaluate(call, &partition))
+                .collect::<Result<Vec<_>, _>>()?;
+            let first = self.input.schema().fields.len();
+            for (i, mut row) in partition.rows.into_iter().enumerate() {
+                for (column, values) in columns.iter().enumerate() {
+                    let data_type = self.schema.fields[first + column].data_type.clone();
+                    row.push(values[i].clone().coerce_to(data_type).map_err(|e| ExecutionError::new(&e))?);
+                }
+                self.output.push_back(row);
+            }
+        }
+        Ok(self.output.pop_front())
+    }
+
+    fn children(&self) -> Vec<&ExecutionNode> {
+        vec![&self.input]
+    }
+}
@@ -0,0 +1,1063 @@
+use crate::decimal::{Decimal, RoundingMode, MAX_PRECISION};
+use crate::errors::{ExecutionError, ParsingError};
+use crate::function::lookup;
+use crate::json::Json;
+use crate::lexer::{Token, Tokens};
+use crate::logical_plan::LogicalPlan;
+use crate::parser::parse_query;
+use crate::schema::Schema;
+use crate::temporal::DatePart;
+use crate::value::{DataType, Value};
+use std::cmp::Ordering;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOperator {
+    Or,
+    And,
+    Eq,
+    NotEq,
+    Lt,
+    LtEq,
+    Gt,
+    GtEq,
+    /// `IS DISTINCT FROM`: `<>` that treats NULLs as equal values
+    IsDistinctFrom,
+    IsNotDistinctFrom,
+    Plus,
+    Minus,
+    Multiply,
+    Divide,
+}
+
+impl BinaryOperator {
+    fn precedence(&self) -> u8 {
+        match self {
+            BinaryOperator::Or => 1,
+            BinaryOperator::And => 2,
+            BinaryOperator::Eq
+            | BinaryOperator::NotEq
+            | BinaryOperator::Lt
+            | BinaryOperator::LtEq
+            | BinaryOperator::Gt
+            | BinaryOperator::GtEq
+            | BinaryOperator::IsDistinctFrom
+            | BinaryOperator::IsNotDistinctFrom => 4,
+            BinaryOperator::Plus | BinaryOperator::Minus => 5,
+            BinaryOperator::Multiply | BinaryOperator::Divide => 6,
+        }
+    }
+
+    fn from_token(token: &Token) -> Option<Self> {
+        match token {
+            Token::Identifier(word) if word.eq_ignore_ascii_case("OR") => Some(BinaryOperator::Or),
+            Token::Identifier(word) if word.eq_ignore_ascii_case("AND") => Some(BinaryOperator::And),
+            Token::Symbol(symbol) => match symbol.as_str() {
+                "=" => Some(BinaryOperator::Eq),
+                "!=" | "<>" => Some(BinaryOperator::NotEq),
+                "<" => Some(BinaryOperator::Lt),
+                "<=" => Some(BinaryOperator::LtEq),
+                ">" => Some(BinaryOperator::Gt),
+                ">=" => Some(BinaryOperator::GtEq),
+                "+" => Some(BinaryOperator::Plus),
+                "-" => Some(BinaryOperator::Minus),
+                "*" => Some(BinaryOperator::Multiply),
+                "/" => Some(BinaryOperator::Divide),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            BinaryOperator::Or => "OR",
+            BinaryOperator::And => "AND",
+            BinaryOperator::Eq => "=",
+            BinaryOperator::NotEq => "!=",
+            BinaryOperator::Lt => "<",
+            BinaryOperator::LtEq => "<=",
+            BinaryOperator::Gt => ">",
+            BinaryOperator::GtEq => ">=",
+            BinaryOperator::IsDistinctFrom => "IS DISTINCT FROM",
+            BinaryOperator::IsNotDistinctFrom => "IS NOT DISTINCT FROM",
+            BinaryOperator::Plus => "+",
+            BinaryOperator::Minus => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOperator {
+    Not,
+    Minus,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Literal(Value),
+    Column {
+        qualifier: Option<String>,
+        name: String,
+    },
+    Unary {
+        op: UnaryOperator,
+        expr: Box<Expr>,
+    },
+    Binary {
+        left: Box<Expr>,
+        op: BinaryOperator,
+        right: Box<Expr>,
+    },
+    IsNull {
+        expr: Box<Expr>,
+        negated: bool,
+    },
+    /// A call of a scalar function, with the name in upper case
+    Function {
+        name: String,
+        args: Vec<Expr>,
+    },
+    /// `CAST(expr AS type)`, or `SAFE_CAST`, which gives NULL where CAST fails
+    Cast {
+        expr: Box<Expr>,
+        data_type: DataType,
+        safe: bool,
+    },
+    /// `*` in a select list
+    Wildcard,
+    /// `(query)` returning at most one row of one column, NULL without rows
+    Subquery(Box<LogicalPlan>),
+    /// `expr [NOT] IN (query)` with a query returning one column
+    InSubquery {
+        expr: Box<Expr>,
+        subquery: Box<LogicalPlan>,
+        negated: bool,
+    },
+    /// `[NOT] EXISTS (query)`
+    Exists {
+        subquery: Box<LogicalPlan>,
+        negated: bool,
+    },
+    /// `STRUCT(expr [AS name], ...)`; fields without a name are anonymous
+    Struct {
+        fields: Vec<(String, Expr)>,
+    },
+    /// `expr.name`, a field of a struct
+    Field {
+        expr: Box<Expr>,
+        name: String,
+    },
+}
+
+impl Expr {
+    pub fn binary(left: Expr, op: BinaryOperator, right: Expr) -> Self {
+        Expr::Binary {
+            left: Box::new(left),
+            op,
+            right: Box::new(right),
+        }
+    }
+
+    /// Joins predicates with AND; `None` for an empty list.
+    pub fn conjunction(predicates: Vec<Expr>) -> Option<Expr> {
+        predicates
+            .into_iter()
+            .reduce(|left, right| Expr::binary(left, BinaryOperator::And, right))
+    }
+
+    /// Splits `a AND b AND c` into its conjuncts.
+    pub fn into_conjuncts(self) -> Vec<Expr> {
+        match self {
+            Expr::Binary {
+                left,
+                op: BinaryOperator::And,
+                right,
+            } => {
+                let mut conjuncts = left.into_conjuncts();
+                conjuncts.extend(right.into_conjuncts());
+                conjuncts
+            }
+            expr => vec![expr],
+        }
+    }
+
+    /// Rebuilds the expression bottom-up, replacing every node by `f(node)`.
+    pub fn transform(self, f: &mut impl FnMut(Expr) -> Expr) -> Expr {
+        let expr = match self {
+            Expr::Unary { op, expr } => Expr::Unary {
+                op,
+                expr: Box::new(expr.transform(f)),
+            },
+            Expr::Binary { left, op, right } => Expr::binary(left.transform(f), op, right.transform(f)),
+            Expr::IsNull { expr, negated } => Expr::IsNull {
+                expr: Box::new(expr.transform(f)),
+                negated,
+            },
+            Expr::Function { name, args } => Expr::Function {
+                name,
+                args: args.into_iter().map(|arg| arg.transform(f)).collect(),
+            },
+            Expr::Cast { expr, data_type, safe } => Expr::Cast {
+                expr: Box::new(expr.transform(f)),
+                data_type,
+                safe,
+            },
+            Expr::InSubquery { expr, subquery, negated } => Expr::InSubquery {
+                expr: Box::new(expr.transform(f)),
+                subquery,
+                negated,
+            },
+            Expr::Struct { fields } => Expr::Struct {
+                fields: fields.into_iter().map(|(name, expr)| (name, expr.transform(f))).collect(),
+            },
+            Expr::Field { expr, name } => Expr::Field {
+                expr: Box::new(expr.transform(f)),
+                name,
+            },
+            expr => expr,
+        };
+        f(expr)
+    }
+
+    /// Every column the expression reads, as `(qualifier, name)`.
+    pub fn columns(&self) -> Vec<(Option<String>, String)> {
+        let mut columns = Vec::new();
+        self.collect_columns(&mut columns);
+        columns
+    }
+
+    fn collect_columns(&self, columns: &mut Vec<(Option<String>, String)>) {
+        match self {
+            Expr::Column { qualifier, name } => columns.push((qualifier.clone(), name.clone())),
+            Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } | Expr::Cast { expr, .. } | Expr::Field { expr, .. } => {
+                expr.collect_columns(columns)
+            }
+            Expr::Binary { left, right, .. } => {
+                left.collect_columns(columns);
+                right.collect_columns(columns);
+            }
+            Expr::Function { args, .. } => {
+                for arg in args {
+                    arg.collect_columns(columns);
+                }
+            }
+            Expr::InSubquery { expr, .. } => expr.collect_columns(columns),
+            Expr::Struct { fields } => {
+                for (_, expr) in fields {
+                    expr.collect_columns(columns);
+                }
+            }
+            // columns of the outer query in a subquery are found by the planner
+            Expr::Literal(_) | Expr::Wildcard | Expr::Subquery(_) | Expr::Exists { .. } => {}
+        }
+    }
+
+    /// Whether the expression contains a subquery.
+    pub fn has_subquery(&self) -> bool {
+        let mut found = false;
+        self.clone().transform(&mut |expr| {
+            found |= matches!(expr, Expr::Subquery(_) | Expr::InSubquery { .. } | Expr::Exists { .. });
+            expr
+        });
+        found
+    }
+
+    pub fn evaluate(&self, schema: &Schema, row: &[Value]) -> Result<Value, ExecutionError> {
+        match self {
+            Expr::Literal(value) => Ok(value.clone()),
+            Expr::Column { qualifier, name } => {
+                let index = schema.resolve(qualifier.as_deref(), name)?;
+                Ok(row[index].clone())
+            }
+            Expr::Unary { op, expr } => {
+                let value = expr.evaluate(schema, row)?;
+                match (op, value) {
+                    (_, Value::Null) => Ok(Value::Null),
+                    (UnaryOperator::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+                    (UnaryOperator::Minus, Value::Int(i)) => Ok(Value::Int(-i)),
+                    (UnaryOperator::Minus, Value::Float(x)) => Ok(Value::Float(-x)),
+                    (UnaryOperator::Minus, Value::Decimal(d)) => Ok(Value::Decimal(d.neg())),
+                    (_, value) => Err(ExecutionError::new(&format!("Invalid operand {} in {}", value, self))),
+                }
+            }
+            Expr::Binary { left, op, right } => {
+                let left = left.evaluate(schema, row)?;
+                let right = right.evaluate(schema, row)?;
+                evaluate_binary(*op, left, right)
+            }
+            Expr::IsNull { expr, negated } => {
+                let is_null = expr.evaluate(schema, row)?.is_null();
+                Ok(Value::Bool(is_null != *negated))
+            }
+            Expr::Function { name, args } => {
+                let args = args
+                    .iter()
+                    .map(|arg| arg.evaluate(schema, row))
+                    .collect::<Result<Vec<_>, _>>()?;
+                lookup(name)?.call(args)
+            }
+            Expr::Cast { expr, data_type, safe } => match expr.evaluate(schema, row)?.cast(data_type) {
+                Ok(value) => Ok(value),
+                Err(_) if *safe => Ok(Value::Null),
+                Err(e) => Err(ExecutionError::new(&e)),
+            },
+            Expr::Struct { fields } => fields
+                .iter()
+                .map(|(_, expr)| expr.evaluate(schema, row))
+                .collect::<Result<_, _>>()
+                .map(Value::Struct),
+            Expr::Field { expr, name } => {
+                let data_type = expr.data_type(schema)?;
+                match expr.evaluate(schema, row)? {
+                    // a missing member is NULL, like a JSON null
+                    Value::Json(json) => Ok(match json.member(name) {
+                        Some(member) => Value::Json(member.clone()),
+                        None => Value::Null,
+                    }),
+                    Value::Struct(mut values) => Ok(values.swap_remove(field_of(&data_type, name)?.0)),
+                    _ if data_type == DataType::Json => Ok(Value::Null),
+                    _ => field_of(&data_type, name).map(|_| Value::Null),
+                }
+            }
+            Expr::Wildcard => Err(ExecutionError::new("* can only be used in a select list")),
+            Expr::Subquery(_) | Expr::InSubquery { .. } | Expr::Exists { .. } => Err(unplanned_subquery()),
+        }
+    }
+
+    /// Type of the expression's result over rows of `schema`. Also checks that
+    /// every column exists. An untyped NULL literal is treated as INT64.
+    pub fn data_type(&self, schema: &Schema) -> Result<DataType, ExecutionError> {
+        match self {
+            Expr::Literal(value) => Ok(value.data_type().unwrap_or(DataType::Int64)),
+            Expr::Column { qualifier, name } => {
+                let index = schema.resolve(qualifier.as_deref(), name)?;
+                Ok(schema.fields[index].data_type.clone())
+            }
+            Expr::Unary {
+                op: UnaryOperator::Not,
+                expr,
+            } => expr.data_type(schema).map(|_| DataType::Bool),
+            Expr::Unary { expr, .. } => expr.data_type(schema),
+            Expr::Binary { left, op, right } => {
+                let (left, right) = (left.data_type(schema)?, right.data_type(schema)?);
+                Ok(match op {
+                    BinaryOperator::Plus | BinaryOperator::Minus | BinaryOperator::Multiply | BinaryOperator::Divide => {
+                        match (&left, &right) {
+                            (DataType::Int64, DataType::Int64) => DataType::Int64,
+                            (DataType::String, DataType::String) => DataType::String,
+                            (DataType::Decimal(..), DataType::Int64 | DataType::Decimal(..))
+                            | (DataType::Int64, DataType::Decimal(..)) => decimal_type(*op, &left, &right),
+                            _ => DataType::Float64,
+                        }
+                    }
+                    _ => DataType::Bool,
+                })
+            }
+            Expr::IsNull { expr, .. } => expr.data_type(schema).map(|_| DataType::Bool),
+            // untyped NULL arguments fit any parameter
+            Expr::Function { name, args } => {
+                let function = lookup(name)?;
+                let types = args
+                    .iter()
+                    .map(|arg| match arg {
+                        Expr::Literal(Value::Null) => Ok(None),
+                        arg => arg.data_type(schema).map(Some),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                function.return_type(&types)
+            }
+            Expr::Cast { expr, data_type, .. } => {
+                let from = expr.data_type(schema)?;
+                if !matches!(**expr, Expr::Literal(Value::Null)) && !from.can_cast_to(data_type) {
+                    return Err(ExecutionError::new(&format!("Cannot cast {} to {}", from, data_type)));
+                }
+                Ok(data_type.clone())
+            }
+            Expr::Struct { fields } => fields
+                .iter()
+                .map(|(name, expr)| expr.data_type(schema).map(|data_type| (name.clone(), data_type)))
+                .collect::<Result<_, _>>()
+                .map(DataType::Struct),
+            Expr::Field { expr, name } => match expr.data_type(schema)? {
+                DataType::Json => Ok(DataType::Json),
+                data_type => field_of(&data_type, name).map(|(_, data_type)| data_type),
+            },
+            Expr::Wildcard => Err(ExecutionError::new("* can only be used in a select list")),
+            Expr::Subquery(_) | Expr::InSubquery { .. } | Expr::Exists { .. } => Err(unplanned_subquery()),
+        }
+    }
+
+    /// Evaluates a predicate; anything but TRUE rejects the row.
+    pub fn is_satisfied(&self, schema: &Schema, row: &[Value]) -> Result<bool, ExecutionError> {
+        Ok(matches!(self.evaluate(schema, row)?, Value::Bool(true)))
+    }
+}
+
+/// The position and type of a field of a struct type.
+fn field_of(data_type: &DataType, name: &str) -> Result<(usize, DataType), ExecutionError> {
+    let DataType::Struct(fields) = data_type else {
+        return Err(ExecutionError::new(&format!("Cannot read field {} of a {}", name, data_type)));
+    };
+    fields
+        .iter()
+        .position(|(field, _)| field.eq_ignore_ascii_case(name))
+        .map(|index| (index, fields[index].1.clone()))
+        .ok_or_else(|| ExecutionError::new(&format!("{} has no field {}", data_type, name)))
+}
+
+/// Subqueries are rewritten into joins and APPLY operators before planning,
+/// so expressions never see them.
+fn unplanned_subquery() -> ExecutionError {
+    ExecutionError::new("Subqueries are not supported here")
+}
+
+/// Applies an operator with SQL's three-valued logic: NULL stands for an
+/// unknown value, so an operand that is NULL makes the result NULL unless the
+/// other operand decides it, as FALSE does for AND and TRUE for OR.
+pub fn evaluate_binary(op: BinaryOperator, left: Value, right: Value) -> Result<Value, ExecutionError> {
+    let invalid = || {
+        ExecutionError::new(&format!("Invalid operands for {}: {} and {}", op, left, right))
+    };
+    match op {
+        BinaryOperator::And | BinaryOperator::Or => {
+            let truth = |value: &Value| match value {
+                Value::Null => Ok(None),
+                Value::Bool(b) => Ok(Some(*b)),
+                _ => Err(invalid()),
+            };
+            let (a, b) = (truth(&left)?, truth(&right)?);
+            let decisive = op == BinaryOperator::Or;
+            Ok(if a == Some(decisive) || b == Some(decisive) {
+                Value::Bool(decisive)
+            } else if a.is_none() || b.is_none() {
+                Value::Null
+            } else {
+                Value::Bool(!decisive)
+            })
+        }
+        BinaryOperator::IsDistinctFrom | BinaryOperator::IsNotDistinctFrom => {
+            let distinct = match (left.is_null(), right.is_null()) {
+                (true, true) => false,
+                (true, false) | (false, true) => true,
+                _ => left.compare(&right).ok_or_else(invalid)? != Ordering::Equal,
+            };
+            Ok(Value::Bool(distinct == (op == BinaryOperator::IsDistinctFrom)))
+        }
+        _ if left.is_null() || right.is_null() => Ok(Value::Null),
+        BinaryOperator::Eq
+        | BinaryOperator::NotEq
+        | BinaryOperator::Lt
+        | BinaryOperator::LtEq
+        | BinaryOperator::Gt
+        | BinaryOperator::GtEq => {
+            let ordering = left.compare(&right).ok_or_else(invalid)?;
+            Ok(Value::Bool(match op {
+                BinaryOperator::Eq => ordering == Ordering::Equal,
+                BinaryOperator::NotEq => ordering != Ordering::Equal,
+                BinaryOperator::Lt => ordering == Ordering::Less,
+                BinaryOperator::LtEq => ordering != Ordering::Greater,
+                BinaryOperator::Gt => ordering == Ordering::Greater,
+                _ => ordering != Ordering::Less,
+            }))
+        }
+        BinaryOperator::Plus | BinaryOperator::Minus | BinaryOperator::Multiply | BinaryOperator::Divide => {
+            match (&left, &right) {
+                (Value::Int(a), Value::Int(b)) => {
+                    let result = match op {
+                        BinaryOperator::Plus => a.checked_add(*b),
+                        BinaryOperator::Minus => a.checked_sub(*b),
+                        BinaryOperator::Multiply => a.checked_mul(*b),
+                        _ if *b == 0 => return Err(ExecutionError::new("Division by zero")),
+                        _ => a.checked_div(*b),
+                    };
+                    result
+                        .map(Value::Int)
+                        .ok_or_else(|| ExecutionError::new("Integer overflow"))
+                }
+                (Value::Decimal(a), Value::Decimal(b)) => evaluate_decimal(op, *a, *b),
+                (Value::Decimal(a), Value::Int(b)) => evaluate_decimal(op, *a, Decimal::from_int(*b)),
+                (Value::Int(a), Value::Decimal(b)) => evaluate_decimal(op, Decimal::from_int(*a), *b),
+                (Value::Int(_) | Value::Float(_) | Value::Decimal(_), Value::Int(_) | Value::Float(_) | Value::Decimal(_)) => {
+                    let (a, b) = (as_float(&left), as_float(&right));
+                    Ok(Value::Float(match op {
+                        BinaryOperator::Plus => a + b,
+                        BinaryOperator::Minus => a - b,
+                        BinaryOperator::Multiply => a * b,
+                        _ => a / b,
+                    }))
+                }
+                (Value::String(a), Value::String(b)) if op == BinaryOperator::Plus => {
+                    Ok(Value::String(format!("{}{}", a, b)))
+                }
+                _ => Err(invalid()),
+            }
+        }
+    }
+}
+
+/// Type of arithmetic on decimals, or a decimal and an integer. Sums keep
+/// the larger scale, products add the scales and quotients get six more
+/// digits, the last two up to the NUMERIC scale or the larger scale of the
+/// operands, and all up to 38 digits.
+fn decimal_type(op: BinaryOperator, left: &DataType, right: &DataType) -> DataType {
+    let ((p1, s1), (p2, s2)) = (
+        left.decimal_digits().expect("decimal operands"),
+        right.decimal_digits().expect("decimal operands"),
+    );
+    let (precision, scale) = match op {
+        BinaryOperator::Plus | BinaryOperator::Minus => {
+            let scale = s1.max(s2);
+            ((p1 - s1).max(p2 - s2) + scale + 1, scale)
+        }
+        BinaryOperator::Multiply => (p1 + p2, Decimal::product_scale(s1, s2)),
+        _ => {
+            let scale = Decimal::quotient_scale(s1, s2);
+            (p1 - s1 + s2 + scale, scale)
+        }
+    };
+    DataType::Decimal(precision.min(MAX_PRECISION).max(scale), scale)
+}
+
+/// Arithmetic on decimals, or a decimal and an integer.
+fn evaluate_decimal(op: BinaryOperator, left: Decimal, right: Decimal) -> Result<Value, ExecutionError> {
+    let result = match op {
+        BinaryOperator::Plus => left.checked_add(right),
+        BinaryOperator::Minus => left.checked_sub(right),
+        BinaryOperator::Multiply => left.checked_mul(right),
+        _ if right.is_zero() => return Err(ExecutionError::new("Division by zero")),
+        _ => {
+            let scale = Decimal::quotient_scale(left.scale(), right.scale());
+            left.checked_div(right, scale, RoundingMode::HalfAwayFromZero)
+        }
+    };
+    result.map(Value::Decimal).ok_or_else(|| ExecutionError::new("NUMERIC overflow"))
+}
+
+fn as_float(value: &Value) -> f64 {
+    match value {
+        Value::Int(i) => *i as f64,
+        Value::Float(x) => *x,
+        Value::Decimal(d) => d.to_f64(),
+        _ => f64::NAN,
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Column {
+                qualifier: Some(qualifier),
+                name,
+            } => write!(f, "{}.{}", qualifier, name),
+            Expr::Column { qualifier: None, name } => write!(f, "{}", name),
+            Expr::Unary {
+                op: UnaryOperator::Not,
+                expr,
+            } => write!(f, "NOT {}", expr),
+            Expr::Unary {
+                op: UnaryOperator::Minus,
+                expr,
+            } => {
+                write!(f, "-")?;
+                write_operand(f, expr, u8::MAX)
+            }
+            Expr::Binary { left, op, right } => {
+                write_operand(f, left, op.precedence())?;
+                write!(f, " {} ", op)?;
+                write_operand(f, right, op.precedence() + 1)
+            }
+            Expr::IsNull { expr, negated } => {
+                write!(f, "{} IS {}NULL", expr, if *negated { "NOT " } else { "" })
+            }
+            Expr::Function { name, args } => write_function(f, name, args),
+            Expr::Cast { expr, data_type, safe } => {
+                write!(f, "{}CAST({} AS {})", if *safe { "SAFE_" } else { "" }, expr, data_type)
+            }
+            Expr::Wildcard => write!(f, "*"),
+            Expr::Subquery(_) => write!(f, "(subquery)"),
+            Expr::InSubquery { expr, negated, .. } => {
+                write!(f, "{} {}IN (subquery)", expr, if *negated { "NOT " } else { "" })
+            }
+            Expr::Exists { negated, .. } => write!(f, "{}EXISTS (subquery)", if *negated { "NOT " } else { "" }),
+            Expr::Struct { fields } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(name, expr)| match expr {
+                        _ if name.is_empty() => expr.to_string(),
+                        Expr::Column { name: column, .. } | Expr::Field { name: column, .. } if column == name => expr.to_string(),
+                        expr => format!("{} AS {}", expr, name),
+                    })
+                    .collect();
+                write!(f, "STRUCT({})", fields.join(", "))
+            }
+            Expr::Field { expr, name } => {
+                write_operand(f, expr, u8::MAX)?;
+                write!(f, ".{}", name)
+            }
+        }
+    }
+}
+
+/// Writes a call the way it is parsed, with date parts as keywords.
+fn write_function(f: &mut fmt::Formatter, name: &str, args: &[Expr]) -> fmt::Result {
+    let part = |i: usize| match args.get(i) {
+        Some(Expr::Literal(Value::String(s))) if DatePart::from_name(s).is_some() => Some(s.as_str()),
+        _ => None,
+    };
+    match (name, part(0), part(2)) {
+        ("EXTRACT", Some(part), _) if (2..=3).contains(&args.len()) => {
+            write!(f, "EXTRACT({} FROM {}", part, args[1])?;
+            if let Some(zone) = args.get(2) {
+                write!(f, " AT TIME ZONE {}", zone)?;
+            }
+            write!(f, ")")
+        }
+        ("DATE_ADD" | "DATE_SUB", _, Some(part)) if args.len() == 3 => {
+            write!(f, "{}({}, INTERVAL {} {})", name, args[0], args[1], part)
+        }
+        ("ARRAY", _, _) => {
+            let items: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+            write!(f, "[{}]", items.join(", "))
+        }
+        (name, _, _) if SUBSCRIPTS.contains(&name) && args.len() == 2 => {
+            write_operand(f, &args[0], u8::MAX)?;
+            write!(f, "[{}({})]", name, args[1])
+        }
+        _ => {
+            let part_position = date_part_position(name);
+            let args: Vec<String> = (0..args.len())
+                .map(|i| match part(i) {
+                    Some(part) if part_position == Some(i) => part.to_string(),
+                    _ => args[i].to_string(),
+                })
+                .collect();
+            write!(f, "{}({})", name, args.join(", "))
+        }
+    }
+}
+
+/// Functions an array subscript such as `[OFFSET(i)]` calls.
+const SUBSCRIPTS: [&str; 4] = ["OFFSET", "ORDINAL", "SAFE_OFFSET", "SAFE_ORDINAL"];
+
+/// Position of the argument DATE_TRUNC and DATE_DIFF take as a keyword
+/// such as DAY, which is a date part even where a column has its name.
+fn date_part_position(function: &str) -> Option<usize> {
+    match function {
+        "DATE_TRUNC" => Some(1),
+        "DATE_DIFF" => Some(2),
+        _ => None,
+    }
+}
+
+/// Parenthesizes operands that bind more loosely than the surrounding operator.
+fn write_operand(f: &mut fmt::Formatter, expr: &Expr, precedence: u8) -> fmt::Result {
+    match expr {
+        Expr::Binary { op, .. } if op.precedence() < precedence => write!(f, "({})", expr),
+        _ => write!(f, "{}", expr),
+    }
+}
+
+pub fn parse_expression(tokens: &mut Tokens) -> Result<Expr, ParsingError> {
+    parse_binary(tokens, 0)
+}
+
+/// Parses an expression without comparisons, IN or IS, for places where one
+/// of those words follows it.
+pub fn parse_operand(tokens: &mut Tokens) -> Result<Expr, ParsingError> {
+    parse_binary(tokens, 4)
+}
+
+fn parse_binary(tokens: &mut Tokens, min_precedence: u8) -> Result<Expr, ParsingError> {
+    let mut left = parse_prefix(tokens, min_precedence)?;
+
+    loop {
+        let negated_in = tokens.peek_keyword("NOT")
+            && matches!(tokens.peek_nth(1), Some(Token::Identifier(word)) if word.eq_ignore_ascii_case("IN"));
+        if (tokens.peek_keyword("IN") || negated_in) && min_precedence < 4 {
+            let negated = tokens.accept_keyword("NOT");
+            tokens.expect_keyword("IN")?;
+            left = Expr::InSubquery {
+                expr: Box::new(left),
+                subquery: Box::new(parse_subquery(tokens)?),
+                negated,
+            };
+            continue;
+        }
+        if tokens.peek_keyword("IS") && min_precedence < 4 {
+            tokens.next();
+            let negated = tokens.accept_keyword("NOT");
+            if tokens.accept_keyword("DISTINCT") {
+                tokens.expect_keyword("FROM")?;
+                let op = if negated {
+                    BinaryOperator::IsNotDistinctFrom
+                } else {
+                    BinaryOperator::IsDistinctFrom
+                };
+                left = Expr::binary(left, op, parse_binary(tokens, op.precedence())?);
+                continue;
+            }
+            tokens.expect_keyword("NULL")?;
+            left = Expr::IsNull {
+                expr: Box::new(left),
+                negated,
+            };
+            continue;
+        }
+
+        let op = match tokens.peek().and_then(BinaryOperator::from_token) {
+            Some(op) if op.precedence() > min_precedence => op,
+            _ => break,
+        };
+        tokens.next();
+        let right = parse_binary(tokens, op.precedence())?;
+        left = Expr::binary(left, op, right);
+    }
+
+    Ok(left)
+}
+
+fn parse_prefix(tokens: &mut Tokens, min_precedence: u8) -> Result<Expr, ParsingError> {
+    let negated_exists = tokens.peek_keyword("NOT")
+        && matches!(tokens.peek_nth(1), Some(Token::Identifier(word)) if word.eq_ignore_ascii_case("EXISTS"));
+    if negated_exists || tokens.peek_keyword("EXISTS") {
+        let negated = tokens.accept_keyword("NOT");
+        tokens.expect_keyword("EXISTS")?;
+        return Ok(Expr::Exists {
+            subquery: Box::new(parse_subquery(tokens)?),
+            negated,
+        });
+    }
+    if tokens.accept_keyword("NOT") {
+        // NOT binds more loosely than comparisons: NOT a = b is NOT (a = b)
+        let expr = parse_binary(tokens, min_precedence.max(3))?;
+        return Ok(Expr::Unary {
+            op: UnaryOperator::Not,
+            expr: Box::new(expr),
+        });
+    }
+    if tokens.accept_symbol("-") {
+        let expr = parse_prefix(tokens, 6)?;
+        return Ok(match expr {
+            Expr::Literal(Value::Int(i)) => Expr::Literal(Value::Int(-i)),
+            Expr::Literal(Value::Float(x)) => Expr::Literal(Value::Float(-x)),
+            Expr::Literal(Value::Decimal(d)) => Expr::Literal(Value::Decimal(d.neg())),
+            expr => Expr::Unary {
+                op: UnaryOperator::Minus,
+                expr: Box::new(expr),
+            },
+        });
+    }
+    let primary = parse_primary(tokens)?;
+    parse_postfix(tokens, primary)
+}
+
+/// Parses the field accesses `.name` and subscripts `[OFFSET(i)]` following
+/// an operand. A bare subscript `[i]` counts from zero like OFFSET.
+fn parse_postfix(tokens: &mut Tokens, mut expr: Expr) -> Result<Expr, ParsingError> {
+    loop {
+        if tokens.accept_symbol(".") {
+            let name = tokens.expect_identifier()?;
+            expr = Expr::Field {
+                expr: Box::new(expr),
+                name,
+            };
+        } else if tokens.accept_symbol("[") {
+            let called = matches!(tokens.peek_nth(1), Some(Token::Symbol(symbol)) if symbol == "(");
+            let (name, position) = match SUBSCRIPTS.iter().find(|name| called && tokens.peek_keyword(name)) {
+                Some(name) => {
+                    tokens.next();
+                    tokens.expect_symbol("(")?;
+                    let position = parse_expression(tokens)?;
+                    tokens.expect_symbol(")")?;
+                    (name.to_string(), position)
+                }
+                None => ("OFFSET".to_string(), parse_expression(tokens)?),
+            };
+            tokens.expect_symbol("]")?;
+            expr = Expr::Function {
+                name,
+                args: vec![expr, position],
+            };
+        } else {
+            return Ok(expr);
+        }
+    }
+}
+
+fn parse_primary(tokens: &mut Tokens) -> Result<Expr, ParsingError> {
+    match tokens.next() {
+        Some(Token::Number(number)) => parse_number(&number).map(Expr::Literal),
+        Some(Token::StringLiteral(s)) => Ok(Expr::Literal(Value::String(s))),
+        Some(Token::Symbol(symbol)) if symbol == "(" && ["FROM", "SELECT", "WITH"].iter().any(|word| tokens.peek_keyword(word)) => {
+            let query = parse_query(tokens)?;
+            tokens.expect_symbol(")")?;
+            Ok(Expr::Subquery(Box::new(query)))
+        }
+        Some(Token::Symbol(symbol)) if symbol == "(" => {
+            let expr = parse_expression(tokens)?;
+            tokens.expect_symbol(")")?;
+            Ok(expr)
+        }
+        Some(Token::Symbol(symbol)) if symbol == "[" => parse_array(tokens),
+        Some(Token::Identifier(word)) => match word.to_uppercase().as_str() {
+            "TRUE" => Ok(Expr::Literal(Value::Bool(true))),
+            "FALSE" => Ok(Expr::Literal(Value::Bool(false))),
+            "NULL" => Ok(Expr::Literal(Value::Null)),
+            name @ ("DATE" | "TIME" | "DATETIME" | "TIMESTAMP") if matches!(tokens.peek(), Some(Token::StringLiteral(_))) => {
+                let Some(Token::StringLiteral(text)) = tokens.next() else {
+                    unreachable!("a string literal was peeked")
+                };
+                let data_type = DataType::from_name(name).expect("temporal type names are types");
+                Value::String(text.clone())
+                    .cast(&data_type)
+                    .map(Expr::Literal)
+                    .map_err(|_| ParsingError::new(&format!("Invalid {} literal '{}'", name, text)))
+            }
+            "JSON" if matches!(tokens.peek(), Some(Token::StringLiteral(_))) => {
+                let Some(Token::StringLiteral(text)) = tokens.next() else {
+                    unreachable!("a string literal was peeked")
+                };
+                Json::parse(&text)
+                    .map(|json| Expr::Literal(Value::Json(json)))
+                    .map_err(|_| ParsingError::new(&format!("Invalid JSON literal '{}'", text)))
+            }
+            name @ ("NUMERIC" | "DECIMAL") if matches!(tokens.peek(), Some(Token::StringLiteral(_))) => {
+                let Some(Token::StringLiteral(text)) = tokens.next() else {
+                    unreachable!("a string literal was peeked")
+                };
+                Decimal::parse(&text)
+                    .map(|d| Expr::Literal(Value::Decimal(d)))
+                    .ok_or_else(|| ParsingError::new(&format!("Invalid {} literal '{}'", name, text)))
+            }
+            name @ ("CURRENT_TIMESTAMP" | "CURRENT_DATE" | "CURRENT_DATETIME") if !tokens.peek_symbol("(") => Ok(Expr::Function {
+                name: name.to_string(),
+                args: Vec::new(),
+            }),
+            "EXTRACT" if tokens.accept_symbol("(") => {
+                let mut args = vec![parse_date_part(tokens)?];
+                tokens.expect_keyword("FROM")?;
+                args.push(parse_expression(tokens)?);
+                if tokens.accept_keyword("AT") {
+                    tokens.expect_keyword("TIME")?;
+                    tokens.expect_keyword("ZONE")?;
+                    args.push(parse_expression(tokens)?);
+                }
+                tokens.expect_symbol(")")?;
+                Ok(Expr::Function {
+                    name: "EXTRACT".to_string(),
+                    args,
+                })
+            }
+            "ARRAY" if tokens.accept_symbol("[") => parse_array(tokens),
+            // `ARRAY<type>[...]` casts the elements, and types an empty array
+            "ARRAY" if matches!(tokens.peek(), Some(Token::Symbol(symbol)) if symbol == "<") => {
+                let data_type = DataType::parse_array(tokens)?;
+                tokens.expect_symbol("[")?;
+                Ok(Expr::Cast {
+                    expr: Box::new(parse_array(tokens)?),
+                    data_type,
+                    safe: false,
+                })
+            }
+            "STRUCT" if tokens.accept_symbol("(") => {
+                let mut fields: Vec<(String, Expr)> = Vec::new();
+                if !tokens.accept_symbol(")") {
+                    loop {
+                        let expr = parse_expression(tokens)?;
+                        // a column or field gives its name to the field
+                        let name = match &expr {
+                            _ if tokens.accept_keyword("AS") => tokens.expect_identifier()?,
+                            Expr::Column { name, .. } | Expr::Field { name, .. } => name.clone(),
+                            _ => String::new(),
+                        };
+                        if !name.is_empty() && fields.iter().any(|(field, _)| field.eq_ignore_ascii_case(&name)) {
+                            return Err(ParsingError::new(&format!("STRUCT has two fields named {}", name)));
+                        }
+                        fields.push((name, expr));
+                        if !tokens.accept_symbol(",") {
+                            break;
+                        }
+                    }
+                    tokens.expect_symbol(")")?;
+                }
+                Ok(Expr::Struct { fields })
+            }
+            name @ ("CAST" | "SAFE_CAST") if tokens.accept_symbol("(") => {
+                let expr = parse_expression(tokens)?;
+                tokens.expect_keyword("AS")?;
+                let data_type = DataType::parse(tokens)?;
+                tokens.expect_symbol(")")?;
+                Ok(Expr::Cast {
+                    expr: Box::new(expr),
+                    data_type,
+                    safe: name == "SAFE_CAST",
+                })
+            }
+            name if tokens.accept_symbol("(") => {
+                let part_position = date_part_position(name);
+                let mut args = Vec::new();
+                if !tokens.accept_symbol(")") {
+                    loop {
+                        let bare_part = part_position == Some(args.len()) && matches!(tokens.peek(), Some(Token::Identifier(_)));
+                        if tokens.accept_keyword("INTERVAL") {
+                            // INTERVAL count part passes the count and the part
+                            args.push(parse_expression(tokens)?);
+                            args.push(parse_date_part(tokens)?);
+                        } else if bare_part {
+                            args.push(parse_date_part(tokens)?);
+                        } else {
+                            args.push(parse_expression(tokens)?);
+                        }
+                        if !tokens.accept_symbol(",") {
+                            break;
+                        }
+                    }
+                    tokens.expect_symbol(")")?;
+                }
+                Ok(Expr::Function {
+                    name: word.to_uppercase(),
+                    args,
+                })
+            }
+            _ => {
+                if tokens.accept_symbol(".") {
+                    let name = tokens.expect_identifier()?;
+                    Ok(Expr::Column {
+                        qualifier: Some(word),
+                        name,
+                    })
+                } else {
+                    Ok(Expr::Column {
+                        qualifier: None,
+                        name: word,
+                    })
+                }
+            }
+        },
+        Some(token) => Err(ParsingError::new(&format!("Unexpected token in expression: {:?}", token))),
+        None => Err(ParsingError::new("Unexpected end of expression")),
+    }
+}
+
+/// Parses a date part such as DAY or MONTH into a string argument.
+fn parse_date_part(tokens: &mut Tokens) -> Result<Expr, ParsingError> {
+    let name = tokens.expect_identifier()?;
+    let part = DatePart::from_name(&name).ok_or_else(|| ParsingError::new(&format!("Unknown date part {}", name)))?;
+    Ok(Expr::Literal(Value::String(part.to_string())))
+}
+
+/// Parses the elements of `[a, b, ...]` after the opening bracket into a
+/// call of ARRAY.
+fn parse_array(tokens: &mut Tokens) -> Result<Expr, ParsingError> {
+    let mut args = Vec::new();
+    if !tokens.accept_symbol("]") {
+        loop {
+            args.push(parse_expression(tokens)?);
+            if !tokens.accept_symbol(",") {
+                break;
+            }
+        }
+        tokens.expect_symbol("]")?;
+    }
+    Ok(Expr::Function {
+        name: "ARRAY".to_string(),
+        args,
+    })
+}
+
+/// Parses `(query)`.
+fn parse_subquery(tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+    tokens.expect_symbol("(")?;
+    let query = parse_query(tokens)?;
+    tokens.expect_symbol(")")?;
+    Ok(query)
+}
+
+fn parse_number(number: &str) -> Result<Value, ParsingError> {
+    if number.contains('.') {
+        number
+            .parse()
+            .map(Value::Float)
+            .map_err(|_| ParsingError::new(&format!("Invalid number {}", number)))
+    } else {
+        number
+            .parse()
+            .map(Value::Int)
+            .map_err(|_| ParsingError::new(&format!("Invalid number {}", number)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::Field;
+
+    fn expr(text: &str) -> Expr {
+        parse_expression(&mut Tokens::new(text).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn follows_three_valued_logic() {
+        let (t, f, null) = (Value::Bool(true), Value::Bool(false), Value::Null);
+        // a, b, a AND b, a OR b
+        for (a, b, and, or) in [
+            (&t, &t, &t, &t),
+            (&t, &f, &f, &t),
+            (&t, &null, &null, &t),
+            (&f, &f, &f, &f),
+            (&f, &null, &f, &null),
+            (&null, &null, &null, &null),
+        ] {
+            for (a, b) in [(a, b), (b, a)] {
+                assert_eq!(&evaluate_binary(BinaryOperator::And, a.clone(), b.clone()).unwrap(), and, "{} AND {}", a, b);
+                assert_eq!(&evaluate_binary(BinaryOperator::Or, a.clone(), b.clone()).unwrap(), or, "{} OR {}", a, b);
+            }
+        }
+        assert!(evaluate_binary(BinaryOperator::And, f.clone(), Value::Int(1)).is_err());
+
+        // comparisons and arithmetic with an unknown operand are unknown,
+        // DISTINCT FROM treats NULLs as equal to each other
+        let one = Value::Int(1);
+        for op in [BinaryOperator::Eq, BinaryOperator::NotEq, BinaryOperator::Lt, BinaryOperator::Plus] {
+            assert_eq!(evaluate_binary(op, one.clone(), null.clone()).unwrap(), null, "{}", op);
+            assert_eq!(evaluate_binary(op, null.clone(), null.clone()).unwrap(), null, "{}", op);
+        }
+        for (a, b, distinct) in [(&one, &one, false), (&one, &Value::Float(1.0), false), (&one, &null, true), (&null, &null, false)] {
+            let is_distinct = evaluate_binary(BinaryOperator::IsDistinctFrom, a.clone(), b.clone()).unwrap();
+            let is_not_distinct = evaluate_binary(BinaryOperator::IsNotDistinctFrom, a.clone(), b.clone()).unwrap();
+            assert_eq!((is_distinct, is_not_distinct), (Value::Bool(distinct), Value::Bool(!distinct)), "{} and {}", a, b);
+        }
+        assert!(evaluate_binary(BinaryOperator::IsDistinctFrom, one, Value::String("1".into())).is_err());
+    }
+
+    #[test]
+    fn rejects_rows_whose_condition_is_unknown() {
+        let schema = Schema::new(vec![Field::new(None, "x", DataType::Int64)]);
+        let row = [Value::Null];
+        let evaluate = |text: &str| expr(text).evaluate(&schema, &row).unwrap();
+        assert_eq!(evaluate("NOT x > 1"), Value::Null);
+        assert_eq!(evaluate("x > 1 OR x IS NULL"), Value::Bool(true));
+        assert_eq!(evaluate("x IS NOT DISTINCT FROM NULL"), Value::Bool(true));
+        for condition in ["x > 1", "NOT x > 1", "x = x", "x > 1 AND TRUE"] {
+            assert!(!expr(condition).is_satisfied(&schema, &row).unwrap(), "{}", condition);
+        }
+
+        // DISTINCT FROM binds like a comparison
+        assert_eq!(
+            expr("x IS DISTINCT FROM 1 + 1 AND TRUE"),
+            Expr::binary(
+                Expr::binary(expr("x"), BinaryOperator::IsDistinctFrom, expr("1 + 1")),
+                BinaryOperator::And,
+                expr("TRUE")
+            )
+        );
+        assert_eq!(expr("x IS NOT DISTINCT FROM NULL").to_string(), "x IS NOT DISTINCT FROM NULL");
+    }
+}
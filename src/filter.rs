@@ -1,12 +1,17 @@
-#[derive(Debug)]
+use crate::expression::Expr;
+use crate::logical_plan::LogicalPlan;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct LogicalFilterNode {
-    pub condition: String,
+    pub input: Box<LogicalPlan>,
+    pub condition: Expr,
 }
 
 impl LogicalFilterNode {
-    pub fn new(condition: &str) -> Self {
+    pub fn new(input: LogicalPlan, condition: Expr) -> Self {
         Self {
-            condition: condition.to_string(),
+            input: Box::new(input),
+            condition,
         }
     }
 }
@@ -0,0 +1,729 @@
+use crate::decimal::RoundingMode;
+use crate::errors::ExecutionError;
+use crate::json::{parse_path, Json};
+use crate::temporal::{self, wall_clock, MICROS_PER_DAY};
+use crate::timezone::TimeZone;
+use crate::value::{DataType, Value};
+use regex::Regex;
+use std::cell::RefCell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What a parameter of a scalar function accepts.
+#[derive(Debug, Clone, Copy)]
+enum Param {
+    Any,
+    Bool,
+    Int,
+    Numeric,
+    String,
+    /// DATE, TIME, DATETIME or TIMESTAMP
+    Temporal,
+    /// DATE, DATETIME or TIMESTAMP
+    Dated,
+    /// DATE, DATETIME or a string spelling a DATETIME
+    Civil,
+    Array,
+    /// JSON or a STRING of JSON text
+    Json,
+}
+
+impl Param {
+    fn accepts(self, data_type: &DataType) -> bool {
+        match self {
+            Param::Any => true,
+            Param::Bool => *data_type == DataType::Bool,
+            Param::Int => *data_type == DataType::Int64,
+            Param::Numeric => matches!(data_type, DataType::Int64 | DataType::Float64 | DataType::Decimal(..)),
+            Param::String => *data_type == DataType::String,
+            Param::Temporal => data_type.is_temporal(),
+            Param::Dated => matches!(data_type, DataType::Date | DataType::DateTime | DataType::Timestamp),
+            Param::Civil => matches!(data_type, DataType::Date | DataType::DateTime | DataType::String),
+            Param::Array => matches!(data_type, DataType::Array(_)),
+            Param::Json => matches!(data_type, DataType::Json | DataType::String),
+        }
+    }
+
+    fn describe(self) -> &'static str {
+        match self {
+            Param::Any => "any value",
+            Param::Bool => "BOOL",
+            Param::Int => "INT64",
+            Param::Numeric => "INT64, FLOAT64 or NUMERIC",
+            Param::String => "STRING",
+            Param::Temporal => "DATE, TIME, DATETIME or TIMESTAMP",
+            Param::Dated => "DATE, DATETIME or TIMESTAMP",
+            Param::Civil => "DATE, DATETIME or STRING",
+            Param::Array => "ARRAY",
+            Param::Json => "JSON or STRING",
+        }
+    }
+}
+
+/// How the result type follows from the argument types.
+#[derive(Debug, Clone, Copy)]
+enum Returns {
+    Bool,
+    Int,
+    Float,
+    String,
+    StringArray,
+    Date,
+    DateTime,
+    Timestamp,
+    /// The type of the first argument
+    First,
+    /// An array of the type of the first argument
+    FirstArray,
+    /// The type of a NUMERIC first argument, FLOAT64 for other numbers
+    Rounded,
+    /// The type the arguments from this position on all convert to
+    Common(usize),
+    /// An array of the type the arguments all convert to
+    Array,
+    /// The element type of the array that is the first argument
+    Element,
+}
+
+/// A built-in scalar function: its signature, checked while planning, and
+/// its implementation.
+pub struct ScalarFunction {
+    pub name: &'static str,
+    params: &'static [Param],
+    /// Number of trailing parameters that may be left out
+    optional: usize,
+    /// Whether the last parameter may be repeated
+    variadic: bool,
+    returns: Returns,
+    /// Whether a NULL argument makes the result NULL; other functions are
+    /// called with the NULLs
+    strict: bool,
+    evaluate: fn(Vec<Value>) -> Result<Value, ExecutionError>,
+}
+
+const fn function(
+    name: &'static str,
+    params: &'static [Param],
+    returns: Returns,
+    evaluate: fn(Vec<Value>) -> Result<Value, ExecutionError>,
+) -> ScalarFunction {
+    ScalarFunction {
+        name,
+        params,
+        optional: 0,
+        variadic: false,
+        returns,
+        strict: true,
+        evaluate,
+    }
+}
+
+impl ScalarFunction {
+    const fn optional(mut self, count: usize) -> Self {
+        self.optional = count;
+        self
+    }
+
+    const fn variadic(mut self) -> Self {
+        self.variadic = true;
+        self
+    }
+
+    const fn lenient(mut self) -> Self {
+        self.strict = false;
+        self
+    }
+}
+
+static FUNCTIONS: [ScalarFunction; 44] = [
+    // strings
+    function("CONCAT", &[Param::String], Returns::String, concat).variadic(),
+    function("LOWER", &[Param::String], Returns::String, |args| map_string(args, |s| s.to_lowercase())),
+    function("UPPER", &[Param::String], Returns::String, |args| map_string(args, |s| s.to_uppercase())),
+    function("SUBSTR", &[Param::String, Param::Int, Param::Int], Returns::String, substr).optional(1),
+    function("TRIM", &[Param::String, Param::String], Returns::String, trim).optional(1),
+    function("REPLACE", &[Param::String, Param::String, Param::String], Returns::String, replace),
+    function("REGEXP_CONTAINS", &[Param::String, Param::String], Returns::Bool, regexp_contains),
+    function("SPLIT", &[Param::String, Param::String], Returns::StringArray, split).optional(1),
+    function("LENGTH", &[Param::String], Returns::Int, |args| {
+        Ok(Value::Int(string(&args[0]).chars().count() as i64))
+    }),
+    // math
+    function("ABS", &[Param::Numeric], Returns::First, abs),
+    function("ROUND", &[Param::Numeric, Param::Int, Param::String], Returns::Rounded, round).optional(2),
+    function("FLOOR", &[Param::Numeric], Returns::Rounded, |args| round_by(&args[0], 0, RoundingMode::Floor)),
+    function("CEIL", &[Param::Numeric], Returns::Rounded, |args| round_by(&args[0], 0, RoundingMode::Ceiling)),
+    function("MOD", &[Param::Int, Param::Int], Returns::Int, modulo),
+    function("POW", &[Param::Numeric, Param::Numeric], Returns::Float, |args| {
+        Ok(Value::Float(float(&args[0]).powf(float(&args[1]))))
+    }),
+    function("SQRT", &[Param::Numeric], Returns::Float, sqrt),
+    function("LN", &[Param::Numeric], Returns::Float, ln),
+    // conditionals
+    function("IF", &[Param::Bool, Param::Any, Param::Any], Returns::Common(1), |mut args| {
+        let otherwise = args.pop().unwrap();
+        let then = args.pop().unwrap();
+        Ok(if args[0] == Value::Bool(true) { then } else { otherwise })
+    })
+    .lenient(),
+    function("COALESCE", &[Param::Any], Returns::Common(0), |args| {
+        Ok(args.into_iter().find(|arg| !arg.is_null()).unwrap_or(Value::Null))
+    })
+    .variadic()
+    .lenient(),
+    function("IFNULL", &[Param::Any, Param::Any], Returns::Common(0), |mut args| {
+        let otherwise = args.pop().unwrap();
+        let value = args.pop().unwrap();
+        Ok(if value.is_null() { otherwise } else { value })
+    })
+    .lenient(),
+    function("NULLIF", &[Param::Any, Param::Any], Returns::Common(0), |mut args| {
+        let other = args.pop().unwrap();
+        let value = args.pop().unwrap();
+        let equal = value.compare(&other) == Some(std::cmp::Ordering::Equal);
+        Ok(if equal { Value::Null } else { value })
+    })
+    .lenient(),
+    // dates and times; the optional last argument of many is a time zone,
+    // UTC by default
+    function("CURRENT_TIMESTAMP", &[], Returns::Timestamp, |_| Ok(Value::Timestamp(now()))),
+    function("CURRENT_DATE", &[Param::String], Returns::Date, |args| {
+        let local = zone(args.first())?.to_local(now());
+        Ok(Value::Date(local.div_euclid(MICROS_PER_DAY) as i32))
+    })
+    .optional(1),
+    function("CURRENT_DATETIME", &[Param::String], Returns::DateTime, |args| {
+        Ok(Value::DateTime(zone(args.first())?.to_local(now())))
+    })
+    .optional(1),
+    // EXTRACT(part FROM value [AT TIME ZONE zone])
+    function("EXTRACT", &[Param::String, Param::Temporal, Param::String], Returns::Int, |args| {
+        temporal::extract(string(&args[0]), &args[1], &zone(args.get(2))?).map(Value::Int)
+    })
+    .optional(1),
+    function("DATE_TRUNC", &[Param::Temporal, Param::String, Param::String], Returns::First, |args| {
+        temporal::trunc(&args[0], string(&args[1]), &zone(args.get(2))?)
+    })
+    .optional(1),
+    // DATE_ADD(value, INTERVAL count part)
+    function("DATE_ADD", &[Param::Temporal, Param::Int, Param::String], Returns::First, |args| {
+        temporal::add("DATE_ADD", &args[0], int(&args[1]), string(&args[2]))
+    }),
+    function("DATE_SUB", &[Param::Temporal, Param::Int, Param::String], Returns::First, |args| {
+        let count = int(&args[1]).checked_neg().ok_or_else(|| ExecutionError::new("DATE_SUB overflows"))?;
+        temporal::add("DATE_SUB", &args[0], count, string(&args[2]))
+    }),
+    function("DATE_DIFF", &[Param::Temporal, Param::Temporal, Param::String], Returns::Int, |args| {
+        temporal::diff(&args[0], &args[1], string(&args[2])).map(Value::Int)
+    }),
+    function("FORMAT_TIMESTAMP", &[Param::String, Param::Temporal, Param::String], Returns::String, |args| {
+        temporal::format(string(&args[0]), &args[1], &zone(args.get(2))?).map(Value::String)
+    })
+    .optional(1),
+    function("PARSE_TIMESTAMP", &[Param::String, Param::String, Param::String], Returns::Timestamp, |args| {
+        temporal::parse(string(&args[0]), string(&args[1]), &zone(args.get(2))?).map(Value::Timestamp)
+    })
+    .optional(1),
+    // conversions between instants and the wall clock of a zone
+    function("DATE", &[Param::Dated, Param::String], Returns::Date, |args| {
+        let local = wall_clock(&args[0], &zone(args.get(1))?);
+        Ok(Value::Date(local.div_euclid(MICROS_PER_DAY) as i32))
+    })
+    .optional(1),
+    function("DATETIME", &[Param::Dated, Param::String], Returns::DateTime, |args| {
+        Ok(Value::DateTime(wall_clock(&args[0], &zone(args.get(1))?)))
+    })
+    .optional(1),
+    function("TIMESTAMP", &[Param::Civil, Param::String], Returns::Timestamp, timestamp).optional(1),
+    // arrays; `[a, b]` and `ARRAY[a, b]` call ARRAY, and `array[OFFSET(i)]`
+    // and the like call the function of the subscript
+    function("ARRAY", &[Param::Any], Returns::Array, |args| unify(args).map(Value::Array))
+        .optional(1)
+        .variadic()
+        .lenient(),
+    function("ARRAY_LENGTH", &[Param::Array], Returns::Int, |args| {
+        Ok(Value::Int(items(&args[0]).len() as i64))
+    }),
+    function("ARRAY_CONCAT", &[Param::Array], Returns::Common(0), |args| {
+        unify(args.iter().flat_map(|arg| items(arg).iter().cloned()).collect()).map(Value::Array)
+    })
+    .variadic(),
+    function("OFFSET", &[Param::Array, Param::Int], Returns::Element, |args| subscript("OFFSET", &args, 0, false)),
+    function("ORDINAL", &[Param::Array, Param::Int], Returns::Element, |args| subscript("ORDINAL", &args, 1, false)),
+    function("SAFE_OFFSET", &[Param::Array, Param::Int], Returns::Element, |args| {
+        subscript("SAFE_OFFSET", &args, 0, true)
+    }),
+    function("SAFE_ORDINAL", &[Param::Array, Param::Int], Returns::Element, |args| {
+        subscript("SAFE_ORDINAL", &args, 1, true)
+    }),
+    // JSON, given as JSON or as text, and a JSONPath into it, `$` by default
+    function("JSON_VALUE", &[Param::Json, Param::String], Returns::String, |args| {
+        Ok(json_at(&args)?.and_then(|json| json.scalar_text()).map_or(Value::Null, Value::String))
+    })
+    .optional(1),
+    function("JSON_QUERY", &[Param::Json, Param::String], Returns::First, |args| {
+        Ok(json_at(&args)?.map_or(Value::Null, |json| like(&args[0], json)))
+    }),
+    function("JSON_EXTRACT_ARRAY", &[Param::Json, Param::String], Returns::FirstArray, |args| {
+        Ok(match json_at(&args)? {
+            Some(Json::Array(items)) => Value::Array(items.into_iter().map(|item| like(&args[0], item)).collect()),
+            _ => Value::Null,
+        })
+    })
+    .optional(1),
+];
+
+/// The built-in function with the given upper case name.
+pub fn lookup(name: &str) -> Result<&'static ScalarFunction, ExecutionError> {
+    FUNCTIONS
+        .iter()
+        .find(|function| function.name == name)
+        .ok_or_else(|| ExecutionError::new(&format!("Unknown function {}", name)))
+}
+
+pub fn function_names() -> impl Iterator<Item = &'static str> {
+    FUNCTIONS.iter().map(|function| function.name)
+}
+
+impl ScalarFunction {
+    /// Checks the number and types of the arguments, `None` standing for an
+    /// untyped NULL, and returns the type of the result.
+    pub fn return_type(&self, args: &[Option<DataType>]) -> Result<DataType, ExecutionError> {
+        let (max, min) = (self.params.len(), self.params.len() - self.optional);
+        if args.len() < min || (args.len() > max && !self.variadic) {
+            let expected = match (min, max) {
+                _ if self.variadic => format!("at least {}", min),
+                (min, max) if min == max => min.to_string(),
+                (min, max) => format!("{} to {}", min, max),
+            };
+            return Err(ExecutionError::new(&format!(
+                "{} takes {} arguments, got {}",
+                self.name,
+                expected,
+                args.len()
+            )));
+        }
+        for (i, arg) in args.iter().enumerate() {
+            let param = self.params[i.min(max - 1)];
+            if let Some(data_type) = arg.as_ref().filter(|data_type| !param.accepts(data_type)) {
+                return Err(ExecutionError::new(&format!(
+                    "Argument {} of {} must be {}, got {}",
+                    i + 1,
+                    self.name,
+                    param.describe(),
+                    data_type
+                )));
+            }
+        }
+        Ok(match self.returns {
+            Returns::Bool => DataType::Bool,
+            Returns::Int => DataType::Int64,
+            Returns::Float => DataType::Float64,
+            Returns::String => DataType::String,
+            Returns::StringArray => DataType::Array(Box::new(DataType::String)),
+            Returns::Date => DataType::Date,
+            Returns::DateTime => DataType::DateTime,
+            Returns::Timestamp => DataType::Timestamp,
+            Returns::First => args[0].clone().unwrap_or(DataType::Int64),
+            Returns::FirstArray => DataType::Array(Box::new(args[0].clone().unwrap_or(DataType::Int64))),
+            Returns::Rounded => match &args[0] {
+                Some(data_type @ DataType::Decimal(..)) => data_type.clone(),
+                _ => DataType::Float64,
+            },
+            // like an untyped NULL literal
+            Returns::Common(start) => self.common_type(&args[start..])?.unwrap_or(DataType::Int64),
+            Returns::Array => match self.common_type(args)?.unwrap_or(DataType::Int64) {
+                DataType::Array(_) => {
+                    return Err(ExecutionError::new("An ARRAY cannot hold ARRAYs, only STRUCTs with ARRAY fields"));
+                }
+                element => DataType::Array(Box::new(element)),
+            },
+            Returns::Element => match &args[0] {
+                Some(DataType::Array(element)) => (**element).clone(),
+                _ => DataType::Int64,
+            },
+        })
+    }
+
+    /// The position from which the arguments all convert to one type, for
+    /// functions returning a value of that type or an array of them.
+    pub fn common_from(&self) -> Option<usize> {
+        match self.returns {
+            Returns::Common(start) => Some(start),
+            Returns::Array => Some(0),
+            _ => None,
+        }
+    }
+
+    /// The type the typed arguments all convert to, if any has a type.
+    pub fn common_type(&self, args: &[Option<DataType>]) -> Result<Option<DataType>, ExecutionError> {
+        let mut common: Option<DataType> = None;
+        for data_type in args.iter().flatten() {
+            common = Some(match common {
+                None => data_type.clone(),
+                Some(common) => common.common_supertype(data_type).ok_or_else(|| {
+                    ExecutionError::new(&format!(
+                        "Arguments of {} have incompatible types {} and {}",
+                        self.name, common, data_type
+                    ))
+                })?,
+            });
+        }
+        Ok(common)
+    }
+
+    pub fn call(&self, args: Vec<Value>) -> Result<Value, ExecutionError> {
+        if self.strict && args.iter().any(Value::is_null) {
+            return Ok(Value::Null);
+        }
+        (self.evaluate)(args)
+    }
+}
+
+// Implementations see arguments of the types the signature allows, and no
+// NULLs unless the function is lenient.
+
+fn string(value: &Value) -> &str {
+    match value {
+        Value::String(s) => s,
+        _ => "",
+    }
+}
+
+fn float(value: &Value) -> f64 {
+    match value {
+        Value::Int(i) => *i as f64,
+        Value::Float(x) => *x,
+        Value::Decimal(d) => d.to_f64(),
+        _ => f64::NAN,
+    }
+}
+
+fn int(value: &Value) -> i64 {
+    match value {
+        Value::Int(i) => *i,
+        _ => 0,
+    }
+}
+
+fn items(value: &Value) -> &[Value] {
+    match value {
+        Value::Array(items) => items,
+        _ => &[],
+    }
+}
+
+fn now() -> i64 {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    since_epoch.as_micros() as i64
+}
+
+/// The time zone named by an optional argument, UTC without one.
+fn zone(arg: Option<&Value>) -> Result<TimeZone, ExecutionError> {
+    match arg {
+        Some(name) => TimeZone::parse(string(name)).map_err(|e| ExecutionError::new(&e)),
+        None => Ok(TimeZone::utc()),
+    }
+}
+
+fn map_string(args: Vec<Value>, f: impl Fn(&str) -> String) -> Result<Value, ExecutionError> {
+    Ok(Value::String(f(string(&args[0]))))
+}
+
+fn concat(args: Vec<Value>) -> Result<Value, ExecutionError> {
+    Ok(Value::String(args.iter().map(string).collect()))
+}
+
+/// `SUBSTR(s, position[, length])`: positions count characters from 1, or
+/// from the end when negative.
+fn substr(args: Vec<Value>) -> Result<Value, ExecutionError> {
+    let chars: Vec<char> = string(&args[0]).chars().collect();
+    let count = chars.len() as i64;
+    let start = match int(&args[1]) {
+        position if position > 0 => position - 1,
+        0 => 0,
+        position => (count + position).max(0),
+    }
+    .min(count);
+    let length = match args.get(2) {
+        Some(length) if int(length) < 0 => {
+            return Err(ExecutionError::new("SUBSTR cannot take a negative length"));
+        }
+        Some(length) => int(length),
+        None => count,
+    };
+    let end = start.saturating_add(length).min(count);
+    Ok(Value::String(chars[start as usize..end as usize].iter().collect()))
+}
+
+/// `TRIM(s[, characters])` removes whitespace, or the given characters, from
+/// both ends.
+fn trim(args: Vec<Value>) -> Result<Value, ExecutionError> {
+    let s = string(&args[0]);
+    Ok(Value::String(match args.get(1) {
+        Some(characters) => {
+            let characters: Vec<char> = string(characters).chars().collect();
+            s.trim_matches(|c| characters.contains(&c)).to_string()
+        }
+        None => s.trim().to_string(),
+    }))
+}
+
+fn replace(args: Vec<Value>) -> Result<Value, ExecutionError> {
+    let (s, from, to) = (string(&args[0]), string(&args[1]), string(&args[2]));
+    if from.is_empty() {
+        return Ok(Value::String(s.to_string()));
+    }
+    Ok(Value::String(s.replace(from, to)))
+}
+
+thread_local! {
+    /// The pattern compiled last, which is usually the pattern of every row
+    static LAST_REGEX: RefCell<Option<Regex>> = const { RefCell::new(None) };
+}
+
+fn regexp_contains(args: Vec<Value>) -> Result<Value, ExecutionError> {
+    let (s, pattern) = (string(&args[0]), string(&args[1]));
+    LAST_REGEX.with(|last| {
+        let mut last = last.borrow_mut();
+        if last.as_ref().is_none_or(|regex| regex.as_str() != pattern) {
+            let regex = Regex::new(pattern)
+                .map_err(|e| ExecutionError::new(&format!("Invalid regular expression {}: {}", pattern, e)))?;
+            *last = Some(regex);
+        }
+        Ok(Value::Bool(last.as_ref().unwrap().is_match(s)))
+    })
+}
+
+/// `SPLIT(s[, delimiter])` with `,` as the default delimiter; an empty
+/// delimiter splits into characters.
+fn split(args: Vec<Value>) -> Result<Value, ExecutionError> {
+    let s = string(&args[0]);
+    let parts: Vec<Value> = match args.get(1).map(string).unwrap_or(",") {
+        "" => s.chars().map(|c| Value::String(c.to_string())).collect(),
+        delimiter => s.split(delimiter).map(|part| Value::String(part.to_string())).collect(),
+    };
+    Ok(Value::Array(parts))
+}
+
+fn abs(args: Vec<Value>) -> Result<Value, ExecutionError> {
+    match &args[0] {
+        Value::Int(i) => i
+            .checked_abs()
+            .map(Value::Int)
+            .ok_or_else(|| ExecutionError::new("Integer overflow")),
+        Value::Decimal(d) => Ok(Value::Decimal(d.abs())),
+        value => Ok(Value::Float(float(value).abs())),
+    }
+}
+
+/// `ROUND(x[, digits[, mode]])`, rounding halves away from zero unless the
+/// mode is another of ROUND_HALF_EVEN, ROUND_TOWARD_ZERO, ROUND_FLOOR and
+/// ROUND_CEILING.
+fn round(args: Vec<Value>) -> Result<Value, ExecutionError> {
+    let digits = args.get(1).map_or(0, int);
+    let mode = match args.get(2).map(string) {
+        Some(name) => RoundingMode::from_name(name)
+            .ok_or_else(|| ExecutionError::new(&format!("Unknown rounding mode {}", name)))?,
+        None => RoundingMode::HalfAwayFromZero,
+    };
+    round_by(&args[0], digits, mode)
+}
+
+/// Rounds to `digits` fractional digits; NUMERIC values keep their scale.
+fn round_by(value: &Value, digits: i64, mode: RoundingMode) -> Result<Value, ExecutionError> {
+    if let Value::Decimal(d) = value {
+        return d
+            .round(digits, mode)
+            .map(Value::Decimal)
+            .ok_or_else(|| ExecutionError::new("NUMERIC overflow"));
+    }
+    let scale = 10f64.powi(digits.clamp(-30, 30) as i32);
+    let scaled = float(value) * scale;
+    let rounded = match mode {
+        RoundingMode::HalfAwayFromZero => scaled.round(),
+        RoundingMode::HalfEven => scaled.round_ties_even(),
+        RoundingMode::TowardZero => scaled.trunc(),
+        RoundingMode::Floor => scaled.floor(),
+        RoundingMode::Ceiling => scaled.ceil(),
+    };
+    Ok(Value::Float(rounded / scale))
+}
+
+/// `MOD(a, b)`, with the sign of `a`.
+fn modulo(args: Vec<Value>) -> Result<Value, ExecutionError> {
+    let (a, b) = (int(&args[0]), int(&args[1]));
+    if b == 0 {
+        return Err(ExecutionError::new("Division by zero"));
+    }
+    Ok(Value::Int(a.checked_rem(b).unwrap_or(0)))
+}
+
+fn sqrt(args: Vec<Value>) -> Result<Value, ExecutionError> {
+    let x = float(&args[0]);
+    if x < 0.0 {
+        return Err(ExecutionError::new(&format!("SQRT of the negative number {}", x)));
+    }
+    Ok(Value::Float(x.sqrt()))
+}
+
+fn ln(args: Vec<Value>) -> Result<Value, ExecutionError> {
+    let x = float(&args[0]);
+    if x <= 0.0 {
+        return Err(ExecutionError::new(&format!("LN of the non-positive number {}", x)));
+    }
+    Ok(Value::Float(x.ln()))
+}
+
+/// Converts the elements of a new array to the type they all convert to,
+/// such as INT64 elements among FLOAT64 ones.
+fn unify(items: Vec<Value>) -> Result<Vec<Value>, ExecutionError> {
+    let Some(common) = items
+        .iter()
+        .filter_map(Value::data_type)
+        .reduce(|common, data_type| common.common_supertype(&data_type).unwrap_or(common))
+    else {
+        return Ok(items);
+    };
+    items
+        .into_iter()
+        .map(|item| match item.data_type() {
+            Some(data_type) if data_type != common => item.cast(&common).map_err(|e| ExecutionError::new(&e)),
+            _ => Ok(item),
+        })
+        .collect()
+}
+
+/// The element at a position counted from `base`; out of range positions
+/// fail unless the subscript is SAFE, which gives NULL.
+fn subscript(name: &str, args: &[Value], base: i64, safe: bool) -> Result<Value, ExecutionError> {
+    let (items, position) = (items(&args[0]), int(&args[1]));
+    match position.checked_sub(base).and_then(|index| usize::try_from(index).ok()) {
+        Some(index) if index < items.len() => Ok(items[index].clone()),
+        _ if safe => Ok(Value::Null),
+        _ => Err(ExecutionError::new(&format!(
+            "{}({}) is out of range for an array of {} elements",
+            name,
+            position,
+            items.len()
+        ))),
+    }
+}
+
+/// What the JSONPath in the second argument, `$` if there is none, finds in
+/// the JSON of the first; `None` where it finds nothing.
+fn json_at(args: &[Value]) -> Result<Option<Json>, ExecutionError> {
+    let path = parse_path(args.get(1).map_or("$", string)).map_err(|e| ExecutionError::new(&e))?;
+    let parsed;
+    let json = match &args[0] {
+        Value::Json(json) => json,
+        value => {
+            parsed = Json::parse(string(value)).map_err(|e| ExecutionError::new(&e))?;
+            &parsed
+        }
+    };
+    Ok(json.get(&path).cloned())
+}
+
+/// JSON found in an argument, as JSON or as text like the argument.
+fn like(arg: &Value, json: Json) -> Value {
+    match arg {
+        Value::Json(_) => Value::Json(json),
+        _ => Value::String(json.to_string()),
+    }
+}
+
+/// `TIMESTAMP(value[, zone])`: the instant of a DATE, DATETIME or string on
+/// the wall clock of the zone. A string may name its own zone.
+fn timestamp(args: Vec<Value>) -> Result<Value, ExecutionError> {
+    let zone = zone(args.get(1))?;
+    match &args[0] {
+        Value::String(s) => temporal::parse_timestamp(s, &zone)
+            .map(Value::Timestamp)
+            .ok_or_else(|| ExecutionError::new(&format!("Invalid TIMESTAMP '{}'", s))),
+        value => Ok(Value::Timestamp(zone.to_utc(wall_clock(value, &zone)))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, args: Vec<Value>) -> Result<Value, ExecutionError> {
+        lookup(name).unwrap().call(args)
+    }
+
+    fn text(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[test]
+    fn checks_arguments_against_the_signature() {
+        let return_type = |name: &str, args: &[Option<DataType>]| lookup(name).unwrap().return_type(args);
+        let error = |name: &str, args: &[Option<DataType>]| return_type(name, args).unwrap_err().message;
+        let (int, float, string) = (Some(DataType::Int64), Some(DataType::Float64), Some(DataType::String));
+
+        assert_eq!(return_type("SUBSTR", &[string.clone(), int.clone()]).unwrap(), DataType::String);
+        assert_eq!(error("SUBSTR", &[]), "SUBSTR takes 2 to 3 arguments, got 0");
+        assert_eq!(error("SUBSTR", &[int.clone(), None]), "Argument 1 of SUBSTR must be STRING, got INT64");
+        assert_eq!(error("CONCAT", &[]), "CONCAT takes at least 1 arguments, got 0");
+        // an untyped NULL fits any parameter
+        assert_eq!(return_type("CONCAT", &[None, string.clone(), None]).unwrap(), DataType::String);
+        assert_eq!(return_type("COALESCE", &[int.clone(), None, float]).unwrap(), DataType::Float64);
+        assert_eq!(return_type("IF", &[None, None, None]).unwrap(), DataType::Int64);
+        assert_eq!(
+            error("COALESCE", &[int.clone(), string]),
+            "Arguments of COALESCE have incompatible types INT64 and STRING"
+        );
+        let numeric = Some(DataType::Decimal(10, 2));
+        assert_eq!(return_type("ROUND", &[numeric.clone(), int.clone()]).unwrap(), DataType::Decimal(10, 2));
+        assert_eq!(return_type("ROUND", &[int]).unwrap(), DataType::Float64);
+        assert!(lookup("SUBSTRING").is_err_and(|e| e.message == "Unknown function SUBSTRING"));
+    }
+
+    #[test]
+    fn propagates_nulls_and_handles_edge_cases() {
+        // strict functions give NULL for a NULL argument, lenient ones see it
+        assert_eq!(call("LENGTH", vec![Value::Null]).unwrap(), Value::Null);
+        assert_eq!(call("SUBSTR", vec![text("hello"), Value::Null]).unwrap(), Value::Null);
+        assert_eq!(call("COALESCE", vec![Value::Null, Value::Int(2), Value::Int(3)]).unwrap(), Value::Int(2));
+        assert_eq!(call("IFNULL", vec![Value::Null, Value::Int(1)]).unwrap(), Value::Int(1));
+        assert_eq!(call("NULLIF", vec![Value::Int(1), Value::Int(1)]).unwrap(), Value::Null);
+        assert_eq!(call("IF", vec![Value::Null, Value::Int(1), Value::Int(2)]).unwrap(), Value::Int(2));
+
+        for (args, expected) in [
+            (vec![text("hello"), Value::Int(0), Value::Int(2)], "he"),
+            (vec![text("hello"), Value::Int(-3)], "llo"),
+            (vec![text("hello"), Value::Int(-10), Value::Int(2)], "he"),
+            (vec![text("hello"), Value::Int(4), Value::Int(100)], "lo"),
+            (vec![text("hello"), Value::Int(9)], ""),
+            (vec![text("héllo"), Value::Int(2), Value::Int(1)], "é"),
+        ] {
+            assert_eq!(call("SUBSTR", args.clone()).unwrap(), text(expected), "{:?}", args);
+        }
+        let negative = call("SUBSTR", vec![text("hello"), Value::Int(1), Value::Int(-1)]);
+        assert_eq!(negative.unwrap_err().message, "SUBSTR cannot take a negative length");
+
+        let round = |x: f64, mode: &str| call("ROUND", vec![Value::Float(x), Value::Int(0), text(mode)]).unwrap();
+        assert_eq!(round(2.5, "ROUND_HALF_AWAY_FROM_ZERO"), Value::Float(3.0));
+        assert_eq!(round(-2.5, "ROUND_HALF_AWAY_FROM_ZERO"), Value::Float(-3.0));
+        assert_eq!(round(2.5, "round_half_even"), Value::Float(2.0));
+        assert_eq!(round(-1.5, "ROUND_FLOOR"), Value::Float(-2.0));
+        assert_eq!(round(-1.5, "ROUND_TOWARD_ZERO"), Value::Float(-1.0));
+        let unknown = call("ROUND", vec![Value::Float(1.0), Value::Int(0), text("ROUND_BANKERS")]);
+        assert_eq!(unknown.unwrap_err().message, "Unknown rounding mode ROUND_BANKERS");
+        assert_eq!(call("ROUND", vec![Value::Float(1234.5), Value::Int(-2)]).unwrap(), Value::Float(1200.0));
+
+        assert_eq!(call("MOD", vec![Value::Int(-7), Value::Int(3)]).unwrap(), Value::Int(-1));
+        assert_eq!(call("MOD", vec![Value::Int(i64::MIN), Value::Int(-1)]).unwrap(), Value::Int(0));
+        assert_eq!(call("MOD", vec![Value::Int(1), Value::Int(0)]).unwrap_err().message, "Division by zero");
+        assert_eq!(call("ABS", vec![Value::Int(i64::MIN)]).unwrap_err().message, "Integer overflow");
+        assert!(call("SQRT", vec![Value::Int(-1)]).is_err());
+        assert!(call("LN", vec![Value::Float(0.0)]).is_err());
+
+        assert_eq!(call("REPLACE", vec![text("aaa"), text(""), text("b")]).unwrap(), text("aaa"));
+        assert_eq!(call("SPLIT", vec![text("ab"), text("")]).unwrap(), Value::Array(vec![text("a"), text("b")]));
+        assert_eq!(call("SPLIT", vec![text("a,,b")]).unwrap(), Value::Array(vec![text("a"), text(""), text("b")]));
+        let invalid = call("REGEXP_CONTAINS", vec![text("a"), text("(")]).unwrap_err();
+        assert!(invalid.message.starts_with("Invalid regular expression ("), "{}", invalid.message);
+    }
+}
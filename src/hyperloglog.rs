@@ -0,0 +1,82 @@
+use crate::value::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Bits of the hash used to pick a register; 2^12 registers give ~1.6% error.
+const PRECISION: u32 = 12;
+const REGISTERS: usize = 1 << PRECISION;
+
+/// Distinct-value sketch. Sketches of disjoint row sets can be merged, which
+/// lets inserts refresh the estimate without rescanning the table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; REGISTERS],
+        }
+    }
+}
+
+impl HyperLogLog {
+    pub fn add(&mut self, value: &Value) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let register = (hash >> (64 - PRECISION)) as usize;
+        // the sentinel bit bounds the rank when the remaining bits are all zero
+        let rest = (hash << PRECISION) | (1 << (PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[register] = self.registers[register].max(rank);
+    }
+
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        for (register, other) in self.registers.iter_mut().zip(&other.registers) {
+            *register = (*register).max(*other);
+        }
+    }
+
+    pub fn estimate(&self) -> f64 {
+        let m = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            // linear counting is more accurate for small cardinalities
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HyperLogLog;
+    use crate::value::Value;
+
+    #[test]
+    fn estimates_distinct_values_within_a_few_percent() {
+        let mut sketch = HyperLogLog::default();
+        for i in 0..50_000 {
+            sketch.add(&Value::Int(i % 20_000));
+        }
+        let estimate = sketch.estimate();
+        assert!((estimate - 20_000.0).abs() < 20_000.0 * 0.05, "estimate {}", estimate);
+
+        let mut other = HyperLogLog::default();
+        for i in 15_000..30_000 {
+            other.add(&Value::Int(i));
+        }
+        sketch.merge(&other);
+        let estimate = sketch.estimate();
+        assert!((estimate - 30_000.0).abs() < 30_000.0 * 0.05, "estimate {}", estimate);
+    }
+}
@@ -1,16 +1,112 @@
-#[derive(Debug)]
+use crate::errors::ExecutionError;
+use crate::expression::{BinaryOperator, Expr};
+use crate::logical_plan::LogicalPlan;
+use crate::schema::Schema;
+use crate::select::LogicalSelectListNode;
+use crate::value::Value;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinType {
+    Inner,
+    /// Keeps unmatched rows of the left input
+    Left,
+    /// Keeps unmatched rows of the right input
+    Right,
+    Full,
+    /// Left rows with at least one match, each returned once
+    Semi,
+    /// Left rows without any match
+    Anti,
+}
+
+impl JoinType {
+    /// Semi and anti joins return only the columns of the left input.
+    pub fn keeps_right_columns(&self) -> bool {
+        !matches!(self, JoinType::Semi | JoinType::Anti)
+    }
+
+    /// Whether left rows without a match are part of the result.
+    pub fn preserves_left(&self) -> bool {
+        matches!(self, JoinType::Left | JoinType::Full | JoinType::Anti)
+    }
+
+    /// Whether right rows without a match are part of the result.
+    pub fn preserves_right(&self) -> bool {
+        matches!(self, JoinType::Right | JoinType::Full)
+    }
+}
+
+impl fmt::Display for JoinType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            JoinType::Inner => "INNER",
+            JoinType::Left => "LEFT",
+            JoinType::Right => "RIGHT",
+            JoinType::Full => "FULL",
+            JoinType::Semi => "SEMI",
+            JoinType::Anti => "ANTI",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct LogicalJoinNode {
-    pub table1: String,
-    pub table2: String,
-    pub condition: String,
+    pub left: Box<LogicalPlan>,
+    pub right: Box<LogicalPlan>,
+    pub join_type: JoinType,
+    pub condition: Expr,
+    /// Columns of `USING (...)`, which the optimizer turns into an equality
+    /// condition and a projection that keeps one copy of them
+    pub using: Vec<String>,
 }
 
 impl LogicalJoinNode {
-    pub fn new(table1: &str, table2: &str, condition: &str) -> Self {
+    pub fn new(left: LogicalPlan, right: LogicalPlan, join_type: JoinType, condition: Expr) -> Self {
         Self {
-            table1: table1.to_string(),
-            table2: table2.to_string(),
-            condition: condition.to_string(),
+            left: Box::new(left),
+            right: Box::new(right),
+            join_type,
+            condition,
+            using: Vec::new(),
+        }
+    }
+
+    /// The condition and select list a USING join stands for: the USING
+    /// columns first, then the remaining left and right columns. A FULL join
+    /// takes each USING column from whichever side has a value.
+    pub fn expand_using(&self, left: &Schema, right: &Schema) -> Result<(Expr, Vec<LogicalSelectListNode>), ExecutionError> {
+        let column = |schema: &Schema, index: usize| Expr::Column {
+            qualifier: schema.fields[index].qualifier.clone(),
+            name: schema.fields[index].name.clone(),
+        };
+        let mut equalities = Vec::new();
+        let mut select_list = Vec::new();
+        let (mut left_used, mut right_used) = (Vec::new(), Vec::new());
+        for name in &self.using {
+            let l = left.resolve(None, name)?;
+            let r = right.resolve(None, name)?;
+            equalities.push(Expr::binary(column(left, l), BinaryOperator::Eq, column(right, r)));
+            select_list.push(match self.join_type {
+                JoinType::Full => {
+                    let args = vec![column(left, l), column(right, r)];
+                    LogicalSelectListNode::new(Expr::Function { name: "COALESCE".to_string(), args }, Some(name))
+                }
+                JoinType::Right => LogicalSelectListNode::new(column(right, r), None),
+                _ => LogicalSelectListNode::new(column(left, l), None),
+            });
+            left_used.push(l);
+            right_used.push(r);
+        }
+        for i in (0..left.fields.len()).filter(|i| !left_used.contains(i)) {
+            select_list.push(LogicalSelectListNode::new(column(left, i), None));
+        }
+        if self.join_type.keeps_right_columns() {
+            for i in (0..right.fields.len()).filter(|i| !right_used.contains(i)) {
+                select_list.push(LogicalSelectListNode::new(column(right, i), None));
+            }
         }
+        Ok((Expr::conjunction(equalities).unwrap_or(Expr::Literal(Value::Bool(true))), select_list))
     }
 }
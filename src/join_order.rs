@@ -0,0 +1,259 @@
+use crate::cost::join_cost;
+use crate::physical_plan::JoinAlgorithm;
+
+/// Above this many relations the exhaustive search is replaced by a greedy one.
+pub const DYNAMIC_PROGRAMMING_LIMIT: usize = 10;
+
+/// Relation sets are bit sets over a `u64`, so at most this many relations
+/// can be ordered; joins over more inputs keep their written order.
+pub const MAX_RELATIONS: usize = u64::BITS as usize;
+
+/// A join predicate with the relations it reads, as bit sets over relation indices.
+pub struct JoinPredicate {
+    pub relations: u64,
+    pub selectivity: f64,
+    /// For `a = b` between columns: the relations of each side
+    pub equi_sides: Option<(u64, u64)>,
+}
+
+#[derive(Debug, Clone)]
+pub enum JoinNode {
+    Relation(usize),
+    Join {
+        algorithm: JoinAlgorithm,
+        left: Box<JoinTree>,
+        right: Box<JoinTree>,
+        /// Indices of the predicates evaluated by this join
+        predicates: Vec<usize>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct JoinTree {
+    pub node: JoinNode,
+    pub relations: u64,
+    pub rows: f64,
+    pub cost: f64,
+}
+
+impl JoinTree {
+    pub fn relation(index: usize, rows: f64, cost: f64) -> Self {
+        Self {
+            node: JoinNode::Relation(index),
+            relations: 1 << index,
+            rows,
+            cost,
+        }
+    }
+}
+
+/// Finds the cheapest join tree over all relations, of which there are at
+/// most `MAX_RELATIONS`. Cross products are only considered when no predicate
+/// connects the remaining inputs.
+pub fn order_joins(relations: Vec<JoinTree>, predicates: &[JoinPredicate]) -> JoinTree {
+    assert!(relations.len() <= MAX_RELATIONS, "cannot order more than {} relations", MAX_RELATIONS);
+    if relations.len() <= DYNAMIC_PROGRAMMING_LIMIT {
+        dynamic_programming(relations, predicates)
+    } else {
+        greedy(relations, predicates)
+    }
+}
+
+fn dynamic_programming(relations: Vec<JoinTree>, predicates: &[JoinPredicate]) -> JoinTree {
+    let count = relations.len();
+    let full = (1u64 << count) - 1;
+    let mut best: Vec<Option<JoinTree>> = (0..=full).map(|_| None).collect();
+    for relation in relations {
+        let mask = relation.relations;
+        best[mask as usize] = Some(relation);
+    }
+
+    // every proper subset of a mask is numerically smaller, so it is already solved
+    for mask in 1..=full {
+        if mask.count_ones() < 2 {
+            continue;
+        }
+        for allow_cross_product in [false, true] {
+            let mut candidate: Option<JoinTree> = None;
+            let mut left = (mask - 1) & mask;
+            while left > 0 {
+                let right = mask ^ left;
+                if let (Some(l), Some(r)) = (&best[left as usize], &best[right as usize]) {
+                    if allow_cross_product || connected(left, right, predicates) {
+                        let (algorithm, cost, applied) = cheapest_join(l, r, predicates);
+                        if candidate.as_ref().is_none_or(|c| cost < c.cost) {
+                            candidate = Some(JoinTree {
+                                node: JoinNode::Join {
+                                    algorithm,
+                                    left: Box::new(l.clone()),
+                                    right: Box::new(r.clone()),
+                                    predicates: applied,
+                                },
+                                relations: mask,
+                                rows: estimate_rows(l, r, predicates),
+                                cost,
+                            });
+                        }
+                    }
+                }
+                left = (left - 1) & mask;
+            }
+            if candidate.is_some() {
+                best[mask as usize] = candidate;
+                break;
+            }
+        }
+    }
+
+    best[full as usize].take().unwrap()
+}
+
+/// Repeatedly joins the pair of inputs with the cheapest result.
+fn greedy(mut trees: Vec<JoinTree>, predicates: &[JoinPredicate]) -> JoinTree {
+    while trees.len() > 1 {
+        let mut best: Option<(bool, f64, usize, usize)> = None;
+        for i in 0..trees.len() {
+            for j in 0..trees.len() {
+                if i == j {
+                    continue;
+                }
+                let is_connected = connected(trees[i].relations, trees[j].relations, predicates);
+                let (_, cost, _) = cheapest_join(&trees[i], &trees[j], predicates);
+                // connected pairs first, then by cost
+                let better = match best {
+                    None => true,
+                    Some((best_connected, best_cost, _, _)) => {
+                        (is_connected && !best_connected) || (is_connected == best_connected && cost < best_cost)
+                    }
+                };
+                if better {
+                    best = Some((is_connected, cost, i, j));
+                }
+            }
+        }
+
+        let (_, _, i, j) = best.unwrap();
+        let (first, second) = (i.max(j), i.min(j));
+        let a = trees.swap_remove(first);
+        let b = trees.swap_remove(second);
+        let (left, right) = if i > j { (a, b) } else { (b, a) };
+        let (algorithm, cost, applied) = cheapest_join(&left, &right, predicates);
+        trees.push(JoinTree {
+            relations: left.relations | right.relations,
+            rows: estimate_rows(&left, &right, predicates),
+            cost,
+            node: JoinNode::Join {
+                algorithm,
+                left: Box::new(left),
+                right: Box::new(right),
+                predicates: applied,
+            },
+        });
+    }
+    trees.pop().unwrap()
+}
+
+fn connected(left: u64, right: u64, predicates: &[JoinPredicate]) -> bool {
+    predicates
+        .iter()
+        .any(|p| p.relations & left != 0 && p.relations & right != 0 && p.relations & !(left | right) == 0)
+}
+
+/// Predicates that become evaluable once `left` and `right` are joined.
+fn applicable(left: u64, right: u64, predicates: &[JoinPredicate]) -> Vec<usize> {
+    let mask = left | right;
+    (0..predicates.len())
+        .filter(|&i| {
+            let relations = predicates[i].relations;
+            relations & !mask == 0 && relations & !left != 0 && relations & !right != 0
+        })
+        .collect()
+}
+
+fn estimate_rows(left: &JoinTree, right: &JoinTree, predicates: &[JoinPredicate]) -> f64 {
+    applicable(left.relations, right.relations, predicates)
+        .iter()
+        .fold(left.rows * right.rows, |rows, &i| rows * predicates[i].selectivity)
+}
+
+/// Picks the join algorithm; hash and sort-merge joins need an equality
+/// between columns of both sides.
+fn cheapest_join(left: &JoinTree, right: &JoinTree, predicates: &[JoinPredicate]) -> (JoinAlgorithm, f64, Vec<usize>) {
+    let applied = applicable(left.relations, right.relations, predicates);
+    let has_equi_key = applied.iter().any(|&i| match predicates[i].equi_sides {
+        Some((a, b)) => {
+            (a & !left.relations == 0 && b & !right.relations == 0)
+                || (a & !right.relations == 0 && b & !left.relations == 0)
+        }
+        None => false,
+    });
+
+    let mut algorithms = vec![JoinAlgorithm::NestedLoop];
+    if has_equi_key {
+        algorithms.extend([JoinAlgorithm::Hash, JoinAlgorithm::SortMerge]);
+    }
+    let (algorithm, cost) = algorithms
+        .into_iter()
+        .map(|algorithm| (algorithm, join_cost(algorithm, left.rows, right.rows)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .unwrap();
+    (algorithm, left.cost + right.cost + cost, applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaf(index: usize, rows: f64) -> JoinTree {
+        JoinTree::relation(index, rows, rows)
+    }
+
+    fn equi(a: usize, b: usize, selectivity: f64) -> JoinPredicate {
+        JoinPredicate {
+            relations: (1 << a) | (1 << b),
+            selectivity,
+            equi_sides: Some((1 << a, 1 << b)),
+        }
+    }
+
+    fn order(tree: &JoinTree) -> String {
+        match &tree.node {
+            JoinNode::Relation(index) => index.to_string(),
+            JoinNode::Join { left, right, .. } => format!("({} {})", order(left), order(right)),
+        }
+    }
+
+    #[test]
+    fn avoids_cross_products_and_builds_on_the_small_side() {
+        // a chain 0 - 1 - 2 written in the order 0, 2, 1
+        let relations = vec![leaf(0, 1_000_000.0), leaf(1, 10.0), leaf(2, 1000.0)];
+        let predicates = vec![equi(0, 1, 0.1), equi(1, 2, 0.001)];
+
+        let tree = order_joins(relations, &predicates);
+        assert_eq!(order(&tree), "(0 (2 1))");
+        assert!(matches!(
+            tree.node,
+            JoinNode::Join {
+                algorithm: JoinAlgorithm::Hash,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn greedy_search_handles_many_relations() {
+        let count = DYNAMIC_PROGRAMMING_LIMIT + 2;
+        let relations = (0..count).map(|i| leaf(i, 100.0 * (i + 1) as f64)).collect();
+        let predicates: Vec<JoinPredicate> = (1..count).map(|i| equi(i - 1, i, 0.01)).collect();
+
+        let tree = order_joins(relations, &predicates);
+        assert_eq!(tree.relations, (1 << count) - 1);
+        assert!(!matches!(
+            tree.node,
+            JoinNode::Join {
+                algorithm: JoinAlgorithm::NestedLoop,
+                ..
+            }
+        ));
+    }
+}
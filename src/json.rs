@@ -0,0 +1,336 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A parsed JSON document. Values are stored in this form rather than as
+/// text, so paths are followed without parsing again. Object members are
+/// kept sorted by key, the first of duplicate keys winning, which makes equal
+/// documents equal values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// A step of a JSONPath: `.name`, `['name']` or `[index]`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathStep {
+    Member(String),
+    Index(usize),
+}
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut chars = text.chars().peekable();
+        let json = parse_value(&mut chars).ok_or_else(|| format!("Invalid JSON {}", text))?;
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(json),
+            Some(_) => Err(format!("Invalid JSON {}", text)),
+        }
+    }
+
+    pub fn member(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members
+                .binary_search_by(|(key, _)| key.as_str().cmp(name))
+                .ok()
+                .map(|index| &members[index].1),
+            _ => None,
+        }
+    }
+
+    /// The value at the end of the path, `None` if a step finds nothing.
+    pub fn get(&self, path: &[PathStep]) -> Option<&Json> {
+        path.iter().try_fold(self, |json, step| match (step, json) {
+            (PathStep::Member(name), json) => json.member(name),
+            (PathStep::Index(index), Json::Array(items)) => items.get(*index),
+            _ => None,
+        })
+    }
+
+    /// The text of a string, number or boolean; `None` for null, arrays and objects.
+    pub fn scalar_text(&self) -> Option<String> {
+        match self {
+            Json::String(s) => Some(s.clone()),
+            Json::Null | Json::Array(_) | Json::Object(_) => None,
+            json => Some(json.to_string()),
+        }
+    }
+}
+
+/// Parses a JSONPath such as `$.items[0]['first name']`.
+pub fn parse_path(path: &str) -> Result<Vec<PathStep>, String> {
+    let invalid = || format!("Invalid JSONPath {}", path);
+    let mut chars = path.trim().chars().peekable();
+    if chars.next() != Some('$') {
+        return Err(invalid());
+    }
+    let mut steps = Vec::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '.' if chars.peek() == Some(&'"') => {
+                chars.next();
+                steps.push(PathStep::Member(parse_string(&mut chars).ok_or_else(invalid)?));
+            }
+            '.' => {
+                let mut name = String::new();
+                while let Some(&c) = chars.peek().filter(|&&c| c != '.' && c != '[') {
+                    name.push(c);
+                    chars.next();
+                }
+                if name.is_empty() {
+                    return Err(invalid());
+                }
+                steps.push(PathStep::Member(name));
+            }
+            '[' => {
+                let step = match chars.next() {
+                    Some(quote @ ('\'' | '"')) => {
+                        let mut name = String::new();
+                        loop {
+                            match chars.next().ok_or_else(invalid)? {
+                                c if c == quote => break,
+                                c => name.push(c),
+                            }
+                        }
+                        PathStep::Member(name)
+                    }
+                    Some(c) if c.is_ascii_digit() => {
+                        let mut digits = c.to_string();
+                        while let Some(&c) = chars.peek().filter(|c| c.is_ascii_digit()) {
+                            digits.push(c);
+                            chars.next();
+                        }
+                        PathStep::Index(digits.parse().map_err(|_| invalid())?)
+                    }
+                    _ => return Err(invalid()),
+                };
+                if chars.next() != Some(']') {
+                    return Err(invalid());
+                }
+                steps.push(step);
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    Ok(steps)
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Option<Json> {
+    skip_whitespace(chars);
+    match *chars.peek()? {
+        '{' => {
+            chars.next();
+            let mut members: Vec<(String, Json)> = Vec::new();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&'}').is_none() {
+                loop {
+                    skip_whitespace(chars);
+                    chars.next_if_eq(&'"')?;
+                    let key = parse_string(chars)?;
+                    skip_whitespace(chars);
+                    chars.next_if_eq(&':')?;
+                    let value = parse_value(chars)?;
+                    if let Err(index) = members.binary_search_by(|(k, _)| k.cmp(&key)) {
+                        members.insert(index, (key, value));
+                    }
+                    skip_whitespace(chars);
+                    match chars.next()? {
+                        ',' => {}
+                        '}' => break,
+                        _ => return None,
+                    }
+                }
+            }
+            Some(Json::Object(members))
+        }
+        '[' => {
+            chars.next();
+            let mut items = Vec::new();
+            skip_whitespace(chars);
+            if chars.next_if_eq(&']').is_none() {
+                loop {
+                    items.push(parse_value(chars)?);
+                    skip_whitespace(chars);
+                    match chars.next()? {
+                        ',' => {}
+                        ']' => break,
+                        _ => return None,
+                    }
+                }
+            }
+            Some(Json::Array(items))
+        }
+        '"' => {
+            chars.next();
+            parse_string(chars).map(Json::String)
+        }
+        c if c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+                number.push(c);
+            }
+            // integers too large for INT64 are kept as floats
+            match number.parse() {
+                Ok(i) => Some(Json::Int(i)),
+                Err(_) => number.parse().ok().filter(|x: &f64| x.is_finite()).map(Json::Float),
+            }
+        }
+        _ => {
+            let mut word = String::new();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+                word.push(c);
+            }
+            match word.as_str() {
+                "null" => Some(Json::Null),
+                "true" => Some(Json::Bool(true)),
+                "false" => Some(Json::Bool(false)),
+                _ => None,
+            }
+        }
+    }
+}
+
+/// Reads a string up to and including its closing quote.
+fn parse_string(chars: &mut Peekable<Chars>) -> Option<String> {
+    let mut s = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(s),
+            '\\' => s.push(match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                'r' => '\r',
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'u' => {
+                    let high = parse_hex(chars)?;
+                    // characters outside the basic plane are surrogate pairs
+                    if (0xd800..0xdc00).contains(&high) {
+                        chars.next_if_eq(&'\\')?;
+                        chars.next_if_eq(&'u')?;
+                        let low = parse_hex(chars)?;
+                        char::from_u32(0x10000 + ((high - 0xd800) << 10) + low.checked_sub(0xdc00)?)?
+                    } else {
+                        char::from_u32(high)?
+                    }
+                }
+                c @ ('"' | '\\' | '/') => c,
+                _ => return None,
+            }),
+            c if (c as u32) < 0x20 => return None,
+            c => s.push(c),
+        }
+    }
+}
+
+fn parse_hex(chars: &mut Peekable<Chars>) -> Option<u32> {
+    (0..4).try_fold(0, |code, _| Some(code * 16 + chars.next()?.to_digit(16)?))
+}
+
+fn write_string(f: &mut fmt::Formatter, s: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            '\r' => write!(f, "\\r")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// Compact JSON text, without spaces.
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Int(i) => write!(f, "{}", i),
+            Json::Float(x) => write!(f, "{}", x),
+            Json::String(s) => write_string(f, s),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_prints_documents() {
+        let json = Json::parse(r#" {"b": [1, 2.5, -3e2, true, null], "a": "xé\n", "a": 1} "#).unwrap();
+        assert_eq!(json.to_string(), r#"{"a":"xé\n","b":[1,2.5,-300,true,null]}"#);
+        assert_eq!(Json::parse(r#""😀""#).unwrap(), Json::String("😀".to_string()));
+        assert_eq!(Json::parse("99999999999999999999").unwrap(), Json::Float(1e20));
+        // members are kept sorted, so the order they are written in does not matter
+        assert_eq!(Json::parse(r#"{"x": 1, "y": {}}"#), Json::parse(r#"{"y": {}, "x": 1}"#));
+
+        for text in [r#"{"a": 1,}"#, "[1 2]", "tru", r#""open"#, r#""\x""#, "1 2", "{'a': 1}", r#""\ud83d""#, "\"a\tb\"", ""] {
+            assert_eq!(Json::parse(text), Err(format!("Invalid JSON {}", text)));
+        }
+    }
+
+    #[test]
+    fn follows_paths_and_rejects_invalid_ones() {
+        let member = |name: &str| PathStep::Member(name.to_string());
+        assert_eq!(
+            parse_path("$.items[0]['first name']").unwrap(),
+            vec![member("items"), PathStep::Index(0), member("first name")]
+        );
+        assert_eq!(parse_path(r#"$."a.b"["c"]"#).unwrap(), vec![member("a.b"), member("c")]);
+        assert_eq!(parse_path(" $ ").unwrap(), vec![]);
+        for path in ["items", "$.", "$..a", "$[", "$[x]", "$['a'", "$[0", "$[-1]", "$[99999999999999999999999]", "$ .a"] {
+            assert_eq!(parse_path(path), Err(format!("Invalid JSONPath {}", path)));
+        }
+
+        let json = Json::parse(r#"{"items": [{"name": "pen", "price": 1.5}], "note": null}"#).unwrap();
+        let at = |path: &str| json.get(&parse_path(path).unwrap()).cloned();
+        assert_eq!(at("$.items[0].name"), Some(Json::String("pen".to_string())));
+        assert_eq!(at("$.note"), Some(Json::Null));
+        for path in ["$.missing", "$.items[1]", "$.items.name", "$[0]", "$.items[0][0]"] {
+            assert_eq!(at(path), None, "{}", path);
+        }
+        let text = |path: &str| at(path).and_then(|json| json.scalar_text());
+        assert_eq!(text("$.items[0].name").as_deref(), Some("pen"));
+        assert_eq!(text("$.items[0].price").as_deref(), Some("1.5"));
+        assert_eq!(text("$.note"), None);
+        assert_eq!(text("$.items"), None);
+    }
+}
@@ -0,0 +1,185 @@
+use crate::errors::ParsingError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Identifier(String),
+    Number(String),
+    StringLiteral(String),
+    Symbol(String),
+}
+
+pub fn tokenize(input: &str) -> Result<Vec<Token>, ParsingError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Identifier(chars[start..i].iter().collect()));
+        } else if c == '`' {
+            let end = find_closing(&chars, i, '`')?;
+            tokens.push(Token::Identifier(chars[i + 1..end].iter().collect()));
+            i = end + 1;
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(Token::Number(chars[start..i].iter().collect()));
+        } else if c == '\'' || c == '"' {
+            let end = find_closing(&chars, i, c)?;
+            tokens.push(Token::StringLiteral(chars[i + 1..end].iter().collect()));
+            i = end + 1;
+        } else {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            if ["|>", "<=", ">=", "<>", "!="].contains(&two.as_str()) {
+                tokens.push(Token::Symbol(two));
+                i += 2;
+            } else if "(),.;=<>+-*/[]".contains(c) {
+                tokens.push(Token::Symbol(c.to_string()));
+                i += 1;
+            } else {
+                return Err(ParsingError::new(&format!("Unexpected character '{}'", c)));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn find_closing(chars: &[char], start: usize, quote: char) -> Result<usize, ParsingError> {
+    chars[start + 1..]
+        .iter()
+        .position(|&c| c == quote)
+        .map(|p| start + 1 + p)
+        .ok_or_else(|| ParsingError::new(&format!("Unterminated literal, missing {}", quote)))
+}
+
+/// Cursor over a token list with the helpers every statement parser needs.
+pub struct Tokens {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Tokens {
+    pub fn new(input: &str) -> Result<Self, ParsingError> {
+        Ok(Self {
+            tokens: tokenize(input)?,
+            position: 0,
+        })
+    }
+
+    pub fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    /// The token `n` places after the next one.
+    pub fn peek_nth(&self, n: usize) -> Option<&Token> {
+        self.tokens.get(self.position + n)
+    }
+
+    pub fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position >= self.tokens.len()
+    }
+
+    pub fn peek_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Identifier(word)) if word.eq_ignore_ascii_case(keyword))
+    }
+
+    pub fn peek_symbol(&self, symbol: &str) -> bool {
+        matches!(self.peek(), Some(Token::Symbol(s)) if s == symbol)
+    }
+
+    /// Consumes the keyword if it is next.
+    pub fn accept_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_keyword(keyword);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    pub fn accept_symbol(&mut self, symbol: &str) -> bool {
+        let found = self.peek_symbol(symbol);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    pub fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParsingError> {
+        if self.accept_keyword(keyword) {
+            Ok(())
+        } else {
+            Err(self.unexpected(keyword))
+        }
+    }
+
+    pub fn expect_symbol(&mut self, symbol: &str) -> Result<(), ParsingError> {
+        if self.accept_symbol(symbol) {
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("'{}'", symbol)))
+        }
+    }
+
+    pub fn expect_identifier(&mut self) -> Result<String, ParsingError> {
+        match self.peek() {
+            Some(Token::Identifier(name)) => {
+                let name = name.clone();
+                self.position += 1;
+                Ok(name)
+            }
+            _ => Err(self.unexpected("identifier")),
+        }
+    }
+
+    /// Parses `(a, b, c)`.
+    pub fn parse_identifier_list(&mut self) -> Result<Vec<String>, ParsingError> {
+        self.expect_symbol("(")?;
+        let mut names = vec![self.expect_identifier()?];
+        while self.accept_symbol(",") {
+            names.push(self.expect_identifier()?);
+        }
+        self.expect_symbol(")")?;
+        Ok(names)
+    }
+
+    pub fn expect_end(&mut self) -> Result<(), ParsingError> {
+        self.accept_symbol(";");
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self.unexpected("end of statement"))
+        }
+    }
+
+    pub fn unexpected(&self, expected: &str) -> ParsingError {
+        match self.peek() {
+            Some(token) => ParsingError::new(&format!("Expected {}, found {}", expected, describe(token))),
+            None => ParsingError::new(&format!("Expected {}, found end of input", expected)),
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Identifier(name) => name.clone(),
+        Token::Number(number) => number.clone(),
+        Token::StringLiteral(s) => format!("'{}'", s),
+        Token::Symbol(s) => format!("'{}'", s),
+    }
+}
@@ -0,0 +1,19 @@
+use crate::logical_plan::LogicalPlan;
+
+/// Skips the first `offset` rows and stops after `limit` more.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalLimitNode {
+    pub input: Box<LogicalPlan>,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+impl LogicalLimitNode {
+    pub fn new(input: LogicalPlan, limit: usize, offset: usize) -> Self {
+        Self {
+            input: Box::new(input),
+            limit,
+            offset,
+        }
+    }
+}
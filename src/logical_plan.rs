@@ -1,78 +1,891 @@
+use crate::aggregate::{aggregate_fields, AggregateCall, LogicalAggregateListNode, LogicalAggregateNode};
+use crate::alias::LogicalAliasNode;
+use crate::catalog::Catalog;
+use crate::common_table::{CommonTableExpr, LogicalCommonTableScanNode, LogicalRecursiveUnionNode, LogicalWithNode};
+use crate::errors::{ExecutionError, ParsingError};
+use crate::expression::{parse_expression, Expr};
 use crate::filter::LogicalFilterNode;
-use crate::join::LogicalJoinNode;
+use crate::join::{JoinType, LogicalJoinNode};
+use crate::lexer::{Token, Tokens};
+use crate::limit::LogicalLimitNode;
+use crate::nested::LogicalUnnestNode;
+use crate::order_by::{LogicalOrderByNode, SortKey};
+use crate::parser::parse_query;
+use crate::pivot::{LogicalPivotNode, LogicalUnpivotNode};
+use crate::sample::TableSample;
 use crate::scanner::LogicalScanNode;
-use crate::select::LogicalSelectListNode;
-use crate::order_by::LogicalOrderByNode;
-use crate::errors::ParsingError;
-
-#[derive(Debug)]
-pub struct LogicalPlan {
-    pub query: String,
-    pub filters: Vec<LogicalFilterNode>,
-    pub joins: Vec<LogicalJoinNode>,
-    pub scans: Vec<LogicalScanNode>,
-    pub select_list: Vec<LogicalSelectListNode>,
-    pub order_by: Vec<LogicalOrderByNode>,
+use crate::schema::{Field, Schema};
+use crate::select::{
+    expand_select_list, LogicalDropNode, LogicalExtendNode, LogicalProjectNode, LogicalRenameNode, LogicalSelectListNode,
+    LogicalSetNode,
+};
+use crate::set_operation::{set_operation_fields, LogicalSetOperationNode, SetOperator};
+use crate::subquery::{apply_type, ApplyKind, LogicalApplyNode};
+use crate::value::Value;
+use crate::window::{window_fields, LogicalWindowListNode, LogicalWindowNode, WindowCall, WindowFunction};
+
+/// Words that end a FROM item, so they cannot be an alias without `AS`.
+const FROM_ITEM_TERMINATORS: [&str; 3] = ["ON", "USING", "TABLESAMPLE"];
+
+/// A query as a tree of operators; every `|>` stage wraps the plan of the
+/// stages before it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LogicalPlan {
+    /// A single row without columns, the input of queries without FROM
+    OneRow,
+    Scan(LogicalScanNode),
+    Filter(LogicalFilterNode),
+    Project(LogicalProjectNode),
+    Join(LogicalJoinNode),
+    Sort(LogicalOrderByNode),
+    Alias(LogicalAliasNode),
+    Aggregate(LogicalAggregateNode),
+    Extend(LogicalExtendNode),
+    Set(LogicalSetNode),
+    Drop(LogicalDropNode),
+    Rename(LogicalRenameNode),
+    Limit(LogicalLimitNode),
+    SetOperation(LogicalSetOperationNode),
+    Window(LogicalWindowNode),
+    Apply(LogicalApplyNode),
+    With(LogicalWithNode),
+    CommonTableScan(LogicalCommonTableScanNode),
+    RecursiveUnion(LogicalRecursiveUnionNode),
+    Pivot(LogicalPivotNode),
+    Unpivot(LogicalUnpivotNode),
+    Unnest(LogicalUnnestNode),
 }
 
 impl LogicalPlan {
-    pub fn new(query: String) -> Self {
-        Self {
-            query,
-            filters: Vec::new(),
-            joins: Vec::new(),
-            scans: Vec::new(),
-            select_list: Vec::new(),
-            order_by: Vec::new(),
+    pub fn inputs(&self) -> Vec<&LogicalPlan> {
+        match self {
+            LogicalPlan::OneRow | LogicalPlan::Scan(_) | LogicalPlan::CommonTableScan(_) => Vec::new(),
+            LogicalPlan::Filter(filter) => vec![&filter.input],
+            LogicalPlan::Project(project) => vec![&project.input],
+            LogicalPlan::Join(join) => vec![&join.left, &join.right],
+            LogicalPlan::Sort(sort) => vec![&sort.input],
+            LogicalPlan::Alias(alias) => vec![&alias.input],
+            LogicalPlan::Aggregate(aggregate) => vec![&aggregate.input],
+            LogicalPlan::Extend(extend) => vec![&extend.input],
+            LogicalPlan::Set(set) => vec![&set.input],
+            LogicalPlan::Drop(drop) => vec![&drop.input],
+            LogicalPlan::Rename(rename) => vec![&rename.input],
+            LogicalPlan::Limit(limit) => vec![&limit.input],
+            LogicalPlan::SetOperation(set) => vec![&set.left, &set.right],
+            LogicalPlan::Window(window) => vec![&window.input],
+            LogicalPlan::Apply(apply) => vec![&apply.input],
+            // the tables first, in the order they are computed
+            LogicalPlan::With(with) => {
+                let mut inputs: Vec<&LogicalPlan> = with.tables.iter().map(|table| &table.plan).collect();
+                inputs.push(&with.input);
+                inputs
+            }
+            LogicalPlan::RecursiveUnion(union) => vec![&union.anchor, &union.recursive],
+            LogicalPlan::Pivot(pivot) => vec![&pivot.input],
+            LogicalPlan::Unpivot(unpivot) => vec![&unpivot.input],
+            LogicalPlan::Unnest(unnest) => vec![&unnest.input],
+        }
+    }
+
+    /// Rebuilds the node with every input replaced by `f(input)`.
+    pub fn map_inputs(self, mut f: impl FnMut(LogicalPlan) -> LogicalPlan) -> LogicalPlan {
+        let mut map = |input: Box<LogicalPlan>| Box::new(f(*input));
+        match self {
+            LogicalPlan::OneRow | LogicalPlan::Scan(_) | LogicalPlan::CommonTableScan(_) => self,
+            LogicalPlan::Filter(mut filter) => {
+                filter.input = map(filter.input);
+                LogicalPlan::Filter(filter)
+            }
+            LogicalPlan::Project(mut project) => {
+                project.input = map(project.input);
+                LogicalPlan::Project(project)
+            }
+            LogicalPlan::Join(mut join) => {
+                join.left = map(join.left);
+                join.right = map(join.right);
+                LogicalPlan::Join(join)
+            }
+            LogicalPlan::Sort(mut sort) => {
+                sort.input = map(sort.input);
+                LogicalPlan::Sort(sort)
+            }
+            LogicalPlan::Alias(mut alias) => {
+                alias.input = map(alias.input);
+                LogicalPlan::Alias(alias)
+            }
+            LogicalPlan::Aggregate(mut aggregate) => {
+                aggregate.input = map(aggregate.input);
+                LogicalPlan::Aggregate(aggregate)
+            }
+            LogicalPlan::Extend(mut extend) => {
+                extend.input = map(extend.input);
+                LogicalPlan::Extend(extend)
+            }
+            LogicalPlan::Set(mut set) => {
+                set.input = map(set.input);
+                LogicalPlan::Set(set)
+            }
+            LogicalPlan::Drop(mut drop) => {
+                drop.input = map(drop.input);
+                LogicalPlan::Drop(drop)
+            }
+            LogicalPlan::Rename(mut rename) => {
+                rename.input = map(rename.input);
+                LogicalPlan::Rename(rename)
+            }
+            LogicalPlan::Limit(mut limit) => {
+                limit.input = map(limit.input);
+                LogicalPlan::Limit(limit)
+            }
+            LogicalPlan::SetOperation(mut set) => {
+                set.left = map(set.left);
+                set.right = map(set.right);
+                LogicalPlan::SetOperation(set)
+            }
+            LogicalPlan::Window(mut window) => {
+                window.input = map(window.input);
+                LogicalPlan::Window(window)
+            }
+            LogicalPlan::Apply(mut apply) => {
+                apply.input = map(apply.input);
+                LogicalPlan::Apply(apply)
+            }
+            LogicalPlan::With(mut with) => {
+                for table in &mut with.tables {
+                    let plan = std::mem::replace(&mut table.plan, LogicalPlan::OneRow);
+                    table.plan = *map(Box::new(plan));
+                }
+                with.input = map(with.input);
+                LogicalPlan::With(with)
+            }
+            LogicalPlan::RecursiveUnion(mut union) => {
+                union.anchor = map(union.anchor);
+                union.recursive = map(union.recursive);
+                LogicalPlan::RecursiveUnion(union)
+            }
+            LogicalPlan::Pivot(mut pivot) => {
+                pivot.input = map(pivot.input);
+                LogicalPlan::Pivot(pivot)
+            }
+            LogicalPlan::Unpivot(mut unpivot) => {
+                unpivot.input = map(unpivot.input);
+                LogicalPlan::Unpivot(unpivot)
+            }
+            LogicalPlan::Unnest(mut unnest) => {
+                unnest.input = map(unnest.input);
+                LogicalPlan::Unnest(unnest)
+            }
+        }
+    }
+
+    /// The columns an expression of the node can refer to.
+    pub fn expr_scope(&self, catalog: &Catalog) -> Result<Schema, ExecutionError> {
+        match self {
+            LogicalPlan::OneRow | LogicalPlan::Scan(_) | LogicalPlan::CommonTableScan(_) => self.schema(catalog),
+            LogicalPlan::Join(join) => {
+                let mut fields = join.left.schema(catalog)?.fields;
+                fields.extend(join.right.schema(catalog)?.fields);
+                Ok(Schema::new(fields))
+            }
+            _ => self.inputs()[0].schema(catalog),
+        }
+    }
+
+    /// Columns produced by the plan.
+    pub fn schema(&self, catalog: &Catalog) -> Result<Schema, ExecutionError> {
+        match self {
+            LogicalPlan::OneRow => Ok(Schema::default()),
+            LogicalPlan::Scan(scan) => {
+                let table = catalog.get_table(&scan.table_name)?;
+                let columns = match &scan.projection {
+                    Some(projection) => table.column_indices(projection)?,
+                    None => (0..table.columns.len()).collect(),
+                };
+                let fields = columns
+                    .into_iter()
+                    .map(|i| Field::new(Some(scan.qualifier()), &table.columns[i].name, table.columns[i].data_type.clone()))
+                    .collect();
+                Ok(Schema::new(fields))
+            }
+            LogicalPlan::Filter(filter) => filter.input.schema(catalog),
+            LogicalPlan::Sort(sort) => sort.input.schema(catalog),
+            LogicalPlan::Limit(limit) => limit.input.schema(catalog),
+            LogicalPlan::SetOperation(set) => {
+                let (left, right) = (set.left.schema(catalog)?, set.right.schema(catalog)?);
+                Ok(Schema::new(set_operation_fields(set.operator, &left, &right)?))
+            }
+            LogicalPlan::Window(window) => {
+                let input = window.input.schema(catalog)?;
+                Ok(Schema::new(window_fields(&window.window_list(), &input)?))
+            }
+            LogicalPlan::With(with) => with.input.schema(catalog),
+            LogicalPlan::CommonTableScan(scan) => Ok(Schema::new(scan.fields.clone())),
+            LogicalPlan::RecursiveUnion(union) => {
+                let (anchor, recursive) = (union.anchor.schema(catalog)?, union.recursive.schema(catalog)?);
+                Ok(Schema::new(set_operation_fields(SetOperator::Union, &anchor, &recursive)?))
+            }
+            LogicalPlan::Apply(apply) => {
+                let mut fields = apply.input.schema(catalog)?.fields;
+                fields.push(Field::new(None, &apply.name, apply_type(&apply.subquery, &apply.kind, catalog)?));
+                Ok(Schema::new(fields))
+            }
+            LogicalPlan::Project(project) => {
+                let input = project.input.schema(catalog)?;
+                let select_list = project
+                    .select_list
+                    .iter()
+                    .map(|select| (&select.field, select.alias.as_deref()));
+                let fields = expand_select_list(select_list, &input)?;
+                Ok(Schema::new(fields.into_iter().map(|(_, field)| field).collect()))
+            }
+            LogicalPlan::Join(join) => {
+                let (left, right) = (join.left.schema(catalog)?, join.right.schema(catalog)?);
+                if !join.using.is_empty() {
+                    let (_, select_list) = join.expand_using(&left, &right)?;
+                    let mut fields = left.fields;
+                    fields.extend(right.fields);
+                    let select_list = select_list.iter().map(|s| (&s.field, s.alias.as_deref()));
+                    let fields = expand_select_list(select_list, &Schema::new(fields))?;
+                    return Ok(Schema::new(fields.into_iter().map(|(_, field)| field).collect()));
+                }
+                let mut fields = left.fields;
+                if join.join_type.keeps_right_columns() {
+                    fields.extend(right.fields);
+                }
+                Ok(Schema::new(fields))
+            }
+            LogicalPlan::Alias(alias) => {
+                let fields = alias
+                    .input
+                    .schema(catalog)?
+                    .fields
+                    .into_iter()
+                    .map(|field| Field::new(Some(&alias.alias), &field.name, field.data_type))
+                    .collect();
+                Ok(Schema::new(fields))
+            }
+            LogicalPlan::Aggregate(aggregate) => {
+                let input = aggregate.input.schema(catalog)?;
+                let fields = aggregate_fields(&aggregate.group_by_list(), &aggregate.aggregate_list(), &input)?;
+                Ok(Schema::new(fields))
+            }
+            LogicalPlan::Pivot(pivot) => {
+                let input = pivot.input.schema(catalog)?;
+                LogicalPlan::Aggregate(pivot.aggregate(&input)?).schema(catalog)
+            }
+            LogicalPlan::Unpivot(unpivot) => {
+                let input = unpivot.input.schema(catalog)?;
+                unpivot.union(&input)?.schema(catalog)
+            }
+            LogicalPlan::Unnest(unnest) => {
+                let fields = unnest.fields(&unnest.input.schema(catalog)?)?;
+                let schema = Schema::new(fields);
+                unnest.condition.data_type(&schema)?;
+                Ok(schema)
+            }
+            LogicalPlan::Extend(_) | LogicalPlan::Set(_) | LogicalPlan::Drop(_) | LogicalPlan::Rename(_) => {
+                let input = self.inputs()[0].schema(catalog)?;
+                let select_list = self.select_list(&input)?.expect("a column rewriting stage");
+                let select_list = select_list.iter().map(|s| (&s.field, s.alias.as_deref()));
+                let fields = expand_select_list(select_list, &input)?;
+                Ok(Schema::new(fields.into_iter().map(|(_, field)| field).collect()))
+            }
+        }
+    }
+
+    /// The select list that EXTEND, SET, DROP and RENAME stand for over rows
+    /// of `input`, or `None` for other nodes.
+    pub fn select_list(&self, input: &Schema) -> Result<Option<Vec<LogicalSelectListNode>>, ExecutionError> {
+        Ok(Some(match self {
+            LogicalPlan::Extend(extend) => extend.select_list(input)?,
+            LogicalPlan::Set(set) => set.select_list(input)?,
+            LogicalPlan::Drop(drop) => drop.select_list(input)?,
+            LogicalPlan::Rename(rename) => rename.select_list(input)?,
+            _ => return Ok(None),
+        }))
+    }
+
+    /// Rebuilds the node with every expression it evaluates, but not those of
+    /// its inputs, replaced by `f(expr)`.
+    pub fn map_exprs(self, f: &mut impl FnMut(Expr) -> Expr) -> LogicalPlan {
+        let map_list = |list: Vec<LogicalSelectListNode>, f: &mut dyn FnMut(Expr) -> Expr| {
+            list.into_iter()
+                .map(|mut select| {
+                    select.field = f(select.field);
+                    select
+                })
+                .collect()
+        };
+        let map_keys = |keys: Vec<SortKey>, f: &mut dyn FnMut(Expr) -> Expr| {
+            keys.into_iter()
+                .map(|mut key| {
+                    key.expr = f(key.expr);
+                    key
+                })
+                .collect()
+        };
+        match self {
+            LogicalPlan::Scan(mut scan) => {
+                scan.filters = scan.filters.into_iter().map(&mut *f).collect();
+                LogicalPlan::Scan(scan)
+            }
+            LogicalPlan::Filter(mut filter) => {
+                filter.condition = f(filter.condition);
+                LogicalPlan::Filter(filter)
+            }
+            LogicalPlan::Project(mut project) => {
+                project.select_list = map_list(project.select_list, f);
+                LogicalPlan::Project(project)
+            }
+            LogicalPlan::Join(mut join) => {
+                join.condition = f(join.condition);
+                LogicalPlan::Join(join)
+            }
+            LogicalPlan::Sort(mut sort) => {
+                sort.keys = map_keys(sort.keys, f);
+                LogicalPlan::Sort(sort)
+            }
+            LogicalPlan::Aggregate(mut aggregate) => {
+                aggregate.group_by = map_list(aggregate.group_by, f);
+                for aggregate in &mut aggregate.aggregates {
+                    aggregate.call.argument = aggregate.call.argument.take().map(&mut *f);
+                }
+                LogicalPlan::Aggregate(aggregate)
+            }
+            LogicalPlan::Extend(mut extend) => {
+                extend.extensions = map_list(extend.extensions, f);
+                LogicalPlan::Extend(extend)
+            }
+            LogicalPlan::Set(mut set) => {
+                set.assignments = set.assignments.into_iter().map(|(name, expr)| (name, f(expr))).collect();
+                LogicalPlan::Set(set)
+            }
+            LogicalPlan::Window(mut window) => {
+                for window in &mut window.windows {
+                    let call = &mut window.call;
+                    call.args = std::mem::take(&mut call.args).into_iter().map(&mut *f).collect();
+                    call.spec.partition_by = std::mem::take(&mut call.spec.partition_by).into_iter().map(&mut *f).collect();
+                    call.spec.order_by = map_keys(std::mem::take(&mut call.spec.order_by), f);
+                    if let WindowFunction::Aggregate(aggregate) = &mut call.function {
+                        aggregate.argument = aggregate.argument.take().map(&mut *f);
+                    }
+                }
+                LogicalPlan::Window(window)
+            }
+            LogicalPlan::Apply(mut apply) => {
+                if let ApplyKind::In(expr) = apply.kind {
+                    apply.kind = ApplyKind::In(f(expr));
+                }
+                LogicalPlan::Apply(apply)
+            }
+            LogicalPlan::Pivot(mut pivot) => {
+                for aggregate in &mut pivot.aggregates {
+                    aggregate.call.argument = aggregate.call.argument.take().map(&mut *f);
+                }
+                pivot.column = f(pivot.column);
+                LogicalPlan::Pivot(pivot)
+            }
+            LogicalPlan::Unnest(mut unnest) => {
+                unnest.array = f(unnest.array);
+                unnest.condition = f(unnest.condition);
+                LogicalPlan::Unnest(unnest)
+            }
+            LogicalPlan::OneRow
+            | LogicalPlan::Alias(_)
+            | LogicalPlan::Drop(_)
+            | LogicalPlan::Rename(_)
+            | LogicalPlan::Limit(_)
+            | LogicalPlan::SetOperation(_)
+            | LogicalPlan::With(_)
+            | LogicalPlan::CommonTableScan(_)
+            | LogicalPlan::RecursiveUnion(_)
+            | LogicalPlan::Unpivot(_) => self,
+        }
+    }
+
+    /// The name of every column the node computes from an expression or a
+    /// call, with the alias it is given, if any.
+    pub fn output_names(&mut self) -> Vec<(String, &mut Option<String>)> {
+        match self {
+            LogicalPlan::Project(project) => project
+                .select_list
+                .iter_mut()
+                .map(|select| (select.field.to_string(), &mut select.alias))
+                .collect(),
+            LogicalPlan::Extend(extend) => extend
+                .extensions
+                .iter_mut()
+                .map(|select| (select.field.to_string(), &mut select.alias))
+                .collect(),
+            LogicalPlan::Aggregate(aggregate) => {
+                let group_by = aggregate.group_by.iter_mut().map(|select| (select.field.to_string(), &mut select.alias));
+                let calls = aggregate.aggregates.iter_mut().map(|aggregate| (aggregate.call.to_string(), &mut aggregate.alias));
+                group_by.chain(calls).collect()
+            }
+            LogicalPlan::Window(window) => window
+                .windows
+                .iter_mut()
+                .map(|window| (window.call.to_string(), &mut window.alias))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Aliases the columns whose expression no longer reads as `names`, the
+    /// output names taken before a rewrite, so that rewriting does not rename
+    /// them.
+    pub fn keep_output_names(&mut self, names: Vec<String>) {
+        for ((name, alias), original) in self.output_names().into_iter().zip(names) {
+            if alias.is_none() && name != original {
+                *alias = Some(original);
+            }
+        }
+    }
+
+    /// Parses `WITH [RECURSIVE] name AS (query), ...` and the query that
+    /// follows it.
+    pub fn parse_with(tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("WITH")?;
+        let recursive = tokens.accept_keyword("RECURSIVE");
+        let mut tables = Vec::new();
+        loop {
+            let name = tokens.expect_identifier()?;
+            if tables.iter().any(|table: &CommonTableExpr| table.name.eq_ignore_ascii_case(&name)) {
+                return Err(ParsingError::new(&format!("WITH defines {} twice", name)));
+            }
+            tokens.expect_keyword("AS")?;
+            tokens.expect_symbol("(")?;
+            let plan = parse_query(tokens)?;
+            tokens.expect_symbol(")")?;
+            tables.push(CommonTableExpr::new(&name, plan));
+            if !tokens.accept_symbol(",") {
+                break;
+            }
         }
+        let input = parse_query(tokens)?;
+        Ok(LogicalPlan::With(LogicalWithNode::new(tables, recursive, input)))
     }
 
-    pub fn add_scan(&mut self, scan: LogicalScanNode) {
-        self.scans.push(scan);
+    /// Parses `FROM item`, the stage every query with a table starts with,
+    /// or `FROM UNNEST(array)`, which has a row per element.
+    pub fn parse_from(tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("FROM")?;
+        if Self::peek_unnest(tokens) {
+            return LogicalUnnestNode::parse(LogicalPlan::OneRow, tokens, false).map(LogicalPlan::Unnest);
+        }
+        Self::parse_from_item(tokens)
     }
 
-    pub fn parse_select(&mut self, token: &str) -> Result<(), ParsingError> {
-        let fields: Vec<String> = token
-            .trim_start_matches("SELECT")
-            .trim()
-            .split(',')
-            .map(|s| s.trim().to_string())
-            .collect();
+    fn peek_unnest(tokens: &Tokens) -> bool {
+        tokens.peek_keyword("UNNEST") && matches!(tokens.peek_nth(1), Some(Token::Symbol(symbol)) if symbol == "(")
+    }
 
-        for field in fields {
-            self.select_list.push(LogicalSelectListNode::new(&field));
+    /// Parses `table [[AS] alias] [TABLESAMPLE ...]` or `(query) [[AS] alias]`.
+    fn parse_from_item(tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        if tokens.accept_symbol("(") {
+            let query = parse_query(tokens)?;
+            tokens.expect_symbol(")")?;
+            return Ok(match Self::parse_alias(tokens)? {
+                Some(alias) => LogicalPlan::Alias(LogicalAliasNode::new(query, &alias)),
+                None => query,
+            });
+        }
+        let table_name = tokens.expect_identifier()?;
+        let alias = Self::parse_alias(tokens)?;
+        let mut scan = LogicalScanNode::new(&table_name, alias.as_deref());
+        if tokens.peek_keyword("TABLESAMPLE") {
+            scan.sample = Some(TableSample::parse(tokens)?);
         }
+        Ok(LogicalPlan::Scan(scan))
+    }
 
-        Ok(())
+    fn parse_alias(tokens: &mut Tokens) -> Result<Option<String>, ParsingError> {
+        if tokens.accept_keyword("AS") {
+            return tokens.expect_identifier().map(Some);
+        }
+        if FROM_ITEM_TERMINATORS.iter().any(|word| tokens.peek_keyword(word)) {
+            return Ok(None);
+        }
+        Ok(tokens.expect_identifier().ok())
     }
 
-    pub fn parse_where(&mut self, token: &str) -> Result<(), ParsingError> {
-        let condition = token.trim_start_matches("WHERE").trim();
-        self.filters.push(LogicalFilterNode::new(condition));
-        Ok(())
+    pub fn parse_select(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("SELECT")?;
+        let mut select_list = Vec::new();
+        loop {
+            let field = if tokens.accept_symbol("*") {
+                Expr::Wildcard
+            } else {
+                parse_expression(tokens)?
+            };
+            let alias = if tokens.accept_keyword("AS") {
+                Some(tokens.expect_identifier()?)
+            } else {
+                None
+            };
+            select_list.push(LogicalSelectListNode::new(field, alias.as_deref()));
+
+            if !tokens.accept_symbol(",") {
+                break;
+            }
+        }
+
+        Ok(LogicalPlan::Project(LogicalProjectNode::new(self, select_list)))
     }
 
-    pub fn parse_join(&mut self, token: &str, last_table: Option<String>) -> Result<(), ParsingError> {
-        let parts: Vec<&str> = token.trim_start_matches("JOIN").trim().split("ON").map(|s| s.trim()).collect();
-        if parts.len() != 2 {
-            return Err(ParsingError::new("Invalid JOIN syntax. Expected: JOIN table ON condition"));
+    /// Parses `EXTEND item [AS alias], ...` where an item is an expression or
+    /// a window function call. Runs of expressions and of window calls become
+    /// EXTEND and WINDOW nodes in turn, which keeps the columns in order.
+    pub fn parse_extend(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("EXTEND")?;
+        let mut plan = self;
+        let mut extensions = Vec::new();
+        let mut windows = Vec::new();
+        loop {
+            if WindowCall::is_next(tokens) {
+                if !extensions.is_empty() {
+                    plan = LogicalPlan::Extend(LogicalExtendNode::new(plan, std::mem::take(&mut extensions)));
+                }
+                let call = WindowCall::parse(tokens)?;
+                windows.push(LogicalWindowListNode {
+                    call,
+                    alias: Self::parse_item_alias(tokens)?,
+                });
+            } else {
+                if !windows.is_empty() {
+                    plan = LogicalPlan::Window(LogicalWindowNode::new(plan, std::mem::take(&mut windows)));
+                }
+                let expr = parse_expression(tokens)?;
+                let alias = Self::parse_item_alias(tokens)?;
+                extensions.push(LogicalSelectListNode::new(expr, alias.as_deref()));
+            }
+            if !tokens.accept_symbol(",") {
+                break;
+            }
+        }
+        if !windows.is_empty() {
+            plan = LogicalPlan::Window(LogicalWindowNode::new(plan, windows));
         }
+        if !extensions.is_empty() {
+            plan = LogicalPlan::Extend(LogicalExtendNode::new(plan, extensions));
+        }
+        Ok(plan)
+    }
 
-        let table2 = parts[0].to_string();
-        let condition = parts[1].to_string();
+    /// Parses `WINDOW call OVER (spec) [AS alias], ...`.
+    pub fn parse_window(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("WINDOW")?;
+        let mut windows = Vec::new();
+        loop {
+            let call = WindowCall::parse(tokens)?;
+            windows.push(LogicalWindowListNode {
+                call,
+                alias: Self::parse_item_alias(tokens)?,
+            });
+            if !tokens.accept_symbol(",") {
+                break;
+            }
+        }
+        Ok(LogicalPlan::Window(LogicalWindowNode::new(self, windows)))
+    }
 
-        if let Some(table1) = last_table {
-            self.joins.push(LogicalJoinNode::new(&table1, &table2, &condition));
+    fn parse_item_alias(tokens: &mut Tokens) -> Result<Option<String>, ParsingError> {
+        if tokens.accept_keyword("AS") {
+            tokens.expect_identifier().map(Some)
         } else {
-            return Err(ParsingError::new("JOIN must have a preceding FROM clause"));
+            Ok(None)
         }
+    }
 
-        Ok(())
+    /// Parses `SET column = expr, ...`.
+    pub fn parse_set(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("SET")?;
+        let mut assignments = Vec::new();
+        loop {
+            let column = tokens.expect_identifier()?;
+            tokens.expect_symbol("=")?;
+            assignments.push((column, parse_expression(tokens)?));
+            if !tokens.accept_symbol(",") {
+                break;
+            }
+        }
+        Ok(LogicalPlan::Set(LogicalSetNode::new(self, assignments)))
     }
 
-    pub fn parse_order_by(&mut self, token: &str) -> Result<(), ParsingError> {
-        let field = token.trim_start_matches("ORDER BY").trim();
-        self.order_by.push(LogicalOrderByNode::new(field));
-        Ok(())
+    /// Parses `DROP column, ...`.
+    pub fn parse_drop(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("DROP")?;
+        let mut columns = Vec::new();
+        loop {
+            columns.push(tokens.expect_identifier()?);
+            if !tokens.accept_symbol(",") {
+                break;
+            }
+        }
+        Ok(LogicalPlan::Drop(LogicalDropNode::new(self, columns)))
     }
+
+    /// Parses `RENAME column [AS] name, ...`.
+    pub fn parse_rename(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("RENAME")?;
+        let mut renames = Vec::new();
+        loop {
+            let column = tokens.expect_identifier()?;
+            tokens.accept_keyword("AS");
+            renames.push((column, tokens.expect_identifier()?));
+            if !tokens.accept_symbol(",") {
+                break;
+            }
+        }
+        Ok(LogicalPlan::Rename(LogicalRenameNode::new(self, renames)))
+    }
+
+    /// Parses `PIVOT (call [AS alias], ... FOR expr IN (value [AS name], ...))`.
+    pub fn parse_pivot(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("PIVOT")?;
+        LogicalPivotNode::parse(self, tokens).map(LogicalPlan::Pivot)
+    }
+
+    /// Parses `UNPIVOT [INCLUDE NULLS | EXCLUDE NULLS] (value FOR name IN (column [AS label], ...))`.
+    pub fn parse_unpivot(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("UNPIVOT")?;
+        LogicalUnpivotNode::parse(self, tokens).map(LogicalPlan::Unpivot)
+    }
+
+    /// Parses `TABLESAMPLE method (n PERCENT) [REPEATABLE (seed)]`, which
+    /// samples the table the query starts with.
+    pub fn parse_tablesample(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        let sample = TableSample::parse(tokens)?;
+        match self {
+            LogicalPlan::Scan(mut scan) if scan.sample.is_none() => {
+                scan.sample = Some(sample);
+                Ok(LogicalPlan::Scan(scan))
+            }
+            _ => Err(ParsingError::new("TABLESAMPLE must directly follow FROM with a table")),
+        }
+    }
+
+    /// Parses `AS alias`, which names the rows so far as a table.
+    pub fn parse_as(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("AS")?;
+        let alias = tokens.expect_identifier()?;
+        Ok(LogicalPlan::Alias(LogicalAliasNode::new(self, &alias)))
+    }
+
+    pub fn parse_where(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("WHERE")?;
+        let condition = parse_expression(tokens)?;
+        Ok(LogicalPlan::Filter(LogicalFilterNode::new(self, condition)))
+    }
+
+    /// Parses `[INNER | LEFT | RIGHT | FULL [OUTER] | CROSS] JOIN item`
+    /// followed by `ON condition` or `USING (columns)`, except for CROSS joins.
+    /// The item can be `UNNEST(array)` of the joined rows, with an optional
+    /// condition, in all but RIGHT and FULL joins.
+    pub fn parse_join(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        let join_type = if tokens.accept_keyword("LEFT") {
+            Some(JoinType::Left)
+        } else if tokens.accept_keyword("RIGHT") {
+            Some(JoinType::Right)
+        } else if tokens.accept_keyword("FULL") {
+            Some(JoinType::Full)
+        } else if tokens.accept_keyword("CROSS") {
+            None
+        } else {
+            tokens.accept_keyword("INNER");
+            Some(JoinType::Inner)
+        };
+        if matches!(join_type, Some(JoinType::Left | JoinType::Right | JoinType::Full)) {
+            tokens.accept_keyword("OUTER");
+        }
+        tokens.expect_keyword("JOIN")?;
+        if matches!(self, LogicalPlan::OneRow) {
+            return Err(ParsingError::new("JOIN must follow a FROM clause"));
+        }
+        if Self::peek_unnest(tokens) {
+            let outer = match join_type {
+                None | Some(JoinType::Inner) => false,
+                Some(JoinType::Left) => true,
+                Some(join_type) => return Err(ParsingError::new(&format!("UNNEST cannot be joined with a {} JOIN", join_type))),
+            };
+            let mut unnest = LogicalUnnestNode::parse(self, tokens, outer)?;
+            if join_type.is_some() && tokens.accept_keyword("ON") {
+                unnest.condition = parse_expression(tokens)?;
+            }
+            return Ok(LogicalPlan::Unnest(unnest));
+        }
+        let right = Self::parse_from_item(tokens)?;
+
+        let Some(join_type) = join_type else {
+            let condition = Expr::Literal(Value::Bool(true));
+            return Ok(LogicalPlan::Join(LogicalJoinNode::new(self, right, JoinType::Inner, condition)));
+        };
+        if tokens.accept_keyword("USING") {
+            let mut join = LogicalJoinNode::new(self, right, join_type, Expr::Literal(Value::Bool(true)));
+            join.using = tokens.parse_identifier_list()?;
+            return Ok(LogicalPlan::Join(join));
+        }
+        tokens.expect_keyword("ON")?;
+        let condition = parse_expression(tokens)?;
+        Ok(LogicalPlan::Join(LogicalJoinNode::new(self, right, join_type, condition)))
+    }
+
+    /// Parses `ORDER BY expr [ASC | DESC] [NULLS FIRST | NULLS LAST], ...`.
+    pub fn parse_order_by(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("ORDER")?;
+        tokens.expect_keyword("BY")?;
+        let mut keys = Vec::new();
+        loop {
+            let expr = parse_expression(tokens)?;
+            keys.push(parse_sort_order(tokens, expr)?);
+            if !tokens.accept_symbol(",") {
+                break;
+            }
+        }
+        Ok(LogicalPlan::Sort(LogicalOrderByNode::new(self, keys)))
+    }
+
+    /// Parses `LIMIT count [OFFSET skipped]`.
+    pub fn parse_limit(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("LIMIT")?;
+        let limit = parse_row_count(tokens, "LIMIT")?;
+        let offset = if tokens.accept_keyword("OFFSET") {
+            parse_row_count(tokens, "OFFSET")?
+        } else {
+            0
+        };
+        Ok(LogicalPlan::Limit(LogicalLimitNode::new(self, limit, offset)))
+    }
+
+    /// Parses `UNION {ALL | DISTINCT} (query), ...` or `INTERSECT DISTINCT` /
+    /// `EXCEPT DISTINCT` followed by queries; several queries are combined
+    /// from left to right.
+    pub fn parse_set_operation(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        let operator = if tokens.accept_keyword("UNION") {
+            SetOperator::Union
+        } else if tokens.accept_keyword("INTERSECT") {
+            SetOperator::Intersect
+        } else {
+            tokens.expect_keyword("EXCEPT")?;
+            SetOperator::Except
+        };
+        let all = tokens.accept_keyword("ALL");
+        if !all {
+            tokens.expect_keyword("DISTINCT")?;
+        } else if operator != SetOperator::Union {
+            return Err(ParsingError::new(&format!("{} ALL is not supported, use {} DISTINCT", operator, operator)));
+        }
+
+        let mut plan = self;
+        loop {
+            tokens.expect_symbol("(")?;
+            let query = parse_query(tokens)?;
+            tokens.expect_symbol(")")?;
+            plan = LogicalPlan::SetOperation(LogicalSetOperationNode::new(plan, query, operator, all));
+            if !tokens.accept_symbol(",") {
+                break;
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Parses `AGGREGATE call [AS alias], ... [GROUP [AND ORDER] BY expr [AS alias], ...]`.
+    /// GROUP AND ORDER BY sorts the result by the grouping columns, each in
+    /// the direction given after it.
+    pub fn parse_aggregate(self, tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+        tokens.expect_keyword("AGGREGATE")?;
+        let mut aggregates = Vec::new();
+        if !tokens.peek_keyword("GROUP") {
+            loop {
+                let call = AggregateCall::parse(tokens)?;
+                let alias = if tokens.accept_keyword("AS") {
+                    Some(tokens.expect_identifier()?)
+                } else {
+                    None
+                };
+                aggregates.push(LogicalAggregateListNode { call, alias });
+                if !tokens.accept_symbol(",") {
+                    break;
+                }
+            }
+        }
+
+        let mut group_by = Vec::new();
+        let mut order = Vec::new();
+        let mut ordered = false;
+        if tokens.accept_keyword("GROUP") {
+            if tokens.accept_keyword("AND") {
+                tokens.expect_keyword("ORDER")?;
+                ordered = true;
+            }
+            tokens.expect_keyword("BY")?;
+            loop {
+                let expr = parse_expression(tokens)?;
+                let alias = if tokens.accept_keyword("AS") {
+                    Some(tokens.expect_identifier()?)
+                } else {
+                    None
+                };
+                if ordered {
+                    // sort on the output column the key becomes
+                    let output = match (&alias, &expr) {
+                        (Some(alias), _) => Expr::Column {
+                            qualifier: None,
+                            name: alias.clone(),
+                        },
+                        (None, Expr::Column { .. }) => expr.clone(),
+                        (None, expr) => Expr::Column {
+                            qualifier: None,
+                            name: expr.to_string(),
+                        },
+                    };
+                    order.push(parse_sort_order(tokens, output)?);
+                }
+                group_by.push(LogicalSelectListNode::new(expr, alias.as_deref()));
+                if !tokens.accept_symbol(",") {
+                    break;
+                }
+            }
+        }
+        if aggregates.is_empty() && group_by.is_empty() {
+            return Err(tokens.unexpected("an aggregate function or GROUP BY"));
+        }
+
+        let plan = LogicalPlan::Aggregate(LogicalAggregateNode::new(self, group_by, aggregates));
+        if ordered {
+            return Ok(LogicalPlan::Sort(LogicalOrderByNode::new(plan, order)));
+        }
+        Ok(plan)
+    }
+}
+
+/// Parses the non-negative integer literal after LIMIT or OFFSET.
+fn parse_row_count(tokens: &mut Tokens, clause: &str) -> Result<usize, ParsingError> {
+    match tokens.next() {
+        Some(Token::Number(number)) => number
+            .parse::<usize>()
+            .map_err(|_| ParsingError::new(&format!("{} must be a non-negative integer, found {}", clause, number))),
+        _ => Err(ParsingError::new(&format!("Expected a row count after {}", clause))),
+    }
+}
+
+/// Parses the optional `ASC | DESC` and `NULLS FIRST | NULLS LAST` after a sort key.
+pub fn parse_sort_order(tokens: &mut Tokens, expr: Expr) -> Result<SortKey, ParsingError> {
+    let descending = if tokens.accept_keyword("DESC") {
+        true
+    } else {
+        tokens.accept_keyword("ASC");
+        false
+    };
+    let nulls_first = if tokens.accept_keyword("NULLS") {
+        if tokens.accept_keyword("FIRST") {
+            Some(true)
+        } else {
+            tokens.expect_keyword("LAST")?;
+            Some(false)
+        }
+    } else {
+        None
+    };
+    Ok(SortKey::new(expr, descending, nulls_first))
 }
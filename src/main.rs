@@ -7,32 +7,75 @@ mod scanner;
 mod select;
 mod filter;
 mod order_by;
+mod alias;
+mod limit;
+mod set_operation;
+mod window;
+mod subquery;
+mod common_table;
+mod pivot;
+mod nested;
+mod binder;
+mod aggregate;
+mod lexer;
+mod value;
+mod decimal;
+mod json;
+mod schema;
+mod expression;
+mod function;
+mod temporal;
+mod timezone;
+mod catalog;
+mod statement;
+mod constraints;
+mod database;
+mod optimizer;
+mod statistics;
+mod cost;
+mod join_order;
+mod random;
+mod sample;
+mod hyperloglog;
+mod analyze;
+mod executor;
 
-use parser::parse_pipe_sql;
-use physical_plan::convert_to_physical_plan;
+use database::Database;
+use parser::parse_statement;
 use std::io::{self, Write};
+use std::path::PathBuf;
 
 fn main() {
     println!("🚀 BigQuery Pipe SQL Parser 🚀");
 
+    let directory = PathBuf::from(std::env::args().nth(1).unwrap_or_else(|| "sloth_data".to_string()));
+    let mut database = match Database::open(&directory) {
+        Ok(database) => database,
+        Err(e) => {
+            println!("❌ Error: {}", e);
+            return;
+        }
+    };
+
     loop {
         print!("BigQuery |> ");
         io::stdout().flush().unwrap();
 
         let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+        if io::stdin().read_line(&mut input).unwrap() == 0 {
+            break;
+        }
 
         if input.trim().eq_ignore_ascii_case("exit") {
             println!("Exiting...");
             break;
         }
 
-        match parse_pipe_sql(&input) {
-            Ok(logical_plan) => {
-                println!("✅ Logical Plan:\n{:#?}", logical_plan);
-                let physical_plan = convert_to_physical_plan(&logical_plan);
-                println!("✅ Physical Plan:\n{:#?}", physical_plan);
-            }
+        match parse_statement(&input) {
+            Ok(statement) => match database.execute(statement) {
+                Ok(message) => println!("✅ {}", message),
+                Err(e) => println!("❌ Error: {}", e),
+            },
             Err(e) => {
                 println!("❌ Error: {}", e);
             }
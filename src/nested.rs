@@ -0,0 +1,225 @@
+use crate::catalog::Catalog;
+use crate::errors::{ExecutionError, ParsingError};
+use crate::expression::{parse_expression, Expr};
+use crate::lexer::{Token, Tokens};
+use crate::logical_plan::LogicalPlan;
+use crate::schema::{Field, Schema};
+use crate::value::{DataType, Value};
+
+/// Words after `UNNEST(...)` that cannot be its alias.
+const UNNEST_TERMINATORS: [&str; 3] = ["ON", "USING", "WITH"];
+
+/// `UNNEST(array) [AS] alias [WITH OFFSET [AS] offset]` joined to its input:
+/// every input row is repeated for each element of the array it gives, with
+/// the element, and its position from zero, as new columns. An outer unnest
+/// (`LEFT JOIN UNNEST`) also keeps, with NULLs, the rows whose array has no
+/// element satisfying the condition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalUnnestNode {
+    pub input: Box<LogicalPlan>,
+    pub array: Expr,
+    pub alias: String,
+    pub offset: Option<String>,
+    pub outer: bool,
+    pub condition: Expr,
+}
+
+impl LogicalUnnestNode {
+    /// Parses `UNNEST(array) [[AS] alias] [WITH OFFSET [[AS] name]]`. Without
+    /// an alias, the elements of a column are named after the column.
+    pub fn parse(input: LogicalPlan, tokens: &mut Tokens, outer: bool) -> Result<Self, ParsingError> {
+        tokens.expect_keyword("UNNEST")?;
+        tokens.expect_symbol("(")?;
+        let array = parse_expression(tokens)?;
+        tokens.expect_symbol(")")?;
+        let alias = match parse_alias(tokens)? {
+            Some(alias) => alias,
+            None => match &array {
+                Expr::Column { name, .. } | Expr::Field { name, .. } => name.clone(),
+                array => return Err(ParsingError::new(&format!("UNNEST({}) needs an alias", array))),
+            },
+        };
+        let offset = if tokens.accept_keyword("WITH") {
+            tokens.expect_keyword("OFFSET")?;
+            Some(parse_alias(tokens)?.unwrap_or_else(|| "offset".to_string()))
+        } else {
+            None
+        };
+        Ok(Self {
+            input: Box::new(input),
+            array,
+            alias,
+            offset,
+            outer,
+            condition: Expr::Literal(Value::Bool(true)),
+        })
+    }
+
+    /// The input columns followed by the element and offset columns, which
+    /// the condition reads.
+    pub fn fields(&self, input: &Schema) -> Result<Vec<Field>, ExecutionError> {
+        let element = match self.array.data_type(input)? {
+            DataType::Array(element) => *element,
+            data_type => return Err(ExecutionError::new(&format!("UNNEST needs an ARRAY, got {}", data_type))),
+        };
+        let mut fields = input.fields.clone();
+        fields.push(Field::new(None, &self.alias, element));
+        if let Some(offset) = &self.offset {
+            fields.push(Field::new(None, offset, DataType::Int64));
+        }
+        Ok(fields)
+    }
+}
+
+fn parse_alias(tokens: &mut Tokens) -> Result<Option<String>, ParsingError> {
+    if tokens.accept_keyword("AS") {
+        return tokens.expect_identifier().map(Some);
+    }
+    match tokens.peek() {
+        Some(Token::Identifier(word)) if !UNNEST_TERMINATORS.iter().any(|w| word.eq_ignore_ascii_case(w)) => {
+            tokens.expect_identifier().map(Some)
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Rewrites every `a.b` that names no column but field `b` of a struct or
+/// JSON column `a` into a field access, which the parser cannot tell apart. Nodes whose
+/// inputs have no schema are left for planning to report.
+pub fn resolve_fields(plan: LogicalPlan, catalog: &Catalog) -> LogicalPlan {
+    let plan = match plan.map_inputs(|input| resolve_fields(input, catalog)) {
+        LogicalPlan::Apply(mut apply) => {
+            let subquery = std::mem::replace(&mut *apply.subquery, LogicalPlan::OneRow);
+            *apply.subquery = resolve_fields(subquery, catalog);
+            LogicalPlan::Apply(apply)
+        }
+        // the condition also reads the element, whose type needs the array
+        LogicalPlan::Unnest(mut unnest) => {
+            if let Ok(input) = unnest.input.schema(catalog) {
+                unnest.array = resolve_field_accesses(unnest.array, &input);
+                if let Ok(fields) = unnest.fields(&input) {
+                    unnest.condition = resolve_field_accesses(unnest.condition, &Schema::new(fields));
+                }
+            }
+            return LogicalPlan::Unnest(unnest);
+        }
+        plan => plan,
+    };
+    match plan.expr_scope(catalog) {
+        Ok(scope) => plan.map_exprs(&mut |expr| resolve_field_accesses(expr, &scope)),
+        Err(_) => plan,
+    }
+}
+
+fn resolve_field_accesses(expr: Expr, scope: &Schema) -> Expr {
+    expr.transform(&mut |expr| match expr {
+        Expr::Column {
+            qualifier: Some(qualifier),
+            name,
+        } if scope.resolve(Some(&qualifier), &name).is_err()
+            && scope
+                .resolve(None, &qualifier)
+                .is_ok_and(|index| matches!(scope.fields[index].data_type, DataType::Struct(_) | DataType::Json)) =>
+        {
+            Expr::Field {
+                expr: Box::new(Expr::Column {
+                    qualifier: None,
+                    name: qualifier,
+                }),
+                name,
+            }
+        }
+        expr => expr,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::function::lookup;
+    use std::cmp::Ordering;
+
+    fn unnest(text: &str) -> Result<LogicalUnnestNode, ParsingError> {
+        LogicalUnnestNode::parse(LogicalPlan::OneRow, &mut Tokens::new(text).unwrap(), false)
+    }
+
+    fn data_type(text: &str) -> Result<DataType, ParsingError> {
+        DataType::parse(&mut Tokens::new(text).unwrap())
+    }
+
+    #[test]
+    fn parses_unnest_aliases_and_offsets() {
+        let node = unnest("UNNEST(t.tags) WITH OFFSET").unwrap();
+        assert_eq!((node.alias.as_str(), node.offset.as_deref()), ("tags", Some("offset")));
+        let node = unnest("UNNEST([1, 2]) AS n WITH OFFSET AS i").unwrap();
+        assert_eq!((node.alias.as_str(), node.offset.as_deref()), ("n", Some("i")));
+        // a join condition is not an alias
+        let node = unnest("UNNEST(tags) ON tags > 1").unwrap();
+        assert_eq!((node.alias.as_str(), node.offset), ("tags", None));
+        assert_eq!(unnest("UNNEST([1, 2]) ON TRUE").unwrap_err().message, "UNNEST([1, 2]) needs an alias");
+
+        let input = Schema::new(vec![
+            Field::new(None, "id", DataType::Int64),
+            Field::new(None, "tags", DataType::Array(Box::new(DataType::String))),
+        ]);
+        let fields = unnest("UNNEST(tags) AS tag WITH OFFSET").unwrap().fields(&input).unwrap();
+        let names: Vec<(&str, String)> = fields.iter().map(|f| (f.name.as_str(), f.data_type.to_string())).collect();
+        assert_eq!(names[2..], [("tag", "STRING".to_string()), ("offset", "INT64".to_string())]);
+        assert_eq!(unnest("UNNEST(id) AS i").unwrap().fields(&input).unwrap_err().message, "UNNEST needs an ARRAY, got INT64");
+    }
+
+    #[test]
+    fn resolves_struct_fields_that_shadow_no_column() {
+        let point = DataType::Struct(vec![("x".to_string(), DataType::Int64), ("y".to_string(), DataType::Int64)]);
+        let scope = Schema::new(vec![Field::new(Some("t"), "p", point), Field::new(Some("t"), "x", DataType::Int64)]);
+        let expr = |text: &str| parse_expression(&mut Tokens::new(text).unwrap()).unwrap();
+        let field = |name: &str| Expr::Field {
+            expr: Box::new(expr("p")),
+            name: name.to_string(),
+        };
+        assert_eq!(resolve_field_accesses(expr("p.y"), &scope), field("y"));
+        assert_eq!(resolve_field_accesses(expr("t.x"), &scope), expr("t.x"));
+        assert_eq!(resolve_field_accesses(expr("q.y"), &scope), expr("q.y"));
+    }
+
+    #[test]
+    fn types_and_compares_nested_values() {
+        let parsed = data_type("STRUCT<name STRING, scores ARRAY<INT64>, STRUCT<BOOL>>").unwrap();
+        assert_eq!(parsed.to_string(), "STRUCT<name STRING, scores ARRAY<INT64>, STRUCT<BOOL>>");
+        assert_eq!(
+            data_type("ARRAY<ARRAY<INT64>>").unwrap_err().message,
+            "An ARRAY cannot hold ARRAYs, only STRUCTs with ARRAY fields"
+        );
+        assert_eq!(data_type("STRUCT<a INT64, A STRING>").unwrap_err().message, "STRUCT has two fields named A");
+
+        // structs match field by field, keeping the names of the first
+        let named = data_type("STRUCT<a INT64, b STRING>").unwrap();
+        let anonymous = data_type("STRUCT<FLOAT64, STRING>").unwrap();
+        assert_eq!(named.common_supertype(&anonymous).unwrap().to_string(), "STRUCT<a FLOAT64, b STRING>");
+        assert_eq!(anonymous.common_supertype(&named).unwrap().to_string(), "STRUCT<a FLOAT64, b STRING>");
+        assert!(named.common_supertype(&data_type("STRUCT<INT64>").unwrap()).is_none());
+        assert!(named.can_cast_to(&anonymous));
+        assert!(!named.can_cast_to(&data_type("ARRAY<STRING>").unwrap()));
+
+        // up to the first field that differs, which is unknown if it is NULL
+        let pair = |a: Value, b: Value| Value::Struct(vec![a, b]);
+        let (one, two) = (Value::Int(1), Value::Int(2));
+        assert_eq!(pair(one.clone(), two.clone()).compare(&pair(one.clone(), one.clone())), Some(Ordering::Greater));
+        assert_eq!(pair(one.clone(), Value::Null).compare(&pair(two.clone(), Value::Null)), Some(Ordering::Less));
+        assert_eq!(pair(one.clone(), Value::Null).compare(&pair(one.clone(), two.clone())), None);
+        let array = Value::Array(vec![one, Value::Float(2.5)]);
+        assert_eq!(array.coerce_to(DataType::Array(Box::new(DataType::Float64))).unwrap().to_string(), "[1, 2.5]");
+        assert_eq!(pair(two, Value::Null).to_string(), "STRUCT(2, NULL)");
+
+        // subscripts out of range fail unless they are SAFE
+        let subscript = |name: &str, position: i64| {
+            let items = Value::Array(vec![Value::Int(10), Value::Int(20)]);
+            lookup(name).unwrap().call(vec![items, Value::Int(position)])
+        };
+        assert_eq!(subscript("OFFSET", 1).unwrap(), Value::Int(20));
+        assert_eq!(subscript("ORDINAL", 1).unwrap(), Value::Int(10));
+        assert_eq!(subscript("SAFE_ORDINAL", 0).unwrap(), Value::Null);
+        assert_eq!(subscript("SAFE_OFFSET", -1).unwrap(), Value::Null);
+        assert_eq!(subscript("OFFSET", 2).unwrap_err().message, "OFFSET(2) is out of range for an array of 2 elements");
+    }
+}
@@ -0,0 +1,799 @@
+use crate::catalog::Catalog;
+use crate::expression::{BinaryOperator, Expr, UnaryOperator};
+use crate::filter::LogicalFilterNode;
+use crate::join::JoinType;
+use crate::logical_plan::LogicalPlan;
+use crate::schema::{Field, Schema};
+use crate::select::{expand_select_list, LogicalProjectNode};
+use crate::set_operation::set_operation_fields;
+use crate::value::{DataType, Value};
+use crate::window::WindowFunction;
+
+const TRUE: Expr = Expr::Literal(Value::Bool(true));
+
+/// Applies every rewrite rule to the plan.
+pub fn optimize(plan: LogicalPlan, catalog: &Catalog) -> LogicalPlan {
+    let plan = expand_column_stages(plan, catalog);
+    let plan = expand_using(plan, catalog);
+    let plan = simplify_expressions(plan);
+    let plan = push_down_predicates(plan, catalog);
+    prune_columns(plan, catalog)
+}
+
+/// Rewrites EXTEND, SET, DROP and RENAME into the projections they stand
+/// for, PIVOT into an aggregation and UNPIVOT into a union of projections.
+/// The plan must have a schema, which the caller checks beforehand.
+pub fn expand_column_stages(plan: LogicalPlan, catalog: &Catalog) -> LogicalPlan {
+    let plan = plan.map_inputs(|input| expand_column_stages(input, catalog));
+    let Some(input) = plan.inputs().first().and_then(|input| input.schema(catalog).ok()) else {
+        return plan;
+    };
+    let expanded = match &plan {
+        LogicalPlan::Pivot(pivot) => pivot.aggregate(&input).map(LogicalPlan::Aggregate).ok(),
+        // the branches repeat the input, whose stages are already expanded
+        LogicalPlan::Unpivot(unpivot) => unpivot.union(&input).ok(),
+        _ => None,
+    };
+    if let Some(expanded) = expanded {
+        return expanded;
+    }
+    match plan.select_list(&input) {
+        Ok(Some(select_list)) => {
+            let input = plan.inputs()[0].clone();
+            LogicalPlan::Project(LogicalProjectNode::new(input, select_list))
+        }
+        _ => plan,
+    }
+}
+
+/// Rewrites `JOIN ... USING (columns)` into a join on the equality of the
+/// columns below a projection that keeps one copy of each. Joins whose inputs
+/// have no schema (e.g. an unknown table) are left for execution to report.
+pub fn expand_using(plan: LogicalPlan, catalog: &Catalog) -> LogicalPlan {
+    match plan.map_inputs(|input| expand_using(input, catalog)) {
+        LogicalPlan::Join(mut join) if !join.using.is_empty() => {
+            let expanded = match (join.left.schema(catalog), join.right.schema(catalog)) {
+                (Ok(left), Ok(right)) => join.expand_using(&left, &right).ok(),
+                _ => None,
+            };
+            let Some((condition, select_list)) = expanded else {
+                return LogicalPlan::Join(join);
+            };
+            join.condition = condition;
+            join.using.clear();
+            LogicalPlan::Project(LogicalProjectNode::new(LogicalPlan::Join(join), select_list))
+        }
+        plan => plan,
+    }
+}
+
+/// Folds constants and simplifies boolean logic in every expression, dropping
+/// filters that are always true.
+pub fn simplify_expressions(plan: LogicalPlan) -> LogicalPlan {
+    let simplify = |expr: Expr| simplify_boolean(fold_constants(expr));
+
+    let mut plan = plan.map_inputs(simplify_expressions);
+    let names: Vec<String> = plan.output_names().into_iter().map(|(name, _)| name).collect();
+    let mut plan = match plan {
+        LogicalPlan::Filter(filter) => {
+            let condition = simplify(filter.condition);
+            if condition == TRUE {
+                *filter.input
+            } else {
+                LogicalPlan::Filter(LogicalFilterNode { condition, ..filter })
+            }
+        }
+        LogicalPlan::Join(mut join) => {
+            join.condition = simplify(join.condition);
+            LogicalPlan::Join(join)
+        }
+        LogicalPlan::Project(mut project) => {
+            for select in &mut project.select_list {
+                select.field = simplify(std::mem::replace(&mut select.field, Expr::Wildcard));
+            }
+            LogicalPlan::Project(project)
+        }
+        LogicalPlan::Aggregate(mut aggregate) => {
+            for group in &mut aggregate.group_by {
+                group.field = simplify(std::mem::replace(&mut group.field, Expr::Wildcard));
+            }
+            for aggregate in &mut aggregate.aggregates {
+                aggregate.call.argument = aggregate.call.argument.take().map(simplify);
+            }
+            LogicalPlan::Aggregate(aggregate)
+        }
+        LogicalPlan::Sort(mut sort) => {
+            for key in &mut sort.keys {
+                key.expr = simplify(std::mem::replace(&mut key.expr, Expr::Wildcard));
+            }
+            LogicalPlan::Sort(sort)
+        }
+        LogicalPlan::Window(mut window) => {
+            for window in &mut window.windows {
+                let call = &mut window.call;
+                for expr in call.args.iter_mut().chain(&mut call.spec.partition_by) {
+                    *expr = simplify(std::mem::replace(expr, Expr::Wildcard));
+                }
+                for key in &mut call.spec.order_by {
+                    key.expr = simplify(std::mem::replace(&mut key.expr, Expr::Wildcard));
+                }
+                if let WindowFunction::Aggregate(aggregate) = &mut call.function {
+                    aggregate.argument = aggregate.argument.take().map(simplify);
+                }
+            }
+            LogicalPlan::Window(window)
+        }
+        LogicalPlan::Unnest(mut unnest) => {
+            unnest.array = simplify(unnest.array);
+            unnest.condition = simplify(unnest.condition);
+            LogicalPlan::Unnest(unnest)
+        }
+        plan => plan,
+    };
+    // folding does not rename the columns it computes
+    plan.keep_output_names(names);
+    plan
+}
+
+/// Replaces subexpressions without column references by their value.
+/// Expressions that fail to evaluate (e.g. division by zero) are kept so the
+/// error surfaces at execution time, and so are those that give NULL, an
+/// empty array or a struct but have another type than the literal would.
+pub fn fold_constants(expr: Expr) -> Expr {
+    let expr = match expr {
+        Expr::Unary { op, expr } => Expr::Unary {
+            op,
+            expr: Box::new(fold_constants(*expr)),
+        },
+        Expr::Binary { left, op, right } => Expr::binary(fold_constants(*left), op, fold_constants(*right)),
+        Expr::IsNull { expr, negated } => Expr::IsNull {
+            expr: Box::new(fold_constants(*expr)),
+            negated,
+        },
+        Expr::Function { name, args } => Expr::Function {
+            name,
+            args: args.into_iter().map(fold_constants).collect(),
+        },
+        Expr::Cast { expr, data_type, safe } => Expr::Cast {
+            expr: Box::new(fold_constants(*expr)),
+            data_type,
+            safe,
+        },
+        expr => return expr,
+    };
+
+    let constant = match &expr {
+        Expr::Unary { expr, .. } | Expr::IsNull { expr, .. } | Expr::Cast { expr, .. } => {
+            matches!(**expr, Expr::Literal(_))
+        }
+        Expr::Binary { left, right, .. } => {
+            matches!(**left, Expr::Literal(_)) && matches!(**right, Expr::Literal(_))
+        }
+        Expr::Function { args, .. } => args.iter().all(|arg| matches!(arg, Expr::Literal(_))),
+        _ => false,
+    };
+    if constant {
+        match expr.evaluate(&Schema::default(), &[]) {
+            Ok(Value::Null) if expr.data_type(&Schema::default()).ok() != Some(DataType::Int64) => {}
+            Ok(value @ (Value::Array(_) | Value::Struct(_))) if value.data_type() != expr.data_type(&Schema::default()).ok() => {}
+            Ok(value) => return Expr::Literal(value),
+            Err(_) => {}
+        }
+    }
+    expr
+}
+
+/// Removes redundant boolean structure: identities of AND/OR, double negation,
+/// negated literals and negated comparisons.
+pub fn simplify_boolean(expr: Expr) -> Expr {
+    const FALSE: Expr = Expr::Literal(Value::Bool(false));
+
+    match expr {
+        Expr::Binary { left, op, right } if matches!(op, BinaryOperator::And | BinaryOperator::Or) => {
+            let left = simplify_boolean(*left);
+            let right = simplify_boolean(*right);
+            // (absorbing element, identity element)
+            let (absorbing, identity) = if op == BinaryOperator::And {
+                (FALSE, TRUE)
+            } else {
+                (TRUE, FALSE)
+            };
+            if left == absorbing || right == absorbing {
+                absorbing
+            } else if left == identity || left == right {
+                right
+            } else if right == identity {
+                left
+            } else {
+                Expr::binary(left, op, right)
+            }
+        }
+        Expr::Binary { left, op, right } => {
+            Expr::binary(simplify_boolean(*left), op, simplify_boolean(*right))
+        }
+        Expr::Unary {
+            op: UnaryOperator::Not,
+            expr,
+        } => match simplify_boolean(*expr) {
+            Expr::Unary {
+                op: UnaryOperator::Not,
+                expr,
+            } => *expr,
+            Expr::Literal(Value::Bool(b)) => Expr::Literal(Value::Bool(!b)),
+            Expr::Binary { left, op, right } if negate_comparison(op).is_some() => Expr::Binary {
+                left,
+                op: negate_comparison(op).unwrap(),
+                right,
+            },
+            Expr::IsNull { expr, negated } => Expr::IsNull {
+                expr,
+                negated: !negated,
+            },
+            expr => Expr::Unary {
+                op: UnaryOperator::Not,
+                expr: Box::new(expr),
+            },
+        },
+        Expr::Unary { op, expr } => Expr::Unary {
+            op,
+            expr: Box::new(simplify_boolean(*expr)),
+        },
+        Expr::IsNull { expr, negated } => Expr::IsNull {
+            expr: Box::new(simplify_boolean(*expr)),
+            negated,
+        },
+        expr => expr,
+    }
+}
+
+fn negate_comparison(op: BinaryOperator) -> Option<BinaryOperator> {
+    match op {
+        BinaryOperator::Eq => Some(BinaryOperator::NotEq),
+        BinaryOperator::NotEq => Some(BinaryOperator::Eq),
+        BinaryOperator::Lt => Some(BinaryOperator::GtEq),
+        BinaryOperator::LtEq => Some(BinaryOperator::Gt),
+        BinaryOperator::Gt => Some(BinaryOperator::LtEq),
+        BinaryOperator::GtEq => Some(BinaryOperator::Lt),
+        BinaryOperator::IsDistinctFrom => Some(BinaryOperator::IsNotDistinctFrom),
+        BinaryOperator::IsNotDistinctFrom => Some(BinaryOperator::IsDistinctFrom),
+        _ => None,
+    }
+}
+
+/// Moves filter conjuncts as close to the scans as possible: through sorts,
+/// through projections and subquery aliases (rewriting names into the
+/// expressions they stand for) and into the side of a join whose columns they
+/// read. Conjuncts reading both sides of an inner join become part of its
+/// condition. Outer joins only take filters of the side whose rows they
+/// preserve, and push their own condition only into the other side.
+pub fn push_down_predicates(plan: LogicalPlan, catalog: &Catalog) -> LogicalPlan {
+    push_down(plan, Vec::new(), catalog)
+}
+
+fn push_down(plan: LogicalPlan, mut predicates: Vec<Expr>, catalog: &Catalog) -> LogicalPlan {
+    match plan {
+        LogicalPlan::Filter(filter) => {
+            predicates.extend(filter.condition.into_conjuncts());
+            push_down(*filter.input, predicates, catalog)
+        }
+        LogicalPlan::Scan(mut scan) => {
+            scan.filters.extend(predicates);
+            LogicalPlan::Scan(scan)
+        }
+        LogicalPlan::Sort(mut sort) => {
+            sort.input = Box::new(push_down(*sort.input, predicates, catalog));
+            LogicalPlan::Sort(sort)
+        }
+        // a row passes the filter on either side exactly when it passes it above
+        LogicalPlan::SetOperation(mut set) => {
+            let schemas = (set.left.schema(catalog), set.right.schema(catalog));
+            let (Ok(left), Ok(right)) = schemas else {
+                let plan = LogicalPlan::SetOperation(set).map_inputs(|input| push_down(input, Vec::new(), catalog));
+                return with_filter(plan, predicates);
+            };
+            let Ok(output) = set_operation_fields(set.operator, &left, &right) else {
+                return with_filter(LogicalPlan::SetOperation(set), predicates);
+            };
+            let positional = |input: &Schema| -> Vec<(Expr, Field)> {
+                input
+                    .fields
+                    .iter()
+                    .zip(&output)
+                    .map(|(field, output)| {
+                        let column = Expr::Column {
+                            qualifier: field.qualifier.clone(),
+                            name: field.name.clone(),
+                        };
+                        (column, output.clone())
+                    })
+                    .collect()
+            };
+            let (left_fields, right_fields) = (positional(&left), positional(&right));
+            let (mut to_left, mut to_right, mut above) = (Vec::new(), Vec::new(), Vec::new());
+            for predicate in predicates {
+                match (substitute(&predicate, &left_fields), substitute(&predicate, &right_fields)) {
+                    (Some(l), Some(r)) if reads_only(&l, &left) && reads_only(&r, &right) => {
+                        to_left.push(l);
+                        to_right.push(r);
+                    }
+                    _ => above.push(predicate),
+                }
+            }
+            set.left = Box::new(push_down(*set.left, to_left, catalog));
+            set.right = Box::new(push_down(*set.right, to_right, catalog));
+            with_filter(LogicalPlan::SetOperation(set), above)
+        }
+        // removing whole partitions leaves the windows of the others unchanged,
+        // so filters on columns every window partitions by go below
+        LogicalPlan::Window(mut window) => {
+            let input = window.input.schema(catalog).ok();
+            let partitioned_by = |input: &Schema, index: usize| {
+                window.windows.iter().all(|window| {
+                    window.call.spec.partition_by.iter().any(|expr| {
+                        matches!(expr, Expr::Column { qualifier, name }
+                            if input.resolve(qualifier.as_deref(), name).ok() == Some(index))
+                    })
+                })
+            };
+            let (mut below, mut above) = (Vec::new(), Vec::new());
+            for predicate in predicates {
+                let pushable = input.as_ref().is_some_and(|input| {
+                    reads_only(&predicate, input)
+                        && predicate.columns().iter().all(|(qualifier, name)| {
+                            let index = input.resolve(qualifier.as_deref(), name).unwrap();
+                            partitioned_by(input, index)
+                        })
+                });
+                if pushable {
+                    below.push(predicate);
+                } else {
+                    above.push(predicate);
+                }
+            }
+            window.input = Box::new(push_down(*window.input, below, catalog));
+            with_filter(LogicalPlan::Window(window), above)
+        }
+        // filtering before the limit would let other rows through
+        LogicalPlan::Limit(mut limit) => {
+            limit.input = Box::new(push_down(*limit.input, Vec::new(), catalog));
+            with_filter(LogicalPlan::Limit(limit), predicates)
+        }
+        LogicalPlan::Project(mut project) => {
+            let fields = project.input.schema(catalog).ok().and_then(|input| {
+                let select_list = project.select_list.iter().map(|s| (&s.field, s.alias.as_deref()));
+                expand_select_list(select_list, &input).ok()
+            });
+            let (mut below, mut above) = (Vec::new(), Vec::new());
+            for predicate in predicates {
+                match fields.as_ref().and_then(|fields| substitute(&predicate, fields)) {
+                    Some(rewritten) => below.push(rewritten),
+                    None => above.push(predicate),
+                }
+            }
+            project.input = Box::new(push_down(*project.input, below, catalog));
+            with_filter(LogicalPlan::Project(project), above)
+        }
+        LogicalPlan::Join(mut join) => {
+            let on = std::mem::replace(&mut join.condition, TRUE).into_conjuncts();
+            let (left, right) = match (join.left.schema(catalog), join.right.schema(catalog)) {
+                (Ok(left), Ok(right)) if join.using.is_empty() => (left, right),
+                _ => {
+                    join.condition = Expr::conjunction(on).unwrap_or(TRUE);
+                    return with_filter(LogicalPlan::Join(join), predicates);
+                }
+            };
+
+            let join_type = join.join_type;
+            let (mut to_left, mut to_right, mut condition, mut above) = (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+            // a side can be filtered early by conjuncts of the condition unless
+            // its unmatched rows are kept, and by filters above the join
+            // unless its columns may be NULL padding
+            for predicate in on {
+                if predicate == TRUE {
+                    continue;
+                }
+                if reads_only(&predicate, &left) && !join_type.preserves_left() {
+                    to_left.push(predicate);
+                } else if reads_only(&predicate, &right) && !join_type.preserves_right() {
+                    to_right.push(predicate);
+                } else {
+                    condition.push(predicate);
+                }
+            }
+            for predicate in predicates {
+                if reads_only(&predicate, &left) && !join_type.preserves_right() {
+                    to_left.push(predicate);
+                } else if reads_only(&predicate, &right) && !join_type.preserves_left() {
+                    to_right.push(predicate);
+                } else if join_type == JoinType::Inner {
+                    condition.push(predicate);
+                } else {
+                    above.push(predicate);
+                }
+            }
+            join.condition = Expr::conjunction(condition).unwrap_or(TRUE);
+            join.left = Box::new(push_down(*join.left, to_left, catalog));
+            join.right = Box::new(push_down(*join.right, to_right, catalog));
+            with_filter(LogicalPlan::Join(join), above)
+        }
+        // only filters on grouping columns can be applied before grouping; an
+        // aggregate without them returns a row even for an empty input
+        LogicalPlan::Aggregate(mut aggregate) => {
+            let grouped = !aggregate.group_by.is_empty();
+            let fields = aggregate.input.schema(catalog).ok().filter(|_| grouped).and_then(|input| {
+                let group_by = aggregate.group_by.iter().map(|g| (&g.field, g.alias.as_deref()));
+                expand_select_list(group_by, &input).ok()
+            });
+            let (mut below, mut above) = (Vec::new(), Vec::new());
+            for predicate in predicates {
+                match fields.as_ref().and_then(|fields| substitute(&predicate, fields)) {
+                    Some(rewritten) => below.push(rewritten),
+                    None => above.push(predicate),
+                }
+            }
+            aggregate.input = Box::new(push_down(*aggregate.input, below, catalog));
+            with_filter(LogicalPlan::Aggregate(aggregate), above)
+        }
+        LogicalPlan::Alias(mut alias) => {
+            let input = alias.input.schema(catalog).ok();
+            let fields: Option<Vec<(Expr, Field)>> = input.as_ref().map(|input| {
+                input
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        let column = Expr::Column {
+                            qualifier: field.qualifier.clone(),
+                            name: field.name.clone(),
+                        };
+                        (column, Field::new(Some(&alias.alias), &field.name, field.data_type.clone()))
+                    })
+                    .collect()
+            });
+            let (mut below, mut above) = (Vec::new(), Vec::new());
+            for predicate in predicates {
+                let rewritten = fields.as_ref().and_then(|fields| substitute(&predicate, fields));
+                match rewritten {
+                    // an unqualified input column may be ambiguous in the input
+                    Some(rewritten) if reads_only(&rewritten, input.as_ref().unwrap()) => below.push(rewritten),
+                    _ => above.push(predicate),
+                }
+            }
+            alias.input = Box::new(push_down(*alias.input, below, catalog));
+            with_filter(LogicalPlan::Alias(alias), above)
+        }
+        LogicalPlan::OneRow | LogicalPlan::CommonTableScan(_) => with_filter(plan, predicates),
+        // every reader of a table sees the same rows
+        LogicalPlan::With(mut with) => {
+            for table in &mut with.tables {
+                let plan = std::mem::replace(&mut table.plan, LogicalPlan::OneRow);
+                table.plan = push_down(plan, Vec::new(), catalog);
+            }
+            with.input = Box::new(push_down(*with.input, predicates, catalog));
+            LogicalPlan::With(with)
+        }
+        // a row filtered out early could still produce rows in later steps
+        LogicalPlan::RecursiveUnion(union) => {
+            let plan = LogicalPlan::RecursiveUnion(union).map_inputs(|input| push_down(input, Vec::new(), catalog));
+            with_filter(plan, predicates)
+        }
+        // the subquery runs for fewer rows when the input is filtered first
+        LogicalPlan::Apply(mut apply) => {
+            let input = apply.input.schema(catalog).ok();
+            let (below, above) = predicates
+                .into_iter()
+                .partition(|predicate| input.as_ref().is_some_and(|input| reads_only(predicate, input)));
+            apply.input = Box::new(push_down(*apply.input, below, catalog));
+            with_filter(LogicalPlan::Apply(apply), above)
+        }
+        // every input row is kept or repeated, so filters on its columns alone
+        // select the same rows before unnesting
+        LogicalPlan::Unnest(mut unnest) => {
+            let input = unnest.input.schema(catalog).ok();
+            let (below, above) = predicates
+                .into_iter()
+                .partition(|predicate| input.as_ref().is_some_and(|input| reads_only(predicate, input)));
+            unnest.input = Box::new(push_down(*unnest.input, below, catalog));
+            with_filter(LogicalPlan::Unnest(unnest), above)
+        }
+        // left by expand_column_stages only when their input has no schema
+        LogicalPlan::Extend(_)
+        | LogicalPlan::Set(_)
+        | LogicalPlan::Drop(_)
+        | LogicalPlan::Rename(_)
+        | LogicalPlan::Pivot(_)
+        | LogicalPlan::Unpivot(_) => {
+            let plan = plan.map_inputs(|input| push_down(input, Vec::new(), catalog));
+            with_filter(plan, predicates)
+        }
+    }
+}
+
+fn with_filter(plan: LogicalPlan, predicates: Vec<Expr>) -> LogicalPlan {
+    match Expr::conjunction(predicates) {
+        Some(condition) => LogicalPlan::Filter(LogicalFilterNode::new(plan, condition)),
+        None => plan,
+    }
+}
+
+/// Whether every column of the expression is a column of the schema.
+fn reads_only(expr: &Expr, schema: &Schema) -> bool {
+    expr.columns()
+        .iter()
+        .all(|(qualifier, name)| schema.resolve(qualifier.as_deref(), name).is_ok())
+}
+
+/// Rewrites a predicate over the output of a projection into one over its
+/// input, replacing every column by the expression that computes it.
+fn substitute(predicate: &Expr, fields: &[(Expr, Field)]) -> Option<Expr> {
+    let output = Schema::new(fields.iter().map(|(_, field)| field.clone()).collect());
+    if !reads_only(predicate, &output) {
+        return None;
+    }
+    Some(predicate.clone().transform(&mut |expr| match expr {
+        Expr::Column { qualifier, name } => {
+            let index = output.resolve(qualifier.as_deref(), &name).unwrap();
+            fields[index].0.clone()
+        }
+        expr => expr,
+    }))
+}
+
+/// Restricts every scan of a known table to the columns the plan above it
+/// reads. Below a `*` projection, or without any projection, all columns are needed.
+pub fn prune_columns(plan: LogicalPlan, catalog: &Catalog) -> LogicalPlan {
+    prune(plan, None, catalog)
+}
+
+/// `required` lists the columns read above the node, `None` meaning all of them.
+fn prune(plan: LogicalPlan, required: Option<Vec<(Option<String>, String)>>, catalog: &Catalog) -> LogicalPlan {
+    let with = |mut required: Option<Vec<(Option<String>, String)>>, exprs: &[&Expr]| {
+        if let Some(required) = &mut required {
+            for expr in exprs {
+                required.extend(expr.columns());
+            }
+        }
+        required
+    };
+
+    match plan {
+        LogicalPlan::Scan(mut scan) => {
+            let (Some(required), Ok(table)) = (required, catalog.get_table(&scan.table_name)) else {
+                return LogicalPlan::Scan(scan);
+            };
+            let required = with(Some(required), &scan.filters.iter().collect::<Vec<_>>()).unwrap();
+            let projection = table
+                .columns
+                .iter()
+                .filter(|column| {
+                    required.iter().any(|(qualifier, name)| {
+                        name.eq_ignore_ascii_case(&column.name)
+                            && qualifier
+                                .as_deref()
+                                .is_none_or(|q| q.eq_ignore_ascii_case(scan.qualifier()))
+                    })
+                })
+                .map(|column| column.name.clone())
+                .collect();
+            scan.projection = Some(projection);
+            LogicalPlan::Scan(scan)
+        }
+        LogicalPlan::Project(project) => {
+            let required = if project.select_list.iter().any(|s| s.field == Expr::Wildcard) {
+                None
+            } else {
+                with(Some(Vec::new()), &project.select_list.iter().map(|s| &s.field).collect::<Vec<_>>())
+            };
+            LogicalPlan::Project(project).map_inputs(|input| prune(input, required.clone(), catalog))
+        }
+        LogicalPlan::Filter(filter) => {
+            let required = with(required, &[&filter.condition]);
+            LogicalPlan::Filter(filter).map_inputs(|input| prune(input, required.clone(), catalog))
+        }
+        LogicalPlan::Join(join) => {
+            let required = with(required, &[&join.condition]);
+            LogicalPlan::Join(join).map_inputs(|input| prune(input, required.clone(), catalog))
+        }
+        LogicalPlan::Unnest(unnest) => {
+            let required = with(required, &[&unnest.array, &unnest.condition]);
+            LogicalPlan::Unnest(unnest).map_inputs(|input| prune(input, required.clone(), catalog))
+        }
+        LogicalPlan::Sort(sort) => {
+            let required = with(required, &sort.keys.iter().map(|key| &key.expr).collect::<Vec<_>>());
+            LogicalPlan::Sort(sort).map_inputs(|input| prune(input, required.clone(), catalog))
+        }
+        LogicalPlan::Aggregate(aggregate) => {
+            let mut exprs: Vec<&Expr> = aggregate.group_by.iter().map(|g| &g.field).collect();
+            exprs.extend(aggregate.aggregates.iter().filter_map(|a| a.call.argument.as_ref()));
+            let required = with(Some(Vec::new()), &exprs);
+            LogicalPlan::Aggregate(aggregate).map_inputs(|input| prune(input, required.clone(), catalog))
+        }
+        // the qualifiers above refer to the alias, not to the tables below it
+        LogicalPlan::Alias(alias) => LogicalPlan::Alias(alias).map_inputs(|input| prune(input, None, catalog)),
+        LogicalPlan::OneRow | LogicalPlan::CommonTableScan(_) => plan,
+        LogicalPlan::With(mut with) => {
+            for table in &mut with.tables {
+                let plan = std::mem::replace(&mut table.plan, LogicalPlan::OneRow);
+                table.plan = prune(plan, None, catalog);
+            }
+            with.input = Box::new(prune(*with.input, required, catalog));
+            LogicalPlan::With(with)
+        }
+        LogicalPlan::RecursiveUnion(union) => LogicalPlan::RecursiveUnion(union).map_inputs(|input| prune(input, None, catalog)),
+        LogicalPlan::Limit(limit) => LogicalPlan::Limit(limit).map_inputs(|input| prune(input, required.clone(), catalog)),
+        LogicalPlan::Window(window) => {
+            let mut exprs = Vec::new();
+            for window in &window.windows {
+                let call = &window.call;
+                exprs.extend(call.args.iter().chain(&call.spec.partition_by));
+                exprs.extend(call.spec.order_by.iter().map(|key| &key.expr));
+                if let WindowFunction::Aggregate(aggregate) = &call.function {
+                    exprs.extend(&aggregate.argument);
+                }
+            }
+            let required = with(required, &exprs);
+            LogicalPlan::Window(window).map_inputs(|input| prune(input, required.clone(), catalog))
+        }
+        // the subquery may read any column of the input
+        LogicalPlan::Apply(apply) => LogicalPlan::Apply(apply).map_inputs(|input| prune(input, None, catalog)),
+        // every column takes part in comparing rows
+        LogicalPlan::SetOperation(set) => LogicalPlan::SetOperation(set).map_inputs(|input| prune(input, None, catalog)),
+        LogicalPlan::Extend(_)
+        | LogicalPlan::Set(_)
+        | LogicalPlan::Drop(_)
+        | LogicalPlan::Rename(_)
+        | LogicalPlan::Pivot(_)
+        | LogicalPlan::Unpivot(_) => plan.map_inputs(|input| prune(input, None, catalog)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::{Column, TableSchema};
+    use crate::expression::parse_expression;
+    use crate::lexer::Tokens;
+    use crate::parser::parse_pipe_sql;
+    use crate::scanner::LogicalScanNode;
+    use crate::value::DataType;
+    use tempfile::tempdir;
+
+    fn expr(text: &str) -> Expr {
+        parse_expression(&mut Tokens::new(text).unwrap()).unwrap()
+    }
+
+    fn table(name: &str, columns: &[&str]) -> TableSchema {
+        TableSchema {
+            name: name.to_string(),
+            columns: columns
+                .iter()
+                .map(|column| Column {
+                    name: column.to_string(),
+                    data_type: DataType::Int64,
+                    nullable: true,
+                })
+                .collect(),
+            primary_key: Vec::new(),
+            foreign_keys: Vec::new(),
+        }
+    }
+
+    fn scans(plan: &LogicalPlan) -> Vec<&LogicalScanNode> {
+        match plan {
+            LogicalPlan::Scan(scan) => vec![scan],
+            plan => plan.inputs().into_iter().flat_map(scans).collect(),
+        }
+    }
+
+    #[test]
+    fn folds_constants_and_simplifies_booleans() {
+        assert_eq!(fold_constants(expr("a > 2 * 3 + 1")), expr("a > 7"));
+        assert_eq!(fold_constants(expr("1 / 0")), expr("1 / 0"));
+        assert_eq!(simplify_boolean(fold_constants(expr("a = 1 AND 1 < 2"))), expr("a = 1"));
+        assert_eq!(simplify_boolean(expr("a = 1 OR TRUE")), expr("TRUE"));
+        assert_eq!(simplify_boolean(expr("NOT NOT (a < 1)")), expr("a < 1"));
+        assert_eq!(simplify_boolean(expr("NOT (a < 1 AND a < 1)")), expr("a >= 1"));
+        assert_eq!(simplify_boolean(expr("NOT a IS NULL")), expr("a IS NOT NULL"));
+    }
+
+    #[test]
+    fn pushes_filters_to_their_side_of_the_join_and_prunes_columns() {
+        let dir = tempdir().unwrap();
+        let mut catalog = Catalog::open(dir.path()).unwrap();
+        catalog.add_table(table("users", &["id", "name", "age"])).unwrap();
+        catalog.add_table(table("orders", &["id", "user_id", "amount", "note"])).unwrap();
+
+        let plan = parse_pipe_sql(
+            "FROM users |> JOIN orders ON users.id = orders.user_id \
+             |> WHERE age > 18 + 2 AND orders.amount > 10 AND users.name = orders.note \
+             |> SELECT name, amount",
+        )
+        .unwrap();
+        let plan = optimize(plan, &catalog);
+
+        let LogicalPlan::Project(project) = &plan else {
+            panic!("expected a projection, got {:?}", plan);
+        };
+        let LogicalPlan::Join(join) = &*project.input else {
+            panic!("the filter should have been merged into the join");
+        };
+        assert_eq!(join.condition, expr("users.id = orders.user_id AND users.name = orders.note"));
+
+        let scans = scans(&plan);
+        assert_eq!(scans[0].filters, vec![expr("age > 20")]);
+        assert_eq!(scans[1].filters, vec![expr("orders.amount > 10")]);
+        let names = |projection: &Option<Vec<String>>| projection.clone().unwrap().join(",");
+        assert_eq!(names(&scans[0].projection), "id,name,age");
+        assert_eq!(names(&scans[1].projection), "user_id,amount,note");
+    }
+
+    #[test]
+    fn pushes_filters_only_into_the_side_an_outer_join_does_not_pad() {
+        let dir = tempdir().unwrap();
+        let mut catalog = Catalog::open(dir.path()).unwrap();
+        catalog.add_table(table("users", &["id", "age"])).unwrap();
+        catalog.add_table(table("orders", &["id", "user_id", "amount"])).unwrap();
+
+        let plan = parse_pipe_sql(
+            "FROM users |> LEFT JOIN orders ON users.id = orders.user_id AND users.age > 1 AND orders.amount > 2 \
+             |> WHERE users.age < 3 AND orders.amount < 4",
+        )
+        .unwrap();
+        let plan = optimize(plan, &catalog);
+
+        let LogicalPlan::Filter(filter) = &plan else {
+            panic!("the filter on the padded side should stay above the join, got {:?}", plan);
+        };
+        assert_eq!(filter.condition, expr("orders.amount < 4"));
+        let LogicalPlan::Join(join) = &*filter.input else {
+            panic!("expected the join below the filter");
+        };
+        assert_eq!(join.condition, expr("users.id = orders.user_id AND users.age > 1"));
+        let scans = scans(&plan);
+        assert_eq!(scans[0].filters, vec![expr("users.age < 3")]);
+        assert_eq!(scans[1].filters, vec![expr("orders.amount > 2")]);
+    }
+
+    #[test]
+    fn keeps_pipe_order_and_rewrites_aliases() {
+        let dir = tempdir().unwrap();
+        let mut catalog = Catalog::open(dir.path()).unwrap();
+        catalog.add_table(table("users", &["id", "name", "age"])).unwrap();
+
+        // the WHERE reads the alias of the SELECT before it
+        let plan = parse_pipe_sql("FROM users |> SELECT id, age * 2 AS double_age |> WHERE double_age > 40").unwrap();
+        assert!(matches!(&plan, LogicalPlan::Filter(filter) if matches!(*filter.input, LogicalPlan::Project(_))));
+
+        let plan = optimize(plan, &catalog);
+        let LogicalPlan::Project(project) = &plan else {
+            panic!("the filter should have moved below the projection");
+        };
+        let LogicalPlan::Scan(scan) = &*project.input else {
+            panic!("expected a scan below the projection");
+        };
+        assert_eq!(scan.filters, vec![expr("age * 2 > 40")]);
+        assert_eq!(scan.projection.clone().unwrap(), vec!["id", "age"]);
+    }
+
+    #[test]
+    fn keeps_the_names_of_folded_columns() {
+        let dir = tempdir().unwrap();
+        let mut catalog = Catalog::open(dir.path()).unwrap();
+        catalog.add_table(table("users", &["id", "age"])).unwrap();
+
+        let plan = parse_pipe_sql("FROM users |> EXTEND 1 + 2 |> SELECT id, SUBSTR('hello', 0, 2), 2 * 3 AS six, `1 + 2`")
+            .unwrap();
+        let names = |plan: &LogicalPlan| {
+            let schema = plan.schema(&catalog).unwrap();
+            schema.fields.iter().map(|field| field.name.clone()).collect::<Vec<_>>()
+        };
+        let expected = names(&plan);
+        assert_eq!(expected, vec!["id", "SUBSTR('hello', 0, 2)", "six", "1 + 2"]);
+
+        let plan = optimize(plan, &catalog);
+        let LogicalPlan::Project(project) = &plan else {
+            panic!("expected a projection, got {:?}", plan);
+        };
+        assert_eq!(project.select_list[1].field, expr("'he'"));
+        assert_eq!(names(&plan), expected);
+    }
+}
@@ -1,12 +1,81 @@
-#[derive(Debug)]
+use crate::expression::Expr;
+use crate::logical_plan::LogicalPlan;
+use crate::value::Value;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// One key of an ORDER BY: NULLs come first in ascending order and last in
+/// descending order unless `NULLS FIRST`/`NULLS LAST` says otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub expr: Expr,
+    pub descending: bool,
+    pub nulls_first: bool,
+}
+
+impl SortKey {
+    pub fn new(expr: Expr, descending: bool, nulls_first: Option<bool>) -> Self {
+        Self {
+            expr,
+            descending,
+            nulls_first: nulls_first.unwrap_or(!descending),
+        }
+    }
+
+    /// Orders two values of this key; incomparable values are equal.
+    pub fn compare(&self, a: &Value, b: &Value) -> Ordering {
+        let nulls = if self.nulls_first { Ordering::Less } else { Ordering::Greater };
+        match (a.is_null(), b.is_null()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => nulls,
+            (false, true) => nulls.reverse(),
+            _ => {
+                let ordering = a.compare(b).unwrap_or(Ordering::Equal);
+                if self.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+        }
+    }
+}
+
+/// Orders rows of key values by the keys they were computed for.
+pub fn compare_sort_keys(keys: &[SortKey], a: &[Value], b: &[Value]) -> Ordering {
+    keys.iter()
+        .zip(a.iter().zip(b))
+        .map(|(key, (a, b))| key.compare(a, b))
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// Prints the key with its direction and NULL placement where they differ
+/// from the defaults.
+impl fmt::Display for SortKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.expr)?;
+        if self.descending {
+            write!(f, " DESC")?;
+        }
+        if self.nulls_first == self.descending {
+            write!(f, " NULLS {}", if self.nulls_first { "FIRST" } else { "LAST" })?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct LogicalOrderByNode {
-    pub field: String,
+    pub input: Box<LogicalPlan>,
+    pub keys: Vec<SortKey>,
 }
 
 impl LogicalOrderByNode {
-    pub fn new(field: &str) -> Self {
+    pub fn new(input: LogicalPlan, keys: Vec<SortKey>) -> Self {
         Self {
-            field: field.to_string(),
+            input: Box::new(input),
+            keys,
         }
     }
 }
@@ -1,37 +1,124 @@
 use crate::logical_plan::LogicalPlan;
-use crate::scanner::LogicalScanNode;
 use crate::errors::ParsingError;
+use crate::lexer::Tokens;
+use crate::statement::{
+    parse_analyze, parse_create_table, parse_delete, parse_insert, parse_set, parse_update, Statement,
+};
 
-pub fn parse_pipe_sql(input: &str) -> Result<LogicalPlan, ParsingError> {
-    let tokens: Vec<&str> = input.trim().split("|>").map(|t| t.trim()).collect();
+/// Words a JOIN stage can start with.
+const JOIN_KEYWORDS: [&str; 6] = ["JOIN", "INNER", "LEFT", "RIGHT", "FULL", "CROSS"];
+
+/// Words a set operation stage can start with.
+const SET_OPERATION_KEYWORDS: [&str; 3] = ["UNION", "INTERSECT", "EXCEPT"];
+
+pub fn parse_statement(input: &str) -> Result<Statement, ParsingError> {
+    let mut tokens = Tokens::new(input)?;
+
+    if tokens.peek_keyword("CREATE") {
+        parse_create_table(&mut tokens)
+    } else if tokens.peek_keyword("INSERT") {
+        parse_insert(&mut tokens)
+    } else if tokens.peek_keyword("UPDATE") {
+        parse_update(&mut tokens)
+    } else if tokens.peek_keyword("DELETE") {
+        parse_delete(&mut tokens)
+    } else if tokens.peek_keyword("ANALYZE") {
+        parse_analyze(&mut tokens)
+    } else if tokens.peek_keyword("SET") {
+        parse_set(&mut tokens)
+    } else if tokens.accept_keyword("EXPLAIN") {
+        let mut query = strip_keyword(input, "EXPLAIN");
+        let analyze = tokens.accept_keyword("ANALYZE");
+        if analyze {
+            query = strip_keyword(query, "ANALYZE");
+        }
+        let plan = parse_pipe_sql(query)?;
+        Ok(Statement::Explain {
+            analyze,
+            query: query.trim().to_string(),
+            plan,
+        })
+    } else {
+        parse_pipe_sql(input).map(Statement::Query)
+    }
+}
 
+/// The input after its leading keyword, which the caller has already matched.
+fn strip_keyword<'a>(input: &'a str, keyword: &str) -> &'a str {
+    input.trim_start()[keyword.len()..].trim_start()
+}
+
+/// Parses a pipe query: an optional `FROM` item followed by `|>` stages, each
+/// applied to the result of the stages before it.
+pub fn parse_pipe_sql(input: &str) -> Result<LogicalPlan, ParsingError> {
+    let mut tokens = Tokens::new(input)?;
     if tokens.is_empty() {
         return Err(ParsingError::new("Empty SQL statement"));
     }
+    let plan = parse_query(&mut tokens)?;
+    tokens.expect_end()?;
+    Ok(plan)
+}
 
-    let mut logical_plan = LogicalPlan::new(input.to_string());
-    let mut last_table: Option<String> = None;
-
-    for token in tokens {
-        if token.starts_with("SELECT") {
-            logical_plan.parse_select(token)?;
-        } else if token.starts_with("FROM") {
-            let table_name = token.trim_start_matches("FROM").trim();
-            logical_plan.add_scan(LogicalScanNode::new(table_name));
-            last_table = Some(table_name.to_string());
-        } else if token.starts_with("WHERE") {
-            logical_plan.parse_where(token)?;
-        } else if token.starts_with("JOIN") {
-            if last_table.is_none() {
-                return Err(ParsingError::new("JOIN must follow a FROM clause"));
-            }
-            logical_plan.parse_join(token, last_table.clone())?;
-        } else if token.starts_with("ORDER BY") {
-            logical_plan.parse_order_by(token)?;
+/// Parses a query up to the first token that does not continue it, so that
+/// it can also be a subquery.
+pub fn parse_query(tokens: &mut Tokens) -> Result<LogicalPlan, ParsingError> {
+    if tokens.peek_keyword("WITH") {
+        return LogicalPlan::parse_with(tokens);
+    }
+    let mut plan = if tokens.peek_keyword("FROM") {
+        let plan = LogicalPlan::parse_from(tokens)?;
+        if !tokens.accept_symbol("|>") {
+            return Ok(plan);
+        }
+        plan
+    } else {
+        LogicalPlan::OneRow
+    };
+
+    loop {
+        plan = if tokens.peek_keyword("SELECT") {
+            plan.parse_select(tokens)?
+        } else if tokens.peek_keyword("WHERE") {
+            plan.parse_where(tokens)?
+        } else if JOIN_KEYWORDS.iter().any(|keyword| tokens.peek_keyword(keyword)) {
+            plan.parse_join(tokens)?
+        } else if tokens.peek_keyword("ORDER") {
+            plan.parse_order_by(tokens)?
+        } else if tokens.peek_keyword("LIMIT") {
+            plan.parse_limit(tokens)?
+        } else if SET_OPERATION_KEYWORDS.iter().any(|keyword| tokens.peek_keyword(keyword)) {
+            plan.parse_set_operation(tokens)?
+        } else if tokens.peek_keyword("AGGREGATE") {
+            plan.parse_aggregate(tokens)?
+        } else if tokens.peek_keyword("WINDOW") {
+            plan.parse_window(tokens)?
+        } else if tokens.peek_keyword("EXTEND") {
+            plan.parse_extend(tokens)?
+        } else if tokens.peek_keyword("TABLESAMPLE") {
+            plan.parse_tablesample(tokens)?
+        } else if tokens.peek_keyword("PIVOT") {
+            plan.parse_pivot(tokens)?
+        } else if tokens.peek_keyword("UNPIVOT") {
+            plan.parse_unpivot(tokens)?
+        } else if tokens.peek_keyword("SET") {
+            plan.parse_set(tokens)?
+        } else if tokens.peek_keyword("DROP") {
+            plan.parse_drop(tokens)?
+        } else if tokens.peek_keyword("RENAME") {
+            plan.parse_rename(tokens)?
+        } else if tokens.peek_keyword("AS") {
+            plan.parse_as(tokens)?
+        } else if tokens.peek_keyword("FROM") {
+            return Err(ParsingError::new("FROM must start the query"));
         } else {
-            return Err(ParsingError::new(&format!("Unsupported SQL component: {}", token)));
+            return Err(tokens.unexpected("a pipe operator"));
+        };
+
+        if !tokens.accept_symbol("|>") {
+            break;
         }
     }
 
-    Ok(logical_plan)
+    Ok(plan)
 }
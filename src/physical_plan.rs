@@ -1,39 +1,804 @@
+use crate::aggregate::AggregateCall;
+use crate::catalog::Catalog;
+use crate::cost::{aggregate_cost, array_length, join_cost, sort_cost, top_n_cost, Estimator};
+use crate::expression::{BinaryOperator, Expr};
+use crate::join::{JoinType, LogicalJoinNode};
+use crate::join_order::{order_joins, JoinNode, JoinPredicate, JoinTree, MAX_RELATIONS};
+use crate::limit::LogicalLimitNode;
 use crate::logical_plan::LogicalPlan;
+use crate::optimizer::optimize;
+use crate::order_by::SortKey;
+use crate::sample::{SampleMethod, TableSample};
+use crate::schema::{Field, Schema};
+use crate::set_operation::SetOperator;
+use crate::subquery::{bind_outer_columns, ApplyKind};
+use crate::value::Value;
+use crate::window::{LogicalWindowNode, WindowCall};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JoinAlgorithm {
+    NestedLoop,
+    Hash,
+    SortMerge,
+}
+
+impl JoinAlgorithm {
+    fn name(&self) -> &'static str {
+        match self {
+            JoinAlgorithm::NestedLoop => "NESTED LOOP",
+            JoinAlgorithm::Hash => "HASH",
+            JoinAlgorithm::SortMerge => "SORT MERGE",
+        }
+    }
+}
+
+impl fmt::Display for JoinAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} JOIN", self.name())
+    }
+}
+
+#[derive(Debug)]
+pub enum Operator {
+    /// A single empty row, the input of queries without FROM
+    OneRow,
+    Scan {
+        table: String,
+        alias: Option<String>,
+        columns: Option<Vec<String>>,
+        filter: Option<Expr>,
+        sample: Option<TableSample>,
+    },
+    Join {
+        algorithm: JoinAlgorithm,
+        join_type: JoinType,
+        condition: Option<Expr>,
+    },
+    Filter {
+        condition: Expr,
+    },
+    Project {
+        fields: Vec<(Expr, Option<String>)>,
+    },
+    Sort {
+        keys: Vec<SortKey>,
+    },
+    /// Groups rows in a hash table; without grouping columns the result is
+    /// a single row even for an empty input
+    Aggregate {
+        group_by: Vec<(Expr, Option<String>)>,
+        aggregates: Vec<(AggregateCall, Option<String>)>,
+    },
+    /// Qualifies every column of its input by the name of a subquery
+    Alias {
+        name: String,
+    },
+    /// Stops reading its input once `offset + limit` rows were read
+    Limit {
+        limit: usize,
+        offset: usize,
+    },
+    /// Combines the rows of its two children; the distinct variants hash rows
+    SetOperation {
+        operator: SetOperator,
+        all: bool,
+    },
+    /// Window functions over input sorted by their partition and order keys,
+    /// which all calls share
+    Window {
+        windows: Vec<(WindowCall, Option<String>)>,
+    },
+    /// Appends what a subquery returns for each input row; the subquery is
+    /// planned anew for every distinct binding of the columns it refers to
+    Apply {
+        subquery: LogicalPlan,
+        kind: ApplyKind,
+        name: String,
+        /// The subquery planned with its outer columns unbound, shown under
+        /// the node
+        plan: Box<PhysicalNode>,
+    },
+    /// Computes the tables of a WITH clause, its first children, and keeps
+    /// their rows for the scans of them in the last child
+    With {
+        tables: Vec<(usize, String)>,
+    },
+    /// Reads the rows a WITH or a recursive union keeps for table `id`
+    CommonTableScan {
+        id: usize,
+        name: String,
+        alias: Option<String>,
+        fields: Vec<Field>,
+    },
+    /// Runs the second child with the rows added last as working table `id`
+    /// until it returns no new rows
+    RecursiveUnion {
+        id: usize,
+        name: String,
+        all: bool,
+    },
+    /// ORDER BY followed by LIMIT, keeping only the first `offset + limit`
+    /// rows in a bounded heap
+    TopN {
+        keys: Vec<SortKey>,
+        limit: usize,
+        offset: usize,
+    },
+    /// Repeats every input row for each element of its array that satisfies
+    /// the condition, or once with NULLs when outer and none does
+    Unnest {
+        array: Expr,
+        alias: String,
+        offset: Option<String>,
+        outer: bool,
+        condition: Option<Expr>,
+    },
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operator::OneRow => write!(f, "ONE ROW"),
+            Operator::Scan {
+                table,
+                alias,
+                columns,
+                filter,
+                sample,
+            } => {
+                write!(f, "SCAN TABLE {}", table)?;
+                if let Some(alias) = alias {
+                    write!(f, " AS {}", alias)?;
+                }
+                if let Some(sample) = sample {
+                    write!(f, " {}", sample)?;
+                }
+                if let Some(columns) = columns {
+                    write!(f, " COLUMNS ({})", columns.join(", "))?;
+                }
+                if let Some(filter) = filter {
+                    write!(f, " FILTER {}", filter)?;
+                }
+                Ok(())
+            }
+            Operator::Join {
+                algorithm,
+                join_type,
+                condition,
+            } => {
+                match join_type {
+                    JoinType::Inner => write!(f, "{}", algorithm)?,
+                    join_type => write!(f, "{} {} JOIN", algorithm.name(), join_type)?,
+                }
+                match condition {
+                    Some(condition) => write!(f, " ON {}", condition),
+                    None => write!(f, " (CROSS)"),
+                }
+            }
+            Operator::Filter { condition } => write!(f, "FILTER WHERE {}", condition),
+            Operator::Project { fields } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field, alias)| match alias {
+                        Some(alias) => format!("{} AS {}", field, alias),
+                        None => field.to_string(),
+                    })
+                    .collect();
+                write!(f, "PROJECT {}", fields.join(", "))
+            }
+            Operator::Sort { keys } => {
+                let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+                write!(f, "ORDER BY {}", keys.join(", "))
+            }
+            Operator::Aggregate { group_by, aggregates } => {
+                let aliased = |item: String, alias: &Option<String>| match alias {
+                    Some(alias) => format!("{} AS {}", item, alias),
+                    None => item,
+                };
+                let aggregates: Vec<String> = aggregates
+                    .iter()
+                    .map(|(call, alias)| aliased(call.to_string(), alias))
+                    .collect();
+                write!(f, "HASH AGGREGATE {}", aggregates.join(", "))?;
+                if !group_by.is_empty() {
+                    let group_by: Vec<String> = group_by
+                        .iter()
+                        .map(|(expr, alias)| aliased(expr.to_string(), alias))
+                        .collect();
+                    write!(f, " GROUP BY {}", group_by.join(", "))?;
+                }
+                Ok(())
+            }
+            Operator::Alias { name } => write!(f, "SUBQUERY AS {}", name),
+            Operator::Limit { limit, offset } => write_limit(f, *limit, *offset),
+            Operator::Window { windows } => {
+                let windows: Vec<String> = windows
+                    .iter()
+                    .map(|(call, alias)| match alias {
+                        Some(alias) => format!("{} AS {}", call, alias),
+                        None => call.to_string(),
+                    })
+                    .collect();
+                write!(f, "WINDOW {}", windows.join(", "))
+            }
+            Operator::Apply { kind, name, .. } => write!(f, "APPLY {} SUBQUERY AS {}", kind, name),
+            Operator::With { tables } => {
+                let names: Vec<&str> = tables.iter().map(|(_, name)| name.as_str()).collect();
+                write!(f, "WITH {}", names.join(", "))
+            }
+            Operator::CommonTableScan { name, alias, .. } => {
+                write!(f, "SCAN COMMON TABLE {}", name)?;
+                if let Some(alias) = alias {
+                    write!(f, " AS {}", alias)?;
+                }
+                Ok(())
+            }
+            Operator::RecursiveUnion { name, all, .. } => {
+                write!(f, "RECURSIVE UNION {} {}", if *all { "ALL" } else { "DISTINCT" }, name)
+            }
+            Operator::SetOperation { operator, all: true } => write!(f, "{} ALL", operator),
+            Operator::SetOperation { operator, all: false } => write!(f, "HASH {} DISTINCT", operator),
+            Operator::TopN { keys, limit, offset } => {
+                let keys: Vec<String> = keys.iter().map(|key| key.to_string()).collect();
+                write!(f, "TOP-N ORDER BY {} ", keys.join(", "))?;
+                write_limit(f, *limit, *offset)
+            }
+            Operator::Unnest {
+                array,
+                alias,
+                offset,
+                outer,
+                condition,
+            } => {
+                write!(f, "{}UNNEST {} AS {}", if *outer { "LEFT " } else { "" }, array, alias)?;
+                if let Some(offset) = offset {
+                    write!(f, " WITH OFFSET AS {}", offset)?;
+                }
+                if let Some(condition) = condition {
+                    write!(f, " ON {}", condition)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn write_limit(f: &mut fmt::Formatter, limit: usize, offset: usize) -> fmt::Result {
+    write!(f, "LIMIT {}", limit)?;
+    if offset > 0 {
+        write!(f, " OFFSET {}", offset)?;
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct PhysicalNode {
+    pub operator: Operator,
+    pub children: Vec<PhysicalNode>,
+    pub estimated_rows: f64,
+    pub estimated_cost: f64,
+}
+
+impl PhysicalNode {
+    fn new(operator: Operator, children: Vec<PhysicalNode>, estimated_rows: f64, estimated_cost: f64) -> Self {
+        Self {
+            operator,
+            children,
+            estimated_rows,
+            estimated_cost,
+        }
+    }
+
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        writeln!(
+            f,
+            "{}{} (rows={:.0}, cost={:.0})",
+            "  ".repeat(depth),
+            self.operator,
+            self.estimated_rows,
+            self.estimated_cost
+        )?;
+        for child in &self.children {
+            child.fmt_indented(f, depth + 1)?;
+        }
+        if let Operator::Apply { plan, .. } = &self.operator {
+            plan.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
 
 #[derive(Debug)]
 pub struct PhysicalPlan {
-    pub query: String,
-    pub operations: Vec<String>,
+    pub root: PhysicalNode,
 }
 
-pub fn convert_to_physical_plan(logical_plan: &LogicalPlan) -> PhysicalPlan {
-    let mut operations = Vec::new();
+impl fmt::Display for PhysicalPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.root.fmt_indented(f, 0)
+    }
+}
 
-    for scan in &logical_plan.scans {
-        operations.push(format!("SCAN TABLE {}", scan.table_name));
+pub fn convert_to_physical_plan(logical_plan: &LogicalPlan, catalog: &Catalog) -> PhysicalPlan {
+    let mut tables = Vec::new();
+    collect_tables(logical_plan, &mut tables);
+    let estimator = Estimator::new(catalog, &tables);
+    PhysicalPlan {
+        root: convert(logical_plan, catalog, &estimator),
     }
+}
 
-    for join in &logical_plan.joins {
-        operations.push(format!(
-            "JOIN {} ON {} = {}",
-            join.table2, join.condition, join.table1
-        ));
+/// The (table, qualifier) pair of every scan.
+fn collect_tables(plan: &LogicalPlan, tables: &mut Vec<(String, String)>) {
+    if let LogicalPlan::Scan(scan) = plan {
+        tables.push((scan.table_name.clone(), scan.qualifier().to_string()));
+    }
+    for input in plan.inputs() {
+        collect_tables(input, tables);
     }
+}
 
-    for filter in &logical_plan.filters {
-        operations.push(format!("FILTER WHERE {}", filter.condition));
+fn convert(plan: &LogicalPlan, catalog: &Catalog, estimator: &Estimator) -> PhysicalNode {
+    match plan {
+        LogicalPlan::OneRow => PhysicalNode::new(Operator::OneRow, Vec::new(), 1.0, 0.0),
+        LogicalPlan::Scan(scan) => {
+            let table_rows = estimator.table_rows(scan.qualifier());
+            let (sampled_rows, cost) = match &scan.sample {
+                // only the kept pages are read
+                Some(sample) if sample.method == SampleMethod::System => {
+                    (table_rows * sample.fraction(), table_rows * sample.fraction())
+                }
+                Some(sample) => (table_rows * sample.fraction(), table_rows),
+                None => (table_rows, table_rows),
+            };
+            let rows = scan
+                .filters
+                .iter()
+                .fold(sampled_rows, |rows, filter| rows * estimator.selectivity(filter));
+            let operator = Operator::Scan {
+                table: scan.table_name.clone(),
+                alias: scan.alias.clone(),
+                columns: scan.projection.clone(),
+                filter: Expr::conjunction(scan.filters.clone()),
+                sample: scan.sample.clone(),
+            };
+            PhysicalNode::new(operator, Vec::new(), rows, cost)
+        }
+        LogicalPlan::Filter(filter) => {
+            let input = convert(&filter.input, catalog, estimator);
+            with_filter(input, filter.condition.clone(), estimator)
+        }
+        LogicalPlan::Project(project) => {
+            let input = convert(&project.input, catalog, estimator);
+            let fields = project
+                .select_list
+                .iter()
+                .map(|select| (select.field.clone(), select.alias.clone()))
+                .collect();
+            let (rows, cost) = (input.estimated_rows, input.estimated_cost + input.estimated_rows);
+            PhysicalNode::new(Operator::Project { fields }, vec![input], rows, cost)
+        }
+        LogicalPlan::Sort(sort) => {
+            let input = convert(&sort.input, catalog, estimator);
+            let (rows, cost) = (input.estimated_rows, input.estimated_cost + sort_cost(input.estimated_rows));
+            PhysicalNode::new(Operator::Sort { keys: sort.keys.clone() }, vec![input], rows, cost)
+        }
+        LogicalPlan::Aggregate(aggregate) => {
+            let input = convert(&aggregate.input, catalog, estimator);
+            let (group_by, aggregates) = (aggregate.group_by_list(), aggregate.aggregate_list());
+            let keys: Vec<Expr> = group_by.iter().map(|(expr, _)| expr.clone()).collect();
+            let rows = estimator.group_count(&keys, input.estimated_rows);
+            let cost = input.estimated_cost + aggregate_cost(input.estimated_rows);
+            PhysicalNode::new(Operator::Aggregate { group_by, aggregates }, vec![input], rows, cost)
+        }
+        LogicalPlan::Limit(limit) => convert_limit(limit, catalog, estimator),
+        LogicalPlan::Window(window) => convert_window(window, catalog, estimator),
+        LogicalPlan::SetOperation(set) => {
+            let left = convert(&set.left, catalog, estimator);
+            let right = convert(&set.right, catalog, estimator);
+            let (left_rows, right_rows) = (left.estimated_rows, right.estimated_rows);
+            let rows = match set.operator {
+                SetOperator::Union => left_rows + right_rows,
+                SetOperator::Intersect => left_rows.min(right_rows),
+                SetOperator::Except => left_rows,
+            };
+            let mut cost = left.estimated_cost + right.estimated_cost;
+            if !set.all {
+                cost += aggregate_cost(left_rows + right_rows);
+            }
+            let operator = Operator::SetOperation {
+                operator: set.operator,
+                all: set.all,
+            };
+            PhysicalNode::new(operator, vec![left, right], rows, cost)
+        }
+        LogicalPlan::With(with) => {
+            let mut children = Vec::new();
+            for table in &with.tables {
+                let child = convert(&table.plan, catalog, estimator);
+                estimator.set_common_table_rows(table.id, child.estimated_rows);
+                children.push(child);
+            }
+            let input = convert(&with.input, catalog, estimator);
+            let rows = input.estimated_rows;
+            children.push(input);
+            let cost = children.iter().map(|child| child.estimated_cost).sum();
+            let tables = with.tables.iter().map(|table| (table.id, table.name.clone())).collect();
+            PhysicalNode::new(Operator::With { tables }, children, rows, cost)
+        }
+        LogicalPlan::CommonTableScan(scan) => {
+            let rows = estimator.common_table_rows(scan.id);
+            let operator = Operator::CommonTableScan {
+                id: scan.id,
+                name: scan.name.clone(),
+                alias: scan.alias.clone(),
+                fields: scan.fields.clone(),
+            };
+            PhysicalNode::new(operator, Vec::new(), rows, rows)
+        }
+        // estimated as if the recursion stopped after one step
+        LogicalPlan::RecursiveUnion(union) => {
+            let anchor = convert(&union.anchor, catalog, estimator);
+            estimator.set_common_table_rows(union.id, anchor.estimated_rows);
+            let recursive = convert(&union.recursive, catalog, estimator);
+            let rows = anchor.estimated_rows + recursive.estimated_rows;
+            let mut cost = anchor.estimated_cost + recursive.estimated_cost;
+            if !union.all {
+                cost += aggregate_cost(rows);
+            }
+            let operator = Operator::RecursiveUnion {
+                id: union.id,
+                name: union.name.clone(),
+                all: union.all,
+            };
+            PhysicalNode::new(operator, vec![anchor, recursive], rows, cost)
+        }
+        LogicalPlan::Apply(apply) => {
+            let input = convert(&apply.input, catalog, estimator);
+            // estimated as if the outer columns were NULL
+            let run_cost = bind_outer_columns((*apply.subquery).clone(), catalog, &mut |_| Expr::Literal(Value::Null))
+                .map(|subquery| convert_to_physical_plan(&optimize(subquery, catalog), catalog).root.estimated_cost)
+                .unwrap_or(1.0);
+            let (rows, cost) = (input.estimated_rows, input.estimated_cost + input.estimated_rows * run_cost);
+            let plan = convert_to_physical_plan(&optimize((*apply.subquery).clone(), catalog), catalog).root;
+            let operator = Operator::Apply {
+                subquery: (*apply.subquery).clone(),
+                kind: apply.kind.clone(),
+                name: apply.name.clone(),
+                plan: Box::new(plan),
+            };
+            PhysicalNode::new(operator, vec![input], rows, cost)
+        }
+        LogicalPlan::Alias(alias) => {
+            let input = convert(&alias.input, catalog, estimator);
+            let (rows, cost) = (input.estimated_rows, input.estimated_cost);
+            PhysicalNode::new(Operator::Alias { name: alias.alias.clone() }, vec![input], rows, cost)
+        }
+        LogicalPlan::Join(join) if join.join_type == JoinType::Inner => convert_joins(plan, catalog, estimator),
+        LogicalPlan::Join(join) => convert_outer_join(join, catalog, estimator),
+        LogicalPlan::Unnest(unnest) => {
+            let input = convert(&unnest.input, catalog, estimator);
+            let condition = Some(unnest.condition.clone()).filter(|condition| *condition != Expr::Literal(Value::Bool(true)));
+            let elements = array_length(&unnest.array);
+            let mut rows = input.estimated_rows * elements;
+            if let Some(condition) = &condition {
+                rows *= estimator.selectivity(condition);
+            }
+            if unnest.outer {
+                rows = rows.max(input.estimated_rows);
+            }
+            let cost = input.estimated_cost + input.estimated_rows * elements;
+            let operator = Operator::Unnest {
+                array: unnest.array.clone(),
+                alias: unnest.alias.clone(),
+                offset: unnest.offset.clone(),
+                outer: unnest.outer,
+                condition,
+            };
+            PhysicalNode::new(operator, vec![input], rows, cost)
+        }
+        LogicalPlan::Extend(_)
+        | LogicalPlan::Set(_)
+        | LogicalPlan::Drop(_)
+        | LogicalPlan::Rename(_)
+        | LogicalPlan::Pivot(_)
+        | LogicalPlan::Unpivot(_) => {
+            unreachable!("column stages are rewritten by the optimizer")
+        }
     }
+}
 
-    for select in &logical_plan.select_list {
-        operations.push(format!("PROJECT {}", select.field));
+/// Plans window functions as a sort on the partition and order keys followed
+/// by a window operator, for every run of calls that share those keys.
+fn convert_window(window: &LogicalWindowNode, catalog: &Catalog, estimator: &Estimator) -> PhysicalNode {
+    let mut node = convert(&window.input, catalog, estimator);
+    let windows = window.window_list();
+    for group in windows.chunk_by(|(a, _), (b, _)| a.spec.sorts_like(&b.spec)) {
+        let spec = &group[0].0.spec;
+        let mut keys: Vec<SortKey> = spec
+            .partition_by
+            .iter()
+            .map(|expr| SortKey::new(expr.clone(), false, None))
+            .collect();
+        keys.extend(spec.order_by.iter().cloned());
+        let rows = node.estimated_rows;
+        if !keys.is_empty() {
+            let cost = node.estimated_cost + sort_cost(rows);
+            node = PhysicalNode::new(Operator::Sort { keys }, vec![node], rows, cost);
+        }
+        let cost = node.estimated_cost + rows * group.len() as f64;
+        let operator = Operator::Window { windows: group.to_vec() };
+        node = PhysicalNode::new(operator, vec![node], rows, cost);
     }
+    node
+}
+
+/// Most rows a top-N sort keeps in memory; larger limits use the sort, which
+/// can spill.
+const MAX_TOP_N_ROWS: usize = 100_000;
+
+/// Plans a LIMIT. A LIMIT right after an ORDER BY becomes a top-N sort that
+/// only keeps the rows it returns.
+fn convert_limit(limit: &LogicalLimitNode, catalog: &Catalog, estimator: &Estimator) -> PhysicalNode {
+    let (operator, input) = match &*limit.input {
+        LogicalPlan::Sort(sort) if limit.offset.saturating_add(limit.limit) <= MAX_TOP_N_ROWS => {
+            let operator = Operator::TopN {
+                keys: sort.keys.clone(),
+                limit: limit.limit,
+                offset: limit.offset,
+            };
+            (operator, convert(&sort.input, catalog, estimator))
+        }
+        input => {
+            let operator = Operator::Limit {
+                limit: limit.limit,
+                offset: limit.offset,
+            };
+            (operator, convert(input, catalog, estimator))
+        }
+    };
+    let rows = (input.estimated_rows - limit.offset as f64).clamp(0.0, limit.limit as f64);
+    let cost = match operator {
+        Operator::TopN { .. } => {
+            let kept = limit.offset.saturating_add(limit.limit) as f64;
+            input.estimated_cost + top_n_cost(input.estimated_rows, kept)
+        }
+        _ => input.estimated_cost,
+    };
+    PhysicalNode::new(operator, vec![input], rows, cost)
+}
+
+/// Plans a join in its written order, as for a join that keeps unmatched rows,
+/// whose inputs cannot be reordered: a hash join if the condition has an
+/// equality between the inputs, a nested loop join otherwise.
+fn convert_outer_join(join: &LogicalJoinNode, catalog: &Catalog, estimator: &Estimator) -> PhysicalNode {
+    let left = convert(&join.left, catalog, estimator);
+    let right = convert(&join.right, catalog, estimator);
+    let schemas = [join.left.schema(catalog).ok(), join.right.schema(catalog).ok()];
+    let condition = Some(join.condition.clone()).filter(|condition| *condition != Expr::Literal(Value::Bool(true)));
+
+    let conjuncts = condition.clone().map(Expr::into_conjuncts).unwrap_or_default();
+    let algorithm = if conjuncts.iter().any(|conjunct| equi_sides(&schemas, conjunct).is_some()) {
+        JoinAlgorithm::Hash
+    } else {
+        JoinAlgorithm::NestedLoop
+    };
+    let (left_rows, right_rows) = (left.estimated_rows, right.estimated_rows);
+    let matched = left_rows * right_rows * condition.as_ref().map_or(1.0, |c| estimator.selectivity(c));
+    let rows = match join.join_type {
+        JoinType::Left => matched.max(left_rows),
+        JoinType::Right => matched.max(right_rows),
+        JoinType::Full => matched.max(left_rows + right_rows),
+        JoinType::Semi | JoinType::Anti => left_rows,
+        JoinType::Inner => matched,
+    };
+    let cost = left.estimated_cost + right.estimated_cost + join_cost(algorithm, left_rows, right_rows);
+    let operator = Operator::Join {
+        algorithm,
+        join_type: join.join_type,
+        condition,
+    };
+    PhysicalNode::new(operator, vec![left, right], rows, cost)
+}
+
+/// Adds a predicate to a node, merging it into the filter of a scan.
+fn with_filter(mut node: PhysicalNode, condition: Expr, estimator: &Estimator) -> PhysicalNode {
+    let rows = node.estimated_rows * estimator.selectivity(&condition);
+    if let Operator::Scan { filter, .. } = &mut node.operator {
+        *filter = Some(match filter.take() {
+            Some(existing) => Expr::binary(existing, BinaryOperator::And, condition),
+            None => condition,
+        });
+        node.estimated_rows = rows;
+        return node;
+    }
+    let cost = node.estimated_cost + node.estimated_rows;
+    PhysicalNode::new(Operator::Filter { condition }, vec![node], rows, cost)
+}
 
-    for order_by in &logical_plan.order_by {
-        operations.push(format!("ORDER BY {}", order_by.field));
+/// Plans a tree of adjacent joins at once: its inputs become the relations of
+/// the join order search and all join conditions its predicates.
+fn convert_joins(plan: &LogicalPlan, catalog: &Catalog, estimator: &Estimator) -> PhysicalNode {
+    let mut inputs = Vec::new();
+    let mut conditions = Vec::new();
+    flatten_joins(plan, &mut inputs, &mut conditions);
+    if let LogicalPlan::Join(join) = plan {
+        // too many inputs for the search: keep this join where it is written
+        // and order the joins below it
+        if inputs.len() > MAX_RELATIONS {
+            return convert_outer_join(join, catalog, estimator);
+        }
     }
 
-    PhysicalPlan {
-        query: logical_plan.query.clone(),
-        operations,
+    let schemas: Vec<Option<Schema>> = inputs.iter().map(|input| input.schema(catalog).ok()).collect();
+    let mut nodes: Vec<Option<PhysicalNode>> = inputs
+        .iter()
+        .map(|input| Some(convert(input, catalog, estimator)))
+        .collect();
+
+    let mut join_predicates = Vec::new();
+    let mut remaining_filters = Vec::new();
+    for conjunct in conditions {
+        if conjunct == Expr::Literal(Value::Bool(true)) {
+            continue;
+        }
+        match predicate_relations(&schemas, &conjunct) {
+            Some(relations) if relations.count_ones() >= 2 => join_predicates.push((conjunct, relations)),
+            // a "join" condition on a single input is just a filter of it
+            Some(relations) if relations.count_ones() == 1 => {
+                let index = relations.trailing_zeros() as usize;
+                let node = nodes[index].take().unwrap();
+                nodes[index] = Some(with_filter(node, conjunct, estimator));
+            }
+            _ => remaining_filters.push(conjunct),
+        }
+    }
+
+    let relations = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| {
+            let node = node.as_ref().unwrap();
+            JoinTree::relation(i, node.estimated_rows, node.estimated_cost)
+        })
+        .collect();
+    let predicates: Vec<JoinPredicate> = join_predicates
+        .iter()
+        .map(|(conjunct, relations)| JoinPredicate {
+            relations: *relations,
+            selectivity: estimator.selectivity(conjunct),
+            equi_sides: equi_sides(&schemas, conjunct),
+        })
+        .collect();
+    let tree = order_joins(relations, &predicates);
+    let mut order = Vec::new();
+    relation_order(&tree, &mut order);
+    let columns = if order.iter().enumerate().all(|(i, &index)| i == index) {
+        None
+    } else {
+        // the search may put any input first, but the columns keep their
+        // written order; keep the join as written where a projection cannot
+        // tell them apart
+        match (written_columns(&schemas), plan) {
+            (Some(columns), _) => Some(columns),
+            (None, LogicalPlan::Join(join)) => return convert_outer_join(join, catalog, estimator),
+            (None, _) => None,
+        }
+    };
+    let node = build_join_tree(&tree, &mut nodes, &join_predicates);
+
+    let node = match Expr::conjunction(remaining_filters) {
+        Some(condition) => with_filter(node, condition, estimator),
+        None => node,
+    };
+    match columns {
+        Some(columns) => {
+            let fields = columns.into_iter().map(|column| (column, None)).collect();
+            let (rows, cost) = (node.estimated_rows, node.estimated_cost + node.estimated_rows);
+            PhysicalNode::new(Operator::Project { fields }, vec![node], rows, cost)
+        }
+        None => node,
+    }
+}
+
+/// The join inputs in the order the tree returns their columns.
+fn relation_order(tree: &JoinTree, order: &mut Vec<usize>) {
+    match &tree.node {
+        JoinNode::Relation(index) => order.push(*index),
+        JoinNode::Join { left, right, .. } => {
+            relation_order(left, order);
+            relation_order(right, order);
+        }
+    }
+}
+
+/// The columns of the join inputs in their written order, `None` if a schema
+/// is unknown or two columns share a qualified name.
+fn written_columns(schemas: &[Option<Schema>]) -> Option<Vec<Expr>> {
+    let fields: Vec<Field> = schemas
+        .iter()
+        .map(|schema| schema.as_ref().map(|schema| schema.fields.clone()))
+        .collect::<Option<Vec<_>>>()?
+        .concat();
+    let schema = Schema::new(fields);
+    schema
+        .fields
+        .iter()
+        .map(|field| {
+            schema.resolve(field.qualifier.as_deref(), &field.name).ok()?;
+            Some(Expr::Column {
+                qualifier: field.qualifier.clone(),
+                name: field.name.clone(),
+            })
+        })
+        .collect()
+}
+
+fn flatten_joins<'a>(plan: &'a LogicalPlan, inputs: &mut Vec<&'a LogicalPlan>, conditions: &mut Vec<Expr>) {
+    match plan {
+        LogicalPlan::Join(join) if join.join_type == JoinType::Inner => {
+            flatten_joins(&join.left, inputs, conditions);
+            flatten_joins(&join.right, inputs, conditions);
+            conditions.extend(join.condition.clone().into_conjuncts());
+        }
+        plan => inputs.push(plan),
+    }
+}
+
+fn build_join_tree(tree: &JoinTree, nodes: &mut [Option<PhysicalNode>], join_predicates: &[(Expr, u64)]) -> PhysicalNode {
+    match &tree.node {
+        JoinNode::Relation(index) => nodes[*index].take().unwrap(),
+        JoinNode::Join {
+            algorithm,
+            left,
+            right,
+            predicates,
+        } => {
+            let condition = Expr::conjunction(predicates.iter().map(|&i| join_predicates[i].0.clone()).collect());
+            let children = vec![
+                build_join_tree(left, nodes, join_predicates),
+                build_join_tree(right, nodes, join_predicates),
+            ];
+            let operator = Operator::Join {
+                algorithm: *algorithm,
+                join_type: JoinType::Inner,
+                condition,
+            };
+            PhysicalNode::new(operator, children, tree.rows, tree.cost)
+        }
+    }
+}
+
+/// The join inputs a predicate reads as a bit set, `None` if a column cannot
+/// be attributed to exactly one input.
+fn predicate_relations(schemas: &[Option<Schema>], predicate: &Expr) -> Option<u64> {
+    predicate.columns().iter().try_fold(0u64, |mask, (qualifier, name)| {
+        let owners: Vec<usize> = (0..schemas.len())
+            .filter(|&i| {
+                schemas[i]
+                    .as_ref()
+                    .is_some_and(|schema| schema.resolve(qualifier.as_deref(), name).is_ok())
+            })
+            .collect();
+        match owners.as_slice() {
+            [owner] => Some(mask | 1 << owner),
+            _ => None,
+        }
+    })
+}
+
+fn equi_sides(schemas: &[Option<Schema>], predicate: &Expr) -> Option<(u64, u64)> {
+    match predicate {
+        Expr::Binary {
+            left,
+            op: BinaryOperator::Eq,
+            right,
+        } if matches!(**left, Expr::Column { .. }) && matches!(**right, Expr::Column { .. }) => {
+            let left = predicate_relations(schemas, left)?;
+            let right = predicate_relations(schemas, right)?;
+            (left != right).then_some((left, right))
+        }
+        _ => None,
     }
 }
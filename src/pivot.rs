@@ -0,0 +1,232 @@
+use crate::aggregate::{AggregateCall, LogicalAggregateListNode, LogicalAggregateNode};
+use crate::errors::{ExecutionError, ParsingError};
+use crate::expression::{parse_expression, parse_operand, BinaryOperator, Expr};
+use crate::filter::LogicalFilterNode;
+use crate::lexer::{Token, Tokens};
+use crate::logical_plan::LogicalPlan;
+use crate::schema::Schema;
+use crate::select::{LogicalProjectNode, LogicalSelectListNode};
+use crate::set_operation::{LogicalSetOperationNode, SetOperator};
+use crate::value::Value;
+
+/// `PIVOT (call [AS alias], ... FOR expr IN (value [AS name], ...))`: one
+/// output column per aggregate and value, aggregating the rows whose `column`
+/// equals the value. The rows are grouped by the input columns that neither
+/// the aggregates nor `column` read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalPivotNode {
+    pub input: Box<LogicalPlan>,
+    pub aggregates: Vec<LogicalAggregateListNode>,
+    pub column: Expr,
+    pub values: Vec<(Value, Option<String>)>,
+}
+
+impl LogicalPivotNode {
+    /// Parses the parenthesized part after PIVOT.
+    pub fn parse(input: LogicalPlan, tokens: &mut Tokens) -> Result<Self, ParsingError> {
+        tokens.expect_symbol("(")?;
+        let mut aggregates = Vec::new();
+        loop {
+            let call = AggregateCall::parse(tokens)?;
+            let alias = parse_alias(tokens)?;
+            aggregates.push(LogicalAggregateListNode { call, alias });
+            if !tokens.accept_symbol(",") {
+                break;
+            }
+        }
+        if aggregates.len() > 1 && aggregates.iter().any(|aggregate| aggregate.alias.is_none()) {
+            return Err(ParsingError::new("PIVOT needs an alias for every aggregate when there are several"));
+        }
+        tokens.expect_keyword("FOR")?;
+        let column = parse_operand(tokens)?;
+        tokens.expect_keyword("IN")?;
+        tokens.expect_symbol("(")?;
+        let mut values = Vec::new();
+        loop {
+            let value = match parse_expression(tokens)? {
+                Expr::Literal(value) if !value.is_null() => value,
+                expr => return Err(ParsingError::new(&format!("PIVOT values must be literals, found {}", expr))),
+            };
+            values.push((value, parse_alias(tokens)?));
+            if !tokens.accept_symbol(",") {
+                break;
+            }
+        }
+        tokens.expect_symbol(")")?;
+        tokens.expect_symbol(")")?;
+        Ok(Self {
+            input: Box::new(input),
+            aggregates,
+            column,
+            values,
+        })
+    }
+
+    /// The aggregation the pivot stands for over rows of `input`, the schema
+    /// of `self.input`: each call only reads the rows of its value, through
+    /// `IF(column = value, argument, NULL)`.
+    pub fn aggregate(&self, input: &Schema) -> Result<LogicalAggregateNode, ExecutionError> {
+        let mut read = Vec::new();
+        for expr in self.aggregates.iter().filter_map(|a| a.call.argument.as_ref()).chain([&self.column]) {
+            for (qualifier, name) in expr.columns() {
+                read.push(input.resolve(qualifier.as_deref(), &name)?);
+            }
+        }
+        let group_by = input
+            .fields
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !read.contains(i))
+            .map(|(_, field)| {
+                let column = Expr::Column {
+                    qualifier: field.qualifier.clone(),
+                    name: field.name.clone(),
+                };
+                LogicalSelectListNode::new(column, None)
+            })
+            .collect();
+        let mut aggregates = Vec::new();
+        for aggregate in &self.aggregates {
+            for (value, name) in &self.values {
+                let name = name.clone().unwrap_or_else(|| match value {
+                    Value::String(s) => s.clone(),
+                    value => value.to_string(),
+                });
+                let condition = Expr::Binary {
+                    left: Box::new(self.column.clone()),
+                    op: BinaryOperator::Eq,
+                    right: Box::new(Expr::Literal(value.clone())),
+                };
+                // COUNT(*) counts the rows of the value
+                let argument = aggregate.call.argument.clone().unwrap_or(Expr::Literal(Value::Int(1)));
+                let call = AggregateCall {
+                    argument: Some(Expr::Function {
+                        name: "IF".to_string(),
+                        args: vec![condition, argument, Expr::Literal(Value::Null)],
+                    }),
+                    ..aggregate.call.clone()
+                };
+                let alias = match &aggregate.alias {
+                    Some(alias) => format!("{}_{}", alias, name),
+                    None => name,
+                };
+                aggregates.push(LogicalAggregateListNode {
+                    call,
+                    alias: Some(alias),
+                });
+            }
+        }
+        Ok(LogicalAggregateNode::new((*self.input).clone(), group_by, aggregates))
+    }
+}
+
+/// `UNPIVOT [INCLUDE NULLS | EXCLUDE NULLS] (value FOR name IN (column [AS label], ...))`:
+/// one output row per input row and listed column, with the label of the
+/// column in `name_column` and its value in `value_column`. The other input
+/// columns are kept. Rows whose value is NULL are left out unless INCLUDE
+/// NULLS is given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalUnpivotNode {
+    pub input: Box<LogicalPlan>,
+    pub value_column: String,
+    pub name_column: String,
+    pub columns: Vec<(String, Option<String>)>,
+    pub include_nulls: bool,
+}
+
+impl LogicalUnpivotNode {
+    /// Parses what follows UNPIVOT.
+    pub fn parse(input: LogicalPlan, tokens: &mut Tokens) -> Result<Self, ParsingError> {
+        let include_nulls = if tokens.accept_keyword("INCLUDE") {
+            tokens.expect_keyword("NULLS")?;
+            true
+        } else {
+            if tokens.accept_keyword("EXCLUDE") {
+                tokens.expect_keyword("NULLS")?;
+            }
+            false
+        };
+        tokens.expect_symbol("(")?;
+        let value_column = tokens.expect_identifier()?;
+        tokens.expect_keyword("FOR")?;
+        let name_column = tokens.expect_identifier()?;
+        tokens.expect_keyword("IN")?;
+        tokens.expect_symbol("(")?;
+        let mut columns = Vec::new();
+        loop {
+            let column = tokens.expect_identifier()?;
+            let label = if tokens.accept_keyword("AS") {
+                match tokens.next() {
+                    Some(Token::StringLiteral(label) | Token::Identifier(label)) => Some(label),
+                    _ => return Err(ParsingError::new("Expected a label after AS in UNPIVOT")),
+                }
+            } else {
+                None
+            };
+            columns.push((column, label));
+            if !tokens.accept_symbol(",") {
+                break;
+            }
+        }
+        tokens.expect_symbol(")")?;
+        tokens.expect_symbol(")")?;
+        Ok(Self {
+            input: Box::new(input),
+            value_column,
+            name_column,
+            columns,
+            include_nulls,
+        })
+    }
+
+    /// The plan the unpivot stands for over rows of `input`, the schema of
+    /// `self.input`: a UNION ALL of one projection per listed column.
+    pub fn union(&self, input: &Schema) -> Result<LogicalPlan, ExecutionError> {
+        let mut unpivoted = Vec::new();
+        for (name, _) in &self.columns {
+            let index = input.resolve(None, name)?;
+            if unpivoted.contains(&index) {
+                return Err(ExecutionError::new(&format!("UNPIVOT lists {} twice", name)));
+            }
+            unpivoted.push(index);
+        }
+        let column = |index: usize| Expr::Column {
+            qualifier: input.fields[index].qualifier.clone(),
+            name: input.fields[index].name.clone(),
+        };
+        let kept: Vec<LogicalSelectListNode> = (0..input.fields.len())
+            .filter(|i| !unpivoted.contains(i))
+            .map(|i| LogicalSelectListNode::new(column(i), None))
+            .collect();
+
+        let mut plan: Option<LogicalPlan> = None;
+        for ((name, label), index) in self.columns.iter().zip(unpivoted) {
+            let mut branch = (*self.input).clone();
+            if !self.include_nulls {
+                let condition = Expr::IsNull {
+                    expr: Box::new(column(index)),
+                    negated: true,
+                };
+                branch = LogicalPlan::Filter(LogicalFilterNode::new(branch, condition));
+            }
+            let mut select_list = kept.clone();
+            let label = Expr::Literal(Value::String(label.clone().unwrap_or_else(|| name.clone())));
+            select_list.push(LogicalSelectListNode::new(label, Some(&self.name_column)));
+            select_list.push(LogicalSelectListNode::new(column(index), Some(&self.value_column)));
+            let branch = LogicalPlan::Project(LogicalProjectNode::new(branch, select_list));
+            plan = Some(match plan {
+                Some(plan) => LogicalPlan::SetOperation(LogicalSetOperationNode::new(plan, branch, SetOperator::Union, true)),
+                None => branch,
+            });
+        }
+        Ok(plan.expect("UNPIVOT lists at least one column"))
+    }
+}
+
+fn parse_alias(tokens: &mut Tokens) -> Result<Option<String>, ParsingError> {
+    if tokens.accept_keyword("AS") {
+        tokens.expect_identifier().map(Some)
+    } else {
+        Ok(None)
+    }
+}
@@ -0,0 +1,119 @@
+use crate::errors::ParsingError;
+use crate::lexer::{Token, Tokens};
+use crate::random::Random;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SampleMethod {
+    /// Keeps whole pages, skipping the others without reading them
+    System,
+    /// Keeps every row on its own
+    Bernoulli,
+}
+
+impl fmt::Display for SampleMethod {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SampleMethod::System => write!(f, "SYSTEM"),
+            SampleMethod::Bernoulli => write!(f, "BERNOULLI"),
+        }
+    }
+}
+
+/// `TABLESAMPLE method (n PERCENT) [REPEATABLE (seed)]` on a table: each
+/// page or row is kept with probability `percent / 100`. The same seed
+/// keeps the same rows of an unchanged table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSample {
+    pub method: SampleMethod,
+    pub percent: f64,
+    pub seed: Option<u64>,
+}
+
+impl TableSample {
+    /// Parses `TABLESAMPLE {SYSTEM | BERNOULLI} (n PERCENT) [REPEATABLE (seed)]`.
+    pub fn parse(tokens: &mut Tokens) -> Result<Self, ParsingError> {
+        tokens.expect_keyword("TABLESAMPLE")?;
+        let method = if tokens.accept_keyword("SYSTEM") {
+            SampleMethod::System
+        } else if tokens.accept_keyword("BERNOULLI") {
+            SampleMethod::Bernoulli
+        } else {
+            return Err(tokens.unexpected("SYSTEM or BERNOULLI"));
+        };
+        tokens.expect_symbol("(")?;
+        let percent = match tokens.next() {
+            Some(Token::Number(number)) => number
+                .parse::<f64>()
+                .map_err(|_| ParsingError::new(&format!("Invalid sample size {}", number)))?,
+            _ => return Err(ParsingError::new("Expected a number in TABLESAMPLE")),
+        };
+        if !(0.0..=100.0).contains(&percent) {
+            return Err(ParsingError::new("Sample size must be between 0 and 100 percent"));
+        }
+        tokens.expect_keyword("PERCENT")?;
+        tokens.expect_symbol(")")?;
+        let seed = if tokens.accept_keyword("REPEATABLE") {
+            tokens.expect_symbol("(")?;
+            let seed = match tokens.next() {
+                Some(Token::Number(number)) => number
+                    .parse::<u64>()
+                    .map_err(|_| ParsingError::new(&format!("REPEATABLE needs a non-negative integer, found {}", number)))?,
+                _ => return Err(ParsingError::new("Expected a seed after REPEATABLE")),
+            };
+            tokens.expect_symbol(")")?;
+            Some(seed)
+        } else {
+            None
+        };
+        Ok(Self { method, percent, seed })
+    }
+
+    /// Share of the pages or rows that is kept.
+    pub fn fraction(&self) -> f64 {
+        self.percent / 100.0
+    }
+
+    /// The generator that decides which pages or rows are kept; without
+    /// REPEATABLE every sampler draws a different sample.
+    pub fn sampler(&self) -> Sampler {
+        let seed = self.seed.unwrap_or_else(|| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            now.as_nanos() as u64
+        });
+        Sampler {
+            fraction: self.fraction(),
+            seed,
+            random: Random::new(seed),
+        }
+    }
+}
+
+impl fmt::Display for TableSample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "TABLESAMPLE {} ({} PERCENT)", self.method, self.percent)?;
+        if let Some(seed) = self.seed {
+            write!(f, " REPEATABLE ({})", seed)?;
+        }
+        Ok(())
+    }
+}
+
+/// Draws which pages or rows a sample keeps. `restart` makes it draw the
+/// same ones again, so a scan that is read twice returns the same rows.
+pub struct Sampler {
+    fraction: f64,
+    seed: u64,
+    random: Random,
+}
+
+impl Sampler {
+    pub fn restart(&mut self) {
+        self.random = Random::new(self.seed);
+    }
+
+    pub fn keep(&mut self) -> bool {
+        self.random.next_f64() < self.fraction
+    }
+}
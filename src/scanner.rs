@@ -1,12 +1,32 @@
-#[derive(Debug)]
+use crate::expression::Expr;
+use crate::sample::TableSample;
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct LogicalScanNode {
     pub table_name: String,
+    /// Name the query refers to the table by, from `table AS alias`
+    pub alias: Option<String>,
+    /// Predicates pushed into the scan by the optimizer
+    pub filters: Vec<Expr>,
+    /// Columns the rest of the plan needs, `None` for all of them
+    pub projection: Option<Vec<String>>,
+    /// `TABLESAMPLE` after the table
+    pub sample: Option<TableSample>,
 }
 
 impl LogicalScanNode {
-    pub fn new(table_name: &str) -> Self {
+    pub fn new(table_name: &str, alias: Option<&str>) -> Self {
         Self {
             table_name: table_name.to_string(),
+            alias: alias.map(|a| a.to_string()),
+            filters: Vec::new(),
+            projection: None,
+            sample: None,
         }
     }
+
+    /// The qualifier of the columns the scan produces.
+    pub fn qualifier(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.table_name)
+    }
 }
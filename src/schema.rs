@@ -0,0 +1,56 @@
+use crate::errors::ExecutionError;
+use crate::value::DataType;
+
+/// A column of an intermediate result, optionally qualified by the relation it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub qualifier: Option<String>,
+    pub name: String,
+    pub data_type: DataType,
+}
+
+impl Field {
+    pub fn new(qualifier: Option<&str>, name: &str, data_type: DataType) -> Self {
+        Self {
+            qualifier: qualifier.map(|q| q.to_string()),
+            name: name.to_string(),
+            data_type,
+        }
+    }
+
+    pub fn matches(&self, qualifier: Option<&str>, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            && qualifier.is_none_or(|q| {
+                self.qualifier
+                    .as_deref()
+                    .is_some_and(|own| own.eq_ignore_ascii_case(q))
+            })
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schema {
+    pub fields: Vec<Field>,
+}
+
+impl Schema {
+    pub fn new(fields: Vec<Field>) -> Self {
+        Self { fields }
+    }
+
+    /// Finds the position of a (possibly qualified) column.
+    pub fn resolve(&self, qualifier: Option<&str>, name: &str) -> Result<usize, ExecutionError> {
+        let matches: Vec<usize> = (0..self.fields.len())
+            .filter(|&i| self.fields[i].matches(qualifier, name))
+            .collect();
+        let display = match qualifier {
+            Some(q) => format!("{}.{}", q, name),
+            None => name.to_string(),
+        };
+        match matches.as_slice() {
+            [index] => Ok(*index),
+            [] => Err(ExecutionError::new(&format!("Unknown column {}", display))),
+            _ => Err(ExecutionError::new(&format!("Column {} is ambiguous", display))),
+        }
+    }
+}
@@ -1,12 +1,183 @@
-#[derive(Debug)]
+use crate::errors::ExecutionError;
+use crate::expression::Expr;
+use crate::logical_plan::LogicalPlan;
+use crate::schema::{Field, Schema};
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct LogicalSelectListNode {
-    pub field: String,
+    pub field: Expr,
+    pub alias: Option<String>,
 }
 
 impl LogicalSelectListNode {
-    pub fn new(field: &str) -> Self {
+    pub fn new(field: Expr, alias: Option<&str>) -> Self {
+        Self {
+            field,
+            alias: alias.map(|a| a.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalProjectNode {
+    pub input: Box<LogicalPlan>,
+    pub select_list: Vec<LogicalSelectListNode>,
+}
+
+impl LogicalProjectNode {
+    pub fn new(input: LogicalPlan, select_list: Vec<LogicalSelectListNode>) -> Self {
+        Self {
+            input: Box::new(input),
+            select_list,
+        }
+    }
+}
+
+/// Pairs every output column of a select list with the expression computing
+/// it; `*` expands to the columns of the input. A selected column keeps its
+/// qualifier unless it is renamed.
+pub fn expand_select_list<'a>(
+    select_list: impl IntoIterator<Item = (&'a Expr, Option<&'a str>)>,
+    input: &Schema,
+) -> Result<Vec<(Expr, Field)>, ExecutionError> {
+    let mut output = Vec::new();
+    for (expr, alias) in select_list {
+        if *expr == Expr::Wildcard {
+            for field in &input.fields {
+                let column = Expr::Column {
+                    qualifier: field.qualifier.clone(),
+                    name: field.name.clone(),
+                };
+                output.push((column, field.clone()));
+            }
+            continue;
+        }
+
+        let data_type = expr.data_type(input)?;
+        let field = match (expr, alias) {
+            (_, Some(alias)) => Field::new(None, alias, data_type),
+            (Expr::Column { qualifier, name }, None) => input.fields[input.resolve(qualifier.as_deref(), name)?].clone(),
+            (expr, None) => Field::new(None, &expr.to_string(), data_type),
+        };
+        output.push((expr.clone(), field));
+    }
+    Ok(output)
+}
+
+/// The column of `schema` at `index` as an expression.
+fn column_at(schema: &Schema, index: usize) -> Expr {
+    Expr::Column {
+        qualifier: schema.fields[index].qualifier.clone(),
+        name: schema.fields[index].name.clone(),
+    }
+}
+
+/// `|> EXTEND expr [AS alias], ...`: appends computed columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalExtendNode {
+    pub input: Box<LogicalPlan>,
+    pub extensions: Vec<LogicalSelectListNode>,
+}
+
+impl LogicalExtendNode {
+    pub fn new(input: LogicalPlan, extensions: Vec<LogicalSelectListNode>) -> Self {
         Self {
-            field: field.to_string(),
+            input: Box::new(input),
+            extensions,
+        }
+    }
+
+    pub fn select_list(&self, input: &Schema) -> Result<Vec<LogicalSelectListNode>, ExecutionError> {
+        let mut select_list: Vec<LogicalSelectListNode> = (0..input.fields.len())
+            .map(|i| LogicalSelectListNode::new(column_at(input, i), None))
+            .collect();
+        select_list.extend(self.extensions.iter().cloned());
+        Ok(select_list)
+    }
+}
+
+/// `|> SET column = expr, ...`: replaces the values of existing columns,
+/// keeping their position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalSetNode {
+    pub input: Box<LogicalPlan>,
+    pub assignments: Vec<(String, Expr)>,
+}
+
+impl LogicalSetNode {
+    pub fn new(input: LogicalPlan, assignments: Vec<(String, Expr)>) -> Self {
+        Self {
+            input: Box::new(input),
+            assignments,
+        }
+    }
+
+    pub fn select_list(&self, input: &Schema) -> Result<Vec<LogicalSelectListNode>, ExecutionError> {
+        let mut select_list: Vec<LogicalSelectListNode> = (0..input.fields.len())
+            .map(|i| LogicalSelectListNode::new(column_at(input, i), None))
+            .collect();
+        for (column, expr) in &self.assignments {
+            let index = input.resolve(None, column)?;
+            select_list[index] = LogicalSelectListNode::new(expr.clone(), Some(&input.fields[index].name));
+        }
+        Ok(select_list)
+    }
+}
+
+/// `|> DROP column, ...`: removes columns.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalDropNode {
+    pub input: Box<LogicalPlan>,
+    pub columns: Vec<String>,
+}
+
+impl LogicalDropNode {
+    pub fn new(input: LogicalPlan, columns: Vec<String>) -> Self {
+        Self {
+            input: Box::new(input),
+            columns,
+        }
+    }
+
+    pub fn select_list(&self, input: &Schema) -> Result<Vec<LogicalSelectListNode>, ExecutionError> {
+        let dropped = self
+            .columns
+            .iter()
+            .map(|column| input.resolve(None, column))
+            .collect::<Result<Vec<_>, _>>()?;
+        if dropped.len() == input.fields.len() {
+            return Err(ExecutionError::new("DROP cannot remove every column"));
+        }
+        Ok((0..input.fields.len())
+            .filter(|i| !dropped.contains(i))
+            .map(|i| LogicalSelectListNode::new(column_at(input, i), None))
+            .collect())
+    }
+}
+
+/// `|> RENAME column AS name, ...`: renames columns in place.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalRenameNode {
+    pub input: Box<LogicalPlan>,
+    pub renames: Vec<(String, String)>,
+}
+
+impl LogicalRenameNode {
+    pub fn new(input: LogicalPlan, renames: Vec<(String, String)>) -> Self {
+        Self {
+            input: Box::new(input),
+            renames,
+        }
+    }
+
+    pub fn select_list(&self, input: &Schema) -> Result<Vec<LogicalSelectListNode>, ExecutionError> {
+        let mut select_list: Vec<LogicalSelectListNode> = (0..input.fields.len())
+            .map(|i| LogicalSelectListNode::new(column_at(input, i), None))
+            .collect();
+        for (column, name) in &self.renames {
+            let index = input.resolve(None, column)?;
+            select_list[index].alias = Some(name.clone());
         }
+        Ok(select_list)
     }
 }
@@ -0,0 +1,73 @@
+use crate::errors::ExecutionError;
+use crate::logical_plan::LogicalPlan;
+use crate::schema::{Field, Schema};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetOperator {
+    Union,
+    Intersect,
+    Except,
+}
+
+impl fmt::Display for SetOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SetOperator::Union => "UNION",
+            SetOperator::Intersect => "INTERSECT",
+            SetOperator::Except => "EXCEPT",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Combines the rows of two queries; columns are matched by position. Only
+/// UNION keeps duplicates with ALL, the others always remove them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalSetOperationNode {
+    pub left: Box<LogicalPlan>,
+    pub right: Box<LogicalPlan>,
+    pub operator: SetOperator,
+    pub all: bool,
+}
+
+impl LogicalSetOperationNode {
+    pub fn new(left: LogicalPlan, right: LogicalPlan, operator: SetOperator, all: bool) -> Self {
+        Self {
+            left: Box::new(left),
+            right: Box::new(right),
+            operator,
+            all,
+        }
+    }
+}
+
+/// The columns of a set operation: named like those of the left input, with
+/// the type both inputs convert to.
+pub fn set_operation_fields(operator: SetOperator, left: &Schema, right: &Schema) -> Result<Vec<Field>, ExecutionError> {
+    if left.fields.len() != right.fields.len() {
+        return Err(ExecutionError::new(&format!(
+            "{} needs inputs with the same number of columns, got {} and {}",
+            operator,
+            left.fields.len(),
+            right.fields.len()
+        )));
+    }
+    left.fields
+        .iter()
+        .zip(&right.fields)
+        .enumerate()
+        .map(|(i, (l, r))| {
+            let data_type = l.data_type.common_supertype(&r.data_type).ok_or_else(|| {
+                ExecutionError::new(&format!(
+                    "Column {} of {} has incompatible types {} and {}",
+                    i + 1,
+                    operator,
+                    l.data_type,
+                    r.data_type
+                ))
+            })?;
+            Ok(Field::new(l.qualifier.as_deref(), &l.name, data_type))
+        })
+        .collect()
+}
@@ -0,0 +1,276 @@
+use crate::catalog::{Column, ForeignKey, ReferentialAction, TableSchema};
+use crate::errors::ParsingError;
+use crate::expression::{parse_expression, Expr};
+use crate::lexer::{Token, Tokens};
+use crate::logical_plan::LogicalPlan;
+use crate::value::DataType;
+
+#[derive(Debug)]
+pub enum Statement {
+    CreateTable(TableSchema),
+    Insert {
+        table: String,
+        columns: Option<Vec<String>>,
+        rows: Vec<Vec<Expr>>,
+    },
+    Update {
+        table: String,
+        assignments: Vec<(String, Expr)>,
+        filter: Option<Expr>,
+    },
+    Delete {
+        table: String,
+        filter: Option<Expr>,
+    },
+    Analyze {
+        table: Option<String>,
+        sample_percent: Option<f64>,
+    },
+    /// `SET name = value` for a session setting
+    Set {
+        name: String,
+        value: Expr,
+    },
+    Query(LogicalPlan),
+    /// `EXPLAIN [ANALYZE] query`
+    Explain {
+        analyze: bool,
+        query: String,
+        plan: LogicalPlan,
+    },
+}
+
+pub fn parse_create_table(tokens: &mut Tokens) -> Result<Statement, ParsingError> {
+    tokens.expect_keyword("CREATE")?;
+    tokens.expect_keyword("TABLE")?;
+    let name = tokens.expect_identifier()?;
+    let mut table = TableSchema {
+        name,
+        columns: Vec::new(),
+        primary_key: Vec::new(),
+        foreign_keys: Vec::new(),
+    };
+
+    tokens.expect_symbol("(")?;
+    loop {
+        if tokens.accept_keyword("PRIMARY") {
+            tokens.expect_keyword("KEY")?;
+            set_primary_key(&mut table, tokens.parse_identifier_list()?)?;
+        } else if tokens.accept_keyword("FOREIGN") {
+            tokens.expect_keyword("KEY")?;
+            let columns = tokens.parse_identifier_list()?;
+            table.foreign_keys.push(parse_references(tokens, columns)?);
+        } else {
+            parse_column_definition(tokens, &mut table)?;
+        }
+
+        if !tokens.accept_symbol(",") {
+            break;
+        }
+    }
+    tokens.expect_symbol(")")?;
+    tokens.expect_end()?;
+
+    Ok(Statement::CreateTable(table))
+}
+
+fn parse_column_definition(tokens: &mut Tokens, table: &mut TableSchema) -> Result<(), ParsingError> {
+    let name = tokens.expect_identifier()?;
+    let data_type = DataType::parse(tokens)?;
+    let mut nullable = true;
+
+    loop {
+        if tokens.accept_keyword("NOT") {
+            tokens.expect_keyword("NULL")?;
+            nullable = false;
+        } else if tokens.accept_keyword("NULL") {
+            nullable = true;
+        } else if tokens.accept_keyword("PRIMARY") {
+            tokens.expect_keyword("KEY")?;
+            set_primary_key(table, vec![name.clone()])?;
+        } else if tokens.peek_keyword("REFERENCES") {
+            let foreign_key = parse_references(tokens, vec![name.clone()])?;
+            table.foreign_keys.push(foreign_key);
+        } else {
+            break;
+        }
+    }
+
+    table.columns.push(Column {
+        name,
+        data_type,
+        nullable,
+    });
+    Ok(())
+}
+
+fn set_primary_key(table: &mut TableSchema, columns: Vec<String>) -> Result<(), ParsingError> {
+    if !table.primary_key.is_empty() {
+        return Err(ParsingError::new(&format!(
+            "Table {} has more than one PRIMARY KEY",
+            table.name
+        )));
+    }
+    table.primary_key = columns;
+    Ok(())
+}
+
+/// Parses `REFERENCES table [(columns)] [ON DELETE action] [ON UPDATE action]`.
+fn parse_references(tokens: &mut Tokens, columns: Vec<String>) -> Result<ForeignKey, ParsingError> {
+    tokens.expect_keyword("REFERENCES")?;
+    let referenced_table = tokens.expect_identifier()?;
+    let referenced_columns = if tokens.peek_symbol("(") {
+        tokens.parse_identifier_list()?
+    } else {
+        Vec::new()
+    };
+
+    let mut foreign_key = ForeignKey {
+        columns,
+        referenced_table,
+        referenced_columns,
+        on_delete: ReferentialAction::Restrict,
+        on_update: ReferentialAction::Restrict,
+    };
+
+    while tokens.accept_keyword("ON") {
+        if tokens.accept_keyword("DELETE") {
+            foreign_key.on_delete = parse_referential_action(tokens)?;
+        } else if tokens.accept_keyword("UPDATE") {
+            foreign_key.on_update = parse_referential_action(tokens)?;
+        } else {
+            return Err(tokens.unexpected("DELETE or UPDATE"));
+        }
+    }
+
+    Ok(foreign_key)
+}
+
+fn parse_referential_action(tokens: &mut Tokens) -> Result<ReferentialAction, ParsingError> {
+    if tokens.accept_keyword("RESTRICT") {
+        Ok(ReferentialAction::Restrict)
+    } else if tokens.accept_keyword("NO") {
+        tokens.expect_keyword("ACTION")?;
+        Ok(ReferentialAction::NoAction)
+    } else if tokens.accept_keyword("CASCADE") {
+        Ok(ReferentialAction::Cascade)
+    } else if tokens.accept_keyword("SET") {
+        tokens.expect_keyword("NULL")?;
+        Ok(ReferentialAction::SetNull)
+    } else {
+        Err(tokens.unexpected("RESTRICT, NO ACTION, CASCADE or SET NULL"))
+    }
+}
+
+pub fn parse_insert(tokens: &mut Tokens) -> Result<Statement, ParsingError> {
+    tokens.expect_keyword("INSERT")?;
+    tokens.expect_keyword("INTO")?;
+    let table = tokens.expect_identifier()?;
+    let columns = if tokens.peek_symbol("(") {
+        Some(tokens.parse_identifier_list()?)
+    } else {
+        None
+    };
+
+    tokens.expect_keyword("VALUES")?;
+    let mut rows = Vec::new();
+    loop {
+        tokens.expect_symbol("(")?;
+        let mut row = vec![parse_expression(tokens)?];
+        while tokens.accept_symbol(",") {
+            row.push(parse_expression(tokens)?);
+        }
+        tokens.expect_symbol(")")?;
+        rows.push(row);
+
+        if !tokens.accept_symbol(",") {
+            break;
+        }
+    }
+    tokens.expect_end()?;
+
+    Ok(Statement::Insert { table, columns, rows })
+}
+
+pub fn parse_update(tokens: &mut Tokens) -> Result<Statement, ParsingError> {
+    tokens.expect_keyword("UPDATE")?;
+    let table = tokens.expect_identifier()?;
+    tokens.expect_keyword("SET")?;
+
+    let mut assignments = Vec::new();
+    loop {
+        let column = tokens.expect_identifier()?;
+        tokens.expect_symbol("=")?;
+        assignments.push((column, parse_expression(tokens)?));
+        if !tokens.accept_symbol(",") {
+            break;
+        }
+    }
+
+    let filter = parse_optional_where(tokens)?;
+    tokens.expect_end()?;
+
+    Ok(Statement::Update {
+        table,
+        assignments,
+        filter,
+    })
+}
+
+pub fn parse_delete(tokens: &mut Tokens) -> Result<Statement, ParsingError> {
+    tokens.expect_keyword("DELETE")?;
+    tokens.expect_keyword("FROM")?;
+    let table = tokens.expect_identifier()?;
+    let filter = parse_optional_where(tokens)?;
+    tokens.expect_end()?;
+
+    Ok(Statement::Delete { table, filter })
+}
+
+fn parse_optional_where(tokens: &mut Tokens) -> Result<Option<Expr>, ParsingError> {
+    if tokens.accept_keyword("WHERE") {
+        Ok(Some(parse_expression(tokens)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Parses `ANALYZE [table] [SAMPLE n PERCENT]`.
+pub fn parse_analyze(tokens: &mut Tokens) -> Result<Statement, ParsingError> {
+    tokens.expect_keyword("ANALYZE")?;
+    let table = if tokens.peek_keyword("SAMPLE") || tokens.is_empty() || tokens.peek_symbol(";") {
+        None
+    } else {
+        Some(tokens.expect_identifier()?)
+    };
+
+    let sample_percent = if tokens.accept_keyword("SAMPLE") {
+        let percent = match tokens.next() {
+            Some(Token::Number(number)) => number
+                .parse::<f64>()
+                .map_err(|_| ParsingError::new(&format!("Invalid sample size {}", number)))?,
+            _ => return Err(ParsingError::new("Expected a number after SAMPLE")),
+        };
+        tokens.expect_keyword("PERCENT")?;
+        if !(0.0..=100.0).contains(&percent) {
+            return Err(ParsingError::new("Sample size must be between 0 and 100 percent"));
+        }
+        Some(percent)
+    } else {
+        None
+    };
+    tokens.expect_end()?;
+
+    Ok(Statement::Analyze { table, sample_percent })
+}
+
+/// Parses `SET name = value`.
+pub fn parse_set(tokens: &mut Tokens) -> Result<Statement, ParsingError> {
+    tokens.expect_keyword("SET")?;
+    let name = tokens.expect_identifier()?;
+    tokens.expect_symbol("=")?;
+    let value = parse_expression(tokens)?;
+    tokens.expect_end()?;
+
+    Ok(Statement::Set { name, value })
+}
@@ -0,0 +1,102 @@
+use crate::hyperloglog::HyperLogLog;
+use crate::value::Value;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+/// Rows assumed per page for tables that were never analyzed.
+pub const DEFAULT_ROWS_PER_PAGE: u64 = 50;
+/// Pages assumed for tables the catalog does not know.
+pub const DEFAULT_PAGE_COUNT: u64 = 20;
+/// Share of modified rows after which a table's statistics are refreshed.
+pub const STALE_FRACTION: f64 = 0.1;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TableStatistics {
+    pub row_count: u64,
+    pub page_count: u64,
+    /// Per-column statistics keyed by lowercase column name
+    pub columns: BTreeMap<String, ColumnStatistics>,
+    /// Rows inserted, updated or deleted since the last ANALYZE
+    pub modified_rows: u64,
+}
+
+impl TableStatistics {
+    /// Estimate for a table that has no collected statistics.
+    pub fn from_page_count(page_count: u64) -> Self {
+        Self {
+            row_count: page_count * DEFAULT_ROWS_PER_PAGE,
+            page_count,
+            columns: BTreeMap::new(),
+            modified_rows: 0,
+        }
+    }
+
+    pub fn column(&self, name: &str) -> Option<&ColumnStatistics> {
+        self.columns.get(&name.to_lowercase())
+    }
+
+    /// Whether enough rows changed since the last ANALYZE to collect the statistics again.
+    pub fn is_stale(&self) -> bool {
+        self.modified_rows as f64 > STALE_FRACTION * self.row_count.max(1) as f64
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ColumnStatistics {
+    pub null_fraction: f64,
+    pub distinct_count: f64,
+    pub histogram: Option<Histogram>,
+    /// Sketch of the values seen so far, extended by later inserts
+    pub sketch: HyperLogLog,
+}
+
+/// Equi-depth histogram: every bucket between two consecutive bounds holds
+/// the same share of the non-null rows.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Histogram {
+    pub bounds: Vec<Value>,
+}
+
+impl Histogram {
+    /// Estimated fraction of non-null values below `value` (or equal, when `inclusive`).
+    pub fn fraction_below(&self, value: &Value, inclusive: bool) -> f64 {
+        let buckets = self.bounds.len().saturating_sub(1);
+        if buckets == 0 {
+            return 0.5;
+        }
+        let below = |bound: &Value| match bound.compare(value) {
+            Some(Ordering::Less) => true,
+            Some(Ordering::Equal) => inclusive,
+            _ => false,
+        };
+        if !below(&self.bounds[0]) {
+            return 0.0;
+        }
+        if below(&self.bounds[buckets]) {
+            return 1.0;
+        }
+
+        // the value falls into the bucket [bounds[i], bounds[i + 1])
+        let i = (0..buckets).rfind(|&i| below(&self.bounds[i])).unwrap_or(0);
+        let within = interpolate(&self.bounds[i], &self.bounds[i + 1], value);
+        (i as f64 + within) / buckets as f64
+    }
+}
+
+/// Position of `value` between two bounds, 0.5 for values that are neither
+/// numbers nor temporal.
+fn interpolate(low: &Value, high: &Value, value: &Value) -> f64 {
+    let number = |v: &Value| match v {
+        Value::Int(i) => Some(*i as f64),
+        Value::Float(x) => Some(*x),
+        Value::Decimal(d) => Some(d.to_f64()),
+        Value::Date(days) => Some(*days as f64),
+        Value::Time(micros) | Value::DateTime(micros) | Value::Timestamp(micros) => Some(*micros as f64),
+        _ => None,
+    };
+    match (number(low), number(high), number(value)) {
+        (Some(low), Some(high), Some(value)) if high > low => ((value - low) / (high - low)).clamp(0.0, 1.0),
+        _ => 0.5,
+    }
+}
@@ -4,11 +4,14 @@ mod page;
 mod pager;
 pub mod record_manager;
 
+pub use buffer_pool::BufferStats;
+pub use page::Error;
+
 pub const PAGE_SIZE: usize = 4096;
 
 #[cfg(test)]
 mod tests {
-    use crate::{record::Record, storage::record_manager::RecordManager};
+    use crate::{record::Record, storage::record_manager::RecordManager, storage::PAGE_SIZE};
     use tempfile::tempdir;
 
     #[test]
@@ -27,7 +30,7 @@ mod tests {
                 name: format!("user_{}", i),
                 age: (i % 120) as u8,
             };
-            rm.insert_record(&row);
+            rm.insert_record(&row).unwrap();
             rows.push(row);
         }
 
@@ -55,7 +58,7 @@ mod tests {
             Record { id: 3, name: "Charlie".to_string(), age: 35 },
         ];
         for record in &records_to_insert {
-            rm.insert_record(record);
+            rm.insert_record(record).unwrap();
         }
 
         // scanned rows are equal to initial rows
@@ -69,4 +72,49 @@ mod tests {
         let scanned_rows: Vec<Record> = new_rm.scan_records().collect();
         assert_eq!(records_to_insert, scanned_rows);
     }
+
+    #[test]
+    fn records_larger_than_a_page_overflow() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("overflow.db");
+        let mut rm = RecordManager::new(&db_path);
+
+        let small = Record { id: 1, name: "Alice".to_string(), age: 25 };
+        let large = Record { id: 2, name: "x".repeat(3 * PAGE_SIZE), age: 30 };
+        rm.insert_record(&small).unwrap();
+        let large_id = rm.insert_record(&large).unwrap();
+        rm.insert_record(&Record { id: 3, ..small.clone() }).unwrap();
+        assert_eq!(rm.read_record(large_id).unwrap(), large);
+
+        // the chunks are not records of their own
+        drop(rm);
+        let mut rm = RecordManager::<Record>::new(&db_path);
+        let ids: Vec<u32> = rm.scan_records().map(|record| record.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        assert_eq!(rm.scan_records().nth(1).unwrap(), large);
+        assert!(rm.delete_record(large_id));
+        assert_eq!(rm.scan_records().count(), 2);
+    }
+
+    #[test]
+    fn deleted_records_are_skipped() {
+        let dir = tempdir().unwrap();
+        let db_path = dir.path().join("deletes.db");
+        let mut rm = RecordManager::new(&db_path);
+
+        let alice = Record { id: 1, name: "Alice".to_string(), age: 25 };
+        let bob = Record { id: 2, name: "Bob".to_string(), age: 30 };
+        let alice_id = rm.insert_record(&alice).unwrap();
+        let bob_id = rm.insert_record(&bob).unwrap();
+
+        assert!(rm.delete_record(alice_id));
+        assert!(!rm.delete_record(alice_id));
+        let bob_id = rm.update_record(bob_id, &Record { age: 31, ..bob }).unwrap();
+
+        drop(rm);
+        let mut rm = RecordManager::<Record>::new(&db_path);
+        assert_eq!(rm.read_record(alice_id), None);
+        assert_eq!(rm.read_record(bob_id).unwrap().age, 31);
+        assert_eq!(rm.scan_records().count(), 1);
+    }
 }
@@ -3,10 +3,18 @@ use std::collections::HashMap;
 
 pub const DEFAULT_CAPACITY: usize = 10;
 
+/// Page requests served from the cache (hits) or from disk (misses).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BufferStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
 pub struct BufferPool {
     cache: HashMap<u32, Page>,
     capacity: usize,
     clock_hand: Option<usize>,
+    stats: BufferStats,
 }
 
 impl BufferPool {
@@ -15,11 +23,27 @@ impl BufferPool {
             cache: HashMap::new(),
             capacity,
             clock_hand: None,
+            stats: BufferStats::default(),
         }
     }
 
     pub fn read_page(&mut self, page_id: u32, disk_manager: &mut DiskManager) -> Option<&mut Page> {
-        if !self.cache.contains_key(&page_id) {
+        self.fetch(page_id, disk_manager, true)
+    }
+
+    /// Like `read_page`, for a page the caller already requested, e.g. the
+    /// next slot of a scan: only a miss counts as another request.
+    pub fn reread_page(&mut self, page_id: u32, disk_manager: &mut DiskManager) -> Option<&mut Page> {
+        self.fetch(page_id, disk_manager, false)
+    }
+
+    fn fetch(&mut self, page_id: u32, disk_manager: &mut DiskManager, count_hit: bool) -> Option<&mut Page> {
+        if self.cache.contains_key(&page_id) {
+            if count_hit {
+                self.stats.hits += 1;
+            }
+        } else {
+            self.stats.misses += 1;
             if self.cache.len() == self.capacity {
                 if let Some(victim) = self.find_victim() {
                     self.evict(victim, disk_manager);
@@ -31,7 +55,11 @@ impl BufferPool {
             let page = Page::deserialize(&buffer);
             self.cache.insert(page_id, page);
         }
-        return self.cache.get_mut(&page_id);
+        self.cache.get_mut(&page_id)
+    }
+
+    pub fn stats(&self) -> BufferStats {
+        self.stats
     }
 
     pub fn evict(&mut self, page_id: u32, disk_manager: &mut DiskManager) {
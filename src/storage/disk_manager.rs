@@ -15,6 +15,7 @@ impl DiskManager {
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open(file_path)
             .expect("Failed to open database file");
 
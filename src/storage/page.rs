@@ -1,19 +1,51 @@
 use super::PAGE_SIZE;
-use crate::record::Record;
 use serde::{Deserialize, Serialize};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("The page is full")]
     NotEnoughSpace,
+    #[error("The record does not fit into an empty page")]
+    RecordTooLarge,
+}
+
+/// The top bits of a slot size tell what the slot holds; sizes never need them.
+const OVERFLOW_FLAG: u16 = 0x8000;
+const CHUNK_FLAG: u16 = 0x4000;
+const SIZE_MASK: u16 = 0x3fff;
+
+/// What a slot holds: a whole record or, for a record larger than a page, the
+/// pointer to its bytes or one of the chunks they are split into.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SlotKind {
+    Record,
+    Overflow,
+    Chunk,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Slot {
     offset: u16,
+    // a size of zero marks a deleted record
     size: u16,
 }
 
+impl Slot {
+    fn len(&self) -> usize {
+        (self.size & SIZE_MASK) as usize
+    }
+
+    fn kind(&self) -> SlotKind {
+        if self.size & OVERFLOW_FLAG != 0 {
+            SlotKind::Overflow
+        } else if self.size & CHUNK_FLAG != 0 {
+            SlotKind::Chunk
+        } else {
+            SlotKind::Record
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Page {
     page_id: u32,
@@ -47,48 +79,71 @@ impl Page {
         self.record_count as usize
     }
 
-    pub fn is_enough_space(&self, record: &Record) -> bool {
-        let record_bytes = bincode::serialize(&record).unwrap();
-        let record_size = record_bytes.len();
+    /// Largest serialized record an empty page can hold.
+    pub fn max_record_size() -> usize {
+        PAGE_SIZE - 8 - 4
+    }
+
+    pub fn is_enough_space(&self, record_size: usize) -> bool {
         let required_space = record_size + 4;
 
         8 + self.record_count as usize * 4 + self.data.len() + required_space <= PAGE_SIZE
     }
 
-    pub fn insert_record(&mut self, record: &Record) -> Result<usize, Error> {
+    pub fn insert_record(&mut self, record_bytes: &[u8], kind: SlotKind) -> Result<usize, Error> {
         self.referenced_recently = true;
-        let record_bytes = bincode::serialize(&record).unwrap();
         let record_size = record_bytes.len();
         let required_space = record_size + 4;
 
+        if record_size > Self::max_record_size() {
+            return Err(Error::RecordTooLarge);
+        }
         if 8 + self.record_count as usize * 4 + self.data.len() + required_space > PAGE_SIZE {
             return Err(Error::NotEnoughSpace);
         }
 
         let slot_index = self.slots.len();
+        let flag = match kind {
+            SlotKind::Record => 0,
+            SlotKind::Overflow => OVERFLOW_FLAG,
+            SlotKind::Chunk => CHUNK_FLAG,
+        };
         let slot = Slot {
             offset: self.free_space_offset,
-            size: record_bytes.len() as u16,
+            size: record_size as u16 | flag,
         };
         self.slots.push(slot);
         self.record_count += 1;
 
         // Store record in free space
-        self.data.extend(&record_bytes);
+        self.data.extend(record_bytes);
         self.is_dirty = true;
         self.free_space_offset += record_size as u16;
         Ok(slot_index)
     }
 
-    /// Retrieves a record by slot index
-    pub fn read_record(&mut self, slot_index: usize) -> Option<Record> {
+    /// Retrieves the bytes of a slot, `None` if the slot is missing or deleted
+    pub fn read_record(&mut self, slot_index: usize) -> Option<(SlotKind, &[u8])> {
         self.referenced_recently = true;
-        if let Some(slot) = self.slots.get(slot_index) {
-            let offset = slot.offset as usize;
-            let size = slot.size as usize;
-            Some(bincode::deserialize(&self.data[offset..offset + size]).unwrap())
-        } else {
-            None
+        match self.slots.get(slot_index) {
+            Some(slot) if slot.size > 0 => {
+                let offset = slot.offset as usize;
+                Some((slot.kind(), &self.data[offset..offset + slot.len()]))
+            }
+            _ => None,
+        }
+    }
+
+    /// Marks the slot as deleted. The record bytes stay in place until the page is rewritten.
+    pub fn delete_record(&mut self, slot_index: usize) -> bool {
+        self.referenced_recently = true;
+        match self.slots.get_mut(slot_index) {
+            Some(slot) if slot.size > 0 => {
+                slot.size = 0;
+                self.is_dirty = true;
+                true
+            }
+            _ => false,
         }
     }
 
@@ -130,7 +185,7 @@ impl Page {
 
         Self {
             page_id,
-            record_count: record_count,
+            record_count,
             free_space_offset,
             is_dirty: false,
             referenced_recently: false,
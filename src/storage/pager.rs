@@ -1,7 +1,7 @@
 use std::path::Path;
 
 use super::{
-    buffer_pool::{BufferPool, DEFAULT_CAPACITY},
+    buffer_pool::{BufferPool, BufferStats, DEFAULT_CAPACITY},
     disk_manager::DiskManager,
     page::Page,
 };
@@ -17,7 +17,7 @@ impl Pager {
         let disk_manager = DiskManager::new(file_path);
         Self {
             page_count: disk_manager.get_page_count(),
-            disk_manager: disk_manager,
+            disk_manager,
             buffer_pool: BufferPool::new(buffer_pool_capacity.unwrap_or(DEFAULT_CAPACITY)),
         }
     }
@@ -38,9 +38,17 @@ impl Pager {
         self.buffer_pool.read_page(page_id, &mut self.disk_manager)
     }
 
+    pub fn reread_page(&mut self, page_id: u32) -> Option<&mut Page> {
+        self.buffer_pool.reread_page(page_id, &mut self.disk_manager)
+    }
+
     pub fn get_page_count(&self) -> usize {
         self.page_count
     }
+
+    pub fn buffer_stats(&self) -> BufferStats {
+        self.buffer_pool.stats()
+    }
 }
 
 impl Drop for Pager {
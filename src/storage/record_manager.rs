@@ -1,70 +1,201 @@
+use super::buffer_pool::BufferStats;
+use super::page::{Error, Page, SlotKind};
 use super::pager::Pager;
-use crate::record::Record;
+use serde::{de::DeserializeOwned, Serialize};
+use std::marker::PhantomData;
 use std::path::Path;
 
-pub struct RecordManager {
+/// Location of a record inside a heap file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RecordId {
+    pub page_id: u32,
+    pub slot: usize,
+}
+
+/// Position of a sequential scan, kept by callers that cannot hold a `RecordIterator`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ScanCursor {
+    page_id: u32,
+    slot: usize,
+}
+
+/// Where the bytes of a record larger than a page are: in `chunks` pages of
+/// their own from `first_page` on, filling all but the last.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Overflow {
+    first_page: u32,
+    len: u64,
+}
+
+/// Records live in slotted pages. A record too large for a page is split into
+/// chunks in freshly allocated pages, and its slot only points to them; scans
+/// skip the chunks. Deleting the record leaves them in place.
+pub struct RecordManager<T> {
     pager: Pager,
     page_count: u32,
+    _record: PhantomData<T>,
 }
 
-impl RecordManager {
+impl<T: Serialize + DeserializeOwned> RecordManager<T> {
     pub fn new(file_path: &Path) -> Self {
         let pager = Pager::new(file_path, None);
         Self {
             page_count: pager.get_page_count() as u32,
             pager,
+            _record: PhantomData,
+        }
+    }
+
+    pub fn page_count(&self) -> u32 {
+        self.page_count
+    }
+
+    pub fn buffer_stats(&self) -> BufferStats {
+        self.pager.buffer_stats()
+    }
+
+    pub fn insert_record(&mut self, record: &T) -> Result<RecordId, Error> {
+        let bytes = bincode::serialize(record).unwrap();
+        if bytes.len() <= Page::max_record_size() {
+            return self.insert_bytes(&bytes, SlotKind::Record);
+        }
+        let first_page = self.page_count;
+        for chunk in bytes.chunks(Page::max_record_size()) {
+            self.pager.allocate_page();
+            self.page_count += 1;
+            self.pager.read_page(self.page_count - 1).unwrap().insert_record(chunk, SlotKind::Chunk)?;
         }
+        let overflow = Overflow {
+            first_page,
+            len: bytes.len() as u64,
+        };
+        self.insert_bytes(&bincode::serialize(&overflow).unwrap(), SlotKind::Overflow)
     }
 
-    pub fn insert_record(&mut self, record: &Record) {
+    fn insert_bytes(&mut self, bytes: &[u8], kind: SlotKind) -> Result<RecordId, Error> {
         if self.page_count == 0
             || !self
                 .pager
                 .read_page(self.page_count - 1)
                 .unwrap()
-                .is_enough_space(record)
+                .is_enough_space(bytes.len())
         {
             self.pager.allocate_page();
             self.page_count += 1;
         }
-        let page = self.pager.read_page(self.page_count - 1).unwrap();
-        page.insert_record(&record).unwrap();
+        let page_id = self.page_count - 1;
+        let page = self.pager.read_page(page_id).unwrap();
+        let slot = page.insert_record(bytes, kind)?;
+        Ok(RecordId { page_id, slot })
     }
 
-    pub fn scan_records(&mut self) -> RecordIterator {
+    /// Reads a record by id, `None` if it was deleted or is a chunk of another record.
+    pub fn read_record(&mut self, id: RecordId) -> Option<T> {
+        match self.pager.read_page(id.page_id)?.read_record(id.slot)? {
+            (SlotKind::Record, bytes) => Some(bincode::deserialize(bytes).unwrap()),
+            (SlotKind::Overflow, bytes) => {
+                let overflow = bincode::deserialize(bytes).unwrap();
+                self.read_overflow(overflow)
+            }
+            (SlotKind::Chunk, _) => None,
+        }
+    }
+
+    fn read_overflow(&mut self, overflow: Overflow) -> Option<T> {
+        let mut bytes = Vec::with_capacity(overflow.len as usize);
+        let mut page_id = overflow.first_page;
+        while (bytes.len() as u64) < overflow.len {
+            let (_, chunk) = self.pager.read_page(page_id)?.read_record(0)?;
+            bytes.extend_from_slice(chunk);
+            page_id += 1;
+        }
+        Some(bincode::deserialize(&bytes).unwrap())
+    }
+
+    pub fn delete_record(&mut self, id: RecordId) -> bool {
+        match self.pager.read_page(id.page_id) {
+            Some(page) => page.delete_record(id.slot),
+            None => false,
+        }
+    }
+
+    /// Replaces a record. The new version may move, so its new id is returned.
+    pub fn update_record(&mut self, id: RecordId, record: &T) -> Result<RecordId, Error> {
+        self.delete_record(id);
+        self.insert_record(record)
+    }
+
+    pub fn scan_records(&mut self) -> RecordIterator<'_, T> {
         RecordIterator {
             record_manager: self,
-            current_page: 0,
-            current_slot: 0,
+            cursor: ScanCursor::default(),
         }
     }
-}
 
-pub struct RecordIterator<'a> {
-    record_manager: &'a mut RecordManager,
-    current_page: u32,
-    current_slot: usize,
-}
+    /// Like `scan_records`, but also yields the id of every record.
+    pub fn scan_entries(&mut self) -> impl Iterator<Item = (RecordId, T)> + '_ {
+        let mut cursor = ScanCursor::default();
+        std::iter::from_fn(move || self.next_entry(&mut cursor))
+    }
 
-impl<'a> Iterator for RecordIterator<'a> {
-    type Item = Record;
+    /// Reads the next live record at or after the cursor and advances it.
+    pub fn next_entry(&mut self, cursor: &mut ScanCursor) -> Option<(RecordId, T)> {
+        self.next_sampled_entry(cursor, &mut |_| true)
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        while self.current_page < self.record_manager.page_count {
-            let page = self
-                .record_manager
-                .pager
-                .read_page(self.current_page)
-                .unwrap();
-            if self.current_slot < page.get_record_count() {
-                let record = page.read_record(self.current_slot);
-                self.current_slot += 1;
-                return record;
+    /// Like `next_entry`, but skips the pages `sampled` rejects without
+    /// reading them. It is asked once per page, when the cursor reaches it.
+    pub fn next_sampled_entry(
+        &mut self,
+        cursor: &mut ScanCursor,
+        sampled: &mut impl FnMut(u32) -> bool,
+    ) -> Option<(RecordId, T)> {
+        while cursor.page_id < self.page_count {
+            if cursor.slot == 0 && !sampled(cursor.page_id) {
+                cursor.page_id += 1;
+                continue;
+            }
+            // the page counts as requested once, when the cursor reaches it
+            let page = if cursor.slot == 0 {
+                self.pager.read_page(cursor.page_id).unwrap()
+            } else {
+                self.pager.reread_page(cursor.page_id).unwrap()
+            };
+            if cursor.slot < page.get_record_count() {
+                let id = RecordId {
+                    page_id: cursor.page_id,
+                    slot: cursor.slot,
+                };
+                cursor.slot += 1;
+                // deleted slots and chunks are skipped
+                match page.read_record(id.slot) {
+                    Some((SlotKind::Record, bytes)) => return Some((id, bincode::deserialize(bytes).unwrap())),
+                    Some((SlotKind::Overflow, bytes)) => {
+                        let overflow = bincode::deserialize(bytes).unwrap();
+                        return Some((id, self.read_overflow(overflow)?));
+                    }
+                    Some((SlotKind::Chunk, _)) | None => {}
+                }
             } else {
-                self.current_page += 1;
-                self.current_slot = 0;
+                cursor.page_id += 1;
+                cursor.slot = 0;
             }
         }
         None
     }
 }
+
+pub struct RecordIterator<'a, T> {
+    record_manager: &'a mut RecordManager<T>,
+    cursor: ScanCursor,
+}
+
+impl<T: Serialize + DeserializeOwned> Iterator for RecordIterator<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.record_manager
+            .next_entry(&mut self.cursor)
+            .map(|(_, record)| record)
+    }
+}
@@ -0,0 +1,395 @@
+use crate::catalog::Catalog;
+use crate::errors::ExecutionError;
+use crate::expression::{BinaryOperator, Expr, UnaryOperator};
+use crate::filter::LogicalFilterNode;
+use crate::join::{JoinType, LogicalJoinNode};
+use crate::logical_plan::LogicalPlan;
+use crate::schema::Schema;
+use crate::select::{LogicalDropNode, LogicalExtendNode, LogicalProjectNode, LogicalSetNode};
+use crate::value::{DataType, Value};
+use std::fmt;
+
+/// What an APPLY computes from the rows of its subquery for each input row.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApplyKind {
+    /// The value of the single row, NULL without rows
+    Scalar,
+    /// Whether there is any row
+    Exists,
+    /// Whether the value of the expression over the input row is one of the rows
+    In(Expr),
+}
+
+impl fmt::Display for ApplyKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ApplyKind::Scalar => write!(f, "SCALAR"),
+            ApplyKind::Exists => write!(f, "EXISTS"),
+            ApplyKind::In(expr) => write!(f, "{} IN", expr),
+        }
+    }
+}
+
+/// Runs a subquery for every input row, with the columns of the row it refers
+/// to bound to their values, and appends what `kind` makes of its rows as the
+/// column `name`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalApplyNode {
+    pub input: Box<LogicalPlan>,
+    pub subquery: Box<LogicalPlan>,
+    pub kind: ApplyKind,
+    pub name: String,
+}
+
+impl LogicalApplyNode {
+    pub fn new(input: LogicalPlan, subquery: LogicalPlan, kind: ApplyKind, name: &str) -> Self {
+        Self {
+            input: Box::new(input),
+            subquery: Box::new(subquery),
+            kind,
+            name: name.to_string(),
+        }
+    }
+}
+
+/// The type of the column an APPLY appends.
+pub fn apply_type(subquery: &LogicalPlan, kind: &ApplyKind, catalog: &Catalog) -> Result<DataType, ExecutionError> {
+    let bound = bind_outer_columns(subquery.clone(), catalog, &mut |_| Expr::Literal(Value::Null))?;
+    let schema = bound.schema(catalog)?;
+    match kind {
+        ApplyKind::Exists => Ok(DataType::Bool),
+        _ if schema.fields.len() != 1 => Err(ExecutionError::new(&format!(
+            "A subquery used as a value must return one column, not {}",
+            schema.fields.len()
+        ))),
+        ApplyKind::Scalar => Ok(schema.fields[0].data_type.clone()),
+        ApplyKind::In(_) => Ok(DataType::Bool),
+    }
+}
+
+/// Replaces every column the plan cannot resolve itself, a column of an
+/// enclosing query, by `f(column)`.
+pub fn bind_outer_columns(
+    plan: LogicalPlan,
+    catalog: &Catalog,
+    f: &mut dyn FnMut(Expr) -> Expr,
+) -> Result<LogicalPlan, ExecutionError> {
+    let mut error = None;
+    let plan = plan.map_inputs(|input| {
+        bind_outer_columns(input, catalog, f).unwrap_or_else(|e| {
+            error.get_or_insert(e);
+            LogicalPlan::OneRow
+        })
+    });
+    if let Some(error) = error {
+        return Err(error);
+    }
+    let scope = plan.expr_scope(catalog)?;
+    let outer = |expr: &Expr| match expr {
+        Expr::Column { qualifier, name } => !scope.fields.iter().any(|field| field.matches(qualifier.as_deref(), name)),
+        _ => false,
+    };
+    let plan = match plan {
+        // columns a nested subquery takes from neither itself nor this node
+        // belong further out
+        LogicalPlan::Apply(mut apply) => {
+            let subquery = std::mem::replace(&mut *apply.subquery, LogicalPlan::OneRow);
+            *apply.subquery = bind_outer_columns(subquery, catalog, &mut |expr| if outer(&expr) { f(expr) } else { expr })?;
+            LogicalPlan::Apply(apply)
+        }
+        plan => plan,
+    };
+    Ok(plan.map_exprs(&mut |expr| expr.transform(&mut |expr| if outer(&expr) { f(expr) } else { expr })))
+}
+
+/// Plans the subqueries in expressions. EXISTS and IN conditions of a WHERE
+/// become semi or anti joins when the subquery refers to the outer query only
+/// in its own WHERE conditions; any other subquery becomes an APPLY.
+pub fn unnest_subqueries(plan: LogicalPlan, catalog: &Catalog) -> Result<LogicalPlan, ExecutionError> {
+    Unnester { catalog, count: 0 }.unnest(plan)
+}
+
+struct Unnester<'a> {
+    catalog: &'a Catalog,
+    /// Subqueries planned as APPLY so far, which number their columns
+    count: usize,
+}
+
+impl Unnester<'_> {
+    fn unnest(&mut self, plan: LogicalPlan) -> Result<LogicalPlan, ExecutionError> {
+        let mut error = None;
+        let plan = plan.map_inputs(|input| {
+            self.unnest(input).unwrap_or_else(|e| {
+                error.get_or_insert(e);
+                LogicalPlan::OneRow
+            })
+        });
+        if let Some(error) = error {
+            return Err(error);
+        }
+        let mut has_subquery = false;
+        let plan = plan.map_exprs(&mut |expr| {
+            has_subquery |= expr.has_subquery();
+            expr
+        });
+        if !has_subquery {
+            return Ok(plan);
+        }
+
+        let mut names = Vec::new();
+        match plan {
+            LogicalPlan::Filter(filter) => {
+                let mut input = *filter.input;
+                let mut remaining = Vec::new();
+                for conjunct in filter.condition.into_conjuncts() {
+                    let join = match &conjunct {
+                        Expr::Exists { subquery, negated } => self.semi_join(&input, subquery, None, *negated)?,
+                        Expr::InSubquery { expr, subquery, negated } if !expr.has_subquery() => {
+                            self.semi_join(&input, subquery, Some(expr), *negated)?
+                        }
+                        _ => None,
+                    };
+                    match join {
+                        Some(join) => input = join,
+                        None => remaining.push(self.replace(conjunct, &mut input, &mut names)?),
+                    }
+                }
+                let plan = match Expr::conjunction(remaining) {
+                    Some(condition) => LogicalPlan::Filter(LogicalFilterNode::new(input, condition)),
+                    None => input,
+                };
+                Ok(without(plan, names))
+            }
+            LogicalPlan::Project(project) => {
+                // only `*` brings the appended columns into the output
+                let wildcard = project.select_list.iter().any(|select| select.field == Expr::Wildcard);
+                let mut input = *project.input;
+                let mut select_list = Vec::new();
+                for mut select in project.select_list {
+                    select.field = self.replace(select.field, &mut input, &mut names)?;
+                    select_list.push(select);
+                }
+                let plan = LogicalPlan::Project(LogicalProjectNode::new(input, select_list));
+                Ok(if wildcard { without(plan, names) } else { plan })
+            }
+            LogicalPlan::Extend(extend) => {
+                let mut input = *extend.input;
+                let mut extensions = Vec::new();
+                for mut extension in extend.extensions {
+                    extension.field = self.replace(extension.field, &mut input, &mut names)?;
+                    extensions.push(extension);
+                }
+                Ok(without(LogicalPlan::Extend(LogicalExtendNode::new(input, extensions)), names))
+            }
+            LogicalPlan::Set(set) => {
+                let mut input = *set.input;
+                let mut assignments = Vec::new();
+                for (name, expr) in set.assignments {
+                    assignments.push((name, self.replace(expr, &mut input, &mut names)?));
+                }
+                Ok(without(LogicalPlan::Set(LogicalSetNode::new(input, assignments)), names))
+            }
+            _ => Err(ExecutionError::new(
+                "Subqueries are only supported in WHERE, SELECT, EXTEND and SET",
+            )),
+        }
+    }
+
+    /// Replaces every subquery of the expression by a column that an APPLY
+    /// over `input` computes, adding the names of those columns to `names`.
+    fn replace(&mut self, expr: Expr, input: &mut LogicalPlan, names: &mut Vec<String>) -> Result<Expr, ExecutionError> {
+        let mut error = None;
+        let expr = expr.transform(&mut |expr| {
+            let (subquery, kind, negated) = match expr {
+                Expr::Subquery(subquery) => (subquery, ApplyKind::Scalar, false),
+                Expr::InSubquery { expr, subquery, negated } => (subquery, ApplyKind::In(*expr), negated),
+                Expr::Exists { subquery, negated } => (subquery, ApplyKind::Exists, negated),
+                expr => return expr,
+            };
+            let subquery = match self.unnest(*subquery) {
+                Ok(subquery) => subquery,
+                Err(e) => {
+                    error.get_or_insert(e);
+                    return Expr::Literal(Value::Null);
+                }
+            };
+            self.count += 1;
+            let name = format!("$subquery_{}", self.count);
+            let outer = std::mem::replace(input, LogicalPlan::OneRow);
+            *input = LogicalPlan::Apply(LogicalApplyNode::new(outer, subquery, kind, &name));
+            names.push(name.clone());
+            let column = Expr::Column { qualifier: None, name };
+            if negated {
+                Expr::Unary {
+                    op: UnaryOperator::Not,
+                    expr: Box::new(column),
+                }
+            } else {
+                column
+            }
+        });
+        match error {
+            Some(error) => Err(error),
+            None => Ok(expr),
+        }
+    }
+
+    /// `input` semi joined with the rows of an EXISTS subquery, or with those
+    /// equal to `value` for IN; anti joined when negated. `None` when the
+    /// subquery refers to the outer query other than in its WHERE conditions.
+    fn semi_join(
+        &mut self,
+        input: &LogicalPlan,
+        subquery: &LogicalPlan,
+        value: Option<&Expr>,
+        negated: bool,
+    ) -> Result<Option<LogicalPlan>, ExecutionError> {
+        let Ok(outer) = input.schema(self.catalog) else {
+            return Ok(None);
+        };
+        let mut plan = self.unnest(subquery.clone())?;
+        // which columns an EXISTS subquery returns and how they are ordered
+        // does not matter; neither does the order of the rows of IN
+        let mut inner_value = None;
+        loop {
+            plan = match plan {
+                LogicalPlan::Sort(sort) => *sort.input,
+                LogicalPlan::Project(project) if value.is_some() && inner_value.is_none() => {
+                    let [select] = project.select_list.as_slice() else {
+                        return Ok(None);
+                    };
+                    if select.field == Expr::Wildcard {
+                        return Ok(None);
+                    }
+                    inner_value = Some(select.field.clone());
+                    *project.input
+                }
+                LogicalPlan::Project(_)
+                | LogicalPlan::Extend(_)
+                | LogicalPlan::Set(_)
+                | LogicalPlan::Drop(_)
+                | LogicalPlan::Rename(_)
+                    if value.is_none() =>
+                {
+                    plan.inputs()[0].clone()
+                }
+                other => {
+                    plan = other;
+                    break;
+                }
+            }
+        }
+        let mut conjuncts = Vec::new();
+        while let LogicalPlan::Filter(filter) = plan {
+            conjuncts.extend(filter.condition.into_conjuncts());
+            plan = *filter.input;
+        }
+
+        // the rest of the subquery is evaluated once for all outer rows
+        let mut correlated = false;
+        let bound = bind_outer_columns(plan.clone(), self.catalog, &mut |expr| {
+            correlated = true;
+            expr
+        });
+        let (Ok(_), false) = (bound, correlated) else {
+            return Ok(None);
+        };
+        let inner = plan.schema(self.catalog)?;
+        let inner_value = match inner_value {
+            Some(expr) => expr,
+            None if value.is_some() && inner.fields.len() == 1 => Expr::Column {
+                qualifier: inner.fields[0].qualifier.clone(),
+                name: inner.fields[0].name.clone(),
+            },
+            None if value.is_some() => return Ok(None),
+            None => Expr::Literal(Value::Null),
+        };
+        if inner_value.has_subquery() || !reads_only(&inner_value, &inner) {
+            return Ok(None);
+        }
+
+        let mut combined = outer.fields.clone();
+        combined.extend(inner.fields.iter().cloned());
+        let combined = Schema::new(combined);
+        let (mut local, mut condition) = (Vec::new(), Vec::new());
+        for conjunct in conjuncts {
+            if conjunct.has_subquery() {
+                return Ok(None);
+            }
+            if reads_only(&conjunct, &inner) {
+                local.push(conjunct);
+            } else {
+                let Some(conjunct) = qualify(&conjunct, &[&inner, &outer], &combined) else {
+                    return Ok(None);
+                };
+                condition.push(conjunct);
+            }
+        }
+        if let Some(value) = value {
+            let (Some(left), Some(right)) = (qualify(value, &[&outer], &combined), qualify(&inner_value, &[&inner], &combined)) else {
+                return Ok(None);
+            };
+            let mut equal = Expr::binary(left.clone(), BinaryOperator::Eq, right.clone());
+            // NOT IN is NULL rather than true when either side is NULL
+            if negated {
+                for side in [left, right] {
+                    let is_null = Expr::IsNull {
+                        expr: Box::new(side),
+                        negated: false,
+                    };
+                    equal = Expr::binary(equal, BinaryOperator::Or, is_null);
+                }
+            }
+            condition.insert(0, equal);
+        }
+
+        let right = match Expr::conjunction(local) {
+            Some(condition) => LogicalPlan::Filter(LogicalFilterNode::new(plan, condition)),
+            None => plan,
+        };
+        let join_type = if negated { JoinType::Anti } else { JoinType::Semi };
+        let condition = Expr::conjunction(condition).unwrap_or(Expr::Literal(Value::Bool(true)));
+        Ok(Some(LogicalPlan::Join(LogicalJoinNode::new(input.clone(), right, join_type, condition))))
+    }
+}
+
+/// The plan without the columns of subqueries it computed along the way.
+fn without(plan: LogicalPlan, names: Vec<String>) -> LogicalPlan {
+    if names.is_empty() {
+        plan
+    } else {
+        LogicalPlan::Drop(LogicalDropNode::new(plan, names))
+    }
+}
+
+fn reads_only(expr: &Expr, schema: &Schema) -> bool {
+    expr.columns()
+        .iter()
+        .all(|(qualifier, name)| schema.fields.iter().any(|field| field.matches(qualifier.as_deref(), name)))
+}
+
+/// The expression with every column qualified by the first schema that has
+/// it, or `None` when a column is ambiguous there or in `combined`.
+fn qualify(expr: &Expr, schemas: &[&Schema], combined: &Schema) -> Option<Expr> {
+    let mut valid = true;
+    let expr = expr.clone().transform(&mut |expr| match expr {
+        Expr::Column { qualifier, name } => {
+            let qualifier = qualifier.as_deref();
+            let field = schemas
+                .iter()
+                .find(|schema| schema.fields.iter().any(|field| field.matches(qualifier, &name)))
+                .and_then(|schema| schema.resolve(qualifier, &name).ok().map(|i| &schema.fields[i]));
+            let Some(field) = field else {
+                valid = false;
+                return Expr::Column { qualifier: qualifier.map(str::to_string), name };
+            };
+            valid &= combined.resolve(field.qualifier.as_deref(), &field.name).is_ok();
+            Expr::Column {
+                qualifier: field.qualifier.clone(),
+                name: field.name.clone(),
+            }
+        }
+        expr => expr,
+    });
+    valid.then_some(expr)
+}
@@ -0,0 +1,773 @@
+use crate::errors::ExecutionError;
+use crate::timezone::{format_offset, TimeZone};
+use crate::value::Value;
+use std::fmt::{self, Write};
+
+// DATE values count days since 1970-01-01; TIME values microseconds since
+// midnight; DATETIME values wall clock microseconds since 1970-01-01 00:00;
+// TIMESTAMP values microseconds since 1970-01-01 00:00 UTC.
+
+pub const MICROS_PER_SECOND: i64 = 1_000_000;
+pub const MICROS_PER_MINUTE: i64 = 60 * MICROS_PER_SECOND;
+pub const MICROS_PER_HOUR: i64 = 60 * MICROS_PER_MINUTE;
+pub const MICROS_PER_DAY: i64 = 24 * MICROS_PER_HOUR;
+
+/// 0001-01-01 and 9999-12-31, the range of every temporal type.
+const MIN_DAY: i64 = days_from_civil(1, 1, 1);
+const MAX_DAY: i64 = days_from_civil(9999, 12, 31);
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+const WEEKDAYS: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+
+/// Days since 1970-01-01 of a date of the proleptic Gregorian calendar.
+pub const fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The year, month and day of a count of days since 1970-01-01.
+pub fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Day of the week, 0 for Sunday.
+pub fn weekday(days: i64) -> i64 {
+    // 1970-01-01 was a Thursday
+    (days + 4).rem_euclid(7)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    (days_from_civil(next_year, next_month, 1) - days_from_civil(year, month, 1)) as u32
+}
+
+fn date(year: i64, month: u32, day: u32) -> Option<i64> {
+    let valid = (1..=9999).contains(&year) && (1..=12).contains(&month) && day >= 1 && day <= days_in_month(year, month);
+    valid.then(|| days_from_civil(year, month, day))
+}
+
+/// Parses `YYYY-[M]M-[D]D`.
+pub fn parse_date(s: &str) -> Option<i64> {
+    let mut parts = s.trim().splitn(3, '-');
+    let mut number = |digits: std::ops::RangeInclusive<usize>| {
+        let part = parts.next()?;
+        let valid = digits.contains(&part.len()) && part.chars().all(|c| c.is_ascii_digit());
+        valid.then(|| part.parse::<i64>().ok()).flatten()
+    };
+    let (year, month, day) = (number(4..=4)?, number(1..=2)?, number(1..=2)?);
+    date(year, month as u32, day as u32)
+}
+
+/// Parses `[H]H:MM[:SS[.ffffff]]`.
+pub fn parse_time(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let (clock, fraction) = match s.split_once('.') {
+        Some((clock, fraction)) => (clock, Some(fraction)),
+        None => (s, None),
+    };
+    let parts: Vec<&str> = clock.split(':').collect();
+    if !(2..=3).contains(&parts.len()) || (fraction.is_some() && parts.len() < 3) {
+        return None;
+    }
+    let mut fields = [0i64; 3];
+    for (i, part) in parts.iter().enumerate() {
+        let digits = if i == 0 { 1..=2 } else { 2..=2 };
+        if !digits.contains(&part.len()) || !part.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        fields[i] = part.parse().ok()?;
+    }
+    let [hour, minute, second] = fields;
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let micros = match fraction {
+        Some(fraction) if (1..=6).contains(&fraction.len()) && fraction.chars().all(|c| c.is_ascii_digit()) => {
+            fraction.parse::<i64>().ok()? * 10i64.pow(6 - fraction.len() as u32)
+        }
+        Some(_) => return None,
+        None => 0,
+    };
+    Some(hour * MICROS_PER_HOUR + minute * MICROS_PER_MINUTE + second * MICROS_PER_SECOND + micros)
+}
+
+/// Splits `date[( |T)time][zone]` into the wall clock time and the rest.
+fn parse_wall_clock(s: &str) -> Option<(i64, &str)> {
+    let s = s.trim();
+    let date_end = s.find([' ', 'T']).unwrap_or(s.len());
+    let days = parse_date(&s[..date_end])?;
+    let rest = s[date_end..].strip_prefix([' ', 'T']).unwrap_or(&s[date_end..]);
+    let time_end = rest.find(|c: char| !(c.is_ascii_digit() || c == ':' || c == '.')).unwrap_or(rest.len());
+    let time = if time_end == 0 { 0 } else { parse_time(&rest[..time_end])? };
+    Some((days * MICROS_PER_DAY + time, rest[time_end..].trim()))
+}
+
+/// Parses `YYYY-MM-DD[( |T)HH:MM[:SS[.ffffff]]]`.
+pub fn parse_datetime(s: &str) -> Option<i64> {
+    match parse_wall_clock(s)? {
+        (local, "") => Some(local),
+        _ => None,
+    }
+}
+
+/// Parses a DATETIME followed by an optional zone name or offset, read in
+/// `zone` when there is none.
+pub fn parse_timestamp(s: &str, zone: &TimeZone) -> Option<i64> {
+    let (local, suffix) = parse_wall_clock(s)?;
+    let utc = match suffix {
+        "" => zone.to_utc(local),
+        suffix => TimeZone::parse(suffix).ok()?.to_utc(local),
+    };
+    in_range(utc).then_some(utc)
+}
+
+fn in_range(micros: i64) -> bool {
+    (MIN_DAY..=MAX_DAY).contains(&micros.div_euclid(MICROS_PER_DAY))
+}
+
+/// A temporal value as text, the way CAST to STRING writes it.
+pub fn format_value(value: &Value) -> Option<String> {
+    Some(match value {
+        Value::Date(days) => format_date(*days as i64),
+        Value::Time(micros) => format_clock(*micros),
+        Value::DateTime(micros) => format_wall_clock(*micros),
+        Value::Timestamp(micros) => format!("{}+00", format_wall_clock(*micros)),
+        _ => return None,
+    })
+}
+
+fn format_date(days: i64) -> String {
+    let (year, month, day) = civil_from_days(days);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+/// `HH:MM:SS`, with as many fractional digits as needed.
+fn format_clock(micros: i64) -> String {
+    let seconds = micros / MICROS_PER_SECOND;
+    let mut clock = format!("{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60);
+    let fraction = micros % MICROS_PER_SECOND;
+    if fraction != 0 {
+        let digits = format!("{:06}", fraction);
+        clock.push('.');
+        clock.push_str(digits.trim_end_matches('0'));
+    }
+    clock
+}
+
+fn format_wall_clock(micros: i64) -> String {
+    let (days, time) = (micros.div_euclid(MICROS_PER_DAY), micros.rem_euclid(MICROS_PER_DAY));
+    format!("{} {}", format_date(days), format_clock(time))
+}
+
+/// A unit of `EXTRACT`, `DATE_TRUNC`, `DATE_ADD`, `DATE_SUB` and `DATE_DIFF`,
+/// from the smallest to the largest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DatePart {
+    Microsecond,
+    Millisecond,
+    Second,
+    Minute,
+    Hour,
+    Day,
+    DayOfWeek,
+    DayOfYear,
+    /// Weeks start on Sunday
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+impl DatePart {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name.to_uppercase().as_str() {
+            "MICROSECOND" => DatePart::Microsecond,
+            "MILLISECOND" => DatePart::Millisecond,
+            "SECOND" => DatePart::Second,
+            "MINUTE" => DatePart::Minute,
+            "HOUR" => DatePart::Hour,
+            "DAY" => DatePart::Day,
+            "DAYOFWEEK" => DatePart::DayOfWeek,
+            "DAYOFYEAR" => DatePart::DayOfYear,
+            "WEEK" => DatePart::Week,
+            "MONTH" => DatePart::Month,
+            "QUARTER" => DatePart::Quarter,
+            "YEAR" => DatePart::Year,
+            _ => return None,
+        })
+    }
+
+    fn parse(name: &str) -> Result<Self, ExecutionError> {
+        Self::from_name(name).ok_or_else(|| ExecutionError::new(&format!("Unknown date part {}", name)))
+    }
+
+    /// Length of a part of a day or less.
+    fn micros(self) -> Option<i64> {
+        match self {
+            DatePart::Microsecond => Some(1),
+            DatePart::Millisecond => Some(1000),
+            DatePart::Second => Some(MICROS_PER_SECOND),
+            DatePart::Minute => Some(MICROS_PER_MINUTE),
+            DatePart::Hour => Some(MICROS_PER_HOUR),
+            DatePart::Day => Some(MICROS_PER_DAY),
+            _ => None,
+        }
+    }
+
+    /// Checks that `function` may use the part on the value: DATE values
+    /// have no parts below a day and TIME values none above an hour. Only
+    /// EXTRACT reads the day of the week or of the year.
+    fn check(self, function: &str, value: &Value) -> Result<(), ExecutionError> {
+        let valid = match value {
+            Value::Date(_) => self >= DatePart::Day,
+            Value::Time(_) => self <= DatePart::Hour,
+            _ => true,
+        } && (function == "EXTRACT" || !matches!(self, DatePart::DayOfWeek | DatePart::DayOfYear));
+        if valid {
+            Ok(())
+        } else {
+            let type_name = value.data_type().map(|data_type| data_type.to_string()).unwrap_or_default();
+            Err(ExecutionError::new(&format!("{} cannot use {} on a {}", function, self, type_name)))
+        }
+    }
+}
+
+impl fmt::Display for DatePart {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&format!("{:?}", self).to_uppercase())
+    }
+}
+
+/// Wall clock microseconds of a temporal value, reading timestamps in `zone`.
+pub fn wall_clock(value: &Value, zone: &TimeZone) -> i64 {
+    match value {
+        Value::Date(days) => *days as i64 * MICROS_PER_DAY,
+        Value::Time(micros) | Value::DateTime(micros) => *micros,
+        Value::Timestamp(micros) => zone.to_local(*micros),
+        _ => 0,
+    }
+}
+
+/// The value of the same type as `like` at a wall clock time.
+fn from_wall_clock(like: &Value, local: i64, zone: &TimeZone, function: &str) -> Result<Value, ExecutionError> {
+    if !matches!(like, Value::Time(_)) && !in_range(local) {
+        return Err(out_of_range(function));
+    }
+    Ok(match like {
+        Value::Date(_) => Value::Date(local.div_euclid(MICROS_PER_DAY) as i32),
+        Value::Time(_) => Value::Time(local.rem_euclid(MICROS_PER_DAY)),
+        Value::DateTime(_) => Value::DateTime(local),
+        _ => Value::Timestamp(zone.to_utc(local)),
+    })
+}
+
+fn out_of_range(function: &str) -> ExecutionError {
+    ExecutionError::new(&format!("{} goes outside the range 0001-01-01 to 9999-12-31", function))
+}
+
+/// `EXTRACT(part FROM value)`, reading timestamps in `zone`.
+pub fn extract(part: &str, value: &Value, zone: &TimeZone) -> Result<i64, ExecutionError> {
+    let part = DatePart::parse(part)?;
+    part.check("EXTRACT", value)?;
+    let local = wall_clock(value, zone);
+    let (days, time) = (local.div_euclid(MICROS_PER_DAY), local.rem_euclid(MICROS_PER_DAY));
+    let (year, month, day) = civil_from_days(days);
+    let day_of_year = days - days_from_civil(year, 1, 1);
+    Ok(match part {
+        DatePart::Microsecond => time % MICROS_PER_SECOND,
+        DatePart::Millisecond => time % MICROS_PER_SECOND / 1000,
+        DatePart::Second => time / MICROS_PER_SECOND % 60,
+        DatePart::Minute => time / MICROS_PER_MINUTE % 60,
+        DatePart::Hour => time / MICROS_PER_HOUR,
+        DatePart::Day => day as i64,
+        DatePart::DayOfWeek => weekday(days) + 1,
+        DatePart::DayOfYear => day_of_year + 1,
+        // the days before the first Sunday are in week 0
+        DatePart::Week => (day_of_year + 7 - weekday(days)) / 7,
+        DatePart::Month => month as i64,
+        DatePart::Quarter => (month as i64 - 1) / 3 + 1,
+        DatePart::Year => year,
+    })
+}
+
+/// `DATE_TRUNC(value, part)`: the start of the part the value falls in,
+/// reading timestamps in `zone`.
+pub fn trunc(value: &Value, part: &str, zone: &TimeZone) -> Result<Value, ExecutionError> {
+    let part = DatePart::parse(part)?;
+    part.check("DATE_TRUNC", value)?;
+    let local = wall_clock(value, zone);
+    let days = local.div_euclid(MICROS_PER_DAY);
+    let (year, month, _) = civil_from_days(days);
+    let truncated = match part {
+        DatePart::Week => (days - weekday(days)) * MICROS_PER_DAY,
+        DatePart::Month => days_from_civil(year, month, 1) * MICROS_PER_DAY,
+        DatePart::Quarter => days_from_civil(year, (month - 1) / 3 * 3 + 1, 1) * MICROS_PER_DAY,
+        DatePart::Year => days_from_civil(year, 1, 1) * MICROS_PER_DAY,
+        part => {
+            let unit = part.micros().expect("parts of a day have a length");
+            local.div_euclid(unit) * unit
+        }
+    };
+    from_wall_clock(value, truncated, zone, "DATE_TRUNC")
+}
+
+/// `DATE_ADD(value, INTERVAL count part)`. Months, quarters and years keep
+/// the day of the month, or the last day of shorter months; timestamps are
+/// moved in UTC.
+pub fn add(function: &str, value: &Value, count: i64, part: &str) -> Result<Value, ExecutionError> {
+    let part = DatePart::parse(part)?;
+    part.check(function, value)?;
+    let overflow = || ExecutionError::new(&format!("{} overflows", function));
+    let utc = TimeZone::utc();
+    let local = wall_clock(value, &utc);
+    let moved = match part {
+        DatePart::Month | DatePart::Quarter | DatePart::Year => {
+            let months = match part {
+                DatePart::Month => Some(count),
+                DatePart::Quarter => count.checked_mul(3),
+                _ => count.checked_mul(12),
+            }
+            .ok_or_else(overflow)?;
+            let (days, time) = (local.div_euclid(MICROS_PER_DAY), local.rem_euclid(MICROS_PER_DAY));
+            let (year, month, day) = civil_from_days(days);
+            let index = (year * 12 + month as i64 - 1).checked_add(months).ok_or_else(overflow)?;
+            let (year, month) = (index.div_euclid(12), index.rem_euclid(12) as u32 + 1);
+            if !(1..=9999).contains(&year) {
+                return Err(out_of_range(function));
+            }
+            let day = day.min(days_in_month(year, month));
+            days_from_civil(year, month, day) * MICROS_PER_DAY + time
+        }
+        part => {
+            let unit = match part {
+                DatePart::Week => 7 * MICROS_PER_DAY,
+                part => part.micros().expect("parts of a day have a length"),
+            };
+            count.checked_mul(unit).and_then(|micros| local.checked_add(micros)).ok_or_else(overflow)?
+        }
+    };
+    from_wall_clock(value, moved, &utc, function)
+}
+
+/// `DATE_DIFF(a, b, part)`: whole parts from `b` to `a` for parts below a
+/// day, and part boundaries crossed for days and longer parts. Timestamps
+/// are compared in UTC.
+pub fn diff(a: &Value, b: &Value, part: &str) -> Result<i64, ExecutionError> {
+    if std::mem::discriminant(a) != std::mem::discriminant(b) {
+        let type_name = |value: &Value| value.data_type().map(|data_type| data_type.to_string()).unwrap_or_default();
+        return Err(ExecutionError::new(&format!(
+            "DATE_DIFF needs two values of the same type, got {} and {}",
+            type_name(a),
+            type_name(b)
+        )));
+    }
+    let part = DatePart::parse(part)?;
+    part.check("DATE_DIFF", a)?;
+    let utc = TimeZone::utc();
+    let (a, b) = (wall_clock(a, &utc), wall_clock(b, &utc));
+    let (a_days, b_days) = (a.div_euclid(MICROS_PER_DAY), b.div_euclid(MICROS_PER_DAY));
+    let months = |days: i64| {
+        let (year, month, _) = civil_from_days(days);
+        year * 12 + month as i64 - 1
+    };
+    Ok(match part {
+        DatePart::Day => a_days - b_days,
+        DatePart::Week => ((a_days - weekday(a_days)) - (b_days - weekday(b_days))) / 7,
+        DatePart::Month => months(a_days) - months(b_days),
+        DatePart::Quarter => months(a_days).div_euclid(3) - months(b_days).div_euclid(3),
+        DatePart::Year => months(a_days).div_euclid(12) - months(b_days).div_euclid(12),
+        part => (a - b) / part.micros().expect("parts of a day have a length"),
+    })
+}
+
+/// `FORMAT_TIMESTAMP(format, value)` with strftime-like elements, reading
+/// timestamps in `zone`.
+pub fn format(pattern: &str, value: &Value, zone: &TimeZone) -> Result<String, ExecutionError> {
+    let local = wall_clock(value, zone);
+    let offset = match value {
+        Value::Timestamp(utc) => (local - utc) / MICROS_PER_MINUTE,
+        _ => 0,
+    };
+    let (days, time) = (local.div_euclid(MICROS_PER_DAY), local.rem_euclid(MICROS_PER_DAY));
+    let (year, month, day) = civil_from_days(days);
+    let day_of_year = days - days_from_civil(year, 1, 1) + 1;
+    let (hour, minute, second) = (time / MICROS_PER_HOUR, time / MICROS_PER_MINUTE % 60, time / MICROS_PER_SECOND % 60);
+    let fraction = time % MICROS_PER_SECOND;
+
+    let mut out = String::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            out.push(c);
+            continue;
+        }
+        let unsupported = |element: &str| ExecutionError::new(&format!("Unsupported format element %{}", element));
+        // writing to a String cannot fail
+        let _ = match chars.next().ok_or_else(|| unsupported(""))? {
+            'Y' => write!(out, "{:04}", year),
+            'y' => write!(out, "{:02}", year % 100),
+            'm' => write!(out, "{:02}", month),
+            'd' => write!(out, "{:02}", day),
+            'e' => write!(out, "{:>2}", day),
+            'j' => write!(out, "{:03}", day_of_year),
+            'H' => write!(out, "{:02}", hour),
+            'I' => write!(out, "{:02}", (hour + 11) % 12 + 1),
+            'p' => write!(out, "{}", if hour < 12 { "AM" } else { "PM" }),
+            'M' => write!(out, "{:02}", minute),
+            'S' => write!(out, "{:02}", second),
+            // %E<n>S and %E*S: seconds with n or all fractional digits
+            'E' => {
+                let digits = match chars.next() {
+                    Some('*') => 6,
+                    Some(digit @ '0'..='6') => digit as usize - '0' as usize,
+                    other => return Err(unsupported(&format!("E{}", other.map(String::from).unwrap_or_default()))),
+                };
+                if chars.next() != Some('S') {
+                    return Err(unsupported("E"));
+                }
+                let fraction = format!("{:06}", fraction);
+                match digits {
+                    0 => write!(out, "{:02}", second),
+                    digits => write!(out, "{:02}.{}", second, &fraction[..digits]),
+                }
+            }
+            'F' => write!(out, "{}", format_date(days)),
+            'T' => write!(out, "{:02}:{:02}:{:02}", hour, minute, second),
+            'R' => write!(out, "{:02}:{:02}", hour, minute),
+            'D' => write!(out, "{:02}/{:02}/{:02}", month, day, year % 100),
+            'a' => write!(out, "{}", &WEEKDAYS[weekday(days) as usize][..3]),
+            'A' => write!(out, "{}", WEEKDAYS[weekday(days) as usize]),
+            'b' | 'h' => write!(out, "{}", &MONTHS[month as usize - 1][..3]),
+            'B' => write!(out, "{}", MONTHS[month as usize - 1]),
+            'u' => write!(out, "{}", (weekday(days) + 6) % 7 + 1),
+            'w' => write!(out, "{}", weekday(days)),
+            'U' => write!(out, "{:02}", (day_of_year - 1 + 7 - weekday(days)) / 7),
+            'Q' => write!(out, "{}", (month - 1) / 3 + 1),
+            's' => write!(out, "{}", (local - offset * MICROS_PER_MINUTE).div_euclid(MICROS_PER_SECOND)),
+            'z' => write!(out, "{}", format_offset(offset, false)),
+            'Z' => write!(out, "{}", zone.name),
+            'n' => out.write_char('\n'),
+            't' => out.write_char('\t'),
+            '%' => out.write_char('%'),
+            other => return Err(unsupported(&other.to_string())),
+        };
+    }
+    Ok(out)
+}
+
+/// `PARSE_TIMESTAMP(format, text)`: reads the elements `format` writes, with
+/// fields that are not in the format taken from 1970-01-01 00:00:00. The
+/// wall clock time is read in `zone` unless the text gives %z or %Z.
+pub fn parse(pattern: &str, text: &str, zone: &TimeZone) -> Result<i64, ExecutionError> {
+    let fail = || ExecutionError::new(&format!("Cannot parse '{}' with format '{}'", text, pattern));
+    let mut input = Input { text, position: 0 };
+    let (mut year, mut month, mut day) = (1970, 1, 1);
+    let (mut hour, mut minute, mut second, mut micros) = (0, 0, 0, 0);
+    let mut afternoon = None;
+    let mut named_zone = None;
+    let mut epoch = None;
+
+    // %F and %T stand for several elements
+    let pattern_expanded = pattern.replace("%F", "%Y-%m-%d").replace("%T", "%H:%M:%S").replace("%R", "%H:%M");
+    let mut chars = pattern_expanded.chars();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            input.skip_whitespace();
+            continue;
+        }
+        if c != '%' {
+            if !input.accept(c) {
+                return Err(fail());
+            }
+            continue;
+        }
+        let element = chars.next().ok_or_else(fail)?;
+        match element {
+            'Y' => year = input.number(1, 4).ok_or_else(fail)?,
+            'y' => year = 2000 + input.number(2, 2).ok_or_else(fail)?,
+            'm' => month = input.number(1, 2).ok_or_else(fail)?,
+            'd' | 'e' => {
+                input.skip_whitespace();
+                day = input.number(1, 2).ok_or_else(fail)?;
+            }
+            'H' | 'I' => hour = input.number(1, 2).ok_or_else(fail)?,
+            'M' => minute = input.number(1, 2).ok_or_else(fail)?,
+            'S' => second = input.number(1, 2).ok_or_else(fail)?,
+            'E' => {
+                // %E<n>S and %E*S read seconds with an optional fraction
+                if !matches!(chars.next(), Some('*' | '0'..='6')) || chars.next() != Some('S') {
+                    return Err(ExecutionError::new("Unsupported format element %E"));
+                }
+                second = input.number(1, 2).ok_or_else(fail)?;
+                if input.accept('.') {
+                    let start = input.position;
+                    let digits = input.number(1, 6).ok_or_else(fail)?;
+                    micros = digits * 10i64.pow(6 - (input.position - start) as u32);
+                }
+            }
+            'p' => {
+                afternoon = Some(if input.accept_word("PM") {
+                    true
+                } else if input.accept_word("AM") {
+                    false
+                } else {
+                    return Err(fail());
+                })
+            }
+            'b' | 'h' | 'B' => {
+                let index = MONTHS
+                    .iter()
+                    .position(|name| input.accept_word(name) || input.accept_word(&name[..3]))
+                    .ok_or_else(fail)?;
+                month = index as i64 + 1;
+            }
+            'a' | 'A' => {
+                WEEKDAYS
+                    .iter()
+                    .find(|name| input.accept_word(name) || input.accept_word(&name[..3]))
+                    .ok_or_else(fail)?;
+            }
+            'z' => {
+                if input.accept('Z') {
+                    named_zone = Some(TimeZone::utc());
+                } else {
+                    let start = input.position;
+                    input.advance_while(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | ':'));
+                    let zone = TimeZone::parse(&text[start..input.position]).map_err(|_| fail())?;
+                    named_zone = Some(zone);
+                }
+            }
+            'Z' => {
+                let start = input.position;
+                input.advance_while(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '_' | '+' | '-' | ':'));
+                let zone = TimeZone::parse(&text[start..input.position])
+                    .map_err(|_| ExecutionError::new(&format!("Unknown time zone {}", &text[start..input.position])))?;
+                named_zone = Some(zone);
+            }
+            's' => {
+                let negative = input.accept('-');
+                let seconds = input.number(1, 18).ok_or_else(fail)?;
+                epoch = Some(if negative { -seconds } else { seconds });
+            }
+            '%' => {
+                if !input.accept('%') {
+                    return Err(fail());
+                }
+            }
+            other => return Err(ExecutionError::new(&format!("Unsupported format element %{}", other))),
+        }
+    }
+    input.skip_whitespace();
+    if input.position < text.len() {
+        return Err(fail());
+    }
+
+    if let Some(seconds) = epoch {
+        let utc = seconds.checked_mul(MICROS_PER_SECOND).filter(|utc| in_range(*utc)).ok_or_else(fail)?;
+        return Ok(utc);
+    }
+    match afternoon {
+        Some(_) if !(1..=12).contains(&hour) => return Err(fail()),
+        Some(afternoon) => hour = hour % 12 + if afternoon { 12 } else { 0 },
+        None => {}
+    }
+    if hour > 23 || minute > 59 || second > 59 || !(1..=12).contains(&month) {
+        return Err(fail());
+    }
+    let days = date(year, month as u32, day as u32).ok_or_else(fail)?;
+    let local = days * MICROS_PER_DAY + hour * MICROS_PER_HOUR + minute * MICROS_PER_MINUTE + second * MICROS_PER_SECOND + micros;
+    let utc = named_zone.as_ref().unwrap_or(zone).to_utc(local);
+    if !in_range(utc) {
+        return Err(fail());
+    }
+    Ok(utc)
+}
+
+/// The text PARSE_TIMESTAMP reads, and how much of it was read.
+struct Input<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl Input<'_> {
+    fn rest(&self) -> &str {
+        &self.text[self.position..]
+    }
+
+    fn accept(&mut self, c: char) -> bool {
+        if self.rest().starts_with(c) {
+            self.position += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn accept_word(&mut self, word: &str) -> bool {
+        let rest = self.rest();
+        if rest.len() >= word.len() && rest.is_char_boundary(word.len()) && rest[..word.len()].eq_ignore_ascii_case(word) {
+            self.position += word.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn advance_while(&mut self, f: impl Fn(char) -> bool) {
+        let length = self.rest().find(|c| !f(c)).unwrap_or(self.rest().len());
+        self.position += length;
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.advance_while(char::is_whitespace);
+    }
+
+    /// Reads `min` to `max` digits.
+    fn number(&mut self, min: usize, max: usize) -> Option<i64> {
+        let length = self.rest().chars().take(max).take_while(char::is_ascii_digit).count();
+        if length < min {
+            return None;
+        }
+        let number = self.rest()[..length].parse().ok()?;
+        self.position += length;
+        Some(number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(text: &str) -> Value {
+        Value::Date(parse_date(text).unwrap() as i32)
+    }
+
+    fn datetime(text: &str) -> Value {
+        Value::DateTime(parse_datetime(text).unwrap())
+    }
+
+    fn timestamp(text: &str) -> Value {
+        Value::Timestamp(parse_timestamp(text, &TimeZone::utc()).unwrap())
+    }
+
+    #[test]
+    fn converts_and_parses_dates_across_leap_years() {
+        for (year, month, day) in [(1970, 1, 1), (1969, 12, 31), (2000, 2, 29), (1900, 3, 1), (1, 1, 1), (9999, 12, 31)] {
+            assert_eq!(civil_from_days(days_from_civil(year, month, day)), (year, month, day));
+        }
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(weekday(days_from_civil(2024, 3, 10)), 0);
+
+        assert_eq!(parse_date("2024-2-9"), Some(days_from_civil(2024, 2, 9)));
+        for text in ["2023-02-29", "1900-02-29", "0000-01-01", "24-01-01", "2024-13-01", "2024-01-1x"] {
+            assert_eq!(parse_date(text), None, "{}", text);
+        }
+        assert_eq!(parse_time("7:05"), Some(7 * MICROS_PER_HOUR + 5 * MICROS_PER_MINUTE));
+        assert_eq!(parse_time("23:59:59.5"), Some(MICROS_PER_DAY - MICROS_PER_SECOND / 2));
+        for text in ["24:00", "12:60", "12:00.5", "12:00:00.1234567", "1:2"] {
+            assert_eq!(parse_time(text), None, "{}", text);
+        }
+
+        // a zone in the text wins over the one it is read in
+        let new_york = TimeZone::parse("America/New_York").unwrap();
+        let utc = TimeZone::utc();
+        assert_eq!(parse_timestamp("2024-07-01 12:00", &new_york), parse_timestamp("2024-07-01T16:00:00 UTC", &utc));
+        assert_eq!(parse_timestamp("2024-07-01 12:00+02", &new_york), parse_timestamp("2024-07-01 10:00", &utc));
+        assert_eq!(parse_timestamp("2024-07-01 12:00 Nowhere", &new_york), None);
+    }
+
+    #[test]
+    fn adds_truncates_and_diffs_by_calendar_parts() {
+        let plus = |value: &Value, count: i64, part: &str| add("DATE_ADD", value, count, part);
+        // months keep the day, or the last day of a shorter month
+        assert_eq!(plus(&day("2024-01-31"), 1, "MONTH").unwrap(), day("2024-02-29"));
+        assert_eq!(plus(&day("2023-01-31"), 1, "month").unwrap(), day("2023-02-28"));
+        assert_eq!(plus(&day("2024-02-29"), 1, "YEAR").unwrap(), day("2025-02-28"));
+        assert_eq!(plus(&day("2024-03-31"), -1, "QUARTER").unwrap(), day("2023-12-31"));
+        assert_eq!(plus(&Value::Time(23 * MICROS_PER_HOUR), 2, "HOUR").unwrap(), Value::Time(MICROS_PER_HOUR));
+        for (value, count, part, error) in [
+            (day("9999-12-31"), 1, "DAY", "DATE_ADD goes outside the range 0001-01-01 to 9999-12-31"),
+            (day("2024-01-01"), 1, "HOUR", "DATE_ADD cannot use HOUR on a DATE"),
+            (day("2024-01-01"), i64::MAX, "WEEK", "DATE_ADD overflows"),
+            (day("2024-01-01"), 1, "FORTNIGHT", "Unknown date part FORTNIGHT"),
+        ] {
+            assert_eq!(plus(&value, count, part).unwrap_err().message, error);
+        }
+
+        // boundaries crossed for days and longer parts, whole parts below
+        assert_eq!(diff(&day("2025-01-01"), &day("2024-12-31"), "YEAR").unwrap(), 1);
+        assert_eq!(diff(&day("2024-12-31"), &day("2024-01-01"), "YEAR").unwrap(), 0);
+        assert_eq!(diff(&day("2024-03-10"), &day("2024-03-09"), "WEEK").unwrap(), 1);
+        assert_eq!(diff(&datetime("2024-01-02 00:59"), &datetime("2024-01-01 23:00"), "HOUR").unwrap(), 1);
+        assert_eq!(diff(&datetime("2024-01-01 23:00"), &datetime("2024-01-02 00:59"), "HOUR").unwrap(), -1);
+        assert_eq!(
+            diff(&day("2024-01-01"), &datetime("2024-01-01 00:00"), "DAY").unwrap_err().message,
+            "DATE_DIFF needs two values of the same type, got DATE and DATETIME"
+        );
+
+        // timestamps are read on the wall clock of the zone, whose offset
+        // changes on this day
+        let new_york = TimeZone::parse("America/New_York").unwrap();
+        let noon = timestamp("2024-03-10 12:00");
+        assert_eq!(trunc(&noon, "DAY", &new_york).unwrap(), timestamp("2024-03-10 05:00"));
+        assert_eq!(trunc(&noon, "DAY", &TimeZone::utc()).unwrap(), timestamp("2024-03-10 00:00"));
+        assert_eq!(extract("HOUR", &noon, &new_york).unwrap(), 8);
+        assert_eq!(extract("HOUR", &timestamp("2024-11-03 06:30"), &new_york).unwrap(), 1);
+        // weeks start on Sunday, and the days before the first are in week 0
+        assert_eq!(trunc(&day("2024-03-13"), "WEEK", &new_york).unwrap(), day("2024-03-10"));
+        assert_eq!(extract("WEEK", &day("2024-01-06"), &new_york).unwrap(), 0);
+        assert_eq!(extract("WEEK", &day("2024-01-07"), &new_york).unwrap(), 1);
+        assert_eq!(extract("DAYOFWEEK", &day("2024-03-10"), &new_york).unwrap(), 1);
+    }
+
+    #[test]
+    fn formats_and_parses_timestamps_in_zones() {
+        let new_york = TimeZone::parse("America/New_York").unwrap();
+        let utc = TimeZone::utc();
+        let value = timestamp("2024-11-03 05:30:00.25");
+        assert_eq!(format("%F %T%z %Z", &value, &new_york).unwrap(), "2024-11-03 01:30:00-0400 America/New_York");
+        assert_eq!(format("%a %b %e %I:%M %p %E2S", &value, &utc).unwrap(), "Sun Nov  3 05:30 AM 00.25");
+        assert_eq!(format("%s", &value, &new_york).unwrap(), "1730611800");
+        assert_eq!(format("%k", &value, &new_york).unwrap_err().message, "Unsupported format element %k");
+
+        // the later 1:30 of the night clocks fall back needs an offset
+        let parsed = |pattern: &str, text: &str, zone: &TimeZone| parse(pattern, text, zone).map(Value::Timestamp);
+        assert_eq!(parsed("%F %H:%M", "2024-11-03 01:30", &new_york).unwrap(), timestamp("2024-11-03 05:30"));
+        assert_eq!(parsed("%F %H:%M %z", "2024-11-03 01:30 -0500", &new_york).unwrap(), timestamp("2024-11-03 06:30"));
+        assert_eq!(parsed("%d %B %Y %I%p", "3 november 2024 1PM", &utc).unwrap(), timestamp("2024-11-03 13:00"));
+        assert_eq!(parse("%s", "-1", &new_york).unwrap(), -MICROS_PER_SECOND);
+        for (pattern, text) in [("%F", "2024-02-30"), ("%H:%M", "24:00"), ("%I %p", "13 PM"), ("%F", "2024-01-01 extra")] {
+            assert_eq!(
+                parse(pattern, text, &new_york).unwrap_err().message,
+                format!("Cannot parse '{}' with format '{}'", text, pattern)
+            );
+        }
+    }
+}
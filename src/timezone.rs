@@ -0,0 +1,324 @@
+use crate::temporal::{civil_from_days, days_from_civil, weekday, MICROS_PER_DAY, MICROS_PER_MINUTE};
+
+/// A Sunday of a month on which clocks change.
+#[derive(Debug, Clone, Copy)]
+enum Sunday {
+    /// The first, second, ... Sunday
+    Nth(u32),
+    Last,
+}
+
+/// A clock change: the day, and the minute of that day in local standard
+/// time, or in UTC where the rule is the same everywhere.
+#[derive(Debug, Clone, Copy)]
+struct Transition {
+    month: u32,
+    sunday: Sunday,
+    minute: i64,
+    utc: bool,
+}
+
+const fn transition(month: u32, sunday: Sunday, minute: i64, utc: bool) -> Transition {
+    Transition {
+        month,
+        sunday,
+        minute,
+        utc,
+    }
+}
+
+/// Daylight saving time, one hour ahead of standard time from `start` to
+/// `end`. In the southern hemisphere `end` comes first in the year.
+#[derive(Debug, Clone, Copy)]
+struct DaylightSaving {
+    start: Transition,
+    end: Transition,
+}
+
+/// Since 2007: the second Sunday of March at 2:00 to the first Sunday of
+/// November at 2:00 daylight time.
+const UNITED_STATES: DaylightSaving = DaylightSaving {
+    start: transition(3, Sunday::Nth(2), 120, false),
+    end: transition(11, Sunday::Nth(1), 60, false),
+};
+
+/// The last Sunday of March to the last Sunday of October, at 1:00 UTC.
+const EUROPE: DaylightSaving = DaylightSaving {
+    start: transition(3, Sunday::Last, 60, true),
+    end: transition(10, Sunday::Last, 60, true),
+};
+
+/// The first Sunday of October at 2:00 to the first Sunday of April at
+/// 3:00 daylight time.
+const AUSTRALIA: DaylightSaving = DaylightSaving {
+    start: transition(10, Sunday::Nth(1), 120, false),
+    end: transition(4, Sunday::Nth(1), 120, false),
+};
+
+/// The last Sunday of September at 2:00 to the first Sunday of April at
+/// 3:00 daylight time.
+const NEW_ZEALAND: DaylightSaving = DaylightSaving {
+    start: transition(9, Sunday::Last, 120, false),
+    end: transition(4, Sunday::Nth(1), 120, false),
+};
+
+struct Zone {
+    name: &'static str,
+    /// Standard offset from UTC in minutes
+    offset: i64,
+    daylight_saving: Option<DaylightSaving>,
+}
+
+const fn zone(name: &'static str, offset: i64, daylight_saving: Option<DaylightSaving>) -> Zone {
+    Zone {
+        name,
+        offset,
+        daylight_saving,
+    }
+}
+
+/// The embedded time zone table, with the current rules of each zone;
+/// historical changes of offsets and rules are not recorded.
+static ZONES: [Zone; 40] = [
+    zone("UTC", 0, None),
+    zone("Etc/UTC", 0, None),
+    zone("GMT", 0, None),
+    zone("America/New_York", -300, Some(UNITED_STATES)),
+    zone("America/Toronto", -300, Some(UNITED_STATES)),
+    zone("America/Chicago", -360, Some(UNITED_STATES)),
+    zone("America/Mexico_City", -360, None),
+    zone("America/Denver", -420, Some(UNITED_STATES)),
+    zone("America/Phoenix", -420, None),
+    zone("America/Los_Angeles", -480, Some(UNITED_STATES)),
+    zone("America/Vancouver", -480, Some(UNITED_STATES)),
+    zone("America/Anchorage", -540, Some(UNITED_STATES)),
+    zone("Pacific/Honolulu", -600, None),
+    zone("America/Sao_Paulo", -180, None),
+    zone("America/Argentina/Buenos_Aires", -180, None),
+    zone("America/Bogota", -300, None),
+    zone("Europe/London", 0, Some(EUROPE)),
+    zone("Europe/Dublin", 0, Some(EUROPE)),
+    zone("Europe/Lisbon", 0, Some(EUROPE)),
+    zone("Europe/Paris", 60, Some(EUROPE)),
+    zone("Europe/Berlin", 60, Some(EUROPE)),
+    zone("Europe/Madrid", 60, Some(EUROPE)),
+    zone("Europe/Rome", 60, Some(EUROPE)),
+    zone("Europe/Amsterdam", 60, Some(EUROPE)),
+    zone("Europe/Stockholm", 60, Some(EUROPE)),
+    zone("Europe/Warsaw", 60, Some(EUROPE)),
+    zone("Europe/Athens", 120, Some(EUROPE)),
+    zone("Europe/Helsinki", 120, Some(EUROPE)),
+    zone("Europe/Istanbul", 180, None),
+    zone("Europe/Moscow", 180, None),
+    zone("Africa/Johannesburg", 120, None),
+    zone("Asia/Dubai", 240, None),
+    zone("Asia/Kolkata", 330, None),
+    zone("Asia/Singapore", 480, None),
+    zone("Asia/Shanghai", 480, None),
+    zone("Asia/Hong_Kong", 480, None),
+    zone("Asia/Tokyo", 540, None),
+    zone("Asia/Seoul", 540, None),
+    zone("Australia/Sydney", 600, Some(AUSTRALIA)),
+    zone("Pacific/Auckland", 720, Some(NEW_ZEALAND)),
+];
+
+/// A time zone of the embedded table or a fixed offset such as `+05:30`.
+#[derive(Debug, Clone)]
+pub struct TimeZone {
+    pub name: String,
+    /// Standard offset from UTC in microseconds
+    offset: i64,
+    daylight_saving: Option<DaylightSaving>,
+}
+
+impl TimeZone {
+    pub fn utc() -> Self {
+        Self {
+            name: "UTC".to_string(),
+            offset: 0,
+            daylight_saving: None,
+        }
+    }
+
+    /// Looks up a zone by name, ignoring case, or reads an offset written as
+    /// `Z`, `±hh[:mm]` or `UTC±hh[:mm]`.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        let name = name.trim();
+        if let Some(zone) = ZONES.iter().find(|zone| zone.name.eq_ignore_ascii_case(name)) {
+            return Ok(Self {
+                name: zone.name.to_string(),
+                offset: zone.offset * MICROS_PER_MINUTE,
+                daylight_saving: zone.daylight_saving,
+            });
+        }
+        if name.eq_ignore_ascii_case("Z") {
+            return Ok(Self::utc());
+        }
+        let offset = name
+            .strip_prefix("UTC")
+            .or_else(|| name.strip_prefix("utc"))
+            .unwrap_or(name);
+        let minutes = parse_offset(offset).ok_or_else(|| format!("Unknown time zone {}", name))?;
+        Ok(Self {
+            name: format_offset(minutes, true),
+            offset: minutes * MICROS_PER_MINUTE,
+            daylight_saving: None,
+        })
+    }
+
+    /// Offset from UTC at the instant, in microseconds.
+    pub fn offset_at(&self, utc: i64) -> i64 {
+        match &self.daylight_saving {
+            Some(rule) if rule.in_effect(utc + self.offset, self.offset) => self.offset + 60 * MICROS_PER_MINUTE,
+            _ => self.offset,
+        }
+    }
+
+    /// The local wall clock time of an instant.
+    pub fn to_local(&self, utc: i64) -> i64 {
+        utc + self.offset_at(utc)
+    }
+
+    /// The instant of a local wall clock time. A time skipped when clocks
+    /// go forward is read with the standard offset, so it lands after the
+    /// change; a time that occurs twice is the earlier instant.
+    pub fn to_utc(&self, local: i64) -> i64 {
+        if self.daylight_saving.is_some() {
+            let daylight = self.offset + 60 * MICROS_PER_MINUTE;
+            if self.offset_at(local - daylight) == daylight {
+                return local - daylight;
+            }
+        }
+        local - self.offset
+    }
+}
+
+impl DaylightSaving {
+    /// Whether daylight time applies at a time given in local standard time.
+    fn in_effect(&self, standard: i64, offset: i64) -> bool {
+        let (year, _, _) = civil_from_days(standard.div_euclid(MICROS_PER_DAY));
+        let start = self.start.at(year, offset);
+        let end = self.end.at(year, offset);
+        if start < end {
+            start <= standard && standard < end
+        } else {
+            standard >= start || standard < end
+        }
+    }
+}
+
+impl Transition {
+    /// When the clocks change in the year, in local standard time.
+    fn at(&self, year: i64, offset: i64) -> i64 {
+        let day = match self.sunday {
+            Sunday::Nth(n) => {
+                let first = days_from_civil(year, self.month, 1);
+                first + (7 - weekday(first)) % 7 + 7 * (n as i64 - 1)
+            }
+            Sunday::Last => {
+                let (next_year, next_month) = if self.month == 12 { (year + 1, 1) } else { (year, self.month + 1) };
+                let last = days_from_civil(next_year, next_month, 1) - 1;
+                last - weekday(last)
+            }
+        };
+        let time = day * MICROS_PER_DAY + self.minute * MICROS_PER_MINUTE;
+        if self.utc {
+            time + offset
+        } else {
+            time
+        }
+    }
+}
+
+/// Minutes of `±hh[:mm]` or `±hhmm`.
+fn parse_offset(offset: &str) -> Option<i64> {
+    let (sign, rest) = match offset.as_bytes().first()? {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    if hours.is_empty() || hours.len() > 2 || !(hours.chars().chain(minutes.chars()).all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+    let (hours, minutes): (i64, i64) = (hours.parse().ok()?, minutes.parse().ok()?);
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes))
+}
+
+/// `+hh:mm`, or `+hhmm` without the colon.
+pub fn format_offset(minutes: i64, colon: bool) -> String {
+    let sign = if minutes < 0 { '-' } else { '+' };
+    let minutes = minutes.abs();
+    format!("{}{:02}{}{:02}", sign, minutes / 60, if colon { ":" } else { "" }, minutes % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporal::MICROS_PER_HOUR;
+
+    /// Microseconds since the epoch of a UTC or wall clock time.
+    fn at(year: i64, month: u32, day: u32, hour: i64, minute: i64) -> i64 {
+        days_from_civil(year, month, day) * MICROS_PER_DAY + hour * MICROS_PER_HOUR + minute * MICROS_PER_MINUTE
+    }
+
+    fn offset_hours(zone: &str, utc: i64) -> f64 {
+        TimeZone::parse(zone).unwrap().offset_at(utc) as f64 / MICROS_PER_HOUR as f64
+    }
+
+    #[test]
+    fn switches_offsets_at_daylight_saving_boundaries() {
+        // New York springs forward at 2:00 EST, 7:00 UTC, and falls back at 2:00 EDT, 6:00 UTC
+        assert_eq!(offset_hours("America/New_York", at(2024, 3, 10, 6, 59)), -5.0);
+        assert_eq!(offset_hours("America/New_York", at(2024, 3, 10, 7, 0)), -4.0);
+        assert_eq!(offset_hours("America/New_York", at(2024, 11, 3, 5, 59)), -4.0);
+        assert_eq!(offset_hours("America/New_York", at(2024, 11, 3, 6, 0)), -5.0);
+        // Europe changes at 1:00 UTC in every zone
+        assert_eq!(offset_hours("Europe/London", at(2024, 3, 31, 0, 59)), 0.0);
+        assert_eq!(offset_hours("Europe/London", at(2024, 3, 31, 1, 0)), 1.0);
+        assert_eq!(offset_hours("Europe/Berlin", at(2024, 10, 27, 0, 59)), 2.0);
+        assert_eq!(offset_hours("Europe/Berlin", at(2024, 10, 27, 1, 0)), 1.0);
+        // a southern summer spans the new year
+        assert_eq!(offset_hours("Australia/Sydney", at(2024, 1, 15, 0, 0)), 11.0);
+        assert_eq!(offset_hours("Australia/Sydney", at(2024, 4, 6, 15, 59)), 11.0);
+        assert_eq!(offset_hours("Australia/Sydney", at(2024, 4, 6, 16, 0)), 10.0);
+        assert_eq!(offset_hours("Australia/Sydney", at(2024, 10, 5, 15, 59)), 10.0);
+        assert_eq!(offset_hours("Australia/Sydney", at(2024, 10, 5, 16, 0)), 11.0);
+        assert_eq!(offset_hours("Asia/Kolkata", at(2024, 7, 1, 0, 0)), 5.5);
+
+        // 2:30 is skipped in spring and lands after the change; 1:30 occurs
+        // twice in autumn and is the earlier instant
+        let new_york = TimeZone::parse("America/New_York").unwrap();
+        assert_eq!(new_york.to_utc(at(2024, 3, 10, 2, 30)), at(2024, 3, 10, 7, 30));
+        assert_eq!(new_york.to_utc(at(2024, 3, 10, 3, 30)), at(2024, 3, 10, 7, 30));
+        assert_eq!(new_york.to_utc(at(2024, 11, 3, 1, 30)), at(2024, 11, 3, 5, 30));
+        assert_eq!(new_york.to_local(at(2024, 11, 3, 6, 30)), at(2024, 11, 3, 1, 30));
+    }
+
+    #[test]
+    fn parses_zone_names_and_offsets() {
+        assert_eq!(TimeZone::parse(" america/new_york ").unwrap().name, "America/New_York");
+        for (text, name, minutes) in [
+            ("Z", "UTC", 0),
+            ("+05:30", "+05:30", 330),
+            ("UTC-8", "-08:00", -480),
+            ("utc+0545", "+05:45", 345),
+            ("-3", "-03:00", -180),
+        ] {
+            let zone = TimeZone::parse(text).unwrap();
+            assert_eq!(zone.name, name, "{}", text);
+            assert_eq!(zone.offset_at(0), minutes * MICROS_PER_MINUTE, "{}", text);
+        }
+        for text in ["Mars/Olympus", "+15:00", "+05:60", "+5:3x", "05:00", "+"] {
+            assert_eq!(TimeZone::parse(text).unwrap_err(), format!("Unknown time zone {}", text));
+        }
+        assert_eq!(format_offset(-330, false), "-0530");
+    }
+}
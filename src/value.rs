@@ -0,0 +1,482 @@
+use crate::decimal::{Decimal, RoundingMode, DEFAULT_SCALE, MAX_PRECISION};
+use crate::errors::ParsingError;
+use crate::json::Json;
+use crate::lexer::{Token, Tokens};
+use crate::temporal::{format_value, parse_date, parse_datetime, parse_time, parse_timestamp, MICROS_PER_DAY};
+use crate::timezone::TimeZone;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DataType {
+    Bool,
+    Int64,
+    Float64,
+    String,
+    Array(Box<DataType>),
+    Date,
+    Time,
+    DateTime,
+    Timestamp,
+    /// DECIMAL(precision, scale): exact numbers of at most `precision`
+    /// digits, `scale` of them after the decimal point
+    Decimal(u8, u8),
+    /// Named fields in order; anonymous fields have an empty name
+    Struct(Vec<(String, DataType)>),
+    Json,
+}
+
+impl DataType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_uppercase().as_str() {
+            "BOOL" | "BOOLEAN" => Some(DataType::Bool),
+            "INT" | "INT64" | "INTEGER" | "BIGINT" => Some(DataType::Int64),
+            "FLOAT" | "FLOAT64" | "DOUBLE" => Some(DataType::Float64),
+            "STRING" | "TEXT" | "VARCHAR" => Some(DataType::String),
+            "DATE" => Some(DataType::Date),
+            "TIME" => Some(DataType::Time),
+            "DATETIME" => Some(DataType::DateTime),
+            "TIMESTAMP" => Some(DataType::Timestamp),
+            "NUMERIC" | "DECIMAL" => Some(DataType::Decimal(MAX_PRECISION, DEFAULT_SCALE)),
+            "JSON" => Some(DataType::Json),
+            _ => None,
+        }
+    }
+
+    /// Parses the `<element>` of an ARRAY type.
+    pub fn parse_array(tokens: &mut Tokens) -> Result<Self, ParsingError> {
+        tokens.expect_symbol("<")?;
+        let element = DataType::parse(tokens)?;
+        tokens.expect_symbol(">")?;
+        if matches!(element, DataType::Array(_)) {
+            return Err(ParsingError::new("An ARRAY cannot hold ARRAYs, only STRUCTs with ARRAY fields"));
+        }
+        Ok(DataType::Array(Box::new(element)))
+    }
+
+    /// Parses a type name, followed by `(precision[, scale])` for DECIMAL and
+    /// NUMERIC, `<element>` for ARRAY and `<[name] type, ...>` for STRUCT.
+    pub fn parse(tokens: &mut Tokens) -> Result<Self, ParsingError> {
+        let name = tokens.expect_identifier()?;
+        if name.eq_ignore_ascii_case("ARRAY") {
+            return Self::parse_array(tokens);
+        }
+        if name.eq_ignore_ascii_case("STRUCT") {
+            tokens.expect_symbol("<")?;
+            let mut fields: Vec<(String, DataType)> = Vec::new();
+            loop {
+                // a field name is followed by the name of its type
+                let named = matches!(tokens.peek_nth(1), Some(Token::Identifier(_)));
+                let field = if named { tokens.expect_identifier()? } else { String::new() };
+                if !field.is_empty() && fields.iter().any(|(name, _)| name.eq_ignore_ascii_case(&field)) {
+                    return Err(ParsingError::new(&format!("STRUCT has two fields named {}", field)));
+                }
+                fields.push((field, DataType::parse(tokens)?));
+                if !tokens.accept_symbol(",") {
+                    break;
+                }
+            }
+            tokens.expect_symbol(">")?;
+            return Ok(DataType::Struct(fields));
+        }
+        let data_type = DataType::from_name(&name).ok_or_else(|| ParsingError::new(&format!("Unknown type {}", name)))?;
+        if !matches!(data_type, DataType::Decimal(..)) || !tokens.accept_symbol("(") {
+            return Ok(data_type);
+        }
+        let number = |tokens: &mut Tokens| match tokens.next() {
+            Some(Token::Number(number)) => number.parse::<u8>().ok(),
+            _ => None,
+        };
+        let precision = number(tokens);
+        let scale = if tokens.accept_symbol(",") { number(tokens) } else { Some(0) };
+        tokens.expect_symbol(")")?;
+        match (precision, scale) {
+            (Some(precision), Some(scale)) if (1..=MAX_PRECISION).contains(&precision) && scale <= precision => {
+                Ok(DataType::Decimal(precision, scale))
+            }
+            _ => Err(ParsingError::new(&format!(
+                "{} takes a precision from 1 to {} and a scale up to the precision",
+                name.to_uppercase(),
+                MAX_PRECISION
+            ))),
+        }
+    }
+
+    pub fn is_temporal(&self) -> bool {
+        matches!(self, DataType::Date | DataType::Time | DataType::DateTime | DataType::Timestamp)
+    }
+
+    /// Whether CAST converts values of this type to `target`. Arrays only
+    /// convert to arrays, structs to structs with as many fields, JSON to and
+    /// from STRING, BOOL not to or from FLOAT64, and temporal types to and
+    /// from STRING and each other, except between DATE and TIME.
+    pub fn can_cast_to(&self, target: &DataType) -> bool {
+        match (self, target) {
+            (a, b) if a == b => true,
+            (DataType::Array(a), DataType::Array(b)) => a.can_cast_to(b),
+            (DataType::Array(_), _) | (_, DataType::Array(_)) => false,
+            (DataType::Struct(a), DataType::Struct(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|((_, a), (_, b))| a.can_cast_to(b))
+            }
+            (DataType::Struct(_), _) | (_, DataType::Struct(_)) => false,
+            (DataType::Json, DataType::String) | (DataType::String, DataType::Json) => true,
+            (DataType::Json, _) | (_, DataType::Json) => false,
+            (DataType::Date, DataType::Time) | (DataType::Time, DataType::Date) => false,
+            (a, b) if a.is_temporal() || b.is_temporal() => {
+                (a.is_temporal() || *a == DataType::String) && (b.is_temporal() || *b == DataType::String)
+            }
+            (DataType::Bool, DataType::Float64 | DataType::Decimal(..)) | (DataType::Float64 | DataType::Decimal(..), DataType::Bool) => false,
+            _ => true,
+        }
+    }
+
+    /// The type both types convert to without loss, if any: INT64 widens to
+    /// DECIMAL and both to FLOAT64, decimals to the digits of both, arrays and
+    /// structs element by element, and other types only match themselves.
+    /// Struct fields keep the names of this type where it has them.
+    pub fn common_supertype(&self, other: &DataType) -> Option<DataType> {
+        match (self, other) {
+            (a, b) if a == b => Some(a.clone()),
+            (DataType::Int64 | DataType::Decimal(..), DataType::Int64 | DataType::Decimal(..)) => {
+                let ((p1, s1), (p2, s2)) = (self.decimal_digits()?, other.decimal_digits()?);
+                let scale = s1.max(s2);
+                Some(DataType::Decimal(((p1 - s1).max(p2 - s2) + scale).min(MAX_PRECISION), scale))
+            }
+            (DataType::Int64 | DataType::Decimal(..), DataType::Float64)
+            | (DataType::Float64, DataType::Int64 | DataType::Decimal(..)) => Some(DataType::Float64),
+            (DataType::Array(a), DataType::Array(b)) => a.common_supertype(b).map(|element| DataType::Array(Box::new(element))),
+            (DataType::Struct(a), DataType::Struct(b)) if a.len() == b.len() => a
+                .iter()
+                .zip(b)
+                .map(|((name, a), (other, b))| {
+                    let name = if name.is_empty() { other } else { name };
+                    a.common_supertype(b).map(|data_type| (name.clone(), data_type))
+                })
+                .collect::<Option<_>>()
+                .map(DataType::Struct),
+            _ => None,
+        }
+    }
+}
+
+impl DataType {
+    /// Precision and scale of a DECIMAL, or of the DECIMAL that holds every
+    /// INT64.
+    pub fn decimal_digits(&self) -> Option<(u8, u8)> {
+        match self {
+            DataType::Decimal(precision, scale) => Some((*precision, *scale)),
+            DataType::Int64 => Some((19, 0)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for DataType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            DataType::Bool => "BOOL",
+            DataType::Int64 => "INT64",
+            DataType::Float64 => "FLOAT64",
+            DataType::String => "STRING",
+            DataType::Date => "DATE",
+            DataType::Time => "TIME",
+            DataType::DateTime => "DATETIME",
+            DataType::Timestamp => "TIMESTAMP",
+            DataType::Json => "JSON",
+            DataType::Array(element) => return write!(f, "ARRAY<{}>", element),
+            DataType::Decimal(MAX_PRECISION, DEFAULT_SCALE) => "NUMERIC",
+            DataType::Decimal(precision, scale) => return write!(f, "NUMERIC({}, {})", precision, scale),
+            DataType::Struct(fields) => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(name, data_type)| match name.as_str() {
+                        "" => data_type.to_string(),
+                        name => format!("{} {}", name, data_type),
+                    })
+                    .collect();
+                return write!(f, "STRUCT<{}>", fields.join(", "));
+            }
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Value>),
+    /// Days since 1970-01-01
+    Date(i32),
+    /// Microseconds since midnight
+    Time(i64),
+    /// Wall clock microseconds since 1970-01-01 00:00, without a time zone
+    DateTime(i64),
+    /// Microseconds since 1970-01-01 00:00 UTC
+    Timestamp(i64),
+    Decimal(Decimal),
+    /// Field values in order; the names are part of the type only
+    Struct(Vec<Value>),
+    Json(Json),
+}
+
+pub type Row = Vec<Value>;
+
+impl Value {
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            Value::Null => None,
+            Value::Bool(_) => Some(DataType::Bool),
+            Value::Int(_) => Some(DataType::Int64),
+            Value::Float(_) => Some(DataType::Float64),
+            Value::String(_) => Some(DataType::String),
+            Value::Date(_) => Some(DataType::Date),
+            Value::Time(_) => Some(DataType::Time),
+            Value::DateTime(_) => Some(DataType::DateTime),
+            Value::Timestamp(_) => Some(DataType::Timestamp),
+            Value::Decimal(d) => Some(DataType::Decimal(MAX_PRECISION, d.scale())),
+            Value::Json(_) => Some(DataType::Json),
+            // the field names of a struct are not part of its value
+            Value::Struct(_) => None,
+            // the element type of an empty array is unknown
+            Value::Array(items) => items
+                .iter()
+                .find_map(|item| item.data_type())
+                .map(|element| DataType::Array(Box::new(element))),
+        }
+    }
+
+    /// Converts the value for storage in a column of the given type. Strings
+    /// are read into temporal columns the way literals are, and parsed into
+    /// JSON columns.
+    pub fn coerce_to(self, data_type: DataType) -> Result<Value, String> {
+        match (self, data_type) {
+            (Value::Null, _) => Ok(Value::Null),
+            (Value::Int(i), DataType::Float64) => Ok(Value::Float(i as f64)),
+            (Value::String(s), data_type) if data_type.is_temporal() || data_type == DataType::Json => {
+                Value::String(s).cast(&data_type)
+            }
+            (value @ (Value::Int(_) | Value::Float(_) | Value::Decimal(_)), data_type @ DataType::Decimal(..)) => {
+                let message = format!("Cannot store {} in a {} column", value, data_type);
+                value.cast(&data_type).map_err(|_| message)
+            }
+            (Value::Array(items), DataType::Array(element)) => items
+                .into_iter()
+                .map(|item| item.coerce_to((*element).clone()))
+                .collect::<Result<_, _>>()
+                .map(Value::Array),
+            (Value::Struct(values), DataType::Struct(fields)) if values.len() == fields.len() => values
+                .into_iter()
+                .zip(fields)
+                .map(|(value, (_, data_type))| value.coerce_to(data_type))
+                .collect::<Result<_, _>>()
+                .map(Value::Struct),
+            (value, data_type) if value.data_type().as_ref() == Some(&data_type) => Ok(value),
+            (value, data_type) => Err(format!("Cannot store {} in a {} column", value, data_type)),
+        }
+    }
+
+    /// `CAST(value AS target)`; fails for strings that do not spell a value
+    /// of the type and floats outside the INT64 range. Timestamps convert to
+    /// and from the other temporal types in UTC, and JSON to its text.
+    pub fn cast(self, target: &DataType) -> Result<Value, String> {
+        let fail = |value: &Value| format!("Cannot cast {} to {}", value, target);
+        match (self, target) {
+            (Value::Null, _) => Ok(Value::Null),
+            (Value::Array(items), DataType::Array(element)) => {
+                items.into_iter().map(|item| item.cast(element)).collect::<Result<_, _>>().map(Value::Array)
+            }
+            (Value::Struct(values), DataType::Struct(fields)) if values.len() == fields.len() => values
+                .into_iter()
+                .zip(fields)
+                .map(|(value, (_, data_type))| value.cast(data_type))
+                .collect::<Result<_, _>>()
+                .map(Value::Struct),
+            (value, DataType::Decimal(precision, scale)) => {
+                let decimal = match &value {
+                    Value::Int(i) => Some(Decimal::from_int(*i)),
+                    Value::Float(x) => Decimal::from_f64(*x),
+                    Value::Decimal(d) => Some(*d),
+                    Value::String(s) => Decimal::parse(s),
+                    _ => None,
+                };
+                // digits beyond the scale are rounded, digits beyond the precision fail
+                match decimal.and_then(|d| d.rescale(*scale, RoundingMode::HalfAwayFromZero)) {
+                    Some(d) if d.integer_digits() <= precision - scale => Ok(Value::Decimal(d)),
+                    _ => Err(fail(&value)),
+                }
+            }
+            (value, target) if value.data_type().as_ref() == Some(target) => Ok(value),
+            (Value::Int(i), DataType::Float64) => Ok(Value::Float(i as f64)),
+            (Value::Decimal(d), DataType::Float64) => Ok(Value::Float(d.to_f64())),
+            (Value::Decimal(d), DataType::Int64) => {
+                d.to_i64(RoundingMode::HalfAwayFromZero).map(Value::Int).ok_or_else(|| fail(&Value::Decimal(d)))
+            }
+            (Value::Decimal(d), DataType::String) => Ok(Value::String(d.to_string())),
+            (Value::Json(json), DataType::String) => Ok(Value::String(json.to_string())),
+            (Value::String(s), DataType::Json) => Json::parse(&s).map(Value::Json).map_err(|_| fail(&Value::String(s))),
+            (Value::Int(i), DataType::Bool) => Ok(Value::Bool(i != 0)),
+            (Value::Float(x), DataType::Int64) => {
+                // halves round away from zero
+                let rounded = x.round();
+                if rounded.is_finite() && rounded >= i64::MIN as f64 && rounded < i64::MAX as f64 {
+                    Ok(Value::Int(rounded as i64))
+                } else {
+                    Err(fail(&Value::Float(x)))
+                }
+            }
+            (Value::Bool(b), DataType::Int64) => Ok(Value::Int(b as i64)),
+            (Value::Bool(b), DataType::String) => Ok(Value::String(b.to_string())),
+            (Value::Int(i), DataType::String) => Ok(Value::String(i.to_string())),
+            (Value::Float(x), DataType::String) => Ok(Value::String(x.to_string())),
+            (Value::String(s), DataType::Int64) => s.trim().parse().map(Value::Int).map_err(|_| fail(&Value::String(s))),
+            (Value::String(s), DataType::Float64) => s.trim().parse().map(Value::Float).map_err(|_| fail(&Value::String(s))),
+            (Value::String(s), DataType::Bool) => match s.trim().to_lowercase().as_str() {
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                _ => Err(fail(&Value::String(s))),
+            },
+            (value, DataType::String) if value.data_type().is_some_and(|data_type| data_type.is_temporal()) => {
+                Ok(Value::String(format_value(&value).unwrap_or_default()))
+            }
+            (Value::String(s), target) if target.is_temporal() => {
+                let parsed = match target {
+                    DataType::Date => parse_date(&s).map(|days| Value::Date(days as i32)),
+                    DataType::Time => parse_time(&s).map(Value::Time),
+                    DataType::DateTime => parse_datetime(&s).map(Value::DateTime),
+                    _ => parse_timestamp(&s, &TimeZone::utc()).map(Value::Timestamp),
+                };
+                parsed.ok_or_else(|| fail(&Value::String(s)))
+            }
+            (Value::Date(days), target) => {
+                let micros = days as i64 * MICROS_PER_DAY;
+                match target {
+                    DataType::DateTime => Ok(Value::DateTime(micros)),
+                    DataType::Timestamp => Ok(Value::Timestamp(micros)),
+                    _ => Err(fail(&Value::Date(days))),
+                }
+            }
+            (Value::DateTime(micros) | Value::Timestamp(micros), target) if target.is_temporal() => Ok(match target {
+                DataType::Date => Value::Date(micros.div_euclid(MICROS_PER_DAY) as i32),
+                DataType::Time => Value::Time(micros.rem_euclid(MICROS_PER_DAY)),
+                DataType::DateTime => Value::DateTime(micros),
+                _ => Value::Timestamp(micros),
+            }),
+            (value, _) => Err(fail(&value)),
+        }
+    }
+
+    /// SQL comparison; `None` when either side is NULL or the types are incomparable.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+            (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+            (Value::Int(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+            (Value::Float(a), Value::Int(b)) => a.partial_cmp(&(*b as f64)),
+            (Value::Float(a), Value::Float(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (Value::Decimal(a), Value::Decimal(b)) => Some(a.cmp(b)),
+            (Value::Decimal(a), Value::Int(b)) => Some(a.cmp(&Decimal::from_int(*b))),
+            (Value::Int(a), Value::Decimal(b)) => Some(Decimal::from_int(*a).cmp(b)),
+            (Value::Decimal(a), Value::Float(b)) => a.to_f64().partial_cmp(b),
+            (Value::Float(a), Value::Decimal(b)) => a.partial_cmp(&b.to_f64()),
+            (Value::Date(a), Value::Date(b)) => Some(a.cmp(b)),
+            (Value::Time(a), Value::Time(b)) | (Value::DateTime(a), Value::DateTime(b)) | (Value::Timestamp(a), Value::Timestamp(b)) => {
+                Some(a.cmp(b))
+            }
+            // field by field, up to the first that differs or is NULL
+            (Value::Struct(a), Value::Struct(b)) if a.len() == b.len() => {
+                for (a, b) in a.iter().zip(b) {
+                    match a.compare(b)? {
+                        Ordering::Equal => {}
+                        ordering => return Some(ordering),
+                    }
+                }
+                Some(Ordering::Equal)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Identity used for keys (primary keys, grouping, hashing): unlike `compare`,
+/// NULL equals NULL and values of different types are never equal.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Null, Value::Null) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Int(a), Value::Int(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b || a.to_bits() == b.to_bits(),
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Array(a), Value::Array(b)) | (Value::Struct(a), Value::Struct(b)) => a == b,
+            (Value::Decimal(a), Value::Decimal(b)) => a == b,
+            (Value::Json(a), Value::Json(b)) => a == b,
+            (Value::Date(a), Value::Date(b)) => a == b,
+            (Value::Time(a), Value::Time(b)) | (Value::DateTime(a), Value::DateTime(b)) | (Value::Timestamp(a), Value::Timestamp(b)) => {
+                a == b
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Null => {}
+            Value::Bool(b) => b.hash(state),
+            Value::Int(i) => i.hash(state),
+            // 0.0 and -0.0 are equal, so they must hash alike
+            Value::Float(x) => (if *x == 0.0 { 0.0f64 } else { *x }).to_bits().hash(state),
+            Value::String(s) => s.hash(state),
+            Value::Array(items) | Value::Struct(items) => items.hash(state),
+            Value::Decimal(d) => d.hash(state),
+            // equal documents have the same text
+            Value::Json(json) => json.to_string().hash(state),
+            Value::Date(days) => days.hash(state),
+            Value::Time(micros) | Value::DateTime(micros) | Value::Timestamp(micros) => micros.hash(state),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Bool(b) => write!(f, "{}", if *b { "TRUE" } else { "FALSE" }),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::String(s) => write!(f, "'{}'", s),
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+                write!(f, "[{}]", items.join(", "))
+            }
+            Value::Struct(values) => {
+                let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+                write!(f, "STRUCT({})", values.join(", "))
+            }
+            Value::Decimal(d) => write!(f, "NUMERIC '{}'", d),
+            Value::Json(json) => write!(f, "JSON '{}'", json),
+            // temporal values are written as literals
+            value => write!(
+                f,
+                "{} '{}'",
+                value.data_type().expect("temporal values have a type"),
+                format_value(value).unwrap_or_default()
+            ),
+        }
+    }
+}
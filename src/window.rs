@@ -0,0 +1,432 @@
+use crate::aggregate::{AggregateCall, AggregateFunction};
+use crate::errors::{ExecutionError, ParsingError};
+use crate::expression::{parse_expression, Expr};
+use crate::lexer::{Token, Tokens};
+use crate::logical_plan::{parse_sort_order, LogicalPlan};
+use crate::order_by::SortKey;
+use crate::schema::{Field, Schema};
+use crate::value::{DataType, Value};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WindowFunction {
+    RowNumber,
+    Rank,
+    DenseRank,
+    Lag,
+    Lead,
+    FirstValue,
+    LastValue,
+    /// An aggregate over the rows of the frame
+    Aggregate(AggregateCall),
+}
+
+impl WindowFunction {
+    /// Names of the functions that are only valid with OVER.
+    const NAMES: [&'static str; 7] = ["ROW_NUMBER", "RANK", "DENSE_RANK", "LAG", "LEAD", "FIRST_VALUE", "LAST_VALUE"];
+
+    /// Whether the function works on the rows of the frame, rather than on
+    /// the position of the row in its partition.
+    pub fn uses_frame(&self) -> bool {
+        matches!(
+            self,
+            WindowFunction::FirstValue | WindowFunction::LastValue | WindowFunction::Aggregate(_)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameUnits {
+    /// Offsets count rows
+    Rows,
+    /// Offsets are distances between values of the ORDER BY key
+    Range,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FrameBound {
+    UnboundedPreceding,
+    Preceding(u64),
+    CurrentRow,
+    Following(u64),
+    UnboundedFollowing,
+}
+
+impl FrameBound {
+    /// Position relative to the current row, for checking that a frame does
+    /// not end before it starts.
+    fn rank(&self) -> (i8, i128) {
+        match self {
+            FrameBound::UnboundedPreceding => (-2, 0),
+            FrameBound::Preceding(n) => (-1, -(*n as i128)),
+            FrameBound::CurrentRow => (0, 0),
+            FrameBound::Following(n) => (1, *n as i128),
+            FrameBound::UnboundedFollowing => (2, 0),
+        }
+    }
+
+    fn parse(tokens: &mut Tokens) -> Result<Self, ParsingError> {
+        if tokens.accept_keyword("UNBOUNDED") {
+            return if tokens.accept_keyword("PRECEDING") {
+                Ok(FrameBound::UnboundedPreceding)
+            } else {
+                tokens.expect_keyword("FOLLOWING")?;
+                Ok(FrameBound::UnboundedFollowing)
+            };
+        }
+        if tokens.accept_keyword("CURRENT") {
+            tokens.expect_keyword("ROW")?;
+            return Ok(FrameBound::CurrentRow);
+        }
+        let offset = match tokens.next() {
+            Some(Token::Number(number)) => number
+                .parse::<u64>()
+                .map_err(|_| ParsingError::new(&format!("Frame offsets must be non-negative integers, found {}", number)))?,
+            _ => return Err(ParsingError::new("Expected a frame bound")),
+        };
+        if tokens.accept_keyword("PRECEDING") {
+            Ok(FrameBound::Preceding(offset))
+        } else {
+            tokens.expect_keyword("FOLLOWING")?;
+            Ok(FrameBound::Following(offset))
+        }
+    }
+}
+
+impl fmt::Display for FrameBound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameBound::UnboundedPreceding => write!(f, "UNBOUNDED PRECEDING"),
+            FrameBound::Preceding(n) => write!(f, "{} PRECEDING", n),
+            FrameBound::CurrentRow => write!(f, "CURRENT ROW"),
+            FrameBound::Following(n) => write!(f, "{} FOLLOWING", n),
+            FrameBound::UnboundedFollowing => write!(f, "UNBOUNDED FOLLOWING"),
+        }
+    }
+}
+
+/// The rows of the partition a frame function sees for the current row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowFrame {
+    pub units: FrameUnits,
+    pub start: FrameBound,
+    pub end: FrameBound,
+}
+
+impl WindowFrame {
+    /// Parses `{ROWS | RANGE} {BETWEEN bound AND bound | bound}`; a single
+    /// bound is the start of a frame ending at the current row.
+    fn parse(tokens: &mut Tokens) -> Result<Self, ParsingError> {
+        let units = if tokens.accept_keyword("ROWS") {
+            FrameUnits::Rows
+        } else {
+            tokens.expect_keyword("RANGE")?;
+            FrameUnits::Range
+        };
+        let (start, end) = if tokens.accept_keyword("BETWEEN") {
+            let start = FrameBound::parse(tokens)?;
+            tokens.expect_keyword("AND")?;
+            (start, FrameBound::parse(tokens)?)
+        } else {
+            (FrameBound::parse(tokens)?, FrameBound::CurrentRow)
+        };
+        if start == FrameBound::UnboundedFollowing || end == FrameBound::UnboundedPreceding || start.rank() > end.rank() {
+            return Err(ParsingError::new(&format!("Invalid window frame from {} to {}", start, end)));
+        }
+        Ok(Self { units, start, end })
+    }
+}
+
+impl fmt::Display for WindowFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let units = match self.units {
+            FrameUnits::Rows => "ROWS",
+            FrameUnits::Range => "RANGE",
+        };
+        write!(f, "{} BETWEEN {} AND {}", units, self.start, self.end)
+    }
+}
+
+/// `OVER (PARTITION BY ... ORDER BY ... frame)`. Without a frame, frame
+/// functions see the whole partition, or with ORDER BY the rows up to the
+/// last row that sorts equal to the current one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowSpec {
+    pub partition_by: Vec<Expr>,
+    pub order_by: Vec<SortKey>,
+    pub frame: Option<WindowFrame>,
+}
+
+impl WindowSpec {
+    fn parse(tokens: &mut Tokens) -> Result<Self, ParsingError> {
+        tokens.expect_symbol("(")?;
+        let mut partition_by = Vec::new();
+        if tokens.accept_keyword("PARTITION") {
+            tokens.expect_keyword("BY")?;
+            loop {
+                partition_by.push(parse_expression(tokens)?);
+                if !tokens.accept_symbol(",") {
+                    break;
+                }
+            }
+        }
+        let mut order_by = Vec::new();
+        if tokens.accept_keyword("ORDER") {
+            tokens.expect_keyword("BY")?;
+            loop {
+                let expr = parse_expression(tokens)?;
+                order_by.push(parse_sort_order(tokens, expr)?);
+                if !tokens.accept_symbol(",") {
+                    break;
+                }
+            }
+        }
+        let frame = if tokens.peek_keyword("ROWS") || tokens.peek_keyword("RANGE") {
+            Some(WindowFrame::parse(tokens)?)
+        } else {
+            None
+        };
+        tokens.expect_symbol(")")?;
+        Ok(Self {
+            partition_by,
+            order_by,
+            frame,
+        })
+    }
+
+    /// Whether both windows see rows in the same partitions and order, so
+    /// one sort serves both.
+    pub fn sorts_like(&self, other: &WindowSpec) -> bool {
+        self.partition_by == other.partition_by && self.order_by == other.order_by
+    }
+}
+
+impl fmt::Display for WindowSpec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut clauses = Vec::new();
+        if !self.partition_by.is_empty() {
+            let exprs: Vec<String> = self.partition_by.iter().map(|expr| expr.to_string()).collect();
+            clauses.push(format!("PARTITION BY {}", exprs.join(", ")));
+        }
+        if !self.order_by.is_empty() {
+            let keys: Vec<String> = self.order_by.iter().map(|key| key.to_string()).collect();
+            clauses.push(format!("ORDER BY {}", keys.join(", ")));
+        }
+        if let Some(frame) = &self.frame {
+            clauses.push(frame.to_string());
+        }
+        write!(f, "({})", clauses.join(" "))
+    }
+}
+
+/// A window function call: `function(args) OVER (spec)`. The arguments of
+/// an aggregate are part of its call.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowCall {
+    pub function: WindowFunction,
+    pub args: Vec<Expr>,
+    pub spec: WindowSpec,
+}
+
+impl WindowCall {
+    /// Whether the next tokens start a window function call rather than an
+    /// expression.
+    pub fn is_next(tokens: &Tokens) -> bool {
+        let Some(Token::Identifier(name)) = tokens.peek() else {
+            return false;
+        };
+        let known = WindowFunction::NAMES.iter().any(|n| n.eq_ignore_ascii_case(name))
+            || AggregateFunction::from_name(name).is_some();
+        known && matches!(tokens.peek_nth(1), Some(Token::Symbol(s)) if s == "(")
+    }
+
+    pub fn parse(tokens: &mut Tokens) -> Result<Self, ParsingError> {
+        let (function, args) = match tokens.peek() {
+            Some(Token::Identifier(name)) if AggregateFunction::from_name(name).is_some() => {
+                (WindowFunction::Aggregate(AggregateCall::parse(tokens)?), Vec::new())
+            }
+            _ => {
+                let name = tokens.expect_identifier()?;
+                let function = match name.to_uppercase().as_str() {
+                    "ROW_NUMBER" => WindowFunction::RowNumber,
+                    "RANK" => WindowFunction::Rank,
+                    "DENSE_RANK" => WindowFunction::DenseRank,
+                    "LAG" => WindowFunction::Lag,
+                    "LEAD" => WindowFunction::Lead,
+                    "FIRST_VALUE" => WindowFunction::FirstValue,
+                    "LAST_VALUE" => WindowFunction::LastValue,
+                    _ => return Err(ParsingError::new(&format!("Expected a window function, found {}", name))),
+                };
+                tokens.expect_symbol("(")?;
+                let mut args = Vec::new();
+                if !tokens.accept_symbol(")") {
+                    loop {
+                        args.push(parse_expression(tokens)?);
+                        if !tokens.accept_symbol(",") {
+                            break;
+                        }
+                    }
+                    tokens.expect_symbol(")")?;
+                }
+                (function, args)
+            }
+        };
+        tokens.expect_keyword("OVER")?;
+        let spec = WindowSpec::parse(tokens)?;
+        if spec.frame.is_some() && !function.uses_frame() {
+            return Err(ParsingError::new(&format!("{} does not take a window frame", function_name(&function))));
+        }
+        Ok(Self { function, args, spec })
+    }
+
+    /// The offset of LAG and LEAD, 1 unless given as the second argument.
+    pub fn offset(&self) -> Result<usize, ExecutionError> {
+        match self.args.get(1) {
+            None => Ok(1),
+            Some(Expr::Literal(Value::Int(offset))) if *offset >= 0 => Ok(*offset as usize),
+            Some(offset) => Err(ExecutionError::new(&format!(
+                "The offset of {} must be a non-negative integer literal, found {}",
+                function_name(&self.function),
+                offset
+            ))),
+        }
+    }
+
+    /// Type of the result for rows of `schema`, checking the call.
+    pub fn data_type(&self, schema: &Schema) -> Result<DataType, ExecutionError> {
+        for expr in &self.spec.partition_by {
+            expr.data_type(schema)?;
+        }
+        for key in &self.spec.order_by {
+            key.expr.data_type(schema)?;
+        }
+        if let Some(WindowFrame {
+            units: FrameUnits::Range,
+            start,
+            end,
+        }) = &self.spec.frame
+        {
+            let offsets = [start, end]
+                .iter()
+                .any(|bound| matches!(bound, FrameBound::Preceding(_) | FrameBound::Following(_)));
+            if offsets {
+                let numeric = match self.spec.order_by.as_slice() {
+                    [key] => matches!(key.expr.data_type(schema)?, DataType::Int64 | DataType::Float64),
+                    _ => false,
+                };
+                if !numeric {
+                    return Err(ExecutionError::new("RANGE frames with offsets need a single numeric ORDER BY key"));
+                }
+            }
+        }
+
+        let name = function_name(&self.function);
+        let arity = |min: usize, max: usize| {
+            if (min..=max).contains(&self.args.len()) {
+                Ok(())
+            } else {
+                Err(ExecutionError::new(&format!(
+                    "{} takes {} arguments, got {}",
+                    name,
+                    if min == max { min.to_string() } else { format!("{} to {}", min, max) },
+                    self.args.len()
+                )))
+            }
+        };
+        match &self.function {
+            WindowFunction::RowNumber | WindowFunction::Rank | WindowFunction::DenseRank => {
+                arity(0, 0)?;
+                Ok(DataType::Int64)
+            }
+            WindowFunction::Lag | WindowFunction::Lead => {
+                arity(1, 3)?;
+                self.offset()?;
+                let data_type = self.args[0].data_type(schema)?;
+                match self.args.get(2) {
+                    Some(default) => {
+                        let default_type = default.data_type(schema)?;
+                        data_type.common_supertype(&default_type).ok_or_else(|| {
+                            ExecutionError::new(&format!(
+                                "The default of {} must be a {}, found {}",
+                                name, data_type, default_type
+                            ))
+                        })
+                    }
+                    None => Ok(data_type),
+                }
+            }
+            WindowFunction::FirstValue | WindowFunction::LastValue => {
+                arity(1, 1)?;
+                self.args[0].data_type(schema)
+            }
+            WindowFunction::Aggregate(call) => call.data_type(schema),
+        }
+    }
+}
+
+fn function_name(function: &WindowFunction) -> String {
+    match function {
+        WindowFunction::RowNumber => "ROW_NUMBER".to_string(),
+        WindowFunction::Rank => "RANK".to_string(),
+        WindowFunction::DenseRank => "DENSE_RANK".to_string(),
+        WindowFunction::Lag => "LAG".to_string(),
+        WindowFunction::Lead => "LEAD".to_string(),
+        WindowFunction::FirstValue => "FIRST_VALUE".to_string(),
+        WindowFunction::LastValue => "LAST_VALUE".to_string(),
+        WindowFunction::Aggregate(call) => call.function.to_string(),
+    }
+}
+
+impl fmt::Display for WindowCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.function {
+            WindowFunction::Aggregate(call) => write!(f, "{}", call)?,
+            function => {
+                let args: Vec<String> = self.args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{}({})", function_name(function), args.join(", "))?;
+            }
+        }
+        write!(f, " OVER {}", self.spec)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalWindowListNode {
+    pub call: WindowCall,
+    pub alias: Option<String>,
+}
+
+/// Adds one column per window function call to every input row.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogicalWindowNode {
+    pub input: Box<LogicalPlan>,
+    pub windows: Vec<LogicalWindowListNode>,
+}
+
+impl LogicalWindowNode {
+    pub fn new(input: LogicalPlan, windows: Vec<LogicalWindowListNode>) -> Self {
+        Self {
+            input: Box::new(input),
+            windows,
+        }
+    }
+
+    /// The window list as (call, alias) pairs.
+    pub fn window_list(&self) -> Vec<(WindowCall, Option<String>)> {
+        self.windows
+            .iter()
+            .map(|window| (window.call.clone(), window.alias.clone()))
+            .collect()
+    }
+}
+
+/// The output of window functions over rows of `input`: the input columns
+/// followed by one column per call, named by its alias or its text.
+pub fn window_fields(windows: &[(WindowCall, Option<String>)], input: &Schema) -> Result<Vec<Field>, ExecutionError> {
+    let mut fields = input.fields.clone();
+    for (call, alias) in windows {
+        let name = alias.clone().unwrap_or_else(|| call.to_string());
+        fields.push(Field::new(None, &name, call.data_type(input)?));
+    }
+    Ok(fields)
+}